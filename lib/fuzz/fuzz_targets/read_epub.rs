@@ -0,0 +1,14 @@
+#![no_main]
+
+use iepub::prelude::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mut book) = read_from_vec(data.to_vec()) {
+        for chap in book.chapters_mut() {
+            let _ = chap.data_mut();
+        }
+        let _ = EpubWriter::write_to_mem(&mut book, true);
+    }
+    let _ = read_from_vec_lenient(data.to_vec());
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use iepub::prelude::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mut book) = MobiReader::new(std::io::Cursor::new(data)).and_then(|mut r| r.load()) {
+        let _ = adapter::mobi_to_epub(&mut book);
+    }
+});
@@ -1,8 +1,18 @@
 use crate::{
-    common::{IError, IResult},
+    cbz::core::{CbzBook, CbzPage},
+    common::{escape_xml, find_tag_end, gen_uuid, get_media_type, tag_name, IError, IResult},
+    epub::{
+        common::DEFAULT_LANGUAGE,
+        layout::{normalize, parent},
+    },
+    fb2::core::{Fb2Binary, Fb2Book, Fb2Section},
     mobi::{builder::MobiBuilder, core::MobiAssets, image::get_attr_value},
     prelude::{EpubBook, EpubBuilder, EpubHtml, EpubNav, MobiBook, MobiHtml, MobiNav},
 };
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
 
 fn to_epub_nav(mobi: &MobiNav, parent: &str) -> EpubNav {
     let mut n = EpubNav::default();
@@ -21,7 +31,10 @@ fn to_epub_nav(mobi: &MobiNav, parent: &str) -> EpubNav {
 
 /// 找到章节对应的目录
 /// 返回多个层级所属目录
-fn get_mobi_chapter_nav<'a>(chap: &MobiHtml, nav: &'a [MobiNav]) -> Option<Vec<&'a MobiNav>> {
+pub(crate) fn get_mobi_chapter_nav<'a>(
+    chap: &MobiHtml,
+    nav: &'a [MobiNav],
+) -> Option<Vec<&'a MobiNav>> {
     for ele in nav {
         if ele.id() == chap.nav_id() {
             return Some(vec![ele]);
@@ -59,10 +72,11 @@ pub fn mobi_to_epub(mobi: &mut MobiBook) -> IResult<EpubBook> {
 
     // 添加图片
     for ele in mobi.assets_mut() {
-        builder = builder.add_assets(
-            get_mobi_assets_file_name(ele).as_str(),
-            ele.data().unwrap().to_vec(),
-        );
+        let data = ele
+            .data()
+            .ok_or_else(|| IError::MissingEntry(ele.file_name().to_string()))?
+            .to_vec();
+        builder = builder.add_assets(get_mobi_assets_file_name(ele).as_str(), data);
     }
 
     // 添加目录
@@ -75,7 +89,9 @@ pub fn mobi_to_epub(mobi: &mut MobiBook) -> IResult<EpubBook> {
     // 添加文本
     for chap in mobi.chapters() {
         let nav: Vec<&str> = get_mobi_chapter_nav(chap, mobi.nav().as_slice())
-            .unwrap()
+            .ok_or_else(|| {
+                IError::Parse(Cow::from(format!("chapter {} is not in nav", chap.title())))
+            })?
             .iter()
             .map(|f| f.title())
             .collect();
@@ -94,7 +110,10 @@ pub fn mobi_to_epub(mobi: &mut MobiBook) -> IResult<EpubBook> {
 
     // 封面
     if let Some(cover) = mobi.cover() {
-        builder = builder.cover(cover.file_name(), cover.data().unwrap().to_vec());
+        let data = cover
+            .data()
+            .ok_or_else(|| IError::MissingEntry(cover.file_name().to_string()))?;
+        builder = builder.cover(cover.file_name(), data.to_vec());
     }
     // 元数据
     builder = builder
@@ -125,6 +144,15 @@ pub fn mobi_to_epub(mobi: &mut MobiBook) -> IResult<EpubBook> {
     if let Some(v) = mobi.subject() {
         builder = builder.with_subject(v);
     }
+    if let Some(v) = mobi.language() {
+        builder = builder.with_language(v);
+    }
+    if let Some(v) = mobi.writing_mode() {
+        builder = builder.with_writing_mode(v);
+    }
+    if let Some(v) = mobi.page_direction() {
+        builder = builder.with_page_direction(v);
+    }
 
     builder.book()
 }
@@ -204,6 +232,50 @@ fn convert_epub_html_img(html: &[u8], path: &str) -> Vec<u8> {
     })
 }
 
+///
+/// mobi 不支持 ruby，把 `<ruby>字<rt>zì</rt></ruby>` 转换为 `字(zì)`
+///
+/// rp 中的内容会被移除，避免出现重复的括号
+///
+fn degrade_ruby(html: Vec<u8>) -> Vec<u8> {
+    let html = match String::from_utf8(html) {
+        Ok(v) if v.to_ascii_lowercase().contains("<ruby") => v,
+        Ok(v) => return v.into_bytes(),
+        Err(e) => return e.into_bytes(),
+    };
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html.as_str();
+    let mut in_rp = false;
+    while !rest.is_empty() {
+        let start = rest.find('<').unwrap_or(rest.len());
+        if !in_rp {
+            out.push_str(&rest[..start]);
+        }
+        rest = &rest[start..];
+        if rest.is_empty() {
+            break;
+        }
+        let end = if rest.starts_with("<!--") {
+            rest.find("-->").map(|i| i + 3)
+        } else {
+            find_tag_end(rest)
+        }
+        .unwrap_or(rest.len());
+        let tag = &rest[..end];
+        rest = &rest[end..];
+        let close = tag.starts_with("</");
+        match tag_name(tag).as_str() {
+            "ruby" | "rb" | "rtc" => {}
+            "rp" => in_rp = !close && !tag.ends_with("/>"),
+            "rt" if !close => out.push('('),
+            "rt" => out.push(')'),
+            _ if !in_rp => out.push_str(tag),
+            _ => {}
+        }
+    }
+    out.into_bytes()
+}
+
 /// 修改xml片段中的img标签的src属性的路径
 pub fn generate_text_img_xml<T: Fn(Vec<u8>) -> Vec<u8>>(html: &[u8], callback: T) -> Vec<u8> {
     let mut text = Vec::new();
@@ -217,7 +289,10 @@ pub fn generate_text_img_xml<T: Fn(Vec<u8>) -> Vec<u8>>(html: &[u8], callback: T
         let mut j = 0;
         while j < key.len() {
             if now == key[j] {
-                now = chars[index + j + 1];
+                match chars.get(index + j + 1) {
+                    Some(v) => now = *v,
+                    None => break,
+                }
             } else {
                 break;
             }
@@ -234,9 +309,7 @@ pub fn generate_text_img_xml<T: Fn(Vec<u8>) -> Vec<u8>>(html: &[u8], callback: T
             if let Some(v) = att.0 {
                 // 有src属性
                 let start = att.1;
-                for i in index..index + start {
-                    text.push(chars[i]);
-                }
+                text.extend_from_slice(&chars[index..(index + start).min(chars.len())]);
                 index += start;
 
                 text.append(&mut callback(v));
@@ -305,6 +378,15 @@ pub fn epub_to_mobi(epub: &mut EpubBook) -> IResult<MobiBook> {
     if let Some(v) = epub.subject() {
         builder = builder.with_subject(v);
     }
+    if let Some(v) = epub.language() {
+        builder = builder.with_language(v);
+    }
+    if let Some(v) = epub.writing_mode() {
+        builder = builder.with_writing_mode(v);
+    }
+    if let Some(v) = epub.page_direction() {
+        builder = builder.with_page_direction(v);
+    }
 
     let chap = epub.chapters_mut();
 
@@ -315,7 +397,7 @@ pub fn epub_to_mobi(epub: &mut EpubBook) -> IResult<MobiBook> {
             (
                 MobiHtml::new(index).with_title(html.title()).with_data(
                     html.data_mut()
-                        .map(|v| convert_epub_html_img(v, file_name.as_str()))
+                        .map(|v| degrade_ruby(convert_epub_html_img(v, file_name.as_str())))
                         // .unwrap_or_else(||Err(FromUtf8Error { bytes: Vec::n, error: e }))
                         .unwrap_or(Vec::new()),
                 ),
@@ -332,7 +414,8 @@ pub fn epub_to_mobi(epub: &mut EpubBook) -> IResult<MobiBook> {
     }
     // 静态资源
     for ele in epub.assets_mut() {
-        let data = ele.data_mut().ok_or(IError::Unknown)?.to_vec();
+        let name = ele.file_name().to_string();
+        let data = ele.data_mut().ok_or(IError::MissingEntry(name))?.to_vec();
         builder = builder.add_assets(ele.file_name(), data);
     }
     // 添加文本
@@ -341,15 +424,522 @@ pub fn epub_to_mobi(epub: &mut EpubBook) -> IResult<MobiBook> {
     }
 
     if let Some(c) = epub.cover_mut() {
-        builder = builder.cover(c.data_mut().ok_or(IError::Unknown)?.to_vec());
+        let name = c.file_name().to_string();
+        builder = builder.cover(c.data_mut().ok_or(IError::MissingEntry(name))?.to_vec());
     }
 
     builder.book()
 }
 
+/// fb2 的 binary 在 epub 中的文件名，id 没有扩展名时根据类型添加
+fn get_fb2_binary_file_name(b: &Fb2Binary) -> String {
+    let name = b.id().replace(['/', '\\'], "_");
+    if name.contains('.') {
+        return format!("image/{}", name);
+    }
+    let ext = match b.content_type() {
+        "image/jpeg" => "jpg",
+        t => t.rsplit('/').next().unwrap_or("bin"),
+    };
+    format!("image/{}.{}", name, ext)
+}
+
+///
+/// fb2 的章节转换为 epub 的章节和目录
+///
+/// 有正文或者没有子章节时生成章节，没有标题的 section 不生成目录，下级目录上移一级
+///
+fn fb2_section_to_epub(
+    sections: std::slice::Iter<Fb2Section>,
+    parent_title: &str,
+    chapters: &mut Vec<EpubHtml>,
+    convert: &dyn Fn(&[u8]) -> Vec<u8>,
+) -> Vec<EpubNav> {
+    let mut navs = Vec::new();
+    for section in sections {
+        let title = if section.title().is_empty() {
+            parent_title
+        } else {
+            section.title()
+        };
+        let first = chapters.len();
+        if section.has_data() || section.children.is_empty() {
+            chapters.push(
+                EpubHtml::default()
+                    .with_title(title)
+                    .with_file_name(format!("chapter_{:04}.xhtml", chapters.len() + 1).as_str())
+                    .with_data(convert(section.data())),
+            );
+        }
+        let children = fb2_section_to_epub(section.children(), title, chapters, convert);
+        // section 的 id 作为锚点放在对应章节的开头
+        if let (Some(id), Some(chap)) = (section.id(), chapters.get_mut(first)) {
+            let mut data = format!(r#"<a id="{}"></a>"#, escape_xml(id)).into_bytes();
+            data.extend_from_slice(chap.data().unwrap_or_default());
+            chap.set_data(data);
+        }
+        if section.title().is_empty() {
+            navs.extend(children);
+            continue;
+        }
+        let mut nav = EpubNav::default().with_title(title);
+        // 没有正文时指向第一个子章节
+        if let Some(chap) = chapters.get(first) {
+            nav.set_file_name(chap.file_name());
+        }
+        for ele in children {
+            nav.push(ele);
+        }
+        navs.push(nav);
+    }
+    navs
+}
+
+///
+/// fb2 中的链接指向元素 id，拆分章节后改为指向元素所在的文件
+///
+fn fb2_link_to_epub(chapters: &mut [EpubHtml]) -> IResult<()> {
+    let id = regex::Regex::new(r#"<[^>]*?\sid="([^"]*)""#)
+        .map_err(|e| IError::Parse(Cow::from(e.to_string())))?;
+    let href = regex::Regex::new(r##"\shref="#([^"]*)""##)
+        .map_err(|e| IError::Parse(Cow::from(e.to_string())))?;
+    let mut files: HashMap<String, String> = HashMap::new();
+    for chap in chapters.iter() {
+        let html = String::from_utf8_lossy(chap.data().unwrap_or_default());
+        for cap in id.captures_iter(&html) {
+            files
+                .entry(cap[1].to_string())
+                .or_insert_with(|| chap.file_name().to_string());
+        }
+    }
+    for chap in chapters.iter_mut() {
+        let html = String::from_utf8_lossy(chap.data().unwrap_or_default()).to_string();
+        let html = href.replace_all(&html, |cap: &regex::Captures| match files.get(&cap[1]) {
+            Some(file) => format!(r#" href="{}#{}""#, file, &cap[1]),
+            None => cap[0].to_string(),
+        });
+        chap.set_data(html.into_owned().into_bytes());
+    }
+    Ok(())
+}
+
+/// fb2 转 epub
+///
+/// # Examples
+/// ```no_run
+/// use iepub::prelude::*;
+/// use iepub::prelude::adapter::fb2_to_epub;
+///
+/// let book = fb2::read_from_file("example.fb2").unwrap();
+/// let mut epub = fb2_to_epub(&book).unwrap();
+/// EpubWriter::write_to_file("example.epub", &mut epub, true).unwrap();
+/// ```
+pub fn fb2_to_epub(fb2: &Fb2Book) -> IResult<EpubBook> {
+    let mut builder = EpubBuilder::new()
+        .custome_nav(true)
+        .with_title(fb2.title())
+        .with_identifier(fb2.identifier());
+
+    // 图片
+    let cover = fb2.cover();
+    for ele in fb2.binaries() {
+        if cover.is_some_and(|c| c.id() == ele.id()) {
+            continue;
+        }
+        builder = builder.add_assets(get_fb2_binary_file_name(ele).as_str(), ele.data().to_vec());
+    }
+    if let Some(cover) = cover {
+        builder = builder.cover(get_fb2_binary_file_name(cover), cover.data().to_vec());
+    }
+
+    // 章节中的图片引用 binary id，改为文件路径
+    let convert = |data: &[u8]| {
+        generate_text_img_xml(data, |v| {
+            let id = String::from_utf8(v).unwrap_or_default();
+            let src = fb2
+                .get_binary(&id)
+                .map(get_fb2_binary_file_name)
+                .unwrap_or(id);
+            format!(r#"src="{}""#, src).into_bytes()
+        })
+    };
+    let mut chapters = Vec::new();
+    let nav = fb2_section_to_epub(fb2.sections(), fb2.title(), &mut chapters, &convert);
+    fb2_link_to_epub(&mut chapters)?;
+    for ele in chapters {
+        builder = builder.add_chapter(ele);
+    }
+    for ele in nav {
+        builder = builder.add_nav(ele);
+    }
+
+    if let Some(v) = fb2.contributor() {
+        builder = builder.with_contributor(v);
+    }
+    if let Some(v) = fb2.creator() {
+        builder = builder.with_creator(v);
+    }
+    if let Some(v) = fb2.description() {
+        builder = builder.with_description(v);
+    }
+    if let Some(v) = fb2.date() {
+        builder = builder.with_date(v);
+    }
+    if let Some(v) = fb2.publisher() {
+        builder = builder.with_publisher(v);
+    }
+    if let Some(v) = fb2.subject() {
+        builder = builder.with_subject(v);
+    }
+    if let Some(v) = fb2.lang() {
+        builder = builder.with_language(v);
+    }
+
+    builder.book()
+}
+
+/// fb2 转 mobi，先转换为 epub
+pub fn fb2_to_mobi(fb2: &Fb2Book) -> IResult<MobiBook> {
+    epub_to_mobi(&mut fb2_to_epub(fb2)?)
+}
+
+fn epub_nav_to_fb2_section(
+    nav: std::slice::Iter<EpubNav>,
+    data: &mut HashMap<String, Vec<u8>>,
+) -> Vec<Fb2Section> {
+    let mut sections = Vec::new();
+    for ele in nav {
+        let file = ele.file_name().split('#').next().unwrap_or_default();
+        let mut section = Fb2Section::default().with_title(ele.title());
+        // 上级目录一般指向第一个子章节，所以先处理下级，多个目录指向同一个文件时只有一个有正文
+        for child in epub_nav_to_fb2_section(ele.child(), data) {
+            section.push(child);
+        }
+        if let Some(v) = data.remove(file) {
+            section.set_data(v);
+        }
+        sections.push(section);
+    }
+    sections
+}
+
+fn flatten_epub_nav(nav: std::slice::Iter<EpubNav>, files: &mut HashSet<String>) {
+    for ele in nav {
+        files.insert(
+            ele.file_name()
+                .split('#')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        );
+        flatten_epub_nav(ele.child(), files);
+    }
+}
+
+/// epub 转 fb2
+///
+/// 按照目录生成章节，不在目录中的章节合并到阅读顺序上的前一个章节，图片转换为 binary
+///
+/// # Examples
+/// ```no_run
+/// use iepub::prelude::*;
+/// use iepub::prelude::adapter::epub_to_fb2;
+///
+/// let mut epub = read_from_file("example.epub").unwrap();
+/// let fb2 = epub_to_fb2(&mut epub).unwrap();
+/// fb2::Fb2Writer::write_to_file("example.fb2.zip", &fb2).unwrap();
+/// ```
+pub fn epub_to_fb2(epub: &mut EpubBook) -> IResult<Fb2Book> {
+    let mut book = Fb2Book::default()
+        .with_title(epub.title())
+        .with_identifier(epub.identifier());
+    if let Some(v) = epub.contributor() {
+        book.set_contributor(v);
+    }
+    if let Some(v) = epub.creator() {
+        book.set_creator(v);
+    }
+    if let Some(v) = epub.description() {
+        book.set_description(v);
+    }
+    if let Some(v) = epub.date() {
+        book.set_date(v);
+    }
+    if let Some(v) = epub.publisher() {
+        book.set_publisher(v);
+    }
+    if let Some(v) = epub.subject() {
+        book.set_subject(v);
+    }
+    if let Some(v) = epub.language() {
+        book.set_lang(v);
+    }
+
+    // 图片，文件路径 -> binary id
+    let mut ids: HashMap<String, String> = HashMap::new();
+    let mut add_binary = |book: &mut Fb2Book, file: &str, data: Vec<u8>| {
+        if let Some(id) = ids.get(file) {
+            return id.clone();
+        }
+        let name: String = file
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let mut id = name.clone();
+        if book.get_binary(&id).is_some() {
+            id = format!("{}_{}", ids.len(), name);
+        }
+        book.add_binary(Fb2Binary::new(id.as_str(), get_media_type(file), data));
+        ids.insert(file.to_string(), id.clone());
+        id
+    };
+    for ele in epub.assets_mut() {
+        if !get_media_type(ele.file_name()).starts_with("image/") {
+            continue;
+        }
+        let name = ele.file_name().to_string();
+        let data = ele.data_mut().ok_or(IError::MissingEntry(name))?.to_vec();
+        add_binary(&mut book, ele.file_name(), data);
+    }
+    if let Some(c) = epub.cover_mut() {
+        let name = c.file_name().to_string();
+        let data = c.data_mut().ok_or(IError::MissingEntry(name))?.to_vec();
+        let id = add_binary(&mut book, c.file_name(), data);
+        book.set_cover(id);
+    }
+
+    let mut nav_files = HashSet::new();
+    flatten_epub_nav(epub.nav(), &mut nav_files);
+
+    // 章节正文，按目录中的文件归类
+    let mut data: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut owner: Option<String> = None;
+    let mut pending = Vec::new();
+    let mut sections = Vec::new();
+    for chap in epub.chapters_mut() {
+        if chap.is_generated() {
+            continue;
+        }
+        let file = chap.file_name().to_string();
+        let title = chap.title().to_string();
+        let html = generate_text_img_xml(chap.data_mut().unwrap_or_default(), |v| {
+            let src = String::from_utf8(v).unwrap_or_default();
+            let src = ids
+                .get(&normalize(parent(&file), &src))
+                .cloned()
+                .unwrap_or(src);
+            format!(r#"src="{}""#, src).into_bytes()
+        });
+        if nav_files.is_empty() {
+            sections.push(Fb2Section::default().with_title(title).with_data(html));
+            continue;
+        }
+        if nav_files.contains(&file) {
+            owner = Some(file);
+        }
+        match &owner {
+            Some(o) => {
+                let v = data.entry(o.clone()).or_default();
+                if v.is_empty() {
+                    // 第一个在目录中的章节之前的内容
+                    v.append(&mut pending);
+                }
+                v.extend(html);
+            }
+            None => pending.extend(html),
+        }
+    }
+    sections.append(&mut epub_nav_to_fb2_section(epub.nav(), &mut data));
+    for ele in sections {
+        book.add_section(ele);
+    }
+    Ok(book)
+}
+
+/// 页面尺寸无法识别时使用的默认尺寸
+const DEFAULT_PAGE_SIZE: (u32, u32) = (1200, 1600);
+
+///
+/// cbz 转 epub
+///
+/// 生成固定版式的 epub3，每张图片一页，宽大于高的图片视为跨页，其余页面按照翻页方向交替放在左右两侧，
+/// 图片在子目录中时按目录生成目录
+///
+pub fn cbz_to_epub(cbz: &CbzBook) -> IResult<EpubBook> {
+    let mut builder = EpubBuilder::new()
+        .custome_nav(true)
+        .with_version("3.0")
+        .with_rendition_layout("pre-paginated")
+        .with_title(cbz.title())
+        .with_identifier(format!("urn:uuid:{}", gen_uuid()))
+        .with_language(cbz.language().unwrap_or(DEFAULT_LANGUAGE));
+    if let Some(v) = cbz.creator() {
+        builder = builder.with_creator(v);
+    }
+    if let Some(v) = cbz.description() {
+        builder = builder.with_description(v);
+    }
+    if let Some(v) = cbz.date() {
+        builder = builder.with_date(v);
+    }
+    if let Some(v) = cbz.publisher() {
+        builder = builder.with_publisher(v);
+    }
+    if let Some(v) = cbz.subject() {
+        builder = builder.with_subject(v);
+    }
+
+    // 两页中的第一页和第二页所在的一侧，第一页单独放在第二页的位置
+    let (first, second) = if cbz.right_to_left() {
+        ("page-spread-right", "page-spread-left")
+    } else {
+        ("page-spread-left", "page-spread-right")
+    };
+    let mut pair_start = false;
+    let mut size = None;
+    let mut dir: Option<&str> = None;
+    for (index, page) in cbz.pages().enumerate() {
+        let ext = page
+            .file_name()
+            .rfind('.')
+            .map_or(String::new(), |i| page.file_name()[i..].to_lowercase());
+        let image = format!("images/{:04}{}", index + 1, ext);
+        let file = format!("page_{:04}.xhtml", index + 1);
+        // 第一页同时作为封面
+        builder = if index == 0 {
+            builder.cover(image.as_str(), page.data().to_vec())
+        } else {
+            builder.add_assets(image.as_str(), page.data().to_vec())
+        };
+
+        let properties = if index == 0 {
+            pair_start = true;
+            second
+        } else if page.is_spread() {
+            pair_start = true;
+            "rendition:page-spread-center"
+        } else if pair_start {
+            pair_start = false;
+            first
+        } else {
+            pair_start = true;
+            second
+        };
+        size = page.size().or(size);
+        let (width, height) = size.unwrap_or(DEFAULT_PAGE_SIZE);
+        let chap = EpubHtml::default()
+            .with_file_name(file.as_str())
+            .with_title((index + 1).to_string())
+            .with_css(format!("html,body{{margin:0;padding:0;width:{width}px;height:{height}px;overflow:hidden}}img{{display:block;width:100%;height:100%}}"))
+            .with_data(format!(r#"<img src="{}" alt=""/>"#, image).into_bytes())
+            .with_viewport(width, height)
+            .with_spine_properties(properties);
+        builder = builder.add_chapter(chap);
+
+        // 按目录生成导航
+        if dir != Some(page.dir()) {
+            dir = Some(page.dir());
+            let title = page.dir().rsplit('/').next().unwrap_or_default();
+            builder = builder.add_nav(
+                EpubNav::default()
+                    .with_title(if title.is_empty() { cbz.title() } else { title })
+                    .with_file_name(file.as_str()),
+            );
+        }
+    }
+
+    if cbz.right_to_left() {
+        builder = builder.with_page_direction("rtl");
+    }
+    builder.book()
+}
+
+///
+/// epub 转 cbz
+///
+/// 按照章节顺序提取其中引用的图片，包括 svg 中的 image，文字内容会被忽略，
+/// 封面没有被章节引用时作为第一页
+///
+pub fn epub_to_cbz(epub: &mut EpubBook) -> IResult<CbzBook> {
+    let mut book = CbzBook::default().with_title(epub.title());
+    if let Some(v) = epub.creator() {
+        book.set_creator(v);
+    }
+    if let Some(v) = epub.description() {
+        book.set_description(v);
+    }
+    if let Some(v) = epub.date() {
+        book.set_date(v);
+    }
+    if let Some(v) = epub.publisher() {
+        book.set_publisher(v);
+    }
+    if let Some(v) = epub.subject() {
+        book.set_subject(v);
+    }
+    if let Some(v) = epub.language() {
+        book.set_language(v);
+    }
+    book.set_right_to_left(epub.page_direction() == Some("rtl"));
+
+    let re = regex::Regex::new(
+        r#"<(?:img|image)\b[^>]*?\s(?:src|xlink:href|href)\s*=\s*(?:"([^"]*)"|'([^']*)')"#,
+    )
+    .map_err(|e| IError::Parse(Cow::from(e.to_string())))?;
+    let mut files: Vec<String> = Vec::new();
+    for chap in epub.chapters_mut() {
+        if chap.is_generated() {
+            continue;
+        }
+        let file = chap.file_name().to_string();
+        let html = String::from_utf8_lossy(chap.data_mut().unwrap_or_default()).to_string();
+        for cap in re.captures_iter(&html) {
+            let Some(src) = cap.get(1).or_else(|| cap.get(2)) else {
+                continue;
+            };
+            let path = normalize(parent(&file), &crate::common::unescape_html(src.as_str()));
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+    if let Some(cover) = epub.cover() {
+        if !files.iter().any(|f| f == cover.file_name()) {
+            files.insert(0, cover.file_name().to_string());
+        }
+    }
+
+    for file in files {
+        let data = if epub.cover().is_some_and(|c| c.file_name() == file) {
+            epub.cover_mut()
+                .and_then(|c| c.data_mut())
+                .map(|f| f.to_vec())
+        } else {
+            epub.get_assets_mut(&file)
+                .and_then(|f| f.data_mut())
+                .map(|f| f.to_vec())
+        };
+        let data = data.ok_or_else(|| IError::MissingEntry(file.clone()))?;
+        book.add_page(CbzPage::new(file, data));
+    }
+    if book.pages.is_empty() {
+        return Err(IError::Parse(Cow::from("epub has no image")));
+    }
+    Ok(book)
+}
+
 pub mod concat {
     use crate::{
-        common::{get_media_type, IResult},
+        common::{get_media_type, IError, IResult},
         path,
         prelude::{EpubBook, EpubBuilder, EpubHtml, EpubNav},
     };
@@ -440,7 +1030,8 @@ pub mod concat {
                 // 暂不考虑非图片资源
                 continue;
             }
-            let f = ele.data_mut().unwrap().to_vec();
+            let name = ele.file_name().to_string();
+            let f = ele.data_mut().ok_or(IError::MissingEntry(name))?.to_vec();
             asset_len += 1;
 
             let sufix = ele.file_name().find(|f| f == '.').unwrap_or(0);
@@ -468,12 +1059,16 @@ pub mod concat {
             }
             let old = ele.file_name().to_string();
             if let Some(v) = new_file_name.get(ele.file_name()) {
+                let title = ele.title().to_string();
+                let data = ele
+                    .data_mut()
+                    .ok_or_else(|| IError::MissingEntry(old.clone()))?;
                 builder = builder.add_chapter(
                     EpubHtml::default()
                         .with_file_name(v.as_str())
-                        .with_title(ele.title())
+                        .with_title(&title)
                         .with_data(replace_html_assets(
-                            ele.data_mut().unwrap(),
+                            data,
                             &new_asset_file_name,
                             old.to_string(),
                             v.as_str(),
@@ -599,7 +1194,7 @@ pub mod concat {
 }
 #[cfg(test)]
 mod tests {
-    use super::{convert_mobi_html_data, epub_to_mobi, mobi_to_epub};
+    use super::{convert_mobi_html_data, degrade_ruby, epub_to_mobi, mobi_to_epub};
     use crate::{
         adapter::core::convert_epub_html_img,
         common::IError,
@@ -702,6 +1297,62 @@ mod tests {
         assert_eq!(epub.assets().len(), n_mobi.assets().len());
     }
 
+    #[test]
+    fn test_writing_mode() {
+        let mut epub = EpubBuilder::default()
+            .with_title("书名")
+            .with_writing_mode("vertical-rl")
+            .with_page_direction("rtl")
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一章")
+                    .with_file_name("0.xhtml")
+                    .with_data("<p>竖排</p>".as_bytes().to_vec()),
+            )
+            .cover("cover.png", crate::cbz::core::tests::png(1, 1))
+            .book()
+            .unwrap();
+
+        let mobi = epub_to_mobi(&mut epub).unwrap();
+        let mut v = std::io::Cursor::new(Vec::new());
+        MobiWriter::new(&mut v).write(&mobi).unwrap();
+        let mut n_mobi = MobiReader::new(&mut v).unwrap().load().unwrap();
+        assert_eq!(Some("vertical-rl"), n_mobi.writing_mode());
+        assert_eq!(Some("rtl"), n_mobi.page_direction());
+
+        let back = mobi_to_epub(&mut n_mobi).unwrap();
+        assert_eq!(Some("vertical-rl"), back.writing_mode());
+        assert_eq!(Some("rtl"), back.page_direction());
+    }
+
+    #[test]
+    fn test_degrade_ruby() {
+        assert_eq!(
+            "<p>汉(hàn)字(zì)<b>!</b></p>".as_bytes(),
+            degrade_ruby(
+                "<p><ruby>汉<rp>(</rp><rt>hàn</rt><rp>)</rp></ruby><RUBY><rb>字</rb><rt>zì</rt></RUBY><b>!</b></p>"
+                    .as_bytes()
+                    .to_vec()
+            )
+        );
+        assert_eq!(b"<p>a</p>".to_vec(), degrade_ruby(b"<p>a</p>".to_vec()));
+
+        let mut epub = EpubBuilder::default()
+            .with_title("书名")
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一章")
+                    .with_file_name("0.xhtml")
+                    .with_data("<p><ruby>注<rt>zhù</rt></ruby>音</p>".as_bytes().to_vec()),
+            )
+            .cover("cover.png", crate::cbz::core::tests::png(1, 1))
+            .book()
+            .unwrap();
+        let mobi = epub_to_mobi(&mut epub).unwrap();
+        let data = mobi.chapters().next().unwrap().string_data();
+        assert!(data.contains("注(zhù)音"), "{}", data);
+    }
+
     #[test]
     fn test_convert_html_img() {
         let data = r#"<h1>插图</h1>
@@ -770,4 +1421,173 @@ mod tests {
             String::from_utf8(v).unwrap()
         );
     }
+
+    #[test]
+    fn test_fb2() {
+        use crate::fb2::{reader::read_from_vec, reader::tests::FB2, writer::Fb2Writer};
+        use crate::prelude::adapter::{epub_to_fb2, fb2_to_epub};
+
+        let fb2 = read_from_vec(FB2.as_bytes().to_vec()).unwrap();
+        let mut epub = fb2_to_epub(&fb2).unwrap();
+        assert_eq!("Война и мир", epub.title());
+        assert_eq!(Some("Лев Толстой,佚名"), epub.creator());
+        assert_eq!("image/cover.jpg", epub.cover().unwrap().file_name());
+        assert_eq!(3, epub.chapters().count());
+
+        let nav: Vec<(&str, &str, usize)> = epub
+            .nav()
+            .map(|f| (f.title(), f.file_name(), f.child().count()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("Том 1 Часть 1", "chapter_0001.xhtml", 2),
+                ("notes", "chapter_0003.xhtml", 1)
+            ],
+            nav
+        );
+        let html = String::from_utf8(
+            epub.get_chapter_mut("chapter_0001.xhtml")
+                .unwrap()
+                .data_mut()
+                .unwrap()
+                .to_vec(),
+        )
+        .unwrap();
+        assert!(html.contains(r#"<img src="image/img1.png" alt=""/>"#));
+        // 链接指向 id 所在的章节
+        assert!(html.starts_with(r#"<a id="s1"></a>"#));
+        assert!(html.contains(r#"<a href="chapter_0003.xhtml#n1">[1]</a>"#));
+        let html = String::from_utf8_lossy(
+            epub.get_chapter_mut("chapter_0003.xhtml")
+                .unwrap()
+                .data_mut()
+                .unwrap(),
+        )
+        .to_string();
+        assert!(html.starts_with(r#"<a id="n1"></a>"#));
+        assert!(html.contains(r#"<a href="chapter_0002.xhtml#p2">см.</a>"#));
+        EpubWriter::write_to_mem(&mut epub, true).unwrap();
+
+        let back = epub_to_fb2(&mut epub).unwrap();
+        assert_eq!("Война и мир", back.title());
+        assert_eq!(&[1, 2, 3], back.cover().unwrap().data());
+        let titles: Vec<&str> = back.sections().map(|f| f.title()).collect();
+        assert_eq!(vec!["Том 1 Часть 1", "notes"], titles);
+        let chap = back.sections().next().unwrap().children().next().unwrap();
+        assert_eq!("Глава 1", chap.title());
+        let html = String::from_utf8(chap.data().to_vec()).unwrap();
+        assert!(html.contains(r#"<img src="img1.png" alt=""/>"#));
+
+        let read = read_from_vec(Fb2Writer::write_to_mem(&back).unwrap()).unwrap();
+        assert_eq!(&[4, 5, 6], read.get_binary("img1.png").unwrap().data());
+        assert_eq!(2, read.sections().next().unwrap().children().count());
+    }
+
+    #[test]
+    fn test_epub_to_fb2_without_nav() {
+        use crate::prelude::adapter::epub_to_fb2;
+
+        let mut epub = EpubBuilder::new()
+            .with_title("书")
+            .custome_nav(true)
+            .add_assets("image/1.png", vec![1])
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("一")
+                    .with_file_name("text/1.xhtml")
+                    .with_data(r#"<p><img src="../image/1.png"/></p>"#.as_bytes().to_vec()),
+            )
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("二")
+                    .with_file_name("text/2.xhtml")
+                    .with_data(b"<p>2</p>".to_vec()),
+            )
+            .book()
+            .unwrap();
+        let fb2 = epub_to_fb2(&mut epub).unwrap();
+        let titles: Vec<&str> = fb2.sections().map(|f| f.title()).collect();
+        assert_eq!(vec!["一", "二"], titles);
+        assert_eq!(
+            r#"<p><img src="1.png"/></p>"#,
+            String::from_utf8(fb2.sections().next().unwrap().data().to_vec()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cbz() {
+        use crate::cbz::core::{tests::png, CbzBook, CbzPage};
+        use crate::prelude::adapter::{cbz_to_epub, epub_to_cbz};
+
+        let cbz = CbzBook::default()
+            .with_title("漫画")
+            .with_right_to_left(true)
+            .with_page(CbzPage::new("第1话/1.png", png(100, 150)))
+            .with_page(CbzPage::new("第1话/2.png", png(100, 150)))
+            .with_page(CbzPage::new("第1话/3.png", png(200, 150)))
+            .with_page(CbzPage::new("第1话/4.png", png(100, 150)))
+            .with_page(CbzPage::new("第2话/1.png", png(100, 150)));
+        let mut epub = cbz_to_epub(&cbz).unwrap();
+        let properties: Vec<&str> = epub
+            .chapters()
+            .map(|f| f.spine_properties().unwrap_or_default())
+            .collect();
+        assert_eq!(
+            vec![
+                "page-spread-left",
+                "page-spread-right",
+                "rendition:page-spread-center",
+                "page-spread-right",
+                "page-spread-left"
+            ],
+            properties
+        );
+        let nav: Vec<(&str, &str)> = epub.nav().map(|f| (f.title(), f.file_name())).collect();
+        assert_eq!(
+            vec![("第1话", "page_0001.xhtml"), ("第2话", "page_0005.xhtml")],
+            nav
+        );
+
+        assert!(epub.identifier().starts_with("urn:uuid:"));
+        assert_eq!(Some("zh"), epub.language());
+        let d = crate::prelude::validate::validate_book(&mut epub).unwrap();
+        assert!(d.is_empty(), "{:?}", d);
+
+        let data = EpubWriter::write_to_mem(&mut epub, true).unwrap();
+        let opf = {
+            let mut zip = zip::ZipArchive::new(std::io::Cursor::new(data.clone())).unwrap();
+            let mut v = String::new();
+            std::io::Read::read_to_string(&mut zip.by_name("OEBPS/content.opf").unwrap(), &mut v)
+                .unwrap();
+            v
+        };
+        assert!(opf.contains(r#"<meta property="rendition:layout">pre-paginated</meta>"#));
+        assert!(opf.contains(r#"<spine toc="ncx" page-progression-direction="rtl">"#));
+        assert!(opf.contains(r#"<itemref idref="toc" linear="no"/>"#));
+        assert!(
+            opf.contains(r#"<itemref idref="chap_2" properties="rendition:page-spread-center"/>"#)
+        );
+
+        let mut book = crate::prelude::read_from_vec(data).unwrap();
+        let html = book
+            .get_chapter_mut("page_0003.xhtml")
+            .unwrap()
+            .raw_data()
+            .unwrap()
+            .to_string();
+        assert!(html.contains(r#"<meta name="viewport" content="width=200, height=150"/>"#));
+        assert!(!html.contains("<h1"));
+
+        assert_eq!(Some("rtl"), book.page_direction());
+
+        let back = epub_to_cbz(&mut epub).unwrap();
+        assert!(back.right_to_left());
+        assert_eq!(5, back.pages().count());
+        assert_eq!("images/0001.png", back.pages().next().unwrap().file_name());
+        assert!(back.pages().nth(2).unwrap().is_spread());
+
+        let back = epub_to_cbz(&mut book).unwrap();
+        assert!(back.right_to_left());
+        assert_eq!(5, back.pages().count());
+    }
 }
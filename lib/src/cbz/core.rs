@@ -0,0 +1,199 @@
+//!
+//! cbz 的数据结构
+//!
+//! cbz 是图片的 zip 压缩包，可选的 `ComicInfo.xml` 保存元数据
+//!
+
+/// 一页图片
+#[derive(Debug, Default, Clone)]
+pub struct CbzPage {
+    /// 压缩包内的路径
+    pub(crate) file_name: String,
+    pub(crate) data: Vec<u8>,
+}
+
+impl CbzPage {
+    pub fn new<T: Into<String>>(file_name: T, data: Vec<u8>) -> Self {
+        CbzPage {
+            file_name: file_name.into(),
+            data,
+        }
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// 图片尺寸，无法识别时返回 None
+    pub fn size(&self) -> Option<(u32, u32)> {
+        image_size(&self.data)
+    }
+
+    /// 是否是跨页，宽大于高时视为跨页
+    pub fn is_spread(&self) -> bool {
+        self.size().is_some_and(|(w, h)| w > h)
+    }
+
+    /// 所在目录，没有时返回空字符串
+    pub(crate) fn dir(&self) -> &str {
+        self.file_name
+            .rfind('/')
+            .map_or("", |index| &self.file_name[..index])
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CbzBook {
+    pub(crate) info: crate::common::BookInfo,
+    /// 按阅读顺序排列的页
+    pub(crate) pages: Vec<CbzPage>,
+    /// 从右往左翻页
+    pub(crate) right_to_left: bool,
+}
+
+impl CbzBook {
+    iepub_derive::option_string_method!(info, creator);
+    iepub_derive::option_string_method!(info, description);
+    iepub_derive::option_string_method!(info, date);
+    iepub_derive::option_string_method!(info, publisher);
+    iepub_derive::option_string_method!(info, subject);
+    iepub_derive::option_string_method!(info, language);
+
+    pub fn set_title<T: AsRef<str>>(&mut self, title: T) {
+        self.info.title.clear();
+        self.info.title.push_str(title.as_ref());
+    }
+    pub fn title(&self) -> &str {
+        self.info.title.as_str()
+    }
+    pub fn with_title<T: AsRef<str>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    pub fn pages(&self) -> std::slice::Iter<'_, CbzPage> {
+        self.pages.iter()
+    }
+
+    pub fn add_page(&mut self, page: CbzPage) {
+        self.pages.push(page);
+    }
+
+    pub fn with_page(mut self, page: CbzPage) -> Self {
+        self.add_page(page);
+        self
+    }
+
+    /// 是否从右往左翻页，日漫一般是从右往左
+    pub fn right_to_left(&self) -> bool {
+        self.right_to_left
+    }
+
+    pub fn set_right_to_left(&mut self, value: bool) {
+        self.right_to_left = value;
+    }
+
+    pub fn with_right_to_left(mut self, value: bool) -> Self {
+        self.set_right_to_left(value);
+        self
+    }
+}
+
+///
+/// 读取图片尺寸
+///
+/// 只解析文件头，支持 png、jpeg、gif、webp、bmp
+///
+pub(crate) fn image_size(data: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |i: usize| Some(u16::from_be_bytes(data.get(i..i + 2)?.try_into().ok()?) as u32);
+    let le16 = |i: usize| Some(u16::from_le_bytes(data.get(i..i + 2)?.try_into().ok()?) as u32);
+    let be32 = |i: usize| Some(u32::from_be_bytes(data.get(i..i + 4)?.try_into().ok()?));
+    let le32 = |i: usize| Some(u32::from_le_bytes(data.get(i..i + 4)?.try_into().ok()?));
+
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some((be32(16)?, be32(20)?));
+    }
+    if data.starts_with(b"GIF8") {
+        return Some((le16(6)?, le16(8)?));
+    }
+    if data.starts_with(b"BM") {
+        // 高度为负数时表示从上往下存储
+        return Some((le32(18)?, (le32(22)? as i32).unsigned_abs()));
+    }
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        return match data.get(12..16)? {
+            b"VP8 " => Some((le16(26)? & 0x3fff, le16(28)? & 0x3fff)),
+            b"VP8L" => {
+                let v = le32(21)?;
+                Some(((v & 0x3fff) + 1, ((v >> 14) & 0x3fff) + 1))
+            }
+            b"VP8X" => {
+                let w = le32(24)? & 0xff_ffff;
+                let h = le32(27)? & 0xff_ffff;
+                Some((w + 1, h + 1))
+            }
+            _ => None,
+        };
+    }
+    if data.starts_with(&[0xff, 0xd8]) {
+        // 查找 SOF 段
+        let mut pos = 2;
+        while pos + 4 <= data.len() {
+            if data[pos] != 0xff {
+                pos += 1;
+                continue;
+            }
+            let marker = data[pos + 1];
+            if marker == 0xff {
+                pos += 1;
+                continue;
+            }
+            if matches!(marker, 0xd0..=0xd9 | 0x01) {
+                pos += 2;
+                continue;
+            }
+            if matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+                return Some((be16(pos + 7)?, be16(pos + 5)?));
+            }
+            pos += 2 + be16(pos + 2)? as usize;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::image_size;
+
+    /// 指定尺寸的 png 文件头
+    pub(crate) fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut v = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        v.extend_from_slice(&width.to_be_bytes());
+        v.extend_from_slice(&height.to_be_bytes());
+        v.extend_from_slice(&[8, 6, 0, 0, 0]);
+        v
+    }
+
+    #[test]
+    fn test_image_size() {
+        assert_eq!(Some((300, 200)), image_size(&png(300, 200)));
+
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend_from_slice(&[0x20, 0x01, 0x10, 0x00]);
+        assert_eq!(Some((288, 16)), image_size(&gif));
+
+        // APP0 后面是 SOF0
+        let jpeg = [
+            0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, 0xff, 0xc0, 0x00, 0x11, 0x08, 0x02,
+            0x58, 0x03, 0x20,
+        ];
+        assert_eq!(Some((800, 600)), image_size(&jpeg));
+
+        assert_eq!(None, image_size(b"not image"));
+        assert_eq!(None, image_size(&[0xff, 0xd8, 0xff]));
+    }
+}
@@ -0,0 +1,4 @@
+//! cbz 漫画压缩包读写
+pub(crate) mod core;
+pub(crate) mod reader;
+pub(crate) mod writer;
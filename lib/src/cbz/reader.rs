@@ -0,0 +1,218 @@
+//!
+//! 读取 cbz
+//!
+//! 图片按照路径自然排序，`ComicInfo.xml` 中的元数据会被读取
+//!
+use std::{cmp::Ordering, io::Read, path::Path};
+
+use quick_xml::{events::Event, Reader};
+
+use crate::common::{ErrorContext, IError, IResult, ResultContext};
+
+use super::core::{CbzBook, CbzPage};
+
+/// 支持的图片后缀
+const IMAGE_EXT: &[&str] = &[".jpg", ".jpeg", ".png", ".gif", ".webp", ".bmp"];
+
+/// 从文件读取cbz
+pub fn read_from_file<P: AsRef<Path>>(file: P) -> IResult<CbzBook> {
+    read_from_vec(std::fs::read(file)?)
+}
+
+/// 从内存读取cbz
+pub fn read_from_vec(data: Vec<u8>) -> IResult<CbzBook> {
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(data))?;
+    let mut book = CbzBook::default();
+    let mut names: Vec<String> = zip
+        .file_names()
+        .filter(|f| is_page(f))
+        .map(|f| f.to_string())
+        .collect();
+    names.sort_by(|a, b| natural_cmp(a, b));
+
+    for name in names {
+        book.add_page(CbzPage::new(name.as_str(), read_entry(&mut zip, &name)?));
+    }
+    if book.pages.is_empty() {
+        return Err(IError::Parse(std::borrow::Cow::from("cbz has no image")));
+    }
+
+    let info = zip
+        .file_names()
+        .find(|f| f.eq_ignore_ascii_case("ComicInfo.xml"))
+        .map(|f| f.to_string());
+    if let Some(name) = info {
+        let data = read_entry(&mut zip, &name)?;
+        read_comic_info(&mut book, &String::from_utf8_lossy(&data))
+            .context(|| ErrorContext::new("read comic info").with_entry(name.as_str()))?;
+    }
+    Ok(book)
+}
+
+fn read_entry(zip: &mut zip::ZipArchive<std::io::Cursor<Vec<u8>>>, name: &str) -> IResult<Vec<u8>> {
+    let mut entry = zip.by_name(name)?;
+    let mut v = Vec::new();
+    entry
+        .read_to_end(&mut v)
+        .context(|| ErrorContext::new("read entry").with_entry(name))?;
+    Ok(v)
+}
+
+/// 是否是页面图片，跳过隐藏文件以及 macOS 压缩时生成的目录
+fn is_page(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    IMAGE_EXT.iter().any(|f| lower.ends_with(f))
+        && !lower.starts_with("__macosx/")
+        && !lower.split('/').any(|f| f.starts_with('.'))
+}
+
+///
+/// 自然排序，连续的数字按数值比较，其余按不区分大小写的字符比较
+///
+/// 例如 `2.jpg` 排在 `10.jpg` 之前
+///
+pub(crate) fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut x = a.chars().peekable();
+    let mut y = b.chars().peekable();
+    loop {
+        match (x.peek().copied(), y.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(c1), Some(c2)) if c1.is_ascii_digit() && c2.is_ascii_digit() => {
+                let mut n1 = String::new();
+                while let Some(c) = x.next_if(|c| c.is_ascii_digit()) {
+                    n1.push(c);
+                }
+                let mut n2 = String::new();
+                while let Some(c) = y.next_if(|c| c.is_ascii_digit()) {
+                    n2.push(c);
+                }
+                let t1 = n1.trim_start_matches('0');
+                let t2 = n2.trim_start_matches('0');
+                let ord = t1.len().cmp(&t2.len()).then_with(|| t1.cmp(t2));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(c1), Some(c2)) => {
+                let ord = c1.to_lowercase().cmp(c2.to_lowercase());
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                x.next();
+                y.next();
+            }
+        }
+    }
+}
+
+/// 读取 ComicInfo.xml
+fn read_comic_info(book: &mut CbzBook, xml: &str) -> IResult<()> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut name = String::new();
+    let mut series = String::new();
+    let (mut year, mut month, mut day) = (String::new(), String::new(), String::new());
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+            }
+            Ok(Event::End(_)) => name.clear(),
+            Ok(Event::Text(e)) => {
+                let text = e
+                    .unescape()
+                    .map_err(IError::Xml)
+                    .context(|| ErrorContext::default().with_position(reader.error_position()))?
+                    .to_string();
+                match name.as_str() {
+                    "Title" => book.set_title(text),
+                    "Series" => series = text,
+                    "Writer" => book.set_creator(text),
+                    "Summary" => book.set_description(text),
+                    "Publisher" => book.set_publisher(text),
+                    "Genre" => book.set_subject(text),
+                    "LanguageISO" => book.set_language(text),
+                    "Year" => year = text,
+                    "Month" => month = text,
+                    "Day" => day = text,
+                    "Manga" => book.set_right_to_left(text == "YesAndRightToLeft"),
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                return Err(IError::Xml(e)
+                    .with_context(ErrorContext::default().with_position(reader.error_position())))
+            }
+            _ => {}
+        }
+    }
+    if book.title().is_empty() {
+        book.set_title(series);
+    }
+    if !year.is_empty() {
+        let mut date = year;
+        for v in [month, day] {
+            match v.parse::<u32>() {
+                Ok(v) => date.push_str(&format!("-{:02}", v)),
+                Err(_) => break,
+            }
+        }
+        book.set_date(date);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{natural_cmp, read_from_vec};
+    use crate::cbz::core::tests::png;
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut v = vec!["10.jpg", "2.jpg", "1.jpg", "a/002.png", "A/1.png", "b.jpg"];
+        v.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            vec!["1.jpg", "2.jpg", "10.jpg", "A/1.png", "a/002.png", "b.jpg"],
+            v
+        );
+    }
+
+    #[test]
+    fn test_read() {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, data) in [
+            ("p10.png", png(200, 100)),
+            ("p2.png", png(100, 200)),
+            ("p1.png", png(100, 200)),
+            ("__MACOSX/._p1.png", Vec::new()),
+            (".hidden.png", Vec::new()),
+            ("readme.txt", Vec::new()),
+        ] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(&data).unwrap();
+        }
+        zip.start_file("ComicInfo.xml", options).unwrap();
+        zip.write_all(
+            r#"<?xml version="1.0"?><ComicInfo><Series>系列</Series><Writer>作者</Writer><Year>2024</Year><Month>3</Month><Manga>YesAndRightToLeft</Manga></ComicInfo>"#
+                .as_bytes(),
+        )
+        .unwrap();
+        let data = zip.finish().unwrap().into_inner();
+
+        let book = read_from_vec(data).unwrap();
+        let names: Vec<&str> = book.pages().map(|f| f.file_name()).collect();
+        assert_eq!(vec!["p1.png", "p2.png", "p10.png"], names);
+        assert_eq!("系列", book.title());
+        assert_eq!(Some("作者"), book.creator());
+        assert_eq!(Some("2024-03"), book.date());
+        assert!(book.right_to_left());
+        assert!(book.pages().nth(2).unwrap().is_spread());
+        assert!(!book.pages().next().unwrap().is_spread());
+    }
+}
@@ -0,0 +1,135 @@
+//!
+//! 写入 cbz
+//!
+use std::{
+    fs::File,
+    io::{Seek, Write},
+    path::Path,
+};
+
+use crate::common::{escape_xml, IResult};
+
+use super::core::CbzBook;
+
+///
+/// 写入cbz
+///
+/// 页面按顺序重命名为 `0001.jpg`、`0002.png` 等，元数据写入 `ComicInfo.xml`
+///
+pub struct CbzWriter<T: Write + Seek> {
+    inner: T,
+}
+
+impl CbzWriter<File> {
+    /// 写入文件
+    pub fn write_to_file<P: AsRef<Path>>(file: P, book: &CbzBook) -> IResult<()> {
+        CbzWriter::new(File::create(file)?).write(book)
+    }
+}
+
+impl CbzWriter<std::io::Cursor<Vec<u8>>> {
+    /// 写入内存
+    pub fn write_to_mem(book: &CbzBook) -> IResult<Vec<u8>> {
+        let mut v = std::io::Cursor::new(Vec::new());
+        CbzWriter::new(&mut v).write(book)?;
+        Ok(v.into_inner())
+    }
+}
+
+impl<T: Write + Seek> CbzWriter<T> {
+    pub fn new(inner: T) -> Self {
+        CbzWriter { inner }
+    }
+
+    pub fn write(&mut self, book: &CbzBook) -> IResult<()> {
+        let mut zip = zip::ZipWriter::new(&mut self.inner);
+        // 图片本身已经压缩过
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        for (index, page) in book.pages().enumerate() {
+            let ext = page
+                .file_name()
+                .rfind('.')
+                .map_or("", |i| &page.file_name()[i..]);
+            zip.start_file(format!("{:04}{}", index + 1, ext.to_lowercase()), options)?;
+            zip.write_all(page.data())?;
+        }
+        zip.start_file(
+            "ComicInfo.xml",
+            options.compression_method(zip::CompressionMethod::Deflated),
+        )?;
+        zip.write_all(to_comic_info(book).as_bytes())?;
+        zip.finish()?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+fn tag(out: &mut String, name: &str, value: Option<&str>) {
+    if let Some(v) = value.map(|f| f.trim()).filter(|f| !f.is_empty()) {
+        out.push_str(&format!("  <{name}>{}</{name}>\n", escape_xml(v)));
+    }
+}
+
+/// 生成 ComicInfo.xml
+fn to_comic_info(book: &CbzBook) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<ComicInfo xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\">\n",
+    );
+    tag(&mut xml, "Title", Some(book.title()));
+    tag(&mut xml, "Summary", book.description());
+    // 日期只取开头的年月日
+    let date = book.date().unwrap_or_default().split('-').map(|f| {
+        let end = f.find(|c: char| !c.is_ascii_digit()).unwrap_or(f.len());
+        f[..end].parse::<u32>().ok()
+    });
+    for (name, value) in ["Year", "Month", "Day"].into_iter().zip(date) {
+        let Some(value) = value else {
+            break;
+        };
+        tag(&mut xml, name, Some(&value.to_string()));
+    }
+    tag(&mut xml, "Writer", book.creator());
+    tag(&mut xml, "Publisher", book.publisher());
+    tag(&mut xml, "Genre", book.subject());
+    tag(&mut xml, "PageCount", Some(&book.pages.len().to_string()));
+    tag(&mut xml, "LanguageISO", book.language());
+    if book.right_to_left() {
+        tag(&mut xml, "Manga", Some("YesAndRightToLeft"));
+    }
+    xml.push_str("</ComicInfo>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cbz::{
+        core::{tests::png, CbzBook, CbzPage},
+        reader::read_from_vec,
+    };
+
+    use super::CbzWriter;
+
+    #[test]
+    fn test_write() {
+        let book = CbzBook::default()
+            .with_title("标题 & 副标题")
+            .with_creator("作者")
+            .with_language("ja")
+            .with_date("2024-03-14T00:00:00Z")
+            .with_right_to_left(true)
+            .with_page(CbzPage::new("b/10.PNG", png(100, 200)))
+            .with_page(CbzPage::new("a/2.png", png(200, 100)));
+        let data = CbzWriter::write_to_mem(&book).unwrap();
+
+        let read = read_from_vec(data).unwrap();
+        let names: Vec<&str> = read.pages().map(|f| f.file_name()).collect();
+        assert_eq!(vec!["0001.png", "0002.png"], names);
+        assert!(read.pages().nth(1).unwrap().is_spread());
+        assert_eq!("标题 & 副标题", read.title());
+        assert_eq!(Some("作者"), read.creator());
+        assert_eq!(Some("2024-03-14"), read.date());
+        assert_eq!(Some("ja"), read.language());
+        assert!(read.right_to_left());
+    }
+}
@@ -113,12 +113,17 @@ pub(crate) mod epub {
     use crate::exec_err;
     use crate::Book;
     use iepub::prelude::adapter::add_into_epub;
+    use iepub::prelude::adapter::epub_to_cbz;
+    use iepub::prelude::adapter::epub_to_fb2;
     use iepub::prelude::adapter::epub_to_mobi;
     use iepub::prelude::appender::write_metadata;
     use iepub::prelude::read_from_file;
+    use iepub::prelude::read_from_file_lenient;
+    use iepub::prelude::validate::{is_valid, to_json, validate_file, Severity};
     use iepub::prelude::EpubBook;
     use iepub::prelude::EpubBuilder;
     use iepub::prelude::EpubNav;
+    use iepub::prelude::EpubWriter;
     use iepub::prelude::IResult;
     use iepub::prelude::MobiBook;
     use iepub::prelude::MobiWriter;
@@ -303,6 +308,24 @@ pub(crate) mod epub {
                     OptionDef::create("contributor", "contributor", OptionType::NoParamter, false),
                     OptionDef::create("modify", "最后修改时间", OptionType::NoParamter, false),
                     OptionDef::create("generator", "电子书创建者", OptionType::NoParamter, false),
+                    OptionDef::create(
+                        "layout",
+                        "版式，pre-paginated为固定版式",
+                        OptionType::NoParamter,
+                        false,
+                    ),
+                    OptionDef::create(
+                        "orientation",
+                        "固定版式的屏幕方向",
+                        OptionType::NoParamter,
+                        false,
+                    ),
+                    OptionDef::create(
+                        "spread",
+                        "固定版式的跨页方式",
+                        OptionType::NoParamter,
+                        false,
+                    ),
                     OptionDef::create("all", "所有元数据", OptionType::NoParamter, false),
                 ],
             }
@@ -328,6 +351,9 @@ pub(crate) mod epub {
                         "contributor" => println!("{}", book.contributor().unwrap_or("")),
                         "modify" => println!("{}", book.last_modify().unwrap_or("")),
                         "generator" => println!("{}", book.generator().unwrap_or("")),
+                        "layout" => println!("{}", book.rendition_layout().unwrap_or("")),
+                        "orientation" => println!("{}", book.rendition_orientation().unwrap_or("")),
+                        "spread" => println!("{}", book.rendition_spread().unwrap_or("")),
                         "all" => {
                             println!("title: {}", book.title());
                             println!("author: {}", book.creator().unwrap_or(""));
@@ -340,6 +366,12 @@ pub(crate) mod epub {
                             println!("contributor: {}", book.contributor().unwrap_or(""));
                             println!("modify: {}", book.last_modify().unwrap_or(""));
                             println!("generator: {}", book.generator().unwrap_or(""));
+                            println!("layout: {}", book.rendition_layout().unwrap_or(""));
+                            println!(
+                                "orientation: {}",
+                                book.rendition_orientation().unwrap_or("")
+                            );
+                            println!("spread: {}", book.rendition_spread().unwrap_or(""));
                         }
                         _ => {}
                     }
@@ -734,7 +766,9 @@ pub(crate) mod epub {
             arg::CommandOptionDef {
                 command: "convert".to_string(),
                 support_args: 0,
-                desc: "转换成mobi".to_string(),
+                desc:
+                    "转换成mobi，输出文件后缀为.fb2或.fb2.zip时转换成fb2，为.cbz时提取图片转换成cbz"
+                        .to_string(),
                 opts: vec![
                     OptionDef::create("f", "输出文件路径", OptionType::String, true),
                     OptionDef::create("n", "不添加标题，默认添加", OptionType::NoParamter, false),
@@ -759,6 +793,43 @@ pub(crate) mod epub {
             let append_title = opts.iter().find(|f| f.key == "n").is_none();
 
             if let Book::EPUB(book) = book {
+                let lower = path.to_lowercase();
+                if lower.ends_with(".fb2") || lower.ends_with(".fb2.zip") {
+                    if std::path::Path::new(path.as_str()).exists()
+                        && !is_overiade(global_opts, opts)
+                        && get_single_input("Override file？(y/n)")
+                            .unwrap()
+                            .to_lowercase()
+                            != "y"
+                    {
+                        return;
+                    }
+                    msg!("writing file {}", path);
+                    if let Err(e) = epub_to_fb2(book).and_then(|f| {
+                        iepub::prelude::fb2::Fb2Writer::write_to_file(path.as_str(), &f)
+                    }) {
+                        exec_err!("err: {}", e);
+                    }
+                    return;
+                }
+                if lower.ends_with(".cbz") {
+                    if std::path::Path::new(path.as_str()).exists()
+                        && !is_overiade(global_opts, opts)
+                        && get_single_input("Override file？(y/n)")
+                            .unwrap()
+                            .to_lowercase()
+                            != "y"
+                    {
+                        return;
+                    }
+                    msg!("writing file {}", path);
+                    if let Err(e) = epub_to_cbz(book).and_then(|f| {
+                        iepub::prelude::cbz::CbzWriter::write_to_file(path.as_str(), &f)
+                    }) {
+                        exec_err!("err: {}", e);
+                    }
+                    return;
+                }
                 let _ = epub_to_mobi(book)
                     .map(|mobi| {
                         (
@@ -793,11 +864,120 @@ pub(crate) mod epub {
             }
         }
     );
+    create_command!(
+        Validate,
+        "validate",
+        {
+            arg::CommandOptionDef {
+                command: "validate".to_string(),
+                support_args: 0,
+                desc: "校验电子书结构，存在错误时返回非0".to_string(),
+                opts: vec![OptionDef::create(
+                    "json",
+                    "以json格式输出",
+                    OptionType::NoParamter,
+                    false,
+                )],
+            }
+        },
+        fn exec(
+            &self,
+            _book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let path = global_opts
+                .iter()
+                .find(|s| s.key == "i")
+                .and_then(|f| f.value.clone())
+                .unwrap();
+            msg!("validating file {}", path);
+            let diagnostics = validate_file(path.as_str()).unwrap_or_else(|e| {
+                exec_err!("err: {}", e);
+            });
+            if opts.iter().any(|s| s.key == "json") {
+                println!("{}", to_json(&diagnostics));
+            } else {
+                for ele in &diagnostics {
+                    println!("{}", ele);
+                }
+            }
+            if !is_valid(&diagnostics) {
+                exec_err!(
+                    "{} errors found",
+                    diagnostics
+                        .iter()
+                        .filter(|v| v.severity() == Severity::Error)
+                        .count()
+                );
+            }
+        }
+    );
+
+    create_command!(
+        Repair,
+        "repair",
+        {
+            arg::CommandOptionDef {
+                command: "repair".to_string(),
+                support_args: 0,
+                desc: "尝试修复损坏的电子书并输出修复后的副本".to_string(),
+                opts: vec![
+                    OptionDef::create("out", "输出文件位置", OptionType::String, true),
+                    OptionDef::create("y", "输出文件存在时直接覆盖", OptionType::NoParamter, false),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            _book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let path = global_opts
+                .iter()
+                .find(|s| s.key == "i")
+                .and_then(|f| f.value.clone())
+                .unwrap();
+            let out = opts
+                .iter()
+                .find(|s| s.key == "out")
+                .and_then(|f| f.value.clone())
+                .unwrap();
+            if std::path::Path::new(&out).exists()
+                && !is_overiade(global_opts, opts)
+                && get_single_input("Override file？(y/n)")
+                    .unwrap()
+                    .to_lowercase()
+                    != "y"
+            {
+                return;
+            }
+            msg!("repairing file {}", path);
+            let (mut book, repairs) = read_from_file_lenient(path.as_str()).unwrap_or_else(|e| {
+                exec_err!("err: {}", e);
+            });
+            for ele in &repairs {
+                println!("{}", ele);
+            }
+            msg!("writing book to {}", out);
+            if let Err(e) = EpubWriter::write_to_file(out.as_str(), &mut book, false) {
+                exec_err!("err: {}", e);
+            }
+        }
+    );
 }
 
 pub(crate) mod mobi {
 
-    use iepub::prelude::{adapter::mobi_to_epub, EpubWriter, MobiNav};
+    use iepub::prelude::{
+        adapter::mobi_to_epub,
+        inspect::inspect_file,
+        validate::{is_valid, Severity},
+        EpubWriter, MobiNav,
+    };
 
     use crate::{
         cli::arg::{self, ArgOption, OptionDef, OptionType},
@@ -1151,4 +1331,1292 @@ pub(crate) mod mobi {
             }
         }
     );
+
+    create_command!(
+        Inspect,
+        "inspect",
+        {
+            arg::CommandOptionDef {
+                command: "inspect".to_string(),
+                support_args: 0,
+                desc: "输出mobi内部结构并检查，存在错误时返回非0".to_string(),
+                opts: vec![],
+            }
+        },
+        fn exec(
+            &self,
+            _book: &mut Book,
+            global_opts: &[ArgOption],
+            _opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let path = global_opts
+                .iter()
+                .find(|s| s.key == "i")
+                .and_then(|f| f.value.clone())
+                .unwrap();
+            msg!("inspecting file {}", path);
+            let inspection = inspect_file(path.as_str()).unwrap_or_else(|e| {
+                exec_err!("err: {}", e);
+            });
+            print!("{}", inspection);
+            if !is_valid(inspection.issues()) {
+                exec_err!(
+                    "{} errors found",
+                    inspection
+                        .issues()
+                        .iter()
+                        .filter(|v| v.severity() == Severity::Error)
+                        .count()
+                );
+            }
+        }
+    );
+}
+
+pub(crate) mod fb2 {
+    use iepub::prelude::adapter::{fb2_to_epub, fb2_to_mobi};
+    use iepub::prelude::{EpubWriter, MobiWriter};
+
+    use crate::{
+        cli::arg::{self, ArgOption, OptionDef, OptionType},
+        exec_err, msg, Book, Command,
+    };
+
+    use super::{get_single_input, is_overiade};
+
+    create_command!(
+        BookInfoGetter,
+        "get-info",
+        {
+            arg::CommandOptionDef {
+                command: "get-info".to_string(),
+                support_args: 0,
+                desc: "提取数据元数据".to_string(),
+                opts: vec![
+                    OptionDef::create("title", "标题", OptionType::NoParamter, false),
+                    OptionDef::create("author", "作者", OptionType::NoParamter, false),
+                    OptionDef::create("isbn", "isbn", OptionType::NoParamter, false),
+                    OptionDef::create("publisher", "出版社", OptionType::NoParamter, false),
+                    OptionDef::create("date", "出版日期", OptionType::NoParamter, false),
+                    OptionDef::create("desc", "简介", OptionType::NoParamter, false),
+                    OptionDef::create("subject", "subject", OptionType::NoParamter, false),
+                    OptionDef::create("lang", "语言", OptionType::NoParamter, false),
+                    OptionDef::create("generator", "电子书创建者", OptionType::NoParamter, false),
+                    OptionDef::create("all", "所有元数据", OptionType::NoParamter, false),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            _global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            if let Book::FB2(book) = book {
+                for ele in opts {
+                    match ele.key.as_str() {
+                        "title" => println!("{}", book.title()),
+                        "author" => println!("{}", book.creator().unwrap_or("")),
+                        "isbn" => println!("{}", book.identifier()),
+                        "publisher" => println!("{}", book.publisher().unwrap_or("")),
+                        "date" => println!("{}", book.date().unwrap_or("")),
+                        "desc" => println!("{}", book.description().unwrap_or("")),
+                        "subject" => println!("{}", book.subject().unwrap_or("")),
+                        "lang" => println!("{}", book.lang().unwrap_or("")),
+                        "generator" => println!("{}", book.generator().unwrap_or("")),
+                        "all" => {
+                            println!("title: {}", book.title());
+                            println!("author: {}", book.creator().unwrap_or(""));
+                            println!("isbn: {}", book.identifier());
+                            println!("publisher: {}", book.publisher().unwrap_or(""));
+                            println!("date: {}", book.date().unwrap_or(""));
+                            println!("desc: {}", book.description().unwrap_or(""));
+                            println!("subject: {}", book.subject().unwrap_or(""));
+                            println!("lang: {}", book.lang().unwrap_or(""));
+                            println!("generator: {}", book.generator().unwrap_or(""));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    );
+
+    create_command!(
+        FormatConvert,
+        "convert",
+        {
+            arg::CommandOptionDef {
+                command: "convert".to_string(),
+                support_args: 0,
+                desc: "转换成epub，输出文件后缀为.mobi时转换成mobi".to_string(),
+                opts: vec![
+                    OptionDef::create("f", "输出文件路径", OptionType::String, true),
+                    OptionDef::create("n", "不添加标题，默认添加", OptionType::NoParamter, false),
+                    OptionDef::over(),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let path = opts
+                .iter()
+                .find(|f| f.key == "f")
+                .and_then(|f| f.value.clone())
+                .unwrap();
+            let append_title = opts.iter().find(|f| f.key == "n").is_none();
+
+            if let Book::FB2(book) = book {
+                if std::path::Path::new(path.as_str()).exists()
+                    && !is_overiade(global_opts, opts)
+                    && get_single_input("Override file？(y/n)")
+                        .unwrap()
+                        .to_lowercase()
+                        != "y"
+                {
+                    return;
+                }
+                msg!("writing file {}", path);
+                let res = if path.to_lowercase().ends_with(".mobi") {
+                    fb2_to_mobi(book).and_then(|mobi| {
+                        MobiWriter::write_to_file(path.as_str(), &mobi, append_title)
+                    })
+                } else {
+                    fb2_to_epub(book).and_then(|mut epub| {
+                        EpubWriter::write_to_file(path.as_str(), &mut epub, append_title)
+                    })
+                };
+                if let Err(e) = res {
+                    exec_err!("err: {}", e);
+                }
+            }
+        }
+    );
+}
+
+pub(crate) mod cbz {
+    use iepub::prelude::adapter::cbz_to_epub;
+    use iepub::prelude::EpubWriter;
+
+    use crate::{
+        cli::arg::{self, ArgOption, OptionDef, OptionType},
+        exec_err, msg, Book, Command,
+    };
+
+    use super::{get_single_input, is_overiade};
+
+    create_command!(
+        FormatConvert,
+        "convert",
+        {
+            arg::CommandOptionDef {
+                command: "convert".to_string(),
+                support_args: 0,
+                desc: "转换成固定版式的epub，每张图片一页".to_string(),
+                opts: vec![
+                    OptionDef::create("f", "输出文件路径", OptionType::String, true),
+                    OptionDef::create(
+                        "title",
+                        "书名，默认使用ComicInfo.xml中的标题或文件名",
+                        OptionType::String,
+                        false,
+                    ),
+                    OptionDef::create("author", "作者", OptionType::String, false),
+                    OptionDef::create(
+                        "rtl",
+                        "从右往左翻页，默认根据ComicInfo.xml判断",
+                        OptionType::NoParamter,
+                        false,
+                    ),
+                    OptionDef::create("ltr", "从左往右翻页", OptionType::NoParamter, false),
+                    OptionDef::over(),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let path = opts
+                .iter()
+                .find(|f| f.key == "f")
+                .and_then(|f| f.value.clone())
+                .unwrap();
+
+            if let Book::CBZ(book) = book {
+                for ele in opts {
+                    match (ele.key.as_str(), ele.value.as_deref()) {
+                        ("title", Some(v)) => book.set_title(v),
+                        ("author", Some(v)) => book.set_creator(v),
+                        ("rtl", _) => book.set_right_to_left(true),
+                        ("ltr", _) => book.set_right_to_left(false),
+                        _ => {}
+                    }
+                }
+                if book.title().is_empty() {
+                    // 使用输入文件名作为书名
+                    let name = global_opts
+                        .iter()
+                        .find(|f| f.key == "i")
+                        .and_then(|f| f.value.as_deref())
+                        .and_then(|f| std::path::Path::new(f).file_stem())
+                        .map(|f| f.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    book.set_title(name);
+                }
+                if std::path::Path::new(path.as_str()).exists()
+                    && !is_overiade(global_opts, opts)
+                    && get_single_input("Override file？(y/n)")
+                        .unwrap()
+                        .to_lowercase()
+                        != "y"
+                {
+                    return;
+                }
+                msg!("writing file {}", path);
+                if let Err(e) = cbz_to_epub(book)
+                    .and_then(|mut epub| EpubWriter::write_to_file(path.as_str(), &mut epub, false))
+                {
+                    exec_err!("err: {}", e);
+                }
+            }
+        }
+    );
+}
+
+pub(crate) mod text {
+    use iepub::prelude::adapter::fb2_to_epub;
+    use iepub::prelude::appender::{append_chapters, append_mobi_chapters};
+    use iepub::prelude::diff::{BookDiff, BookSnapshot};
+    use iepub::prelude::export::{ExportFormat, TextExporter};
+    use iepub::prelude::markdown::MarkdownImporter;
+    use iepub::prelude::rewrite::{RewriteReport, RewriteRules};
+    use iepub::prelude::search::{SearchMatch, SearchQuery};
+    use iepub::prelude::stats::BookStats;
+    use iepub::prelude::txt::{detect_encoding, TxtImporter, TxtPreset};
+    use iepub::prelude::typography::TypographyPolicy;
+    #[cfg(feature = "zh_convert")]
+    use iepub::prelude::zh::{ChineseConverter, ChineseScript};
+    use iepub::prelude::{
+        read_from_file, EpubHtml, EpubWriter, IResult, MobiHtml, MobiReader, MobiWriter,
+    };
+
+    use crate::{
+        cli::arg::{self, ArgOption, OptionDef, OptionType},
+        exec_err, msg, Book, Command,
+    };
+
+    use super::{create_dir, get_single_input, is_overiade, read_book, write_file, OwnBook};
+
+    /// 封面图片在epub中的文件名
+    fn cover_name(file: &str) -> String {
+        std::path::Path::new(file)
+            .extension()
+            .map(|f| format!("cover.{}", f.to_string_lossy()))
+            .unwrap_or_else(|| "cover.jpeg".to_string())
+    }
+
+    /// 导出为文本，没有输出路径时输出到终端
+    fn export(
+        book: &mut Book,
+        format: ExportFormat,
+        global_opts: &[ArgOption],
+        opts: &[ArgOption],
+    ) {
+        let path = opts
+            .iter()
+            .find(|f| f.key == "f")
+            .and_then(|f| f.value.clone());
+        let image_dir = opts
+            .iter()
+            .find(|f| f.key == "images")
+            .and_then(|f| f.value.clone());
+
+        let mut exporter = TextExporter::new(format);
+        if let (Some(dir), Some(_)) = (&image_dir, &path) {
+            exporter = exporter.with_image_dir(dir.as_str());
+        }
+        let res = match book {
+            Book::EPUB(book) => exporter.epub(book),
+            Book::MOBI(book) => exporter.mobi(book),
+            Book::FB2(book) => fb2_to_epub(book).and_then(|mut f| exporter.epub(&mut f)),
+            Book::CBZ(_) | Book::TEXT(_) => return,
+        };
+        let export = res.unwrap_or_else(|e| {
+            exec_err!("err: {}", e);
+        });
+
+        let Some(path) = path else {
+            print!("{}", export.content());
+            return;
+        };
+        if std::path::Path::new(path.as_str()).exists()
+            && !is_overiade(global_opts, opts)
+            && get_single_input("Override file？(y/n)")
+                .unwrap()
+                .to_lowercase()
+                != "y"
+        {
+            return;
+        }
+        // 图片相对于输出文件所在目录
+        let dir = std::path::Path::new(path.as_str())
+            .parent()
+            .unwrap_or(std::path::Path::new(""));
+        if !dir.as_os_str().is_empty() {
+            create_dir(&dir.to_string_lossy());
+        }
+        msg!("writing file {}", path);
+        write_file(path.as_str(), export.content().as_bytes());
+
+        for (name, data) in export.images() {
+            let file = dir.join(name);
+            if let Some(parent) = file.parent() {
+                create_dir(&parent.to_string_lossy());
+            }
+            msg!("writing file {:?}", file);
+            write_file(&file.to_string_lossy(), data);
+        }
+    }
+
+    create_command!(
+        ToText,
+        "to-text",
+        {
+            arg::CommandOptionDef {
+                command: "to-text".to_string(),
+                support_args: 0,
+                desc: "导出为纯文本".to_string(),
+                opts: vec![
+                    OptionDef::create(
+                        "f",
+                        "输出文件路径，没有该参数则直接输出到终端",
+                        OptionType::String,
+                        false,
+                    ),
+                    OptionDef::over(),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            export(book, ExportFormat::Text, global_opts, opts);
+        }
+    );
+
+    create_command!(
+        ToMarkdown,
+        "to-markdown",
+        {
+            arg::CommandOptionDef {
+                command: "to-markdown".to_string(),
+                support_args: 0,
+                desc: "导出为markdown".to_string(),
+                opts: vec![
+                    OptionDef::create(
+                        "f",
+                        "输出文件路径，没有该参数则直接输出到终端",
+                        OptionType::String,
+                        false,
+                    ),
+                    OptionDef::create(
+                        "images",
+                        "提取图片到该目录，相对于输出文件所在目录",
+                        OptionType::String,
+                        false,
+                    ),
+                    OptionDef::over(),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            export(book, ExportFormat::Markdown, global_opts, opts);
+        }
+    );
+
+    create_command!(
+        FromTxt,
+        "from-txt",
+        {
+            arg::CommandOptionDef {
+                command: "from-txt".to_string(),
+                support_args: 0,
+                desc: "txt转换成epub或mobi，自动识别编码和章节".to_string(),
+                opts: vec![
+                    OptionDef::create(
+                        "f",
+                        "输出文件路径，根据后缀生成epub或mobi",
+                        OptionType::String,
+                        true,
+                    ),
+                    OptionDef::create("title", "书名，默认为文件名", OptionType::String, false),
+                    OptionDef::create("author", "作者", OptionType::String, false),
+                    OptionDef::create(
+                        "preset",
+                        "章节规则预设，zh 或 en，默认同时使用",
+                        OptionType::String,
+                        false,
+                    ),
+                    OptionDef::create("chapter", "自定义章节正则", OptionType::String, false),
+                    OptionDef::create("volume", "自定义卷正则", OptionType::String, false),
+                    OptionDef::create(
+                        "encoding",
+                        "文本编码，例如gbk，默认自动识别",
+                        OptionType::String,
+                        false,
+                    ),
+                    OptionDef::create("cover", "封面图片路径", OptionType::String, false),
+                    OptionDef::create(
+                        "font",
+                        "字体文件路径，没有封面时用于生成封面",
+                        OptionType::String,
+                        false,
+                    ),
+                    OptionDef::over(),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            if let Book::TEXT(input) = book {
+                let get = |key: &str| {
+                    opts.iter()
+                        .find(|f| f.key == key)
+                        .and_then(|f| f.value.clone())
+                };
+                let path = get("f").unwrap();
+
+                let data = std::fs::read(*input).unwrap_or_else(|e| {
+                    exec_err!("err: {}", e);
+                });
+
+                let mut importer = TxtImporter::new();
+                match get("preset").as_deref() {
+                    Some("zh") => importer = importer.with_preset(TxtPreset::Chinese),
+                    Some("en") => importer = importer.with_preset(TxtPreset::English),
+                    Some(v) => exec_err!("unsupport preset {}", v),
+                    None => {}
+                }
+                if let Some(v) = get("chapter") {
+                    importer = importer.with_chapter_pattern(v);
+                }
+                if let Some(v) = get("volume") {
+                    importer = importer.with_volume_pattern(v);
+                }
+                match get("encoding") {
+                    Some(v) => importer = importer.with_encoding(v),
+                    None => msg!("detected encoding {}", detect_encoding(&data)),
+                }
+
+                let title = get("title").unwrap_or_else(|| {
+                    std::path::Path::new(*input)
+                        .file_stem()
+                        .map(|f| f.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                });
+                let cover = get("cover").map(|f| {
+                    let data = std::fs::read(&f).unwrap_or_else(|e| {
+                        exec_err!("read cover err: {}", e);
+                    });
+                    (f, data)
+                });
+
+                if std::path::Path::new(path.as_str()).exists()
+                    && !is_overiade(global_opts, opts)
+                    && get_single_input("Override file？(y/n)")
+                        .unwrap()
+                        .to_lowercase()
+                        != "y"
+                {
+                    return;
+                }
+                msg!("writing file {}", path);
+                let res = if path.to_lowercase().ends_with(".mobi") {
+                    importer.mobi_builder(&data).and_then(|mut builder| {
+                        builder = builder.with_title(&title);
+                        if let Some(v) = get("author") {
+                            builder = builder.with_creator(v);
+                        }
+                        if let Some((_, data)) = cover {
+                            builder = builder.cover(data);
+                        } else if let Some(v) = get("font") {
+                            builder = builder.auto_gen_cover(true).with_font(v);
+                        }
+                        builder.file(path.as_str())
+                    })
+                } else {
+                    importer.epub_builder(&data).and_then(|mut builder| {
+                        builder = builder.with_title(&title);
+                        if let Some(v) = get("author") {
+                            builder = builder.with_creator(v);
+                        }
+                        if let Some((file, data)) = cover {
+                            builder = builder.cover(cover_name(&file), data);
+                        } else if let Some(v) = get("font") {
+                            builder = builder.auto_gen_cover(true).with_font(v);
+                        }
+                        builder.file(path.as_str())
+                    })
+                };
+                if let Err(e) = res {
+                    exec_err!("err: {}", e);
+                }
+            }
+        }
+    );
+
+    create_command!(
+        FromMarkdown,
+        "from-markdown",
+        {
+            arg::CommandOptionDef {
+                command: "from-markdown".to_string(),
+                support_args: 0,
+                desc: "markdown文件或目录转换成epub，目录中有book.toml时按mdBook读取".to_string(),
+                opts: vec![
+                    OptionDef::create("f", "输出文件路径", OptionType::String, true),
+                    OptionDef::create(
+                        "split",
+                        "按该级别及以上的标题拆分章节，默认每个文件一个章节",
+                        OptionType::Number,
+                        false,
+                    ),
+                    OptionDef::create(
+                        "title",
+                        "书名，默认读取元数据，没有则为文件名",
+                        OptionType::String,
+                        false,
+                    ),
+                    OptionDef::create("author", "作者", OptionType::String, false),
+                    OptionDef::create("cover", "封面图片路径", OptionType::String, false),
+                    OptionDef::over(),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            if let Book::TEXT(input) = book {
+                let get = |key: &str| {
+                    opts.iter()
+                        .find(|f| f.key == key)
+                        .and_then(|f| f.value.clone())
+                };
+                let path = get("f").unwrap();
+
+                let mut importer = MarkdownImporter::new();
+                importer = if std::path::Path::new(*input).is_dir() {
+                    importer.add_dir(*input)
+                } else {
+                    importer.add_file(*input)
+                };
+                if let Some(v) = get("split").and_then(|f| f.parse::<usize>().ok()) {
+                    importer = importer.with_split_level(v);
+                }
+                let cover = get("cover").map(|f| {
+                    let data = std::fs::read(&f).unwrap_or_else(|e| {
+                        exec_err!("read cover err: {}", e);
+                    });
+                    (f, data)
+                });
+
+                if std::path::Path::new(path.as_str()).exists()
+                    && !is_overiade(global_opts, opts)
+                    && get_single_input("Override file？(y/n)")
+                        .unwrap()
+                        .to_lowercase()
+                        != "y"
+                {
+                    return;
+                }
+                msg!("writing file {}", path);
+                let res = importer.epub_builder().and_then(|mut builder| {
+                    if let Some(v) = get("title") {
+                        builder = builder.with_title(v);
+                    }
+                    if let Some(v) = get("author") {
+                        builder = builder.with_creator(v);
+                    }
+                    if let Some((file, data)) = cover {
+                        builder = builder.cover(cover_name(&file), data);
+                    }
+                    let mut book = builder.book()?;
+                    if book.title().is_empty() {
+                        book.set_title(
+                            std::path::Path::new(*input)
+                                .file_stem()
+                                .map(|f| f.to_string_lossy().to_string())
+                                .unwrap_or_default(),
+                        );
+                    }
+                    EpubWriter::write_to_file(path.as_str(), &mut book, false)
+                });
+                if let Err(e) = res {
+                    exec_err!("err: {}", e);
+                }
+            }
+        }
+    );
+
+    /// 解析目标文字
+    #[cfg(feature = "zh_convert")]
+    fn parse_script(v: &str) -> Option<ChineseScript> {
+        match v.to_lowercase().as_str() {
+            "s" | "hans" | "zh-hans" => Some(ChineseScript::Simplified),
+            "t" | "hant" | "zh-hant" => Some(ChineseScript::Traditional),
+            _ => None,
+        }
+    }
+
+    /// 输出格式为 `章节文件:目录:位置: 上下文[匹配内容]上下文`，mobi 没有章节文件，使用 `#章节序号`
+    fn format_match(m: &SearchMatch) -> String {
+        format!(
+            "{}:{}:{}: {}[{}]{}",
+            m.file_name()
+                .map(String::from)
+                .unwrap_or_else(|| format!("#{}", m.chapter())),
+            m.nav().join("/"),
+            m.offset(),
+            m.before(),
+            m.text(),
+            m.after()
+        )
+    }
+
+    /// 递归查找目录中的 epub、mobi
+    fn find_books(dir: &std::path::Path, out: &mut Vec<std::path::PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|f| f.path()) {
+            if path.is_dir() {
+                find_books(&path, out);
+            } else if path.extension().is_some_and(|f| {
+                ["epub", "mobi", "azw3"]
+                    .iter()
+                    .any(|e| f.eq_ignore_ascii_case(e))
+            }) {
+                out.push(path);
+            }
+        }
+    }
+
+    fn search_file(path: &std::path::Path, query: &SearchQuery) -> IResult<Vec<SearchMatch>> {
+        let is_epub = path
+            .extension()
+            .is_some_and(|f| f.eq_ignore_ascii_case("epub"));
+        if is_epub {
+            read_from_file(path).and_then(|mut book| book.search(query))
+        } else {
+            std::fs::File::open(path)
+                .map_err(iepub::prelude::IError::Io)
+                .and_then(MobiReader::new)
+                .and_then(|mut f| f.load())
+                .and_then(|book| book.search(query))
+        }
+    }
+
+    /// 并行搜索目录中的书，按文件名顺序输出
+    fn grep_dir(dir: &str, query: &SearchQuery, jobs: usize) {
+        let mut files = Vec::new();
+        find_books(std::path::Path::new(dir), &mut files);
+        files.sort();
+        msg!("searching {} books", files.len());
+
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let mut results: Vec<(usize, IResult<Vec<SearchMatch>>)> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..jobs.clamp(1, files.len().max(1)))
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut out = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            let Some(file) = files.get(index) else {
+                                break;
+                            };
+                            out.push((index, search_file(file, query)));
+                        }
+                        out
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap_or_default())
+                .collect()
+        });
+        results.sort_by_key(|(index, _)| *index);
+        for (index, res) in results {
+            let path = files[index].display();
+            match res {
+                Ok(matches) => {
+                    for m in matches {
+                        println!("{}:{}", path, format_match(&m));
+                    }
+                }
+                Err(e) => eprintln!("{}: {}", path, e),
+            }
+        }
+    }
+
+    create_command!(
+        Grep,
+        "grep",
+        {
+            arg::CommandOptionDef {
+                command: "grep".to_string(),
+                support_args: 0,
+                desc: "搜索章节文本，输入为目录时并行搜索目录中所有的epub、mobi".to_string(),
+                opts: vec![
+                    OptionDef::create("e", "搜索内容", OptionType::String, true),
+                    OptionDef::create("regex", "使用正则表达式", OptionType::NoParamter, false),
+                    OptionDef::create(
+                        "icase",
+                        "忽略大小写以及全角半角",
+                        OptionType::NoParamter,
+                        false,
+                    ),
+                    OptionDef::create(
+                        "context",
+                        "匹配内容前后显示的字符数，默认20",
+                        OptionType::Number,
+                        false,
+                    ),
+                    OptionDef::create(
+                        "j",
+                        "搜索目录时的线程数，默认为cpu核数",
+                        OptionType::Number,
+                        false,
+                    ),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            _global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let get = |key: &str| {
+                opts.iter()
+                    .find(|f| f.key == key)
+                    .and_then(|f| f.value.clone())
+            };
+            let has = |key: &str| opts.iter().any(|f| f.key == key);
+            let mut query = SearchQuery::new(get("e").unwrap_or_default())
+                .with_regex(has("regex"))
+                .with_ignore_case(has("icase"));
+            if let Some(context) = get("context").and_then(|f| f.parse().ok()) {
+                query = query.with_context(context);
+            }
+            if let Err(e) = query.check() {
+                exec_err!("err: {}", e);
+            }
+            let res = match book {
+                Book::EPUB(book) => book.search(&query),
+                Book::MOBI(book) => book.search(&query),
+                Book::TEXT(path) if std::path::Path::new(path).is_dir() => {
+                    let jobs = get("j")
+                        .and_then(|f| f.parse().ok())
+                        .or_else(|| std::thread::available_parallelism().ok().map(|f| f.get()))
+                        .unwrap_or(1);
+                    grep_dir(path, &query, jobs);
+                    return;
+                }
+                Book::FB2(_) | Book::CBZ(_) | Book::TEXT(_) => {
+                    exec_err!("grep only supports epub, mobi or directory")
+                }
+            };
+            match res {
+                Ok(matches) => {
+                    for m in matches {
+                        println!("{}", format_match(&m));
+                    }
+                }
+                Err(e) => exec_err!("err: {}", e),
+            }
+        }
+    );
+
+    create_command!(
+        Diff,
+        "diff",
+        {
+            arg::CommandOptionDef {
+                command: "diff".to_string(),
+                support_args: 0,
+                desc: "比较两本书的元数据、章节、目录以及资源，-i 参数对应的书为旧书".to_string(),
+                opts: vec![
+                    OptionDef::create("with", "新书，支持 epub、mobi", OptionType::String, true),
+                    OptionDef::create("json", "以json格式输出", OptionType::NoParamter, false),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            _global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let with = opts
+                .iter()
+                .find(|f| f.key == "with")
+                .and_then(|f| f.value.clone())
+                .unwrap_or_default();
+            let old = match book {
+                Book::EPUB(book) => BookSnapshot::from_epub(book),
+                Book::MOBI(book) => BookSnapshot::from_mobi(book),
+                Book::FB2(_) | Book::CBZ(_) | Book::TEXT(_) => return,
+            };
+            let new = read_book(&with).and_then(|f| match f {
+                OwnBook::EPUB(mut book) => BookSnapshot::from_epub(&mut book),
+                OwnBook::MOBI(book) => BookSnapshot::from_mobi(&book),
+            });
+            let diff = old
+                .and_then(|old| new.map(|new| BookDiff::new(&old, &new)))
+                .unwrap_or_else(|e| {
+                    exec_err!("err: {}", e);
+                });
+            if opts.iter().any(|s| s.key == "json") {
+                println!("{}", diff.to_json());
+            } else if diff.is_empty() {
+                println!("no changes");
+            } else {
+                print!("{}", diff);
+            }
+        }
+    );
+
+    create_command!(
+        Stats,
+        "stats",
+        {
+            arg::CommandOptionDef {
+                command: "stats".to_string(),
+                support_args: 0,
+                desc: "统计字数、段落、图片、资源大小以及预计阅读时间".to_string(),
+                opts: vec![OptionDef::create(
+                    "json",
+                    "以json格式输出",
+                    OptionType::NoParamter,
+                    false,
+                )],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            _global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let stats = match book {
+                Book::EPUB(book) => book.stats(),
+                Book::MOBI(book) => book.stats(),
+                Book::FB2(_) | Book::CBZ(_) | Book::TEXT(_) => return,
+            }
+            .unwrap_or_else(|e| {
+                exec_err!("err: {}", e);
+            });
+            if opts.iter().any(|s| s.key == "json") {
+                println!("{}", stats.to_json());
+            } else {
+                print_stats(&stats);
+            }
+        }
+    );
+
+    fn print_stats(stats: &BookStats) {
+        println!(
+            "{:>5} {:>8} {:>8} {:>10} {:>6}  title",
+            "#", "cjk", "words", "paragraphs", "images"
+        );
+        for c in stats.chapters() {
+            println!(
+                "{:>5} {:>8} {:>8} {:>10} {:>6}  {}",
+                c.chapter(),
+                c.cjk(),
+                c.words(),
+                c.paragraphs(),
+                c.images(),
+                c.title()
+            );
+        }
+        println!(
+            "{:>5} {:>8} {:>8} {:>10} {:>6}",
+            "total",
+            stats.cjk(),
+            stats.words(),
+            stats.paragraphs(),
+            stats.images()
+        );
+        println!();
+        for a in stats.assets() {
+            println!("{:<28} {:>5} {:>12}", a.media_type(), a.count(), a.size());
+        }
+        println!("{:<28} {:>5} {:>12}", "total", "", stats.asset_size());
+        println!();
+        println!("reading time: {} min", stats.reading_minutes());
+    }
+
+    create_command!(
+        Replace,
+        "replace",
+        {
+            arg::CommandOptionDef {
+                command: "replace".to_string(),
+                support_args: 0,
+                desc: "按规则文件批量替换章节文本，只替换文本节点，并输出新文件".to_string(),
+                opts: vec![
+                    OptionDef::create(
+                        "rules",
+                        "规则文件，每行一条规则，格式为 查找内容 => 替换内容，re: 开头的为正则",
+                        OptionType::String,
+                        true,
+                    ),
+                    OptionDef::create("out", "输出文件位置", OptionType::String, true),
+                    OptionDef::over(),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let get = |key: &str| {
+                opts.iter()
+                    .find(|f| f.key == key)
+                    .and_then(|f| f.value.clone())
+                    .unwrap_or_default()
+            };
+            let rules = std::fs::read_to_string(get("rules"))
+                .map_err(iepub::prelude::IError::Io)
+                .and_then(|f| RewriteRules::parse(&f))
+                .unwrap_or_else(|e| {
+                    exec_err!("err: {}", e);
+                });
+            let out = get("out");
+            if std::path::Path::new(&out).exists()
+                && !is_overiade(global_opts, opts)
+                && get_single_input("Override file？(y/n)")
+                    .unwrap()
+                    .to_lowercase()
+                    != "y"
+            {
+                return;
+            }
+            let print = |reports: Vec<RewriteReport>| {
+                for r in &reports {
+                    println!(
+                        "{} {}: {}",
+                        r.file_name()
+                            .map(String::from)
+                            .unwrap_or_else(|| format!("#{}", r.chapter())),
+                        r.title(),
+                        r.count()
+                    );
+                }
+                println!(
+                    "replaced {} in {} chapters",
+                    reports.iter().map(|r| r.count()).sum::<usize>(),
+                    reports.len()
+                );
+            };
+            let res = match book {
+                Book::EPUB(book) => book.rewrite(&rules).and_then(|reports| {
+                    print(reports);
+                    msg!("writing book to {}", out);
+                    EpubWriter::write_to_file(out.as_str(), book, false)
+                }),
+                Book::MOBI(book) => book.rewrite(&rules).and_then(|reports| {
+                    print(reports);
+                    msg!("writing book to {}", out);
+                    MobiWriter::write_to_file(out.as_str(), book, false)
+                }),
+                Book::FB2(_) | Book::CBZ(_) | Book::TEXT(_) => return,
+            };
+            if let Err(e) = res {
+                exec_err!("err: {}", e);
+            }
+        }
+    );
+
+    create_command!(
+        Normalize,
+        "normalize",
+        {
+            arg::CommandOptionDef {
+                command: "normalize".to_string(),
+                support_args: 0,
+                desc: "排版规范化，统一标点宽度、中英文间距、引号，移除空段落，使用首行缩进，并输出新文件".to_string(),
+                opts: vec![
+                    OptionDef::create("out", "输出文件位置", OptionType::String, true),
+                    OptionDef::create(
+                        "indent",
+                        "首行缩进，默认 2em，为 0 时不处理段首空白",
+                        OptionType::String,
+                        false,
+                    ),
+                    OptionDef::create(
+                        "skip",
+                        "跳过的规则，可选 punctuation、spacing、quotes、blank",
+                        OptionType::Array,
+                        false,
+                    ),
+                    OptionDef::over(),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let get = |key: &str| {
+                opts.iter()
+                    .find(|f| f.key == key)
+                    .and_then(|f| f.value.clone())
+            };
+            let mut policy = TypographyPolicy::default();
+            if let Some(indent) = get("indent") {
+                policy = policy.with_text_indent(Some(indent.as_str()).filter(|v| *v != "0"));
+            }
+            for skip in opts
+                .iter()
+                .filter(|f| f.key == "skip")
+                .flat_map(|f| f.values.clone().unwrap_or_default())
+            {
+                policy = match skip.as_str() {
+                    "punctuation" => policy.with_punctuation(false),
+                    "spacing" => policy.with_spacing(false),
+                    "quotes" => policy.with_quotes(false),
+                    "blank" => policy.with_blank(false),
+                    _ => exec_err!("unsupported rule: {}", skip),
+                };
+            }
+            let out = get("out").unwrap_or_default();
+            if std::path::Path::new(&out).exists()
+                && !is_overiade(global_opts, opts)
+                && get_single_input("Override file？(y/n)")
+                    .unwrap()
+                    .to_lowercase()
+                    != "y"
+            {
+                return;
+            }
+            let res = match book {
+                Book::EPUB(book) => book.typeset(&policy).and_then(|_| {
+                    msg!("writing book to {}", out);
+                    EpubWriter::write_to_file(out.as_str(), book, false)
+                }),
+                Book::MOBI(book) => book.typeset(&policy).and_then(|_| {
+                    msg!("writing book to {}", out);
+                    MobiWriter::write_to_file(out.as_str(), book, false)
+                }),
+                Book::FB2(_) | Book::CBZ(_) | Book::TEXT(_) => return,
+            };
+            if let Err(e) = res {
+                exec_err!("err: {}", e);
+            }
+        }
+    );
+
+    create_command!(
+        Append,
+        "append",
+        {
+            arg::CommandOptionDef {
+                command: "append".to_string(),
+                support_args: 0,
+                desc: "追加章节到原文件末尾，目录编号与现有目录保持一致".to_string(),
+                opts: vec![
+                    OptionDef::create(
+                        "chapter",
+                        "章节文件，内容为html片段，按顺序追加",
+                        OptionType::Array,
+                        true,
+                    ),
+                    OptionDef::create(
+                        "title",
+                        "章节标题，与章节文件一一对应，默认使用文件名",
+                        OptionType::Array,
+                        false,
+                    ),
+                    OptionDef::create(
+                        "no-title",
+                        "不在章节正文前添加标题",
+                        OptionType::NoParamter,
+                        false,
+                    ),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let values = |key: &str| {
+                opts.iter()
+                    .filter(|f| f.key == key)
+                    .flat_map(|f| f.values.clone().unwrap_or_default())
+                    .collect::<Vec<String>>()
+            };
+            let titles = values("title");
+            let chapters: Vec<(String, Vec<u8>)> = values("chapter")
+                .iter()
+                .enumerate()
+                .map(|(index, path)| {
+                    let data = std::fs::read(path).unwrap_or_else(|e| {
+                        exec_err!("read {} fail, because {}", path, e);
+                    });
+                    let title = titles.get(index).cloned().unwrap_or_else(|| {
+                        std::path::Path::new(path)
+                            .file_stem()
+                            .map(|f| f.to_string_lossy().to_string())
+                            .unwrap_or_default()
+                    });
+                    (title, data)
+                })
+                .collect();
+            let append_title = !opts.iter().any(|f| f.key == "no-title");
+            let file = global_opts
+                .iter()
+                .find(|f| f.key == "i")
+                .and_then(|f| f.value.as_ref())
+                .unwrap();
+            let res = match book {
+                Book::EPUB(_) => append_chapters(
+                    file,
+                    chapters
+                        .into_iter()
+                        .map(|(title, data)| EpubHtml::default().with_title(title).with_data(data))
+                        .collect(),
+                    append_title,
+                )
+                .map(|names| {
+                    for name in names {
+                        msg!("append {}", name);
+                    }
+                }),
+                Book::MOBI(_) => append_mobi_chapters(
+                    file,
+                    chapters
+                        .into_iter()
+                        .map(|(title, data)| MobiHtml::new(0).with_title(title).with_data(data))
+                        .collect(),
+                    append_title,
+                ),
+                Book::FB2(_) | Book::CBZ(_) | Book::TEXT(_) => return,
+            };
+            if let Err(e) = res {
+                exec_err!("append chapter fail, because {}", e);
+            }
+        }
+    );
+
+    #[cfg(feature = "zh_convert")]
+    create_command!(
+        ConvertScript,
+        "convert-script",
+        {
+            arg::CommandOptionDef {
+                command: "convert-script".to_string(),
+                support_args: 0,
+                desc: "简繁转换，转换章节、目录和元数据，并输出新文件".to_string(),
+                opts: vec![
+                    OptionDef::create(
+                        "to",
+                        "目标文字，s 为简体，t 为繁体",
+                        OptionType::String,
+                        true,
+                    ),
+                    OptionDef::create("out", "输出文件位置", OptionType::String, true),
+                    OptionDef::over(),
+                ],
+            }
+        },
+        fn exec(
+            &self,
+            book: &mut Book,
+            global_opts: &[ArgOption],
+            opts: &[ArgOption],
+            _args: &[String],
+        ) {
+            let get = |key: &str| {
+                opts.iter()
+                    .find(|f| f.key == key)
+                    .and_then(|f| f.value.clone())
+                    .unwrap_or_default()
+            };
+            let to = get("to");
+            let Some(script) = parse_script(&to) else {
+                exec_err!("unsupported script: {}, use s or t", to);
+            };
+            let out = get("out");
+            if std::path::Path::new(&out).exists()
+                && !is_overiade(global_opts, opts)
+                && get_single_input("Override file？(y/n)")
+                    .unwrap()
+                    .to_lowercase()
+                    != "y"
+            {
+                return;
+            }
+            let converter = ChineseConverter::new(script);
+            let res = match book {
+                Book::EPUB(book) => converter.convert_epub(book).and_then(|_| {
+                    msg!("writing book to {}", out);
+                    EpubWriter::write_to_file(out.as_str(), book, false)
+                }),
+                Book::MOBI(book) => converter.convert_mobi(book).and_then(|_| {
+                    msg!("writing book to {}", out);
+                    MobiWriter::write_to_file(out.as_str(), book, false)
+                }),
+                Book::FB2(_) | Book::CBZ(_) | Book::TEXT(_) => return,
+            };
+            if let Err(e) = res {
+                exec_err!("err: {}", e);
+            }
+        }
+    );
 }
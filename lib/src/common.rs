@@ -65,6 +65,117 @@ macro_rules! cache_enum {
     };
 }
 
+///
+/// 错误发生的位置
+///
+/// 各个字段按需填写，例如 epub 会带有压缩包内的文件路径和 xml 中的位置，
+/// mobi 会带有 record 的序号和 record 内的字节偏移
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    operation: Option<Cow<'static, str>>,
+    entry: Option<String>,
+    position: Option<u64>,
+    record: Option<usize>,
+    offset: Option<u64>,
+}
+
+impl ErrorContext {
+    pub(crate) fn new<T: Into<Cow<'static, str>>>(operation: T) -> Self {
+        ErrorContext {
+            operation: Some(operation.into()),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn with_entry<T: Into<String>>(mut self, entry: T) -> Self {
+        self.entry = Some(entry.into());
+        self
+    }
+
+    pub(crate) fn with_position(mut self, position: u64) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    pub(crate) fn with_record(mut self, record: usize) -> Self {
+        self.record = Some(record);
+        self
+    }
+
+    pub(crate) fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// 正在执行的操作
+    pub fn operation(&self) -> Option<&str> {
+        self.operation.as_deref()
+    }
+
+    /// 压缩包内的文件路径
+    pub fn entry(&self) -> Option<&str> {
+        self.entry.as_deref()
+    }
+
+    /// xml 中的字节位置
+    pub fn position(&self) -> Option<u64> {
+        self.position
+    }
+
+    /// mobi 的 record 序号
+    pub fn record(&self) -> Option<usize> {
+        self.record
+    }
+
+    /// record 内的字节偏移，没有 record 时为文件内的偏移
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    /// 用外层的上下文补充缺失的字段
+    fn merge(&mut self, outer: ErrorContext) {
+        if self.operation.is_none() {
+            self.operation = outer.operation;
+        }
+        if self.entry.is_none() {
+            self.entry = outer.entry;
+        }
+        if self.position.is_none() {
+            self.position = outer.position;
+        }
+        if self.record.is_none() {
+            self.record = outer.record;
+        }
+        if self.offset.is_none() {
+            self.offset = outer.offset;
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut fields = Vec::new();
+        if let Some(v) = &self.entry {
+            fields.push(format!("entry {}", v));
+        }
+        if let Some(v) = self.position {
+            fields.push(format!("position {}", v));
+        }
+        if let Some(v) = self.record {
+            fields.push(format!("record {}", v));
+        }
+        if let Some(v) = self.offset {
+            fields.push(format!("offset {}", v));
+        }
+        match (&self.operation, fields.is_empty()) {
+            (Some(op), true) => write!(f, "{}", op),
+            (Some(op), false) => write!(f, "{} ({})", op, fields.join(", ")),
+            (None, _) => write!(f, "{}", fields.join(", ")),
+        }
+    }
+}
+
 ///
 /// 错误
 ///
@@ -84,6 +195,7 @@ pub enum IError {
     /// The password provided is incorrect
     InvalidPassword,
 
+    /// 文本不是合法的 utf-8
     Utf8(std::string::FromUtf8Error),
 
     Xml(quick_xml::Error),
@@ -92,6 +204,75 @@ pub enum IError {
     #[cfg(feature = "cache")]
     Cache(String),
     Unknown,
+
+    /// 内容不符合格式要求
+    Parse(Cow<'static, str>),
+    /// 缺少文件，值为压缩包内的路径
+    MissingEntry(String),
+    /// 不支持的特性
+    Unsupported(Cow<'static, str>),
+    /// 文本编码不受支持或者无法解码
+    Encoding(Cow<'static, str>),
+    /// 附带了上下文的错误
+    Context {
+        context: ErrorContext,
+        source: Box<IError>,
+    },
+}
+
+impl IError {
+    ///
+    /// 附加上下文
+    ///
+    /// 已经带有上下文时不会嵌套，只补充缺失的字段，内层的信息更具体，优先保留
+    ///
+    pub(crate) fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            IError::Context {
+                context: mut inner,
+                source,
+            } => {
+                inner.merge(context);
+                IError::Context {
+                    context: inner,
+                    source,
+                }
+            }
+            _ => IError::Context {
+                context,
+                source: Box::new(self),
+            },
+        }
+    }
+
+    /// 错误的上下文
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            IError::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// 去掉上下文之后的错误
+    pub fn kind(&self) -> &IError {
+        match self {
+            IError::Context { source, .. } => source.kind(),
+            _ => self,
+        }
+    }
+}
+
+///
+/// 为结果附加上下文，只在出错时才会构造上下文
+///
+pub(crate) trait ResultContext<T> {
+    fn context<F: FnOnce() -> ErrorContext>(self, f: F) -> IResult<T>;
+}
+
+impl<T, E: Into<IError>> ResultContext<T> for Result<T, E> {
+    fn context<F: FnOnce() -> ErrorContext>(self, f: F) -> IResult<T> {
+        self.map_err(|e| e.into().with_context(f()))
+    }
 }
 
 #[cfg(feature = "cache")]
@@ -103,11 +284,39 @@ impl From<serde_json::Error> for IError {
 
 impl std::fmt::Display for IError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            IError::Io(e) => write!(f, "io error: {}", e),
+            IError::InvalidArchive(v) => write!(f, "invalid archive: {}", v),
+            IError::UnsupportedArchive(v) => write!(f, "unsupported archive: {}", v),
+            IError::FileNotFound => write!(f, "file not found in archive"),
+            IError::InvalidPassword => write!(f, "invalid password"),
+            IError::Utf8(e) => write!(f, "invalid utf-8: {}", e),
+            IError::Xml(e) => write!(f, "xml error: {}", e),
+            IError::NoNav(v) => write!(f, "no nav: {}", v),
+            IError::Cover(v) => write!(f, "cover error: {}", v),
+            #[cfg(feature = "cache")]
+            IError::Cache(v) => write!(f, "cache error: {}", v),
+            IError::Unknown => write!(f, "unknown error"),
+            IError::Parse(v) => write!(f, "parse error: {}", v),
+            IError::MissingEntry(v) => write!(f, "missing entry: {}", v),
+            IError::Unsupported(v) => write!(f, "unsupported: {}", v),
+            IError::Encoding(v) => write!(f, "encoding error: {}", v),
+            IError::Context { context, source } => write!(f, "{}: {}", context, source),
+        }
     }
 }
 
-impl std::error::Error for IError {}
+impl std::error::Error for IError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IError::Io(e) => Some(e),
+            IError::Utf8(e) => Some(e),
+            IError::Xml(e) => Some(e),
+            IError::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 pub type IResult<T> = Result<T, IError>;
 
@@ -120,6 +329,7 @@ impl From<quick_xml::Error> for IError {
     fn from(value: quick_xml::Error) -> Self {
         match value {
             quick_xml::Error::Io(e) => IError::Io(std::io::Error::other(e)),
+            quick_xml::Error::Encoding(e) => IError::Encoding(Cow::from(e.to_string())),
             _ => IError::Xml(value),
         }
     }
@@ -156,6 +366,12 @@ cache_struct! {
         pub(crate) publisher: Option<String>,
         /// 主题？
         pub(crate) subject: Option<String>,
+        /// 语言，例如 zh-Hans、zh-Hant
+        pub(crate) language: Option<String>,
+        /// 书写方向，css 中的 writing-mode，例如 horizontal-tb、vertical-rl
+        pub(crate) writing_mode: Option<String>,
+        /// 翻页方向，ltr 或 rtl
+        pub(crate) page_direction: Option<String>,
     }
 }
 impl BookInfo {
@@ -202,6 +418,131 @@ pub(crate) fn unescape_html(v: &str) -> String {
     txt
 }
 
+/// 是否是 xml 1.0 允许出现的字符
+pub(crate) fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r') || (c >= ' ' && c != '\u{FFFE}' && c != '\u{FFFF}')
+}
+
+///
+/// 转义文本，用于拼接到xml的文本或者属性值中
+///
+/// 除了转义 `& < > ' "` 之外，还会移除 xml 1.0 不允许出现的控制字符
+///
+/// ```ignore
+/// assert_eq!("A &amp; B &lt;1&gt;", escape_xml("A & B <1>"));
+/// ```
+///
+pub(crate) fn escape_xml(v: &str) -> Cow<'_, str> {
+    if v.chars().all(|c| is_xml_char(c) && c != '\r') {
+        return quick_xml::escape::escape(v);
+    }
+    let v: String = v.chars().filter(|c| is_xml_char(*c)).collect();
+    // 解析器会把 \r 规范化为 \n，所以需要使用字符引用
+    Cow::Owned(quick_xml::escape::escape(v.as_str()).replace('\r', "&#13;"))
+}
+
+///
+/// 替换 html 中的文本节点
+///
+/// 标签、注释、CDATA 以及 script、style 中的内容原样保留，传给 `f` 的文本没有反转义，实体会原样保留
+///
+/// ```ignore
+/// assert_eq!("<p a=\"b\">B</p>", map_html_text("<p a=\"b\">b</p>", |t| t.to_uppercase()));
+/// ```
+///
+pub(crate) fn map_html_text<F: FnMut(&str) -> String>(html: &str, f: F) -> String {
+    map_html_text_skip(html, &[], f)
+}
+
+///
+/// 替换 html 中的文本节点，`skip` 中的标签内的内容也原样保留
+///
+/// 标签名需要小写
+///
+pub(crate) fn map_html_text_skip<F: FnMut(&str) -> String>(
+    html: &str,
+    skip: &[&str],
+    f: F,
+) -> String {
+    map_html_text_tags(html, skip, f, |_| {})
+}
+
+///
+/// 同 [map_html_text_skip]，每遇到一个标签、注释都会调用 `on_tag`
+///
+pub(crate) fn map_html_text_tags<F: FnMut(&str) -> String, T: FnMut(&str)>(
+    html: &str,
+    skip: &[&str],
+    mut f: F,
+    mut on_tag: T,
+) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            out.push_str(&f(rest));
+            break;
+        };
+        if start > 0 {
+            out.push_str(&f(&rest[..start]));
+            rest = &rest[start..];
+        }
+        // 找到标签结束位置，没有结束时剩余部分都作为标签
+        let end = if rest.starts_with("<!--") {
+            rest.find("-->").map(|i| i + 3)
+        } else if rest.starts_with("<![CDATA[") {
+            rest.find("]]>").map(|i| i + 3)
+        } else {
+            find_tag_end(rest)
+        }
+        .unwrap_or(rest.len());
+        let tag = &rest[..end];
+        on_tag(tag);
+        out.push_str(tag);
+        rest = &rest[end..];
+
+        // script 和 style 的内容不是文本
+        let name = tag_name(tag);
+        if (name == "script" || name == "style" || skip.contains(&name.as_str()))
+            && !tag.starts_with("</")
+            && !tag.ends_with("/>")
+        {
+            let close = format!("</{}", name);
+            let end = rest
+                .to_ascii_lowercase()
+                .find(close.as_str())
+                .unwrap_or(rest.len());
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+    out
+}
+
+/// 小写的标签名，结束标签不包括 `/`
+pub(crate) fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches('<')
+        .trim_start_matches('/')
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// 标签结束的位置，跳过属性值中的 `>`
+pub(crate) fn find_tag_end(v: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in v.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
 pub struct DateTimeFormater {
     timestamp: u64,
     start_year: u64,
@@ -422,12 +763,15 @@ pub(crate) fn get_media_type(file_name: &str) -> String {
     types.insert(".mp4", String::from("audio/mp4"));
     types.insert(".css", String::from("text/css"));
     types.insert(".ttf", String::from("application/font-sfnt"));
-    types.insert(".oft", String::from("application/font-sfnt"));
+    types.insert(".otf", String::from("application/font-sfnt"));
     types.insert(".woff", String::from("application/font-woff"));
-    types.insert(".woff", String::from("font/woff2"));
+    types.insert(".woff2", String::from("font/woff2"));
     types.insert(".xhtml", String::from("application/xhtml+xml"));
+    types.insert(".html", String::from("application/xhtml+xml"));
+    types.insert(".htm", String::from("application/xhtml+xml"));
+    types.insert(".ncx", String::from("application/x-dtbncx+xml"));
     types.insert(".js", String::from("application/javascript"));
-    types.insert(".opf", String::from("application/x-dtbncx+xml"));
+    types.insert(".opf", String::from("application/oebps-package+xml"));
     let x: &[_] = &['.'];
     if let Some(index) = f.rfind(x) {
         let sub = &f[index..f.len()];
@@ -440,6 +784,86 @@ pub(crate) fn get_media_type(file_name: &str) -> String {
     String::new()
 }
 
+///
+/// 通过临时文件替换原文件
+///
+/// 临时文件创建在原文件所在目录并使用唯一的文件名，写入成功后直接重命名为原文件，
+/// 写入失败时删除临时文件，原文件保持不变
+///
+pub(crate) fn replace_file<F: FnOnce(&mut std::fs::File) -> IResult<()>>(
+    file: &std::path::Path,
+    write: F,
+) -> IResult<()> {
+    let (temp, mut f) = create_temp_file(file)?;
+    let result = write(&mut f).and_then(|_| Ok(f.sync_all()?));
+    drop(f);
+    match result.and_then(|_| Ok(std::fs::rename(&temp, file)?)) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+/// 在文件所在目录创建 `.{文件名}.{随机值}.tmp`，已存在时重新生成
+fn create_temp_file(file: &std::path::Path) -> IResult<(std::path::PathBuf, std::fs::File)> {
+    let dir = file
+        .parent()
+        .filter(|f| !f.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."));
+    let name = file
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    loop {
+        let temp = dir.join(format!(".{}.{}.tmp", name, &gen_uuid()[..8]));
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)
+        {
+            Ok(f) => return Ok((temp, f)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(IError::Io(e)),
+        }
+    }
+}
+
+///
+/// 生成随机的 uuid（版本 4）
+///
+pub(crate) fn gen_uuid() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let state = std::collections::hash_map::RandomState::new();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|f| f.as_nanos())
+        .unwrap_or_default();
+    let [high, low] = [0u64, 1].map(|i| {
+        let mut hasher = state.build_hasher();
+        hasher.write_u128(nanos);
+        hasher.write_u64(count);
+        hasher.write_u64(i);
+        hasher.finish()
+    });
+    // 版本号 4，变体 10
+    let high = (high & 0xffff_ffff_ffff_0fff) | 0x4000;
+    let low = (low & 0x3fff_ffff_ffff_ffff) | 0x8000_0000_0000_0000;
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use crate::common::DateTimeFormater;
@@ -551,4 +975,103 @@ pub(crate) mod tests {
                 .default_format()
         );
     }
+
+    #[test]
+    fn test_error_context() {
+        use super::{ErrorContext, IError, ResultContext};
+        use std::error::Error;
+
+        let err = Err::<(), _>(std::io::Error::other("broken"))
+            .context(|| ErrorContext::default().with_position(12))
+            .context(|| ErrorContext::new("read opf").with_entry("OEBPS/content.opf"))
+            .unwrap_err();
+
+        // 不会嵌套，只补充缺失的字段
+        let context = err.context().unwrap();
+        assert_eq!(Some("read opf"), context.operation());
+        assert_eq!(Some("OEBPS/content.opf"), context.entry());
+        assert_eq!(Some(12), context.position());
+        assert!(matches!(err.kind(), IError::Io(_)));
+        assert_eq!(
+            "read opf (entry OEBPS/content.opf, position 12): io error: broken",
+            err.to_string()
+        );
+
+        // source 逐层返回
+        let source = err.source().unwrap();
+        assert_eq!("io error: broken", source.to_string());
+        assert_eq!("broken", source.source().unwrap().to_string());
+
+        let err = IError::Parse("not a tagx".into()).with_context(
+            ErrorContext::new("read index")
+                .with_record(3)
+                .with_offset(16),
+        );
+        assert_eq!(
+            "read index (record 3, offset 16): parse error: not a tagx",
+            err.to_string()
+        );
+        assert!(err.source().unwrap().source().is_none());
+    }
+
+    #[test]
+    fn test_map_html_text() {
+        use super::map_html_text;
+
+        let html = r#"<p class="a > b" title='c'>text&amp;<!-- text --><b>x</b></p><script>var a = "b";</script><![CDATA[c]]>end"#;
+        assert_eq!(
+            r#"<p class="a > b" title='c'>TEXT&AMP;<!-- text --><b>X</b></p><script>var a = "b";</script><![CDATA[c]]>END"#,
+            map_html_text(html, |t| t.to_uppercase())
+        );
+        assert_eq!("", map_html_text("", |t| t.to_uppercase()));
+        assert_eq!("<p>A", map_html_text("<p>a", |t| t.to_uppercase()));
+    }
+
+    #[test]
+    fn test_map_html_text_skip() {
+        use super::map_html_text_skip;
+
+        assert_eq!(
+            "A<RUBY>b<rt>c</rt></ruby>D<ruby/>E",
+            map_html_text_skip("a<RUBY>b<rt>c</rt></ruby>d<ruby/>e", &["ruby"], |t| t
+                .to_uppercase())
+        );
+    }
+
+    #[test]
+    fn test_gen_uuid() {
+        let v = super::gen_uuid();
+        assert_eq!(36, v.len(), "{}", v);
+        assert_eq!(
+            vec![8, 4, 4, 4, 12],
+            v.split('-').map(|f| f.len()).collect::<Vec<_>>()
+        );
+        assert_eq!(Some('4'), v.chars().nth(14));
+        assert!(matches!(v.chars().nth(19), Some('8' | '9' | 'a' | 'b')));
+        assert_ne!(v, super::gen_uuid());
+    }
+
+    #[test]
+    fn test_replace_file() {
+        let dir = std::env::temp_dir().join(format!("{}.test_replace_file", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("book.epub");
+        std::fs::write(&file, "old").unwrap();
+
+        // 可以连续替换
+        for value in ["new", "newer"] {
+            super::replace_file(&file, |f| {
+                Ok(std::io::Write::write_all(f, value.as_bytes())?)
+            })
+            .unwrap();
+            assert_eq!(value, std::fs::read_to_string(&file).unwrap());
+        }
+        // 写入失败时原文件不变
+        assert!(super::replace_file(&file, |_| Err(super::IError::Unknown)).is_err());
+        assert_eq!("newer", std::fs::read_to_string(&file).unwrap());
+        // 没有残留的临时文件
+        assert_eq!(1, std::fs::read_dir(&dir).unwrap().count());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
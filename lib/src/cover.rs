@@ -1,10 +1,13 @@
 //!
 //! 实现封面图片的自动生成
 //!
+#[cfg(feature = "cover")]
+use crate::common::IError;
 use crate::common::IResult;
 
 #[cfg(feature = "cover")]
 mod text_width {
+    use crate::common::{IError, IResult};
     use ab_glyph::PxScale;
     use image::{DynamicImage, GenericImageView, Rgba};
 
@@ -31,7 +34,7 @@ mod text_width {
             ImageCrop { original: img }
         }
 
-        pub fn text_width(&self) -> (u32, u32) {
+        pub fn text_width(&self) -> IResult<(u32, u32)> {
             let (width, height) = self.original.dimensions();
             let mut left_x = 0;
             let mut right_x = 0;
@@ -53,9 +56,10 @@ mod text_width {
                 }
             }
             if right_x == left_x {
-                panic!("找不到文字，可能是字体中不包含该文字");
+                // 找不到文字，可能是字体中不包含该文字
+                return Err(IError::Cover("glyph not found in font".to_string()));
             }
-            (right_x - left_x, left_x)
+            Ok((right_x - left_x, left_x))
         }
     }
     #[cfg(test)]
@@ -94,7 +98,7 @@ mod text_width {
                 .write_to(&mut f, image::ImageFormat::Jpeg)
                 .unwrap();
 
-            println!("real_width {}", img.text_width().0);
+            println!("real_width {}", img.text_width().unwrap().0);
         }
     }
 }
@@ -111,6 +115,9 @@ pub(crate) fn gen_cover(book_name: &str, font: &[u8]) -> IResult<Vec<u8>> {
     let margin = 5;
 
     let text = book_name;
+    if text.is_empty() {
+        return Err(IError::Cover("book name is empty".to_string()));
+    }
 
     let mut img = DynamicImage::new_rgb8(width, height);
 
@@ -136,7 +143,7 @@ pub(crate) fn gen_cover(book_name: &str, font: &[u8]) -> IResult<Vec<u8>> {
         x: use_width as f32,
         y: use_height as f32,
     };
-    let font = FontRef::try_from_slice(font).unwrap();
+    let font = FontRef::try_from_slice(font).map_err(|e| IError::Cover(e.to_string()))?;
 
     for row in 0..row_count {
         for col in 0..col_count {
@@ -146,7 +153,7 @@ pub(crate) fn gen_cover(book_name: &str, font: &[u8]) -> IResult<Vec<u8>> {
             };
             // 获取文字实际的宽度
             let crop = ImageCrop::new(t.as_str(), use_width, 120, &font);
-            let (real_width, begin_x) = crop.text_width();
+            let (real_width, begin_x) = crop.text_width()?;
             let mut x = ((use_width - real_width) / 2) as i32;
             x -= begin_x as i32;
             x += margin as i32;
@@ -165,14 +172,17 @@ pub(crate) fn gen_cover(book_name: &str, font: &[u8]) -> IResult<Vec<u8>> {
 
     let mut buf = std::io::Cursor::new(Vec::new());
 
-    img.write_to(&mut buf, image::ImageFormat::Jpeg).unwrap();
+    img.write_to(&mut buf, image::ImageFormat::Jpeg)
+        .map_err(|e| IError::Cover(e.to_string()))?;
 
     Ok(buf.into_inner())
 }
 
 #[cfg(not(feature = "cover"))]
 pub(crate) fn gen_cover(book_name: &str, font: &[u8]) -> IResult<Vec<u8>> {
-    panic!("自动封面需要启用 cover features")
+    Err(crate::common::IError::Unsupported(std::borrow::Cow::from(
+        r#"auto cover requires feature "cover""#,
+    )))
 }
 
 #[cfg(all(test, feature = "cover"))]
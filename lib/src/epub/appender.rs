@@ -1,32 +1,33 @@
-//! 修改现有epub文件，目前仅支持修改元数据
+//! 修改现有epub文件，支持修改元数据以及追加章节
 //!
 //!
-use std::path::Path;
+use std::{borrow::Cow, collections::HashSet, path::Path};
 
 use super::{
     common, core,
-    html::{to_opf, to_toc_xml},
+    html::{to_html_with_writing_mode, to_opf, to_toc_xml},
+    layout::{normalize, parent, relative, GeneratedFiles},
+    reader::get_opf_location,
     writer::{self, EpubWriterTrait},
 };
-use crate::prelude::*;
+use crate::{
+    common::{escape_xml, replace_file, DateTimeFormater, ErrorContext, ResultContext},
+    prelude::*,
+};
 
 /// 修改电子书元数据
 ///
 /// [file] 原文件路径
 ///
 pub fn write_metadata<P: AsRef<Path> + ?Sized>(file: &P, book: &mut EpubBook) -> IResult<()> {
-    let dir = std::env::temp_dir();
-    let temp_file = dir.join(format!("{}.update.epub", std::process::id()));
-    {
-        let mut reader = zip::ZipArchive::new(std::fs::File::open(file)?)?;
-        let mut fs = std::fs::OpenOptions::new()
-            .create_new(true)
-            .truncate(true)
-            .write(true)
-            .open(temp_file.display().to_string().as_str())?;
-        let mut writer = writer::EpubWriter::new(&mut fs);
+    let mut reader = zip::ZipArchive::new(std::fs::File::open(file)?)?;
+    replace_file(file.as_ref(), move |fs| {
+        let mut writer = writer::EpubWriter::new(fs);
         let index = reader.index_for_name(common::OPF).unwrap_or(usize::MAX);
         let index2 = reader.index_for_name(common::TOC).unwrap_or(usize::MAX);
+        let index3 = reader.index_for_name("mimetype").unwrap_or(usize::MAX);
+        // mimetype 必须是第一个文件
+        writer.write_file("mimetype", "application/epub+zip".as_bytes())?;
 
         // 首先写入元数据文件
         writer.write_file(
@@ -34,6 +35,7 @@ pub fn write_metadata<P: AsRef<Path> + ?Sized>(file: &P, book: &mut EpubBook) ->
             to_opf(
                 book,
                 format!("{}-{}", core::info::PROJECT_NAME, core::info::PKG_VERSION).as_str(),
+                &GeneratedFiles::default(),
             )
             .as_bytes(),
         )?;
@@ -44,7 +46,7 @@ pub fn write_metadata<P: AsRef<Path> + ?Sized>(file: &P, book: &mut EpubBook) ->
         // 遍历其他文件
 
         for i in 0..reader.len() {
-            if i == index || i == index2 {
+            if i == index || i == index2 || i == index3 {
                 continue;
             }
             let options = zip::write::SimpleFileOptions::default()
@@ -54,25 +56,474 @@ pub fn write_metadata<P: AsRef<Path> + ?Sized>(file: &P, book: &mut EpubBook) ->
             writer.inner.start_file(f.name(), options)?;
             std::io::copy(&mut f, &mut writer.inner)?;
         }
+        writer.inner.finish()?;
+        Ok(())
+    })
+}
+
+///
+/// 向现有epub追加章节
+///
+/// 新章节按照最后一个章节的文件名顺序编号，例如最后一个章节为 `chapter_0002.xhtml` 时，
+/// 新章节为 `chapter_0003.xhtml`，传入的文件名会被忽略；目录与现有目录的编号方式保持一致
+///
+/// 在原有的 opf、导航文档和 toc.ncx 中添加新章节，原有的 id、文件位置以及其他元数据保持不变，
+/// 只更新 dcterms:modified，其他文件原样复制，不会解压
+///
+/// # Arguments
+///
+/// * `file` - 原文件路径
+/// * `chapters` - 新章节
+/// * `append_title` - 是否在正文前添加标题
+///
+/// # Returns
+///
+/// 新章节的文件名
+///
+/// # Examples
+///
+/// ```no_run
+/// use iepub::prelude::*;
+/// use iepub::prelude::appender::append_chapters;
+///
+/// let names = append_chapters(
+///     "1.epub",
+///     vec![EpubHtml::default()
+///         .with_title("第三章")
+///         .with_data("<p>新的章节</p>".as_bytes().to_vec())],
+///     true,
+/// )
+/// .unwrap();
+/// ```
+///
+pub fn append_chapters<P: AsRef<Path> + ?Sized>(
+    file: &P,
+    chapters: Vec<EpubHtml>,
+    append_title: bool,
+) -> IResult<Vec<String>> {
+    let book = read_from_file(file.as_ref())?;
+    let existing: Vec<String> = book
+        .chapters()
+        .filter(|f| !f.is_generated())
+        .map(|f| f.file_name().to_string())
+        .collect();
+    let names = chapter_names(&existing, chapters.len());
+    let numbered = is_numbered(book.nav().map(|f| f.title()));
+
+    let mut reader = zip::ZipArchive::new(std::fs::File::open(file)?)?;
+    let opf_path = get_opf_location(&read_string(&mut reader, "META-INF/container.xml")?)?;
+    let mut opf = read_string(&mut reader, &opf_path)?;
+    let dir = parent(&opf_path).to_string();
+    let (items, toc) = read_manifest(&opf)
+        .context(|| ErrorContext::new("read opf").with_entry(opf_path.as_str()))?;
+    // 导航文档和 toc.ncx，不存在时不处理
+    let mut nav = match items.iter().find(|f| f.is_nav()) {
+        Some(v) => {
+            let path = normalize(&dir, &v.href);
+            Some((read_string(&mut reader, &path)?, path))
+        }
+        None => None,
+    };
+    let mut ncx = match items.iter().find(|f| {
+        toc.as_deref()
+            .map_or(f.media_type == NCX_MEDIA_TYPE, |v| f.id == v)
+    }) {
+        Some(v) => {
+            let path = normalize(&dir, &v.href);
+            Some((read_string(&mut reader, &path)?, path))
+        }
+        None => None,
+    };
+    let mut ids: HashSet<String> = items.into_iter().map(|f| f.id).collect();
+    let (mut nav_ids, mut play_order) = match &ncx {
+        Some((xml, path)) => {
+            read_nav_points(xml).context(|| ErrorContext::new("read ncx").with_entry(path))?
+        }
+        None => (HashSet::new(), None),
+    };
+
+    let (mut manifest, mut spine) = (String::new(), String::new());
+    let (mut nav_items, mut nav_points) = (String::new(), String::new());
+    let mut htmls = Vec::new();
+    let mut nav_len = book.nav().len();
+    for (mut chap, name) in chapters.into_iter().zip(&names) {
+        chap.set_file_name(name);
+        nav_len += 1;
+        let title = if numbered {
+            format!("{}. {}", nav_len, chap.title())
+        } else {
+            chap.title().to_string()
+        };
+        let id = unique_id(&mut ids, "chap_");
+        manifest.push_str(&format!(
+            r#"<item id="{id}" href="{}" media-type="application/xhtml+xml"/>"#,
+            escape_xml(name)
+        ));
+        spine.push_str(&format!(r#"<itemref idref="{id}"/>"#));
+
+        let path = normalize(&dir, name);
+        if let Some((_, nav_path)) = &nav {
+            nav_items.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>",
+                escape_xml(&relative(parent(nav_path), &path)),
+                escape_xml(&title)
+            ));
+        }
+        if let Some((_, ncx_path)) = &ncx {
+            let order = match play_order.as_mut() {
+                Some(v) => {
+                    *v += 1;
+                    format!(r#" playOrder="{v}""#)
+                }
+                None => String::new(),
+            };
+            nav_points.push_str(&format!(
+                r#"<navPoint id="{}"{order}><navLabel><text>{}</text></navLabel><content src="{}"/></navPoint>"#,
+                unique_id(&mut nav_ids, "navPoint-"),
+                escape_xml(&title),
+                escape_xml(&relative(parent(ncx_path), &path))
+            ));
+        }
+        htmls.push((
+            path,
+            to_html_with_writing_mode(
+                &mut chap,
+                append_title,
+                book.writing_mode(),
+                book.language(),
+            )?,
+        ));
+    }
+
+    insert_before_end(&mut opf, "manifest", |prefix| {
+        manifest.replace("<item ", &format!("<{prefix}item "))
+    })
+    .and_then(|_| {
+        insert_before_end(&mut opf, "spine", |prefix| {
+            spine.replace("<itemref ", &format!("<{prefix}itemref "))
+        })
+    })
+    .and_then(|_| {
+        set_modified(
+            &mut opf,
+            book.version().starts_with('3'),
+            &DateTimeFormater::default().default_format(),
+        )
+    })
+    .context(|| ErrorContext::new("update opf").with_entry(opf_path.as_str()))?;
+    if let Some((xml, path)) = &mut nav {
+        append_nav_items(xml, &nav_items)
+            .context(|| ErrorContext::new("update nav").with_entry(path.as_str()))?;
+    }
+    if let Some((xml, path)) = &mut ncx {
+        insert_before_end(xml, "navMap", |_| nav_points.clone())
+            .context(|| ErrorContext::new("update ncx").with_entry(path.as_str()))?;
+    }
+
+    replace_file(file.as_ref(), move |fs| {
+        let mut writer = writer::EpubWriter::new(fs);
+        // mimetype 必须是第一个文件
+        writer.write_file("mimetype", "application/epub+zip".as_bytes())?;
+        let mut updated = vec![(opf_path.as_str(), opf.as_str())];
+        updated.extend(
+            nav.iter()
+                .chain(ncx.iter())
+                .map(|(xml, path)| (path.as_str(), xml.as_str())),
+        );
+        for (path, xml) in &updated {
+            writer.write_file(path, xml.as_bytes())?;
+        }
+
+        for i in 0..reader.len() {
+            let f = reader.by_index_raw(i)?;
+            if f.name() == "mimetype" || updated.iter().any(|(path, _)| *path == f.name()) {
+                continue;
+            }
+            writer.inner.raw_copy_file(f)?;
+        }
+        for (name, html) in &htmls {
+            writer.write_file(name, html.as_bytes())?;
+        }
+        writer.inner.finish()?;
+        Ok(())
+    })?;
+    Ok(names)
+}
+
+static NCX_MEDIA_TYPE: &str = "application/x-dtbncx+xml";
+
+/// opf 中 manifest 的 item
+struct ManifestItem {
+    id: String,
+    href: String,
+    media_type: String,
+    properties: String,
+}
+
+impl ManifestItem {
+    fn is_nav(&self) -> bool {
+        self.properties.split_whitespace().any(|f| f == "nav")
     }
+}
+
+fn read_string<R: std::io::Read + std::io::Seek>(
+    reader: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> IResult<String> {
+    let mut v = String::new();
+    std::io::Read::read_to_string(&mut reader.by_name(name)?, &mut v)
+        .context(|| ErrorContext::new("read entry").with_entry(name))?;
+    Ok(v)
+}
+
+/// 属性值，不存在时为空
+fn attr_value(e: &quick_xml::events::BytesStart, name: &str) -> IResult<String> {
+    Ok(
+        match e.try_get_attribute(name).map_err(quick_xml::Error::from)? {
+            Some(v) => v.unescape_value()?.to_string(),
+            None => String::new(),
+        },
+    )
+}
 
-    // 替换原始文件
-    std::fs::remove_file(file)?;
-    let mut from = std::fs::File::open(&temp_file)?;
-    let mut to = std::fs::File::options()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(file)?;
-    // 降级到copy
-    std::io::copy(&mut from, &mut to)?;
+///
+/// 读取 opf 中 manifest 的 item 以及 spine 的 toc 属性
+///
+fn read_manifest(opf: &str) -> IResult<(Vec<ManifestItem>, Option<String>)> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(opf);
+    let mut items = Vec::new();
+    let mut toc = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"item" => items.push(ManifestItem {
+                    id: attr_value(&e, "id")?,
+                    href: attr_value(&e, "href")?,
+                    media_type: attr_value(&e, "media-type")?,
+                    properties: attr_value(&e, "properties")?,
+                }),
+                b"spine" => toc = Some(attr_value(&e, "toc")?).filter(|f| !f.is_empty()),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok((items, toc))
+}
+
+///
+/// 读取 toc.ncx 中 navPoint 的 id 以及最大的 playOrder
+///
+fn read_nav_points(ncx: &str) -> IResult<(HashSet<String>, Option<usize>)> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(ncx);
+    let mut ids = HashSet::new();
+    let mut play_order: Option<usize> = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"navPoint" => {
+                ids.insert(attr_value(&e, "id")?);
+                if let Ok(v) = attr_value(&e, "playOrder")?.trim().parse::<usize>() {
+                    play_order = Some(play_order.map_or(v, |f| f.max(v)));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok((ids, play_order))
+}
 
+/// 没有被使用的 id
+fn unique_id(ids: &mut HashSet<String>, prefix: &str) -> String {
+    let mut index = ids.len();
+    loop {
+        let id = format!("{prefix}{index}");
+        if ids.insert(id.clone()) {
+            return id;
+        }
+        index += 1;
+    }
+}
+
+///
+/// 在第一个结束标签 `</name>` 前插入内容
+///
+/// 标签可以带有命名空间前缀，前缀（包括 `:`）会传给 content
+///
+fn insert_before_end<F: FnOnce(&str) -> String>(
+    xml: &mut String,
+    name: &str,
+    content: F,
+) -> IResult<()> {
+    let found = xml.match_indices("</").find_map(|(index, _)| {
+        let tag = xml[(index + 2)..]
+            .split(|c: char| c == '>' || c.is_whitespace())
+            .next()
+            .unwrap_or_default();
+        let (prefix, local) = tag.rfind(':').map_or(("", tag), |i| tag.split_at(i + 1));
+        (local == name).then(|| (index, prefix.to_string()))
+    });
+    let Some((index, prefix)) = found else {
+        return Err(IError::Parse(Cow::from(format!("missing </{name}>"))));
+    };
+    xml.insert_str(index, &content(&prefix));
     Ok(())
 }
 
+///
+/// 更新 dcterms:modified
+///
+/// 不存在时只在 epub3 中添加，epub2 的 meta 不支持 property 属性
+///
+fn set_modified(opf: &mut String, epub3: bool, value: &str) -> IResult<()> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(opf.as_str());
+    let mut start = None;
+    let range = loop {
+        let position = reader.buffer_position() as usize;
+        match reader.read_event()? {
+            Event::Start(e)
+                if e.local_name().as_ref() == b"meta"
+                    && attr_value(&e, "property")? == "dcterms:modified" =>
+            {
+                start = Some(reader.buffer_position() as usize);
+            }
+            Event::End(e) if e.local_name().as_ref() == b"meta" => {
+                if let Some(start) = start {
+                    break Some(start..position);
+                }
+            }
+            Event::Eof => break None,
+            _ => {}
+        }
+    };
+    match range {
+        Some(range) => opf.replace_range(range, value),
+        None if epub3 => insert_before_end(opf, "metadata", |prefix| {
+            format!(r#"<{prefix}meta property="dcterms:modified">{value}</{prefix}meta>"#)
+        })?,
+        None => {}
+    }
+    Ok(())
+}
+
+///
+/// 在导航文档的 toc 中第一层列表的末尾添加目录
+///
+fn append_nav_items(xhtml: &mut String, items: &str) -> IResult<()> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(xhtml.as_str());
+    let mut in_toc = false;
+    let mut depth = 0;
+    let index = loop {
+        let position = reader.buffer_position() as usize;
+        match reader.read_event()? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"nav" => {
+                    in_toc = attr_value(&e, "epub:type")?
+                        .split_whitespace()
+                        .any(|f| f == "toc")
+                }
+                b"ol" | b"ul" if in_toc => depth += 1,
+                _ => {}
+            },
+            Event::End(e) => match e.local_name().as_ref() {
+                b"nav" => in_toc = false,
+                b"ol" | b"ul" if in_toc => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break Some(position);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break None,
+            _ => {}
+        }
+    };
+    let Some(index) = index else {
+        return Err(IError::Parse(Cow::from("nav has no toc list")));
+    };
+    xhtml.insert_str(index, items);
+    Ok(())
+}
+
+///
+/// 按照最后一个章节的文件名生成新的文件名
+///
+/// 文件名末尾没有编号时使用 `chapter_0001.xhtml` 的形式，放在最后一个章节所在的目录
+///
+pub(crate) fn chapter_names(existing: &[String], count: usize) -> Vec<String> {
+    let (prefix, width, ext) = match existing.last().and_then(|f| split_number(f)) {
+        Some((prefix, number, ext)) => (prefix.to_string(), number.len(), ext.to_string()),
+        None => {
+            let dir = existing
+                .last()
+                .and_then(|f| f.rfind('/').map(|i| &f[..=i]))
+                .unwrap_or_default();
+            (format!("{}chapter_", dir), 4, ".xhtml".to_string())
+        }
+    };
+    // 从同一种命名方式的最大编号开始
+    let mut index = existing
+        .iter()
+        .filter_map(|f| split_number(f))
+        .filter(|(p, _, e)| *p == prefix && *e == ext)
+        .filter_map(|(_, number, _)| number.parse::<usize>().ok())
+        .max()
+        .unwrap_or(0);
+    let mut out = Vec::new();
+    while out.len() < count {
+        index += 1;
+        let name = format!("{}{:0width$}{}", prefix, index, ext, width = width);
+        if !existing.contains(&name) {
+            out.push(name);
+        }
+    }
+    out
+}
+
+/// 拆分为 (编号前的部分，编号，后缀)
+fn split_number(file_name: &str) -> Option<(&str, &str, &str)> {
+    let dot = file_name
+        .rfind('.')
+        .filter(|i| !file_name[*i..].contains('/'))
+        .unwrap_or(file_name.len());
+    let stem = &file_name[..dot];
+    let digits = stem
+        .bytes()
+        .rev()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    if digits == 0 {
+        return None;
+    }
+    let (prefix, number) = stem.split_at(stem.len() - digits);
+    Some((prefix, number, &file_name[dot..]))
+}
+
+/// 目录是否是自动生成的 `1. 标题` 形式
+pub(crate) fn is_numbered<'a>(titles: impl Iterator<Item = &'a str>) -> bool {
+    let mut count = 0;
+    for (index, title) in titles.enumerate() {
+        if !title.starts_with(&format!("{}. ", index + 1)) {
+            return false;
+        }
+        count += 1;
+    }
+    count > 0
+}
+
 #[cfg(test)]
 mod tests {
-    use super::write_metadata;
+    use super::{append_chapters, chapter_names, is_numbered, write_metadata};
     use crate::prelude::*;
     #[test]
     fn test_appender() {
@@ -108,4 +559,182 @@ mod tests {
         assert_eq!(book.title(), nb.title());
         let _ = std::fs::remove_file("temp.epub");
     }
+
+    #[test]
+    fn test_chapter_names() {
+        let names = |v: &[&str], count: usize| {
+            chapter_names(&v.iter().map(|f| f.to_string()).collect::<Vec<_>>(), count)
+        };
+        assert_eq!(
+            vec!["Text/chapter_0010.xhtml", "Text/chapter_0011.xhtml"],
+            names(&["Text/chapter_0008.xhtml", "Text/chapter_0009.xhtml"], 2)
+        );
+        assert_eq!(vec!["10.xhtml"], names(&["8.xhtml", "9.xhtml"], 1));
+        // 最后一个章节编号不是最大的
+        assert_eq!(vec!["3.html"], names(&["2.html", "1.html"], 1));
+        assert_eq!(
+            vec!["Text/chapter_0001.xhtml"],
+            names(&["Text/intro.xhtml"], 1)
+        );
+        assert_eq!(vec!["chapter_0001.xhtml"], names(&[], 1));
+
+        assert!(is_numbered(["1. a", "2. b"].into_iter()));
+        assert!(!is_numbered(["1. a", "b"].into_iter()));
+        assert!(!is_numbered([].into_iter()));
+    }
+
+    #[test]
+    fn test_append_chapters() {
+        let file = std::env::temp_dir().join(format!("{}.test_append.epub", std::process::id()));
+        EpubBuilder::new()
+            .with_title("书名")
+            .with_identifier("isbn")
+            .with_language("zh")
+            .with_last_modify("2024-01-01T00:00:00Z")
+            .cover("cover.png", crate::cbz::core::tests::png(1, 1))
+            .add_assets("style.css", "p{}".as_bytes().to_vec())
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一章")
+                    .with_file_name("chapter_0001.xhtml")
+                    .with_data("<p>第一章</p>".as_bytes().to_vec()),
+            )
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第二章")
+                    .with_file_name("chapter_0002.xhtml")
+                    .with_data("<p>第二章</p>".as_bytes().to_vec()),
+            )
+            .file(&file)
+            .unwrap();
+
+        let names = append_chapters(
+            &file,
+            vec![
+                EpubHtml::default()
+                    .with_title("第三章")
+                    .with_file_name("ignored.xhtml")
+                    .with_data("<p>新的章节</p>".as_bytes().to_vec()),
+                EpubHtml::default()
+                    .with_title("第四章")
+                    .with_data("<p>又一章</p>".as_bytes().to_vec()),
+            ],
+            false,
+        )
+        .unwrap();
+        assert_eq!(vec!["chapter_0003.xhtml", "chapter_0004.xhtml"], names);
+
+        let mut book = read_from_file(&file).unwrap();
+        assert_ne!(Some("2024-01-01T00:00:00Z"), book.last_modify());
+        assert_eq!(
+            vec![
+                "chapter_0001.xhtml",
+                "chapter_0002.xhtml",
+                "chapter_0003.xhtml",
+                "chapter_0004.xhtml"
+            ],
+            book.chapters()
+                .filter(|f| !f.is_generated())
+                .map(|f| f.file_name())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["1. 第一章", "2. 第二章", "3. 第三章", "4. 第四章"],
+            book.nav().map(|f| f.title()).collect::<Vec<_>>()
+        );
+        assert_eq!("chapter_0004.xhtml", book.nav().last().unwrap().file_name());
+        let chap = book.get_chapter_mut("chapter_0003.xhtml").unwrap();
+        assert_eq!("3. 第三章", chap.title());
+        assert!(String::from_utf8_lossy(chap.data_mut().unwrap()).contains("<p>新的章节</p>"));
+        let chap = book.get_chapter_mut("chapter_0001.xhtml").unwrap();
+        assert!(String::from_utf8_lossy(chap.data_mut().unwrap()).contains("<p>第一章</p>"));
+        assert_eq!(
+            crate::cbz::core::tests::png(1, 1),
+            book.cover_mut().unwrap().data_mut().unwrap()
+        );
+        assert!(crate::epub::validate::is_valid(
+            &crate::epub::validate::validate_file(&file).unwrap()
+        ));
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_append_chapters_epub3() {
+        let file =
+            std::env::temp_dir().join(format!("{}.test_append_epub3.epub", std::process::id()));
+        std::fs::write(&file, crate::epub::reader::tests::epub3()).unwrap();
+
+        let names = append_chapters(
+            &file,
+            vec![EpubHtml::default()
+                .with_title("第四章")
+                .with_data("<p>新的章节</p>".as_bytes().to_vec())],
+            false,
+        )
+        .unwrap();
+        assert_eq!(vec!["text/c5.xhtml"], names);
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&file).unwrap()).unwrap();
+        let mut files: Vec<&str> = zip.file_names().collect();
+        files.sort();
+        assert_eq!(
+            vec![
+                "EPUB/package.opf",
+                "EPUB/style.css",
+                "EPUB/text/c1.xhtml",
+                "EPUB/text/c2.xhtml",
+                "EPUB/text/c3.xhtml",
+                "EPUB/text/c4.xhtml",
+                "EPUB/text/c5.xhtml",
+                "EPUB/toc.ncx",
+                "EPUB/toc.xhtml",
+                "META-INF/container.xml",
+                "mimetype"
+            ],
+            files
+        );
+        let opf = super::read_string(&mut zip, "EPUB/package.opf").unwrap();
+        let (items, toc) = super::read_manifest(&opf).unwrap();
+        assert_eq!(Some("ncx"), toc.as_deref());
+        assert_eq!(
+            vec![
+                ("ncx", "toc.ncx"),
+                ("nav", "toc.xhtml"),
+                ("ch1", "text/c1.xhtml"),
+                ("ch2", "text/c2.xhtml"),
+                ("ch3", "text/c3.xhtml"),
+                ("appendix", "text/c4.xhtml"),
+                ("css", "style.css"),
+                ("chap_7", "text/c5.xhtml")
+            ],
+            items
+                .iter()
+                .map(|f| (f.id.as_str(), f.href.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert!(opf.contains(
+            r#"<itemref idref="appendix"/>
+  <itemref idref="chap_7"/></spine>"#
+        ));
+        assert_eq!(1, opf.matches("generator").count());
+        assert!(!opf.contains("2024-01-01T00:00:00Z"));
+        let nav = super::read_string(&mut zip, "EPUB/toc.xhtml").unwrap();
+        assert!(nav.contains(
+            r#"<li><a href="text/c3.xhtml">第三章</a></li>
+<li><a href="text/c5.xhtml">第四章</a></li></ol></nav>"#
+        ));
+
+        let mut book = read_from_file(&file).unwrap();
+        assert_eq!(
+            vec!["第一卷", "第三章", "第四章"],
+            book.nav().map(|f| f.title()).collect::<Vec<_>>()
+        );
+        assert_eq!(Some("zh"), book.language());
+        let chap = book.get_chapter_mut("text/c5.xhtml").unwrap();
+        assert!(String::from_utf8_lossy(chap.data_mut().unwrap()).contains("<p>新的章节</p>"));
+        assert!(crate::epub::validate::is_valid(
+            &crate::epub::validate::validate_file(&file).unwrap()
+        ));
+        let _ = std::fs::remove_file(&file);
+    }
 }
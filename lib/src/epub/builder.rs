@@ -1,6 +1,7 @@
 use std::path::Path;
 
 use crate::prelude::*;
+use crate::text::typography::TypographyPolicy;
 
 ///
 /// 简化epub构建
@@ -22,6 +23,14 @@ pub struct EpubBuilder {
     /// 字体文件内容
     /// 用于生成封面图片
     font_byte: Option<Vec<u8>>,
+    /// 输出时的文件布局
+    layout: EpubLayout,
+    /// 章节内容规范化策略
+    normalize: Option<NormalizePolicy>,
+    /// 排版规则
+    typography: Option<TypographyPolicy>,
+    /// 固定版式页面的默认尺寸
+    viewport: Option<(u32, u32)>,
 }
 
 impl Default for EpubBuilder {
@@ -40,6 +49,10 @@ impl EpubBuilder {
             auto_gen_cover: false,
             font: None,
             font_byte: None,
+            layout: EpubLayout::default(),
+            normalize: None,
+            typography: None,
+            viewport: None,
         }
     }
     /// 是否添加标题，默认true
@@ -91,11 +104,65 @@ impl EpubBuilder {
         self
     }
 
+    pub fn with_language<T: AsRef<str>>(mut self, language: T) -> Self {
+        self.book.set_language(language);
+        self
+    }
+
     pub fn with_last_modify<T: AsRef<str>>(mut self, last_modify: T) -> Self {
         self.book.set_last_modify(last_modify);
         self
     }
 
+    ///
+    /// 设置书写方向
+    ///
+    /// 取值同 css 的 writing-mode，例如 horizontal-tb、vertical-rl，写入时会在每个章节中添加对应的样式，
+    /// 竖排的中文、日文一般还需要设置 [EpubBuilder::with_page_direction] 为 rtl
+    ///
+    pub fn with_writing_mode<T: AsRef<str>>(mut self, writing_mode: T) -> Self {
+        self.book.set_writing_mode(writing_mode);
+        self
+    }
+
+    /// 设置翻页方向，ltr 或 rtl，写入 spine 的 page-progression-direction
+    pub fn with_page_direction<T: AsRef<str>>(mut self, page_direction: T) -> Self {
+        self.book.set_page_direction(page_direction);
+        self
+    }
+
+    ///
+    /// 设置 rendition:layout
+    ///
+    /// pre-paginated 为固定版式，此时需要 epub3，每个章节是一页
+    ///
+    pub fn with_rendition_layout<T: AsRef<str>>(mut self, layout: T) -> Self {
+        self.book.set_rendition_layout(layout);
+        self
+    }
+
+    /// 设置 rendition:orientation，auto、landscape 或 portrait
+    pub fn with_rendition_orientation<T: AsRef<str>>(mut self, orientation: T) -> Self {
+        self.book.set_rendition_orientation(orientation);
+        self
+    }
+
+    /// 设置 rendition:spread，none、landscape、both 或 auto
+    pub fn with_rendition_spread<T: AsRef<str>>(mut self, spread: T) -> Self {
+        self.book.set_rendition_spread(spread);
+        self
+    }
+
+    ///
+    /// 设置固定版式页面的默认尺寸
+    ///
+    /// 没有通过 [EpubHtml::set_viewport] 设置尺寸的章节使用该尺寸
+    ///
+    pub fn with_viewport(mut self, width: u32, height: u32) -> Self {
+        self.viewport = Some((width, height));
+        self
+    }
+
     pub fn custome_nav(mut self, value: bool) -> Self {
         self.custome_nav = value;
         self
@@ -118,6 +185,36 @@ impl EpubBuilder {
         self
     }
 
+    ///
+    /// 设置输出时的文件布局
+    ///
+    /// 只在 file()、mem() 时生效，章节和资源的路径以及相互引用会自动调整
+    ///
+    pub fn with_layout(mut self, layout: EpubLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    ///
+    /// 输出前将章节内容规范化为 xhtml
+    ///
+    /// 只在 book()、file()、mem() 时生效，需要修改记录时可以调用 [EpubBook::normalize]
+    ///
+    pub fn with_normalize(mut self, policy: NormalizePolicy) -> Self {
+        self.normalize = Some(policy);
+        self
+    }
+
+    ///
+    /// 输出前按规则调整章节的排版，在规范化之前执行
+    ///
+    /// 只在 book()、file()、mem() 时生效
+    ///
+    pub fn with_typography(mut self, policy: TypographyPolicy) -> Self {
+        self.typography = Some(policy);
+        self
+    }
+
     ///
     /// 添加 metadata
     ///
@@ -207,6 +304,29 @@ impl EpubBuilder {
         }
     }
 
+    fn gen_viewport(&mut self) {
+        if let Some((width, height)) = self.viewport {
+            for ele in self.book.chapters_mut() {
+                if ele.viewport().is_none() {
+                    ele.set_viewport(width, height);
+                }
+            }
+        }
+    }
+
+    fn gen_typography(&mut self) -> IResult<()> {
+        if let Some(policy) = &self.typography {
+            self.book.typeset(policy)?;
+        }
+        Ok(())
+    }
+
+    fn gen_normalize(&mut self) {
+        if let Some(policy) = &self.normalize {
+            self.book.normalize(policy);
+        }
+    }
+
     fn gen_cover(&mut self) -> IResult<()> {
         if self.auto_gen_cover && self.book.cover().is_none() {
             let font_bytes = match self.font_byte.clone() {
@@ -238,6 +358,9 @@ impl EpubBuilder {
     pub fn book(mut self) -> IResult<EpubBook> {
         self.gen_last_modify();
         self.gen_nav();
+        self.gen_viewport();
+        self.gen_typography()?;
+        self.gen_normalize();
         self.gen_cover()?;
         Ok(self.book)
     }
@@ -248,6 +371,9 @@ impl EpubBuilder {
     pub fn file<T: AsRef<Path>>(mut self, file: T) -> IResult<()> {
         self.gen_last_modify();
         self.gen_nav();
+        self.gen_viewport();
+        self.gen_typography()?;
+        self.gen_normalize();
         self.gen_cover()?;
 
         std::fs::OpenOptions::new()
@@ -257,7 +383,11 @@ impl EpubBuilder {
             .open(file)
             .map_or_else(
                 |e| Err(IError::Io(e)),
-                |f| Ok(EpubWriter::new(f).with_append_title(self.append_title)),
+                |f| {
+                    Ok(EpubWriter::new(f)
+                        .with_append_title(self.append_title)
+                        .with_layout(self.layout.clone()))
+                },
             )
             .and_then(|mut w| w.write(&mut self.book))
     }
@@ -268,10 +398,14 @@ impl EpubBuilder {
     pub fn mem(mut self) -> IResult<Vec<u8>> {
         self.gen_last_modify();
         self.gen_nav();
+        self.gen_viewport();
+        self.gen_typography()?;
+        self.gen_normalize();
         self.gen_cover()?;
         let mut v = std::io::Cursor::new(Vec::new());
         EpubWriter::new(&mut v)
             .with_append_title(self.append_title)
+            .with_layout(self.layout.clone())
             .write(&mut self.book)?;
 
         Ok(v.into_inner())
@@ -279,7 +413,7 @@ impl EpubBuilder {
 
     #[cfg(feature = "cache")]
     pub fn cache<T: AsRef<Path>>(&self, file: T) -> IResult<()> {
-        std::fs::write(file, serde_json::to_string(&self.book).unwrap())?;
+        std::fs::write(file, serde_json::to_string(&self.book)?)?;
         Ok(())
     }
 
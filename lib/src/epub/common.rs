@@ -7,11 +7,12 @@ crate::cache_enum!{
 }
 
 pub static EPUB: &str = "OEBPS/";
-pub static EPUB3: &str = "EPUB/";
 pub static TOC: &str = "OEBPS/toc.ncx";
 pub static NAV: &str = "OEBPS/nav.xhtml";
 pub static COVER: &str = "OEBPS/cover.xhtml";
 pub static OPF: &str = "OEBPS/content.opf";
+/// 没有设置语言时写入的语言，与生成的 xhtml 的 lang 一致
+pub static DEFAULT_LANGUAGE: &str = "zh";
 
 impl std::fmt::Display for LinkRel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -1,13 +1,19 @@
 use std::collections::HashMap;
 use std::fmt::{Debug, Display};
 use std::io::Write;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use super::common::{self};
-use super::html::{get_html_info, to_html};
+use super::html::{get_html_info, get_html_viewport, to_html};
 use crate::cache_struct;
-use crate::common::{IError, IResult};
+use crate::common::{get_media_type, ErrorContext, IError, IResult, ResultContext};
 use crate::epub::common::LinkRel;
+use crate::epub::xhtml::{normalize_html, NormalizeChange, NormalizePolicy, NormalizeReport};
+use crate::text::diff::{BookDiff, BookSnapshot};
+use crate::text::export::html_text;
+use crate::text::rewrite::{RewriteReport, RewriteRules};
+use crate::text::search::{epub_nav_path, SearchMatch, SearchQuery};
+use crate::text::stats::{BookStats, ChapterStats};
+use crate::text::typography::{typeset_html, TypographyPolicy};
 
 pub(crate) mod info {
     include!(concat!(env!("OUT_DIR"), "/version.rs"));
@@ -35,10 +41,10 @@ macro_rules! epub_base_field{
                     media_type:String,
                     _data: Option<Vec<u8>>,
                     #[cfg(not(feature="cache"))]
-                    reader:Option<std::sync::Arc<std::sync::Mutex< Box<dyn EpubReaderTrait+Send+Sync>>>>,
+                    reader:Option<std::sync::Arc<dyn EpubReaderTrait>>,
                     #[cfg(feature="cache")]
                     #[serde(skip)]
-                    reader:Option<std::sync::Arc<std::sync::Mutex< Box<dyn EpubReaderTrait+Send+Sync>>>>,
+                    reader:Option<std::sync::Arc<dyn EpubReaderTrait>>,
                     $(
                         $(#[$field_meta])*
                         $field_vis $field_name : $field_type,
@@ -117,6 +123,14 @@ epub_base_field! {
         css: Option<String>,
         /// 文件初始内容
         raw_data:Option<String>,
+        /// 固定版式页面的尺寸，写入 viewport
+        viewport: Option<(u32, u32)>,
+        /// spine 中 itemref 的 properties，例如 page-spread-left
+        spine_properties: Option<String>,
+        /// manifest 中 item 的 properties，例如 nav
+        pub(crate) properties: Option<String>,
+        /// 读取时根据 manifest 识别出的导航等文件，写入时会重新生成
+        pub(crate) generated: bool,
     }
 }
 
@@ -161,27 +175,17 @@ impl EpubHtml {
         } else {
             (None, self.file_name().to_string())
         };
-        let mut f = String::from(self._file_name.as_str());
-        let prefixs = vec!["", common::EPUB, common::EPUB3];
-        if self._data.is_none() && self.reader.is_some() && !f.is_empty() {
-            for prefix in prefixs.iter() {
-                // 添加 前缀再次读取
-                f = format!("{prefix}{origin}");
-                let s = self.reader.as_mut().unwrap();
-                let d = s.lock().unwrap().read_string(f.as_str());
-                match d {
-                    Ok(v) => {
-                        if let Ok((title, data)) = get_html_info(v.as_str(), id) {
-                            if !title.is_empty() {
-                                self.set_title(&title);
-                            }
-                            self.set_data(data);
-                        }
-                        break;
+        if self._data.is_none() && !origin.is_empty() {
+            if let Some(r) = &self.reader {
+                if let Ok(v) = r.read_string(origin.as_str()) {
+                    if self.viewport.is_none() {
+                        self.viewport = get_html_viewport(v.as_str());
                     }
-                    Err(IError::FileNotFound) => {}
-                    Err(e) => {
-                        break;
+                    if let Ok((title, data)) = get_html_info(v.as_str(), id) {
+                        if !title.is_empty() {
+                            self.set_title(&title);
+                        }
+                        self.set_data(data);
                     }
                 }
             }
@@ -198,27 +202,14 @@ impl EpubHtml {
 
     pub fn format(&mut self) -> Option<String> {
         self.data_mut();
-        Some(to_html(self, false))
+        to_html(self, false).ok()
     }
 
     pub fn raw_data(&mut self) -> Option<&str> {
-        let (id, origin) = if let Some(index) = self._file_name.find(|f| f == '#') {
-            (
-                Some(&self._file_name[(index + 1)..]),
-                self._file_name[0..index].to_string(),
-            )
-        } else {
-            (None, self.file_name().to_string())
-        };
-        let mut f = String::from(self._file_name.as_str());
-        let prefixs = vec!["", common::EPUB, common::EPUB3];
-        if self.raw_data.is_none() && self.reader.is_some() && !f.is_empty() {
-            for prefix in prefixs.iter() {
-                // 添加 前缀再次读取
-                f = format!("{prefix}{origin}");
-                let s = self.reader.as_mut().unwrap();
-                let d = s.lock().unwrap().read_string(f.as_str());
-                if let Ok(data) = d {
+        let origin = self._file_name.split('#').next().unwrap_or_default();
+        if self.raw_data.is_none() && !origin.is_empty() {
+            if let Some(r) = &self.reader {
+                if let Ok(data) = r.read_string(origin) {
                     self.raw_data = Some(data);
                 }
             }
@@ -258,6 +249,52 @@ impl EpubHtml {
         self.css.as_deref()
     }
 
+    ///
+    /// 设置固定版式页面的尺寸
+    ///
+    /// 写入时会添加 `<meta name="viewport" content="width=.., height=.."/>`，并且不再添加标题
+    ///
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = Some((width, height));
+    }
+    pub fn with_viewport(mut self, width: u32, height: u32) -> Self {
+        self.set_viewport(width, height);
+        self
+    }
+    /// 页面尺寸，读取时需要先加载数据
+    pub fn viewport(&self) -> Option<(u32, u32)> {
+        self.viewport
+    }
+
+    ///
+    /// 设置 spine 中 itemref 的 properties
+    ///
+    /// 例如 `page-spread-left`、`rendition:page-spread-center`、`rendition:layout-reflowable`，多个值使用空格分隔
+    ///
+    pub fn set_spine_properties<T: Into<String>>(&mut self, properties: T) {
+        self.spine_properties = Some(properties.into());
+    }
+    pub fn with_spine_properties<T: Into<String>>(mut self, properties: T) -> Self {
+        self.set_spine_properties(properties);
+        self
+    }
+    pub fn spine_properties(&self) -> Option<&str> {
+        self.spine_properties.as_deref()
+    }
+
+    /// 是否是 epub3 的导航文档
+    pub(crate) fn is_nav(&self) -> bool {
+        has_nav_property(self.properties.as_deref())
+    }
+    /// 是否是写入时会重新生成的文件
+    pub(crate) fn is_generated(&self) -> bool {
+        self.generated
+    }
+    /// 是否是正文章节，生成的目录、封面以及导航文档不是
+    pub(crate) fn is_content(&self) -> bool {
+        !self.is_generated() && !self.is_nav()
+    }
+
     fn set_language<T: Into<String>>(&mut self, lang: T) {
         self.lang = lang.into();
     }
@@ -274,9 +311,54 @@ impl EpubHtml {
         }
     }
 
-    fn get_links(&mut self) -> Option<&mut Vec<EpubLink>> {
+    pub(crate) fn get_links(&mut self) -> Option<&mut Vec<EpubLink>> {
         self.links.as_mut()
     }
+
+    ///
+    /// 将章节内容规范化为 xhtml
+    ///
+    /// 没有任何修改时保留原数据
+    ///
+    pub fn normalize(&mut self, policy: &NormalizePolicy) -> NormalizeReport {
+        let file_name = self.file_name().to_string();
+        let Some(data) = self.data_mut() else {
+            return NormalizeReport::new(file_name, Vec::new());
+        };
+        let html = String::from_utf8_lossy(data);
+        let (value, mut changes) = normalize_html(&html, policy);
+        if let std::borrow::Cow::Owned(_) = html {
+            // 非 utf8 的字节被替换
+            changes.insert(0, (NormalizeChange::InvalidChar, 1));
+        }
+        if !changes.is_empty() {
+            self.set_data(value.into_bytes());
+        }
+        NormalizeReport::new(file_name, changes)
+    }
+
+    ///
+    /// 按规则调整章节的排版
+    ///
+    /// 设置了首行缩进时，会在章节的 css 中添加 `p{text-indent:..}`，已有 text-indent 时不添加
+    ///
+    pub fn typeset(&mut self, policy: &TypographyPolicy) -> IResult<()> {
+        let file_name = self.file_name().to_string();
+        let Some(data) = self.data_mut() else {
+            return Ok(());
+        };
+        let html = String::from_utf8(data.to_vec())
+            .map_err(IError::Utf8)
+            .context(|| ErrorContext::new("typeset chapter").with_entry(file_name))?;
+        self.set_data(typeset_html(&html, policy).into_bytes());
+        if let Some(indent) = policy.text_indent() {
+            let css = self.css().unwrap_or_default();
+            if !css.contains("text-indent") {
+                self.set_css(format!("{css}p{{text-indent:{indent};}}"));
+            }
+        }
+        Ok(())
+    }
 }
 
 epub_base_field! {
@@ -288,6 +370,10 @@ epub_base_field! {
 #[derive(Default,Clone)]
 pub struct EpubAssets {
    pub(crate) version:String,
+   /// manifest 中 item 的 properties，例如 nav
+   pub(crate) properties: Option<String>,
+   /// 读取时根据 manifest 识别出的导航、toc.ncx 等文件，写入时会重新生成
+   pub(crate) generated: bool,
 }
 }
 
@@ -301,19 +387,10 @@ impl EpubAssets {
     }
 
     pub fn data_mut(&mut self) -> Option<&[u8]> {
-        let mut f = String::from(self._file_name.as_str());
-        if self._data.is_none() && self.reader.is_some() && !f.is_empty() {
-            let prefixs = vec!["", common::EPUB, common::EPUB3];
-            if self._data.is_none() && self.reader.is_some() && !f.is_empty() {
-                for prefix in prefixs.iter() {
-                    let s = self.reader.as_mut().unwrap();
-                    // 添加 前缀再次读取
-                    f = format!("{prefix}{}", self._file_name);
-                    let d = s.lock().unwrap().read_file(f.as_str());
-                    if let Ok(v) = d {
-                        self.set_data(v);
-                        break;
-                    }
+        if self._data.is_none() && !self._file_name.is_empty() {
+            if let Some(r) = &self.reader {
+                if let Ok(v) = r.read_file(self._file_name.as_str()) {
+                    self.set_data(v);
                 }
             }
         }
@@ -329,19 +406,9 @@ impl EpubAssets {
     }
 
     pub fn save_to<T: AsRef<str>>(&mut self, file_path: T) -> IResult<()> {
-        let mut f: String = self._file_name.clone();
-        if self.reader.is_some() && !f.is_empty() {
-            let prefixs = vec!["", common::EPUB, common::EPUB3];
-            for prefix in prefixs.iter() {
-                let s = self.reader.as_mut().unwrap();
-                f = format!("{prefix}{}", self._file_name);
-                let d: Result<(), IError> = s
-                    .lock()
-                    .unwrap()
-                    .read_to_path(f.as_str(), file_path.as_ref());
-                if d.is_ok() {
-                    break;
-                }
+        if !self._file_name.is_empty() {
+            if let Some(r) = &self.reader {
+                r.read_to_path(self._file_name.as_str(), file_path.as_ref())?;
             }
         }
         Ok(())
@@ -353,6 +420,19 @@ impl EpubAssets {
         }
         self._data = None;
     }
+
+    /// 是否是 epub3 的导航文档
+    pub(crate) fn is_nav(&self) -> bool {
+        has_nav_property(self.properties.as_deref())
+    }
+    /// 是否是写入时会重新生成的文件
+    pub(crate) fn is_generated(&self) -> bool {
+        self.generated
+    }
+}
+
+fn has_nav_property(properties: Option<&str>) -> bool {
+    properties.is_some_and(|f| f.split_whitespace().any(|v| v == "nav"))
 }
 
 impl Debug for EpubAssets {
@@ -441,6 +521,10 @@ impl EpubNav {
     pub fn child(&self) -> std::slice::Iter<EpubNav> {
         self.child.iter()
     }
+
+    pub fn child_mut(&mut self) -> std::slice::IterMut<'_, EpubNav> {
+        self.child.iter_mut()
+    }
 }
 
 cache_struct! {
@@ -512,12 +596,18 @@ pub struct EpubBook {
     version: String,
     /// 处于读模式
     #[cfg(not(feature="cache"))]
-    reader:Option<std::sync::Arc<std::sync::Mutex< Box<dyn EpubReaderTrait+Send+Sync>>>>,
+    reader:Option<std::sync::Arc<dyn EpubReaderTrait>>,
     #[cfg(feature="cache")]
     #[serde(skip)]
-    reader:Option<std::sync::Arc<std::sync::Mutex< Box<dyn EpubReaderTrait+Send+Sync>>>>,
+    reader:Option<std::sync::Arc<dyn EpubReaderTrait>>,
     /// PREFIX
     pub(crate) prefix: String,
+    /// rendition:layout，reflowable 或 pre-paginated
+    rendition_layout: Option<String>,
+    /// rendition:orientation，auto、landscape 或 portrait
+    rendition_orientation: Option<String>,
+    /// rendition:spread，none、landscape、both 或 auto
+    rendition_spread: Option<String>,
 }
 }
 
@@ -544,6 +634,9 @@ impl EpubBook {
     iepub_derive::option_string_method!(info, format);
     iepub_derive::option_string_method!(info, publisher);
     iepub_derive::option_string_method!(info, subject);
+    iepub_derive::option_string_method!(info, language);
+    iepub_derive::option_string_method!(info, writing_mode);
+    iepub_derive::option_string_method!(info, page_direction);
     // /
     // / 设置epub最后修改时间
     // /
@@ -556,6 +649,18 @@ impl EpubBook {
     // /
     iepub_derive::option_string_method!(last_modify);
     iepub_derive::option_string_method!(generator);
+    iepub_derive::option_string_method!(rendition_layout);
+    iepub_derive::option_string_method!(rendition_orientation);
+    iepub_derive::option_string_method!(rendition_spread);
+
+    ///
+    /// 是否是固定版式
+    ///
+    /// rendition:layout 为 pre-paginated 时是固定版式，每个章节是一页，尺寸由 [EpubHtml::set_viewport] 设置
+    ///
+    pub fn is_fixed_layout(&self) -> bool {
+        self.rendition_layout() == Some("pre-paginated")
+    }
 }
 
 // 元数据
@@ -612,10 +717,19 @@ impl EpubBook {
         self.meta.len()
     }
 
-    pub(crate) fn set_reader(
-        &mut self,
-        reader: Arc<Mutex<Box<dyn EpubReaderTrait + Send + Sync>>>,
-    ) {
+    ///
+    /// 设置读取句柄，同时分发给已有的章节和资源
+    ///
+    pub(crate) fn set_reader(&mut self, reader: Arc<dyn EpubReaderTrait>) {
+        for ele in self.chapters.iter_mut() {
+            ele.reader = Some(Arc::clone(&reader));
+        }
+        for ele in self.assets.iter_mut() {
+            ele.reader = Some(Arc::clone(&reader));
+        }
+        if let Some(cover) = &mut self.cover {
+            cover.reader = Some(Arc::clone(&reader));
+        }
         self.reader = Some(reader)
     }
 
@@ -679,6 +793,158 @@ impl EpubBook {
         self.chapters.iter()
     }
 
+    ///
+    /// 将所有章节规范化为 xhtml
+    ///
+    /// # Returns
+    ///
+    /// 有修改的章节的修改记录
+    ///
+    pub fn normalize(&mut self, policy: &NormalizePolicy) -> Vec<NormalizeReport> {
+        self.chapters
+            .iter_mut()
+            .map(|chap| chap.normalize(policy))
+            .filter(|report| !report.is_empty())
+            .collect()
+    }
+
+    /// 按规则调整所有章节的排版
+    pub fn typeset(&mut self, policy: &TypographyPolicy) -> IResult<()> {
+        for chap in self.chapters.iter_mut() {
+            chap.typeset(policy)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// 按规则替换所有章节的文本节点，生成的目录、封面以及导航文档不替换
+    ///
+    /// # Returns
+    ///
+    /// 有替换的章节的替换次数，只有这些章节的数据会被修改
+    ///
+    pub fn rewrite(&mut self, rules: &RewriteRules) -> IResult<Vec<RewriteReport>> {
+        let mut out = Vec::new();
+        for (index, chap) in self.chapters.iter_mut().enumerate() {
+            if !chap.is_content() {
+                continue;
+            }
+            let file_name = chap.file_name().to_string();
+            let Some(data) = chap.data_mut() else {
+                continue;
+            };
+            let html = String::from_utf8(data.to_vec())
+                .map_err(IError::Utf8)
+                .context(|| ErrorContext::new("rewrite chapter").with_entry(file_name.as_str()))?;
+            let (value, count) = rules.rewrite_html(&html);
+            if count > 0 {
+                chap.set_data(value.into_bytes());
+                out.push(RewriteReport::new(
+                    index,
+                    Some(file_name),
+                    chap.title(),
+                    count,
+                ));
+            }
+        }
+        Ok(out)
+    }
+
+    ///
+    /// 搜索正文章节的文本，生成的目录、封面以及导航文档不搜索
+    ///
+    /// 延迟读取的章节会先加载，不在目录中的章节使用加载后的标题
+    ///
+    pub fn search(&mut self, query: &SearchQuery) -> IResult<Vec<SearchMatch>> {
+        let mut out = Vec::new();
+        for (index, (file_name, title, html)) in self.content_chapters().into_iter().enumerate() {
+            let nav = epub_nav_path(self.nav.iter(), &file_name).unwrap_or_else(|| vec![title]);
+            for mut m in query.find(&html_text(&html))? {
+                m.chapter = index;
+                m.file_name = Some(file_name.clone());
+                m.nav = nav.clone();
+                out.push(m);
+            }
+        }
+        Ok(out)
+    }
+
+    ///
+    /// 统计字数、段落、图片以及资源大小，生成的目录、封面以及导航文档不统计
+    ///
+    /// 延迟读取的章节和资源会先加载，章节标题优先使用目录中的标题
+    ///
+    pub fn stats(&mut self) -> IResult<BookStats> {
+        let chapters = self
+            .content_chapters()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (file_name, title, html))| {
+                ChapterStats::new(index, Some(file_name), &title, &html)
+            })
+            .collect();
+        // 封面可能同时在资源中
+        let mut files = std::collections::HashSet::new();
+        let mut assets = Vec::new();
+        for asset in self.assets.iter_mut().chain(self.cover.as_mut()) {
+            if asset.is_generated()
+                || asset.is_nav()
+                || !files.insert(asset.file_name().to_string())
+            {
+                continue;
+            }
+            let media_type = Some(asset.media_type.clone())
+                .filter(|f| !f.is_empty())
+                .or_else(|| Some(get_media_type(asset.file_name())).filter(|f| !f.is_empty()))
+                .unwrap_or_else(|| "application/octet-stream".to_string());
+            assets.push((media_type, asset.data_mut().map_or(0, |f| f.len())));
+        }
+        Ok(BookStats::new(chapters, assets))
+    }
+
+    ///
+    /// 加载正文章节，返回文件名、标题和内容
+    ///
+    /// 生成的目录、封面以及导航文档不包括在内。标题在加载后获取，
+    /// 优先使用目录中的标题，同一文件有多个目录时取最下级的，其次是章节自身的 title
+    ///
+    pub(crate) fn content_chapters(&mut self) -> Vec<(String, String, String)> {
+        let mut titles: HashMap<&str, &str> = HashMap::new();
+        let all = flatten_all_nav(&self.nav);
+        for nav in flatten_nav(&self.nav).into_iter().chain(all) {
+            titles.entry(nav.file_name()).or_insert(nav.title());
+        }
+
+        let mut chapters = Vec::new();
+        for chap in self.chapters.iter_mut() {
+            if !chap.is_content() {
+                continue;
+            }
+            let file_name = chap.file_name().to_string();
+            let Some(data) = chap.data_mut() else {
+                continue;
+            };
+            let html = String::from_utf8_lossy(data).to_string();
+            let title = titles
+                .get(file_name.as_str())
+                .map_or_else(|| chap.title().to_string(), |f| f.to_string());
+            chapters.push((file_name, title, html));
+        }
+        chapters
+    }
+
+    ///
+    /// 比较两本书的差异，当前书为旧书
+    ///
+    /// 延迟读取的章节和资源会先加载
+    ///
+    pub fn diff(&mut self, other: &mut EpubBook) -> IResult<BookDiff> {
+        Ok(BookDiff::new(
+            &BookSnapshot::from_epub(self)?,
+            &BookSnapshot::from_epub(other)?,
+        ))
+    }
+
     ///
     /// 查找章节
     ///
@@ -715,6 +981,10 @@ impl EpubBook {
         self.nav.iter()
     }
 
+    pub fn nav_mut(&mut self) -> std::slice::IterMut<'_, EpubNav> {
+        self.nav.iter_mut()
+    }
+
     pub fn set_cover(&mut self, cover: EpubAssets) {
         self.cover = Some(cover);
     }
@@ -772,9 +1042,50 @@ impl EpubBook {
         }
     }
 
+    ///
+    /// 移除文件不存在的章节、资源以及封面
+    ///
+    /// # Returns
+    ///
+    /// 被移除的文件，不重复
+    ///
+    pub(crate) fn retain_files<F: Fn(&str) -> bool>(&mut self, exists: F) -> Vec<String> {
+        let mut removed: Vec<String> = Vec::new();
+        for name in self
+            .chapters
+            .iter()
+            .map(|f| f.file_name())
+            .chain(self.assets.iter().map(|f| f.file_name()))
+            .chain(self.cover.iter().map(|f| f.file_name()))
+        {
+            if !exists(name) && !removed.iter().any(|f| f == name) {
+                removed.push(name.to_string());
+            }
+        }
+        self.chapters.retain(|f| exists(f.file_name()));
+        self.assets.retain(|f| exists(f.file_name()));
+        if self.cover.as_ref().is_some_and(|f| !exists(f.file_name())) {
+            self.cover = None;
+        }
+        removed
+    }
+
+    ///
+    /// 移除不满足条件的目录，其下级目录上移一级
+    ///
+    /// # Returns
+    ///
+    /// 被移除的目录，不包括下级目录
+    ///
+    pub(crate) fn retain_nav<F: Fn(&EpubNav) -> bool>(&mut self, keep: F) -> Vec<EpubNav> {
+        let mut removed = Vec::new();
+        self.nav = retain_nav(std::mem::take(&mut self.nav), &keep, &mut removed);
+        removed
+    }
+
     #[cfg(feature = "cache")]
     pub fn cache<T: AsRef<std::path::Path>>(&self, file: T) -> IResult<()> {
-        std::fs::write(file, serde_json::to_string(self).unwrap())?;
+        std::fs::write(file, serde_json::to_string(self)?)?;
         Ok(())
     }
 
@@ -804,22 +1115,55 @@ fn flatten_nav(nav: &[EpubNav]) -> Vec<&EpubNav> {
     }
     n
 }
+
+/// 按顺序展开所有目录，包括有下级的目录
+fn flatten_all_nav(nav: &[EpubNav]) -> Vec<&EpubNav> {
+    let mut n = Vec::new();
+    for ele in nav {
+        n.push(ele);
+        n.append(&mut flatten_all_nav(&ele.child));
+    }
+    n
+}
+
+fn retain_nav<F: Fn(&EpubNav) -> bool>(
+    nav: Vec<EpubNav>,
+    keep: &F,
+    removed: &mut Vec<EpubNav>,
+) -> Vec<EpubNav> {
+    let mut n = Vec::new();
+    for mut ele in nav {
+        let child = retain_nav(std::mem::take(&mut ele.child), keep, removed);
+        if keep(&ele) {
+            ele.child = child;
+            n.push(ele);
+        } else {
+            n.extend(child);
+            removed.push(ele);
+        }
+    }
+    n
+}
+///
+/// 读取epub内文件
+///
+/// 所有方法只需要共享引用，同一个实例可以在多个线程间同时读取
+///
 pub(crate) trait EpubReaderTrait: Send + Sync {
-    fn read(&mut self, book: &mut EpubBook) -> IResult<()>;
     ///
-    /// file epub中的文件目录
+    /// file 相对于opf所在目录的文件路径
     ///
-    fn read_file(&mut self, file_name: &str) -> IResult<Vec<u8>>;
+    fn read_file(&self, file_name: &str) -> IResult<Vec<u8>>;
 
     ///
-    /// file epub中的文件目录
+    /// file 相对于opf所在目录的文件路径
     ///
-    fn read_string(&mut self, file_name: &str) -> IResult<String>;
+    fn read_string(&self, file_name: &str) -> IResult<String>;
 
     ///
-    /// file epub中的文件目录
+    /// file 相对于opf所在目录的文件路径
     ///
-    fn read_to_path(&mut self, file_name: &str, file_path: &str) -> IResult<()>;
+    fn read_to_path(&self, file_name: &str, file_path: &str) -> IResult<()>;
 }
 
 #[cfg(test)]
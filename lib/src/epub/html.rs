@@ -1,17 +1,35 @@
-use super::common;
-use crate::{common::get_media_type, prelude::*};
+use super::{common, layout::GeneratedFiles};
+use crate::{
+    common::{escape_xml, get_media_type, ErrorContext, ResultContext},
+    prelude::*,
+};
 use quick_xml::events::Event;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 /// 生成html
-pub(crate) fn to_html(chap: &mut EpubHtml, append_title: bool) -> String {
+pub(crate) fn to_html(chap: &mut EpubHtml, append_title: bool) -> IResult<String> {
+    to_html_with_writing_mode(chap, append_title, None, None)
+}
+
+///
+/// 生成html，书写方向不为空时在样式中添加 writing-mode
+///
+/// 语言优先使用章节的 lang，其次是书的语言，都没有时使用默认语言
+///
+pub(crate) fn to_html_with_writing_mode(
+    chap: &mut EpubHtml,
+    append_title: bool,
+    writing_mode: Option<&str>,
+    language: Option<&str>,
+) -> IResult<String> {
     let mut css = String::new();
     if let Some(links) = chap.links() {
         for ele in links {
             css.push_str(
                 format!(
                     "<link href=\"{}\" rel=\"stylesheet\" type=\"text/css\"/>",
-                    ele.href
+                    escape_xml(&ele.href)
                 )
                 .as_str(),
             );
@@ -22,23 +40,43 @@ pub(crate) fn to_html(chap: &mut EpubHtml, append_title: bool) -> String {
     if let Some(v) = cus_css {
         css.push_str(format!("\n<style type=\"text/css\">{}</style>", v).as_str());
     }
-    let mut body = String::new();
-    {
-        body.insert_str(
-            0,
-            String::from_utf8(chap.data_mut().as_ref().unwrap().to_vec())
-                .unwrap()
-                .as_str(),
+    if let Some(v) = writing_mode {
+        let v = escape_xml(v);
+        css.push_str(
+            format!(
+                "\n<style type=\"text/css\">html{{-epub-writing-mode:{v};-webkit-writing-mode:{v};writing-mode:{v}}}</style>"
+            )
+            .as_str(),
         );
-        // 正文
     }
-    let title = chap.title();
-    format!(
+    // 固定版式的页面不需要标题
+    let append_title = append_title && chap.viewport().is_none();
+    let viewport = match chap.viewport() {
+        Some((width, height)) => format!(
+            "\n    <meta name=\"viewport\" content=\"width={}, height={}\"/>",
+            width, height
+        ),
+        None => String::new(),
+    };
+    // 正文
+    let body = match chap.data_mut() {
+        Some(data) => String::from_utf8(data.to_vec())
+            .map_err(IError::Utf8)
+            .context(|| ErrorContext::new("write chapter").with_entry(chap.file_name()))?,
+        None => return Err(IError::MissingEntry(chap.file_name().to_string())),
+    };
+    let title = escape_xml(chap.title());
+    let lang = html_lang(
+        Some(chap.lang.as_str())
+            .filter(|f| !f.is_empty())
+            .or(language),
+    );
+    Ok(format!(
         r#"<?xml version='1.0' encoding='utf-8'?>
 <!DOCTYPE html>
-<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" epub:prefix="z3998: http://www.daisy.org/z3998/2012/vocab/structure/#" lang="zh" xml:lang="zh">
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" epub:prefix="z3998: http://www.daisy.org/z3998/2012/vocab/structure/#" lang="{lang}" xml:lang="{lang}">
   <head>
-    <title>{title}</title>
+    <title>{title}</title>{viewport}
 {css}
 </head>
   <body>
@@ -51,7 +89,7 @@ pub(crate) fn to_html(chap: &mut EpubHtml, append_title: bool) -> String {
         } else {
             String::new()
         }
-    )
+    ))
 }
 
 fn to_nav_xml(nav: std::slice::Iter<EpubNav>) -> String {
@@ -63,8 +101,8 @@ fn to_nav_xml(nav: std::slice::Iter<EpubNav>) -> String {
             xml.push_str(
                 format!(
                     "<li><a href=\"{}\">{}</a></li>",
-                    ele.file_name(),
-                    ele.title()
+                    escape_xml(ele.file_name()),
+                    escape_xml(ele.title())
                 )
                 .as_str(),
             );
@@ -72,8 +110,8 @@ fn to_nav_xml(nav: std::slice::Iter<EpubNav>) -> String {
             xml.push_str(
                 format!(
                     "<li><a href=\"{}\">{}</a>{}</li>",
-                    ele.child().as_slice()[0].file_name(),
-                    ele.title(),
+                    escape_xml(ele.child().as_slice()[0].file_name()),
+                    escape_xml(ele.title()),
                     to_nav_xml(ele.child()).as_str()
                 )
                 .as_str(),
@@ -85,9 +123,24 @@ fn to_nav_xml(nav: std::slice::Iter<EpubNav>) -> String {
 }
 
 /// 生成自定义的导航html
-pub(crate) fn to_nav_html(book_title: &str, nav: std::slice::Iter<EpubNav>) -> String {
+/// html 的 lang 属性，没有设置语言时使用默认语言
+fn html_lang(language: Option<&str>) -> Cow<'_, str> {
+    escape_xml(
+        language
+            .filter(|f| !f.is_empty())
+            .unwrap_or(common::DEFAULT_LANGUAGE),
+    )
+}
+
+pub(crate) fn to_nav_html(
+    book_title: &str,
+    nav: std::slice::Iter<EpubNav>,
+    language: Option<&str>,
+) -> String {
+    let book_title = escape_xml(book_title);
+    let lang = html_lang(language);
     format!(
-        r#"<?xml version='1.0' encoding='utf-8'?><!DOCTYPE html><html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="zh" xml:lang="zh"><head><title>{book_title}</title></head><body><nav epub:type="toc" id="id" role="doc-toc"><h2>{book_title}</h2>{}</nav></body></html>"#,
+        r#"<?xml version='1.0' encoding='utf-8'?><!DOCTYPE html><html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="{lang}" xml:lang="{lang}"><head><title>{book_title}</title></head><body><nav epub:type="toc" id="id" role="doc-toc"><h2>{book_title}</h2>{}</nav></body></html>"#,
         to_nav_xml(nav)
     )
 }
@@ -100,8 +153,8 @@ fn to_toc_xml_point(nav: std::slice::Iter<EpubNav>, parent: usize) -> String {
             xml.push_str(
                 format!(
                     "<navLabel><text>{}</text></navLabel><content src=\"{}\"></content>",
-                    ele.title(),
-                    ele.file_name()
+                    escape_xml(ele.title()),
+                    escape_xml(ele.file_name())
                 )
                 .as_str(),
             );
@@ -109,8 +162,8 @@ fn to_toc_xml_point(nav: std::slice::Iter<EpubNav>, parent: usize) -> String {
             xml.push_str(
                 format!(
                     "<navLabel><text>{}</text></navLabel><content src=\"{}\"></content>{}",
-                    ele.title(),
-                    ele.child().as_slice()[0].file_name(),
+                    escape_xml(ele.title()),
+                    escape_xml(ele.child().as_slice()[0].file_name()),
                     to_toc_xml_point(ele.child(), index).as_str()
                 )
                 .as_str(),
@@ -123,18 +176,35 @@ fn to_toc_xml_point(nav: std::slice::Iter<EpubNav>, parent: usize) -> String {
 
 /// 生成epub中的toc.ncx文件
 pub(crate) fn to_toc_xml(book_title: &str, nav: std::slice::Iter<EpubNav>) -> String {
+    let book_title = escape_xml(book_title);
     format!(
         r#"<?xml version='1.0' encoding='utf-8'?><ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><head><meta content="1394" name="dtb:uid"/><meta content="0" name="dtb:depth"/><meta content="0" name="dtb:totalPageCount"/><meta content="0" name="dtb:maxPageNumber"/></head><docTitle><text>{book_title}</text></docTitle><navMap>{}</navMap></ncx>"#,
         to_toc_xml_point(nav, 0)
     )
 }
 
+/// 转义后的文本节点
+fn text(v: &str) -> quick_xml::events::BytesText<'_> {
+    quick_xml::events::BytesText::from_escaped(escape_xml(v))
+}
+
+/// 转义后的属性
+fn attr<'a>(key: &'a str, value: &'a str) -> quick_xml::events::attributes::Attribute<'a> {
+    quick_xml::events::attributes::Attribute {
+        key: quick_xml::name::QName(key.as_bytes()),
+        value: match escape_xml(value) {
+            std::borrow::Cow::Borrowed(v) => std::borrow::Cow::Borrowed(v.as_bytes()),
+            std::borrow::Cow::Owned(v) => std::borrow::Cow::Owned(v.into_bytes()),
+        },
+    }
+}
+
 fn write_metadata(
     book: &EpubBook,
     generator: &str,
     xml: &mut quick_xml::Writer<std::io::Cursor<Vec<u8>>>,
 ) -> IResult<()> {
-    use quick_xml::events::{BytesStart, BytesText, Event};
+    use quick_xml::events::{BytesStart, Event};
 
     // metadata
     let mut metadata = BytesStart::new("metadata");
@@ -144,45 +214,65 @@ fn write_metadata(
     xml.write_event(Event::Start(metadata.borrow()))?;
 
     // metadata 内元素
-    let now = book
-        .last_modify()
-        .map_or_else(|| crate::common::DateTimeFormater::default().default_format(), String::from);
+    let now = book.last_modify().map_or_else(
+        || crate::common::DateTimeFormater::default().default_format(),
+        String::from,
+    );
 
     xml.create_element("meta")
         .with_attribute(("property", "dcterms:modified"))
-        .write_text_content(BytesText::new(now.as_str()))?;
+        .write_text_content(text(now.as_str()))?;
 
     if let Some(v) = book.date() {
         xml.create_element("dc:date")
             .with_attribute(("id", "date"))
-            .write_text_content(BytesText::new(v))?;
+            .write_text_content(text(v))?;
     }
 
     xml.create_element("meta")
         .with_attribute(("name", "generator"))
-        .with_attribute(("content", generator))
+        .with_attribute(attr("content", generator))
         .write_empty()?;
 
     xml.create_element("dc:identifier")
         .with_attribute(("id", "id"))
-        .write_text_content(BytesText::new(book.identifier()))?;
+        .write_text_content(text(book.identifier()))?;
     xml.create_element("dc:title")
-        .write_text_content(BytesText::new(book.title()))?;
-    // xml
-    // .create_element("dc:lang")
-    // .write_text_content(BytesText::new(book.info.title.as_str()));
+        .write_text_content(text(book.title()))?;
+    if let Some(v) = book.language() {
+        xml.create_element("dc:language")
+            .write_text_content(text(v))?;
+    }
     if let Some(creator) = book.creator() {
         xml.create_element("dc:creator")
             .with_attribute(("id", "creator"))
-            .write_text_content(BytesText::new(creator))?;
+            .write_text_content(text(creator))?;
     }
     if let Some(desc) = book.description() {
         xml.create_element("dc:description")
-            .write_text_content(BytesText::new(desc))?;
+            .write_text_content(text(desc))?;
 
         xml.create_element("meta")
             .with_attribute(("property", "desc"))
-            .write_text_content(BytesText::new(desc))?;
+            .write_text_content(text(desc))?;
+    }
+    // 固定版式
+    for (property, value) in [
+        ("rendition:layout", book.rendition_layout()),
+        ("rendition:orientation", book.rendition_orientation()),
+        ("rendition:spread", book.rendition_spread()),
+    ] {
+        if let Some(v) = value {
+            xml.create_element("meta")
+                .with_attribute(("property", property))
+                .write_text_content(text(v))?;
+        }
+    }
+    if let Some(v) = book.writing_mode() {
+        xml.create_element("meta")
+            .with_attribute(("name", "primary-writing-mode"))
+            .with_attribute(attr("content", v))
+            .write_empty()?;
     }
     if book.cover().is_some() {
         xml.create_element("meta")
@@ -194,32 +284,36 @@ fn write_metadata(
     if let Some(v) = book.format() {
         xml.create_element("dc:format")
             .with_attribute(("id", "format"))
-            .write_text_content(BytesText::new(v))?;
+            .write_text_content(text(v))?;
     }
     if let Some(v) = book.publisher() {
         xml.create_element("dc:publisher")
             .with_attribute(("id", "publisher"))
-            .write_text_content(BytesText::new(v))?;
+            .write_text_content(text(v))?;
     }
     if let Some(v) = book.subject() {
         xml.create_element("dc:subject")
             .with_attribute(("id", "subject"))
-            .write_text_content(BytesText::new(v))?;
+            .write_text_content(text(v))?;
     }
     if let Some(v) = book.contributor() {
         xml.create_element("dc:contributor")
             .with_attribute(("id", "contributor"))
-            .write_text_content(BytesText::new(v))?;
+            .write_text_content(text(v))?;
     }
 
-    // 自定义的meta
-    for ele in book.meta() {
+    // 自定义的meta，修改时间已经单独写入
+    for ele in book
+        .meta()
+        .iter()
+        .filter(|f| f.get_attr("property").map(|v| v.as_str()) != Some("dcterms:modified"))
+    {
         let mut x = xml.create_element("meta");
         for (key, value) in ele.attrs() {
-            x = x.with_attribute((key.as_str(), value.as_str()));
+            x = x.with_attribute(attr(key, value));
         }
         if let Some(t) = ele.text() {
-            x.write_text_content(BytesText::new(t))?;
+            x.write_text_content(text(t))?;
         } else {
             x.write_empty()?;
         }
@@ -230,7 +324,11 @@ fn write_metadata(
     Ok(())
 }
 
-pub(crate) fn do_to_opf(book: &mut EpubBook, generator: &str) -> IResult<String> {
+pub(crate) fn do_to_opf(
+    book: &mut EpubBook,
+    generator: &str,
+    files: &GeneratedFiles,
+) -> IResult<String> {
     let vue: Vec<u8> = Vec::new();
     let mut xml: quick_xml::Writer<std::io::Cursor<Vec<u8>>> =
         quick_xml::Writer::new(std::io::Cursor::new(vue));
@@ -241,7 +339,7 @@ pub(crate) fn do_to_opf(book: &mut EpubBook, generator: &str) -> IResult<String>
     let mut html = BytesStart::new("package");
     html.push_attribute(("xmlns", "http://www.idpf.org/2007/opf"));
     html.push_attribute(("unique-identifier", "id"));
-    html.push_attribute(("version", book.version()));
+    html.push_attribute(attr("version", book.version()));
     html.push_attribute(("prefix", "rendition: http://www.idpf.org/vocab/rendition/#"));
 
     xml.write_event(Event::Start(html.borrow()))?;
@@ -257,54 +355,46 @@ pub(crate) fn do_to_opf(book: &mut EpubBook, generator: &str) -> IResult<String>
 
     // toc
     xml.create_element("item")
-        .with_attribute(("href", common::TOC.replace(common::EPUB, "").as_str()))
+        .with_attribute(attr("href", &files.toc))
         .with_attribute(("id", "ncx"))
         .with_attribute(("media-type", "application/x-dtbncx+xml"))
         .write_empty()?;
     // nav
     xml.create_element("item")
-        .with_attribute(("href", common::NAV.replace(common::EPUB, "").as_str()))
+        .with_attribute(attr("href", &files.nav))
         .with_attribute(("id", "toc"))
         .with_attribute(("media-type", "application/xhtml+xml"))
         .with_attribute(("properties", "nav"))
         .write_empty()?;
     if let Some(cover) = book.cover() {
         xml.create_element("item")
-            .with_attribute(("href", cover.file_name()))
+            .with_attribute(attr("href", cover.file_name()))
             .with_attribute(("id", "cover-img"))
             .with_attribute(("media-type", get_media_type(cover.file_name()).as_str()))
             .with_attribute(("properties", "cover-image"))
             .write_empty()?;
         xml.create_element("item")
-            .with_attribute(("href", common::COVER.replace(common::EPUB, "").as_str()))
+            .with_attribute(attr("href", &files.cover))
             .with_attribute(("id", "cover"))
             .with_attribute(("media-type", "application/xhtml+xml"))
             .write_empty()?;
     }
-    for (index, ele) in book.assets().enumerate() {
+    for (index, ele) in book.assets().filter(|f| !f.is_generated()).enumerate() {
         xml.create_element("item")
-            .with_attribute((
+            .with_attribute(attr(
                 "href",
-                if ele.file_name().starts_with("/") {
-                    &ele.file_name()[1..]
-                } else {
-                    ele.file_name()
-                },
+                ele.file_name().strip_prefix('/').unwrap_or(ele.file_name()),
             ))
             .with_attribute(("id", format!("assets_{}", index).as_str()))
             .with_attribute(("media-type", get_media_type(ele.file_name()).as_str()))
             .write_empty()?;
     }
 
-    for (index, ele) in book.chapters().enumerate() {
+    for (index, ele) in book.chapters().filter(|f| !f.is_generated()).enumerate() {
         xml.create_element("item")
-            .with_attribute((
+            .with_attribute(attr(
                 "href",
-                if ele.file_name().starts_with("/") {
-                    &ele.file_name()[1..]
-                } else {
-                    ele.file_name()
-                },
+                ele.file_name().strip_prefix('/').unwrap_or(ele.file_name()),
             ))
             .with_attribute(("id", format!("chap_{}", index).as_str()))
             .with_attribute(("media-type", "application/xhtml+xml"))
@@ -315,16 +405,27 @@ pub(crate) fn do_to_opf(book: &mut EpubBook, generator: &str) -> IResult<String>
 
     let mut spine = BytesStart::new("spine");
     spine.push_attribute(("toc", "ncx"));
+    if let Some(v) = book.page_direction() {
+        spine.push_attribute(attr("page-progression-direction", v));
+    }
     xml.write_event(Event::Start(spine.borrow()))?;
-    // 把导航放第一个 nav
-    xml.create_element("itemref")
-        .with_attribute(("idref", "toc"))
-        .write_empty()?;
+    // 把导航放第一个 nav，固定版式时导航不参与翻页
+    let mut nav = xml
+        .create_element("itemref")
+        .with_attribute(("idref", "toc"));
+    if book.is_fixed_layout() {
+        nav = nav.with_attribute(("linear", "no"));
+    }
+    nav.write_empty()?;
     // spine 内的 itemref
-    for (index, _ele) in book.chapters().enumerate() {
-        xml.create_element("itemref")
-            .with_attribute(("idref", format!("chap_{}", index).as_str()))
-            .write_empty()?;
+    for (index, ele) in book.chapters().filter(|f| !f.is_generated()).enumerate() {
+        let mut item = xml
+            .create_element("itemref")
+            .with_attribute(("idref", format!("chap_{}", index).as_str()));
+        if let Some(v) = ele.spine_properties() {
+            item = item.with_attribute(attr("properties", v));
+        }
+        item.write_empty()?;
     }
     xml.write_event(Event::End(spine.to_end()))?;
 
@@ -337,13 +438,58 @@ pub(crate) fn do_to_opf(book: &mut EpubBook, generator: &str) -> IResult<String>
 }
 
 /// 生成OPF
-pub(crate) fn to_opf(book: &mut EpubBook, generator: &str) -> String {
-    match do_to_opf(book, generator) {
+pub(crate) fn to_opf(book: &mut EpubBook, generator: &str, files: &GeneratedFiles) -> String {
+    match do_to_opf(book, generator, files) {
         Ok(s) => s,
         Err(_) => String::new(),
     }
 }
 
+///
+/// 解析html中的 viewport
+///
+/// 只识别 `<meta name="viewport" content="width=.., height=..">`，宽高都存在时返回
+///
+pub(crate) fn get_html_viewport(html: &str) -> Option<(u32, u32)> {
+    use quick_xml::reader::Reader;
+    let mut reader = Reader::from_str(html);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => match e.local_name().as_ref() {
+                b"meta" => {
+                    let value = |key: &[u8]| {
+                        e.attributes()
+                            .filter_map(Result::ok)
+                            .find(|a| a.key.as_ref() == key)
+                            .and_then(|a| a.unescape_value().ok())
+                    };
+                    if value(b"name").is_some_and(|f| f.trim() == "viewport") {
+                        let content = value(b"content")?;
+                        let mut width = None;
+                        let mut height = None;
+                        for item in content.split([',', ';']) {
+                            let Some((key, v)) = item.split_once('=') else {
+                                continue;
+                            };
+                            let v = v.trim().trim_end_matches("px").parse::<u32>().ok();
+                            match key.trim() {
+                                "width" => width = v,
+                                "height" => height = v,
+                                _ => {}
+                            }
+                        }
+                        return width.zip(height);
+                    }
+                }
+                b"body" => return None,
+                _ => {}
+            },
+            Ok(Event::Eof) | Err(_) => return None,
+            _ => {}
+        }
+    }
+}
+
 ///
 /// 解析html获取相关数据
 ///
@@ -367,7 +513,8 @@ pub(crate) fn get_html_info(html: &str, id: Option<&str>) -> IResult<(String, Ve
                 break;
             }
             Err(e) => {
-                return Err(IError::Xml(e));
+                return Err(IError::Xml(e)
+                    .with_context(ErrorContext::default().with_position(reader.error_position())));
             }
             Ok(Event::Start(body)) => match body.name().as_ref() {
                 b"html" => {
@@ -375,7 +522,11 @@ pub(crate) fn get_html_info(html: &str, id: Option<&str>) -> IResult<(String, Ve
                 }
                 b"head" => {
                     if parent.len() != 1 || parent[0] != "html" {
-                        return Err(IError::Unknown);
+                        return Err(
+                            IError::Parse(Cow::from("head is not in html")).with_context(
+                                ErrorContext::default().with_position(reader.buffer_position()),
+                            ),
+                        );
                     }
                     parent.push("head");
                 }
@@ -432,7 +583,7 @@ pub(crate) fn get_html_info(html: &str, id: Option<&str>) -> IResult<(String, Ve
         if let Some(id) = id {
             // 重新读取数据
             content.append(&mut get_section_from_html(
-                String::from_utf8(b).unwrap().as_str(),
+                String::from_utf8(b).map_err(IError::Utf8)?.as_str(),
                 id,
             )?);
         } else {
@@ -456,14 +607,15 @@ fn get_section_from_html(body: &str, id: &str) -> IResult<Vec<u8>> {
                 break;
             }
             Err(e) => {
-                return Err(IError::Xml(e));
+                return Err(IError::Xml(e)
+                    .with_context(ErrorContext::default().with_position(reader.error_position())));
             }
             Ok(Event::Start(body)) => match body.name().as_ref() {
                 b"section" => {
                     if body
                         .try_get_attribute("id")
-                        .map_err(|_e| IError::Unknown)
-                        .and_then(|f| f.ok_or(IError::Unknown))
+                        .map_err(|e| IError::Xml(e.into()))
+                        .and_then(|f| f.ok_or(IError::Parse(Cow::from("section has no id"))))
                         .and_then(|f| f.unescape_value().map_err(|e| IError::Xml(e)))
                         .map(|f| f.to_string())
                         .map(|f| f == id)
@@ -495,6 +647,7 @@ mod test {
     use super::{get_html_info, get_media_type, get_section_from_html, to_html, to_toc_xml};
     use super::{to_nav_html, to_opf};
     use crate::common::tests::download_zip_file;
+    use crate::epub::layout::GeneratedFiles;
     use crate::prelude::*;
 
     #[test]
@@ -510,7 +663,7 @@ mod test {
         };
 
         t.add_link(link);
-        let html = to_html(&mut t, true);
+        let html = to_html(&mut t, true).unwrap();
 
         println!("{}", html);
 
@@ -552,7 +705,7 @@ ok
 
         let nav = vec![n, n1];
 
-        let html = to_nav_html("book_title", nav.iter());
+        let html = to_nav_html("book_title", nav.iter(), None);
 
         println!("{}", html);
 
@@ -642,13 +795,195 @@ ok
         epub.set_date("2024-06-28T08:07:07UTC");
         epub.set_last_modify("2024-06-28T03:07:07UTC");
 
-        let res = to_opf(&mut epub, "epub-rs");
+        let res = to_opf(&mut epub, "epub-rs", &GeneratedFiles::default());
         println!("[{}]", res);
 
         let ass: &str = r###"<?xml version="1.0" encoding="utf-8"?><package xmlns="http://www.idpf.org/2007/opf" unique-identifier="id" version="3.0" prefix="rendition: http://www.idpf.org/vocab/rendition/#"><metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf"><meta property="dcterms:modified">2024-06-28T03:07:07UTC</meta><dc:date id="date">2024-06-28T08:07:07UTC</dc:date><meta name="generator" content="epub-rs"/><dc:identifier id="id">identifier</dc:identifier><dc:title>中文</dc:title><dc:creator id="creator">作者</dc:creator><dc:description>description</dc:description><meta property="desc">description</meta><meta name="cover" content="cover-img"/><dc:format id="format">format</dc:format><dc:publisher id="publisher">publisher</dc:publisher><dc:subject id="subject">subject</dc:subject><dc:contributor id="contributor">contributor</dc:contributor><meta ok="ov">new</meta></metadata><manifest><item href="toc.ncx" id="ncx" media-type="application/x-dtbncx+xml"/><item href="nav.xhtml" id="toc" media-type="application/xhtml+xml" properties="nav"/><item href="" id="cover-img" media-type="" properties="cover-image"/><item href="cover.xhtml" id="cover" media-type="application/xhtml+xml"/><item href="1.png" id="assets_0" media-type="image/png"/><item href="2.png" id="assets_1" media-type="image/png"/><item href="" id="chap_0" media-type="application/xhtml+xml"/></manifest><spine toc="ncx"><itemref idref="toc"/><itemref idref="chap_0"/></spine></package>"###;
         assert_eq!(ass, res.as_str());
     }
 
+    /// 移除 xml 中不允许出现的字符，即期望解析得到的值
+    fn sanitize(v: &str) -> String {
+        v.chars()
+            .filter(|c| {
+                matches!(c, '\t' | '\n' | '\r')
+                    || (*c >= ' ' && *c != '\u{FFFE}' && *c != '\u{FFFF}')
+            })
+            .collect()
+    }
+
+    /// 解析xml，返回 (标签名，文本) 以及 (属性名，属性值)，不是合法的xml时返回 None
+    fn parse_xml(xml: &str) -> Option<(Vec<(String, String)>, Vec<(String, String)>)> {
+        use quick_xml::events::{BytesStart, Event};
+        fn read_attrs(e: &BytesStart, attrs: &mut Vec<(String, String)>) -> Option<String> {
+            for attr in e.attributes() {
+                let attr = attr.ok()?;
+                attrs.push((
+                    String::from_utf8(attr.key.as_ref().to_vec()).ok()?,
+                    attr.unescape_value().ok()?.to_string(),
+                ));
+            }
+            String::from_utf8(e.name().as_ref().to_vec()).ok()
+        }
+
+        let mut reader = quick_xml::reader::Reader::from_str(xml);
+        let mut texts: Vec<(String, String)> = Vec::new();
+        let mut attrs = Vec::new();
+        let mut stack = Vec::new();
+        loop {
+            match reader.read_event().ok()? {
+                Event::Start(e) => {
+                    let name = read_attrs(&e, &mut attrs)?;
+                    texts.push((name, String::new()));
+                    stack.push(texts.len() - 1);
+                }
+                Event::Empty(e) => {
+                    let name = read_attrs(&e, &mut attrs)?;
+                    texts.push((name, String::new()));
+                }
+                Event::End(_) => {
+                    stack.pop()?;
+                }
+                Event::Text(e) => {
+                    let v = e.unescape().ok()?;
+                    match stack.last() {
+                        Some(i) => texts[*i].1.push_str(&v),
+                        None if v.trim().is_empty() => {}
+                        None => return None,
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        if stack.is_empty() {
+            Some((texts, attrs))
+        } else {
+            None
+        }
+    }
+
+    fn find<'a>(v: &'a [(String, String)], name: &str) -> Vec<&'a str> {
+        v.iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_escape_property() {
+        fn nav(items: &[(String, String)]) -> Vec<EpubNav> {
+            items
+                .iter()
+                .map(|(title, file)| {
+                    let mut n = EpubNav::default();
+                    n.set_title(title);
+                    n.set_file_name(file.as_str());
+                    n
+                })
+                .collect()
+        }
+
+        fn prop(book_title: String, items: Vec<(String, String)>) -> bool {
+            let nav = nav(&items);
+            let titles: Vec<String> = items.iter().map(|(t, _)| sanitize(t)).collect();
+            let files: Vec<String> = items.iter().map(|(_, f)| sanitize(f)).collect();
+
+            // toc.ncx
+            let Some((texts, attrs)) = parse_xml(&to_toc_xml(&book_title, nav.iter())) else {
+                return false;
+            };
+            let t = find(&texts, "text");
+            if t[0] != sanitize(&book_title) || t[1..] != titles || find(&attrs, "src") != files {
+                return false;
+            }
+
+            // nav.xhtml
+            let Some((texts, attrs)) = parse_xml(&to_nav_html(&book_title, nav.iter(), None))
+            else {
+                return false;
+            };
+            if find(&texts, "title") != [sanitize(&book_title)]
+                || find(&texts, "h2") != [sanitize(&book_title)]
+                || find(&texts, "a") != titles
+                || find(&attrs, "href") != files
+            {
+                return false;
+            }
+
+            // 章节
+            let mut chap = EpubHtml::default();
+            chap.set_title(&book_title);
+            chap.set_data(b"<p>ok</p>".to_vec());
+            let Some((texts, _)) = parse_xml(&to_html(&mut chap, true).unwrap_or_default()) else {
+                return false;
+            };
+            if find(&texts, "title") != [sanitize(&book_title)]
+                || find(&texts, "h1") != [sanitize(&book_title)]
+            {
+                return false;
+            }
+
+            // opf
+            let mut book = EpubBook::default();
+            book.set_title(&book_title);
+            book.set_creator(&book_title);
+            book.set_identifier(&book_title);
+            for (title, file) in &items {
+                let mut chap = EpubHtml::default();
+                chap.set_title(title);
+                chap.set_file_name(file.as_str());
+                book.add_chapter(chap);
+            }
+            let Some((texts, attrs)) =
+                parse_xml(&to_opf(&mut book, "epub-rs", &GeneratedFiles::default()))
+            else {
+                return false;
+            };
+            let hrefs: Vec<&str> = find(&attrs, "href")[2..].to_vec();
+            find(&texts, "dc:title") == [sanitize(&book_title)]
+                && find(&texts, "dc:creator") == [sanitize(&book_title)]
+                && find(&texts, "dc:identifier") == [sanitize(&book_title)]
+                && hrefs
+                    == items
+                        .iter()
+                        .map(|(_, f)| sanitize(f.strip_prefix('/').unwrap_or(f)))
+                        .collect::<Vec<String>>()
+        }
+
+        assert!(prop(
+            "<a & \"b\" 'c'>\u{1}\r".to_string(),
+            vec![("第一章 <1>".to_string(), "a&b\".xhtml".to_string())]
+        ));
+        quickcheck::QuickCheck::new()
+            .tests(500)
+            .quickcheck(prop as fn(String, Vec<(String, String)>) -> bool);
+    }
+
+    #[test]
+    fn test_get_html_viewport() {
+        use super::get_html_viewport;
+
+        assert_eq!(
+            Some((600, 800)),
+            get_html_viewport(
+                r#"<html><head><title>t</title><meta content="width=600, height=800px" name="viewport"/></head><body></body></html>"#
+            )
+        );
+        assert_eq!(
+            None,
+            get_html_viewport(
+                r#"<html><head><meta name="viewport" content="width=device-width"/></head><body></body></html>"#
+            )
+        );
+        assert_eq!(
+            None,
+            get_html_viewport(
+                r#"<html><head></head><body><meta name="viewport" content="width=1, height=1"/></body></html>"#
+            )
+        );
+    }
+
     #[test]
     fn test_get_media_type() {
         assert_eq!(
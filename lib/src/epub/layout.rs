@@ -0,0 +1,713 @@
+//!
+//! epub 内部文件布局
+//!
+//! 控制写入时的根目录、各类文件所在子目录以及文件命名，
+//! 章节中的 img src、link href 等引用会随之自动修正
+//!
+use std::collections::{HashMap, HashSet};
+
+use super::common;
+use crate::common::get_media_type;
+use crate::prelude::*;
+
+///
+/// 文件命名方式
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LayoutNaming {
+    /// 保留原文件名
+    #[default]
+    Keep,
+    /// 按类型顺序编号，例如 chapter_0001.xhtml、image_0001.jpg
+    Sequence,
+}
+
+///
+/// 文件布局
+///
+/// 默认布局与之前保持一致，所有文件都放在 OEBPS 目录下，路径由调用方决定
+///
+/// # Examples
+///
+/// ```rust
+/// use iepub::prelude::*;
+///
+/// let layout = EpubLayout::default()
+///     .with_root("EPUB")
+///     .with_text_dir("Text")
+///     .with_image_dir("Images")
+///     .with_style_dir("Styles")
+///     .with_font_dir("Fonts")
+///     .with_naming(LayoutNaming::Sequence);
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpubLayout {
+    /// 根目录，opf、toc.ncx、nav.xhtml 等文件所在目录
+    root: String,
+    /// 章节目录
+    text: Option<String>,
+    /// 图片目录
+    images: Option<String>,
+    /// 样式目录
+    styles: Option<String>,
+    /// 字体目录
+    fonts: Option<String>,
+    naming: LayoutNaming,
+}
+
+impl Default for EpubLayout {
+    fn default() -> Self {
+        EpubLayout {
+            root: common::EPUB.trim_end_matches('/').to_string(),
+            text: None,
+            images: None,
+            styles: None,
+            fonts: None,
+            naming: LayoutNaming::Keep,
+        }
+    }
+}
+
+/// 文件类型，决定存放目录以及编号前缀
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Text,
+    Cover,
+    Image,
+    Style,
+    Font,
+    Other,
+}
+
+impl Kind {
+    fn of(file_name: &str) -> Kind {
+        let media_type = get_media_type(file_name);
+        let ext = extension(file_name).to_lowercase();
+        if media_type.starts_with("image/") {
+            Kind::Image
+        } else if media_type == "text/css" {
+            Kind::Style
+        } else if media_type.contains("font")
+            || matches!(ext.as_str(), ".otf" | ".ttf" | ".woff" | ".woff2")
+        {
+            Kind::Font
+        } else {
+            Kind::Other
+        }
+    }
+
+    fn prefix(&self) -> &'static str {
+        match self {
+            Kind::Text => "chapter",
+            Kind::Cover => "cover",
+            Kind::Image => "image",
+            Kind::Style => "style",
+            Kind::Font => "font",
+            Kind::Other => "file",
+        }
+    }
+}
+
+///
+/// 写入时生成的文件，路径相对于根目录
+///
+/// 与书中的文件重名时在文件名后添加序号，避免互相覆盖
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GeneratedFiles {
+    pub(crate) opf: String,
+    pub(crate) toc: String,
+    pub(crate) nav: String,
+    pub(crate) cover: String,
+}
+
+impl Default for GeneratedFiles {
+    fn default() -> Self {
+        GeneratedFiles {
+            opf: common::OPF.trim_start_matches(common::EPUB).to_string(),
+            toc: common::TOC.trim_start_matches(common::EPUB).to_string(),
+            nav: common::NAV.trim_start_matches(common::EPUB).to_string(),
+            cover: common::COVER.trim_start_matches(common::EPUB).to_string(),
+        }
+    }
+}
+
+impl GeneratedFiles {
+    pub(crate) fn new(book: &EpubBook) -> Self {
+        let mut used: HashSet<String> = book
+            .chapters()
+            .filter(|f| !f.is_generated())
+            .map(|f| f.file_name())
+            .chain(
+                book.assets()
+                    .filter(|f| !f.is_generated())
+                    .map(|f| f.file_name()),
+            )
+            .chain(book.cover().map(|f| f.file_name()))
+            .map(|f| f.trim_start_matches('/').to_string())
+            .collect();
+        let mut files = GeneratedFiles::default();
+        for name in [
+            &mut files.opf,
+            &mut files.toc,
+            &mut files.nav,
+            &mut files.cover,
+        ] {
+            let ext = extension(name).to_string();
+            let stem = name[..name.len() - ext.len()].to_string();
+            let mut index = 1;
+            while used.contains(name.as_str()) {
+                *name = format!("{}_{}{}", stem, index, ext);
+                index += 1;
+            }
+            used.insert(name.clone());
+        }
+        files
+    }
+}
+
+fn trim_dir<T: Into<String>>(dir: T) -> String {
+    dir.into().trim_matches('/').to_string()
+}
+
+impl EpubLayout {
+    /// 设置根目录，为空表示直接放在压缩包根目录下
+    pub fn with_root<T: Into<String>>(mut self, root: T) -> Self {
+        self.root = trim_dir(root);
+        self
+    }
+    /// 设置章节目录，相对于根目录
+    pub fn with_text_dir<T: Into<String>>(mut self, dir: T) -> Self {
+        self.text = Some(trim_dir(dir));
+        self
+    }
+    /// 设置图片目录，相对于根目录，封面也会放在该目录下
+    pub fn with_image_dir<T: Into<String>>(mut self, dir: T) -> Self {
+        self.images = Some(trim_dir(dir));
+        self
+    }
+    /// 设置css目录，相对于根目录
+    pub fn with_style_dir<T: Into<String>>(mut self, dir: T) -> Self {
+        self.styles = Some(trim_dir(dir));
+        self
+    }
+    /// 设置字体目录，相对于根目录
+    pub fn with_font_dir<T: Into<String>>(mut self, dir: T) -> Self {
+        self.fonts = Some(trim_dir(dir));
+        self
+    }
+    /// 设置文件命名方式
+    pub fn with_naming(mut self, naming: LayoutNaming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    ///
+    /// 根目录下文件在压缩包内的完整路径
+    ///
+    pub(crate) fn entry(&self, file_name: &str) -> String {
+        let file_name = file_name.trim_start_matches('/');
+        if self.root.is_empty() {
+            file_name.to_string()
+        } else {
+            format!("{}/{}", self.root, file_name)
+        }
+    }
+
+    /// 是否需要调整文件路径
+    fn is_identity(&self) -> bool {
+        self.text.is_none()
+            && self.images.is_none()
+            && self.styles.is_none()
+            && self.fonts.is_none()
+            && self.naming == LayoutNaming::Keep
+    }
+
+    fn dir_of(&self, kind: Kind) -> Option<&str> {
+        match kind {
+            Kind::Text => self.text.as_deref(),
+            Kind::Cover | Kind::Image => self.images.as_deref(),
+            Kind::Style => self.styles.as_deref(),
+            Kind::Font => self.fonts.as_deref(),
+            Kind::Other => None,
+        }
+    }
+
+    ///
+    /// 计算新的文件路径
+    ///
+    /// [path] 相对于根目录的原路径
+    ///
+    fn new_name(
+        &self,
+        kind: Kind,
+        path: &str,
+        used: &mut HashSet<String>,
+        counter: &mut HashMap<Kind, usize>,
+    ) -> String {
+        let dir = match self.dir_of(kind) {
+            Some(d) => d.to_string(),
+            None => parent(path).to_string(),
+        };
+        let file = match self.naming {
+            LayoutNaming::Keep => file_name(path).to_string(),
+            LayoutNaming::Sequence if kind == Kind::Cover => {
+                format!("{}{}", kind.prefix(), extension(path))
+            }
+            LayoutNaming::Sequence => {
+                let index = counter.entry(kind).or_insert(0);
+                *index += 1;
+                format!("{}_{:04}{}", kind.prefix(), index, extension(path))
+            }
+        };
+        let mut name = join(&dir, &file);
+        let mut index = 1;
+        while used.contains(&name) {
+            let ext = extension(&file);
+            name = join(
+                &dir,
+                &format!("{}_{}{}", &file[..file.len() - ext.len()], index, ext),
+            );
+            index += 1;
+        }
+        used.insert(name.clone());
+        name
+    }
+
+    ///
+    /// 按照布局调整书中所有文件的路径，并修正章节、css、目录中的引用
+    ///
+    /// 调整前会先读取所有数据，因为修改路径后无法再延迟读取
+    ///
+    pub(crate) fn apply(&self, book: &mut EpubBook) {
+        if self.is_identity() {
+            return;
+        }
+        for ele in book.chapters_mut() {
+            ele.data_mut();
+        }
+        for ele in book.assets_mut() {
+            ele.data_mut();
+        }
+        if let Some(cover) = book.cover_mut() {
+            cover.data_mut();
+        }
+
+        // 原路径 -> 新路径，都是相对于根目录
+        let mut map: HashMap<String, String> = HashMap::new();
+        let mut used = HashSet::new();
+        let mut counter = HashMap::new();
+        let mut files: Vec<(Kind, String)> = Vec::new();
+        for ele in book.chapters() {
+            files.push((Kind::Text, ele.file_name().to_string()));
+        }
+        if let Some(cover) = book.cover() {
+            files.push((Kind::Cover, cover.file_name().to_string()));
+        }
+        for ele in book.assets() {
+            files.push((Kind::of(ele.file_name()), ele.file_name().to_string()));
+        }
+        for (kind, f) in files {
+            let path = normalize("", split_fragment(&f).0);
+            if path.is_empty() || map.contains_key(&path) {
+                continue;
+            }
+            let name = self.new_name(kind, &path, &mut used, &mut counter);
+            map.insert(path, name);
+        }
+
+        for chap in book.chapters_mut() {
+            let (path, fragment) = split_fragment(chap.file_name());
+            let old = normalize("", path);
+            let Some(new) = map.get(&old).cloned() else {
+                continue;
+            };
+            let fragment = fragment.to_string();
+            let remap = |v: &str| remap_ref(v, parent(&old), parent(&new), &map);
+            if let Some(data) = chap.data() {
+                let html = String::from_utf8_lossy(data).to_string();
+                if let Some(v) = rewrite_html_refs(&html, remap) {
+                    chap.set_data(v.into_bytes());
+                }
+            }
+            if let Some(links) = chap.get_links() {
+                for link in links {
+                    if let Some(v) = remap(&link.href) {
+                        link.href = v;
+                    }
+                }
+            }
+            chap.set_file_name(format!("{new}{fragment}"));
+        }
+
+        for asset in book.assets_mut() {
+            let old = normalize("", asset.file_name());
+            let Some(new) = map.get(&old).cloned() else {
+                continue;
+            };
+            if Kind::of(&old) == Kind::Style {
+                if let Some(data) = asset.data() {
+                    let css = String::from_utf8_lossy(data).to_string();
+                    if let Some(v) =
+                        rewrite_css_refs(&css, |v| remap_ref(v, parent(&old), parent(&new), &map))
+                    {
+                        asset.set_data(v.into_bytes());
+                    }
+                }
+            }
+            asset.set_file_name(new);
+        }
+
+        if let Some(cover) = book.cover_mut() {
+            if let Some(new) = map.get(&normalize("", cover.file_name())) {
+                cover.set_file_name(new.as_str());
+            }
+        }
+
+        for nav in book.nav_mut() {
+            remap_nav(nav, &map);
+        }
+    }
+}
+
+fn remap_nav(nav: &mut EpubNav, map: &HashMap<String, String>) {
+    if let Some(v) = remap_ref(nav.file_name(), "", "", map) {
+        nav.set_file_name(v);
+    }
+    for ele in nav.child_mut() {
+        remap_nav(ele, map);
+    }
+}
+
+///
+/// 计算引用的新值，不需要修改时返回 None
+///
+/// [old_dir] 引用所在文件的原目录
+/// [new_dir] 引用所在文件的新目录
+///
+fn remap_ref(
+    value: &str,
+    old_dir: &str,
+    new_dir: &str,
+    map: &HashMap<String, String>,
+) -> Option<String> {
+    let (path, fragment) = split_fragment(value);
+    if path.is_empty() || is_external(path) {
+        return None;
+    }
+    let target = normalize(old_dir, path);
+    let new_target = map.get(&target).cloned().unwrap_or_else(|| target.clone());
+    if old_dir == new_dir && target == new_target {
+        return None;
+    }
+    Some(format!("{}{}", relative(new_dir, &new_target), fragment))
+}
+
+pub(crate) fn is_external(path: &str) -> bool {
+    path.starts_with('/') || path.starts_with("data:") || path.contains("://") || {
+        // mailto:、tel: 等
+        path.find(':')
+            .is_some_and(|i| path[..i].chars().all(|c| c.is_ascii_alphabetic()))
+    }
+}
+
+/// 拆分路径和锚点，锚点包括 #
+pub(crate) fn split_fragment(value: &str) -> (&str, &str) {
+    match value.find('#') {
+        Some(i) => (&value[..i], &value[i..]),
+        None => (value, ""),
+    }
+}
+
+/// 所在目录，没有目录时返回空
+pub(crate) fn parent(path: &str) -> &str {
+    path.rfind('/').map_or("", |i| &path[..i])
+}
+
+fn file_name(path: &str) -> &str {
+    path.rfind('/').map_or(path, |i| &path[(i + 1)..])
+}
+
+/// 扩展名，包括 .
+fn extension(path: &str) -> &str {
+    let name = file_name(path);
+    name.rfind('.').map_or("", |i| &name[i..])
+}
+
+fn join(dir: &str, file: &str) -> String {
+    if dir.is_empty() {
+        file.to_string()
+    } else {
+        format!("{dir}/{file}")
+    }
+}
+
+///
+/// 基于目录解析相对路径，处理 . 和 ..
+///
+pub(crate) fn normalize(dir: &str, path: &str) -> String {
+    let mut out: Vec<&str> = Vec::new();
+    for ele in dir.split('/').chain(path.split('/')) {
+        match ele {
+            "" | "." => {}
+            ".." => {
+                out.pop();
+            }
+            _ => out.push(ele),
+        }
+    }
+    out.join("/")
+}
+
+///
+/// 从目录 from 出发指向 to 的相对路径
+///
+pub(crate) fn relative(from: &str, to: &str) -> String {
+    let from: Vec<&str> = from.split('/').filter(|s| !s.is_empty()).collect();
+    let to: Vec<&str> = to.split('/').filter(|s| !s.is_empty()).collect();
+    let same = from
+        .iter()
+        .zip(to.iter())
+        .take(to.len().saturating_sub(1))
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut out = vec![".."; from.len() - same];
+    out.extend_from_slice(&to[same..]);
+    out.join("/")
+}
+
+///
+/// 修改html中 src、href、xlink:href 属性的值
+///
+/// 没有任何修改时返回 None
+///
+fn rewrite_html_refs<F: Fn(&str) -> Option<String>>(html: &str, f: F) -> Option<String> {
+    let mut out = String::with_capacity(html.len());
+    let mut changed = false;
+    let mut last = 0;
+    let bytes = html.as_bytes();
+    let mut i = 0;
+    while let Some(pos) = html[i..].find('=') {
+        let eq = i + pos;
+        i = eq + 1;
+        // 属性名
+        let name_end = html[..eq].trim_end().len();
+        let name_start = html[..name_end]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !(c.is_ascii_alphanumeric() || *c == ':' || *c == '-'))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let name = html[name_start..name_end].to_ascii_lowercase();
+        if name_start == 0 || !bytes[name_start - 1].is_ascii_whitespace() {
+            continue;
+        }
+        if !matches!(name.as_str(), "src" | "href" | "xlink:href") {
+            continue;
+        }
+        // 属性值
+        let value_start = eq + 1 + (html[(eq + 1)..].len() - html[(eq + 1)..].trim_start().len());
+        let quote = match bytes.get(value_start) {
+            Some(b'"') => '"',
+            Some(b'\'') => '\'',
+            _ => continue,
+        };
+        let Some(len) = html[(value_start + 1)..].find(quote) else {
+            break;
+        };
+        let value_end = value_start + 1 + len;
+        if let Some(v) = f(&html[(value_start + 1)..value_end]) {
+            out.push_str(&html[last..(value_start + 1)]);
+            out.push_str(&v);
+            last = value_end;
+            changed = true;
+        }
+        i = value_end;
+    }
+    if !changed {
+        return None;
+    }
+    out.push_str(&html[last..]);
+    Some(out)
+}
+
+///
+/// 修改css中 url() 的值
+///
+/// 没有任何修改时返回 None
+///
+fn rewrite_css_refs<F: Fn(&str) -> Option<String>>(css: &str, f: F) -> Option<String> {
+    let mut out = String::with_capacity(css.len());
+    let mut changed = false;
+    let mut last = 0;
+    let mut i = 0;
+    while let Some(pos) = css[i..].find("url(") {
+        let start = i + pos + 4;
+        let Some(len) = css[start..].find(')') else {
+            break;
+        };
+        let end = start + len;
+        i = end;
+        let raw = css[start..end].trim();
+        let value = raw.trim_matches(|c| c == '"' || c == '\'');
+        if let Some(v) = f(value) {
+            let quote = if raw.starts_with('\'') {
+                "'"
+            } else if raw.starts_with('"') {
+                "\""
+            } else {
+                ""
+            };
+            out.push_str(&css[last..start]);
+            out.push_str(quote);
+            out.push_str(&v);
+            out.push_str(quote);
+            last = end;
+            changed = true;
+        }
+    }
+    if !changed {
+        return None;
+    }
+    out.push_str(&css[last..]);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::{normalize, relative, rewrite_css_refs, rewrite_html_refs};
+    use crate::prelude::*;
+
+    #[test]
+    fn test_path() {
+        assert_eq!("img/1.png", normalize("chap", "../img/1.png"));
+        assert_eq!("chap/1.png", normalize("chap", "./1.png"));
+        assert_eq!("1.png", normalize("", "1.png"));
+
+        assert_eq!("../Images/1.png", relative("Text", "Images/1.png"));
+        assert_eq!("1.png", relative("Text", "Text/1.png"));
+        assert_eq!("Images/1.png", relative("", "Images/1.png"));
+        assert_eq!("../1.png", relative("Text", "1.png"));
+        assert_eq!("../1.png", relative("a/b", "a/1.png"));
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let html = r#"<p>a=b</p><img  src = "1.png" alt='x'/><a href='2.xhtml#id'>2</a><image xlink:href="1.png"/>"#;
+        let v = rewrite_html_refs(html, |v| Some(format!("../{v}"))).unwrap();
+        assert_eq!(
+            r#"<p>a=b</p><img  src = "../1.png" alt='x'/><a href='../2.xhtml#id'>2</a><image xlink:href="../1.png"/>"#,
+            v
+        );
+        assert_eq!(None, rewrite_html_refs(html, |_| None));
+
+        // 等号前是多字节字符
+        let html = r#"<p>中文=1</p><img 中src="1.png"/><img src="1.png"/>"#;
+        assert_eq!(
+            r#"<p>中文=1</p><img 中src="1.png"/><img src="../1.png"/>"#,
+            rewrite_html_refs(html, |v| Some(format!("../{v}"))).unwrap()
+        );
+
+        let css = r#"@font-face{src:url("f.ttf")} p{background:url(1.png)}"#;
+        assert_eq!(
+            r#"@font-face{src:url("../f.ttf")} p{background:url(../1.png)}"#,
+            rewrite_css_refs(css, |v| Some(format!("../{v}"))).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_layout() {
+        let layout = EpubLayout::default()
+            .with_root("EPUB")
+            .with_text_dir("Text")
+            .with_image_dir("Images")
+            .with_style_dir("Styles")
+            .with_font_dir("Fonts")
+            .with_naming(LayoutNaming::Sequence);
+        let mut link = EpubHtml::default()
+            .with_title("第一章")
+            .with_file_name("chap/1.xhtml")
+            .with_data(
+                r#"<p>内容=1</p><img src="../img/a.png"/><a href="2.xhtml#s">下一章</a>"#
+                    .as_bytes()
+                    .to_vec(),
+            );
+        link.add_link(EpubLink {
+            rel: LinkRel::CSS,
+            file_type: "text/css".to_string(),
+            href: "../style.css".to_string(),
+        });
+        let data = EpubBuilder::new()
+            .with_title("书名")
+            .with_layout(layout)
+            .add_chapter(link)
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第二章")
+                    .with_file_name("chap/2.xhtml")
+                    .with_data("<p>2</p>".as_bytes().to_vec()),
+            )
+            .add_assets("img/a.png", vec![1, 2, 3])
+            .add_assets(
+                "style.css",
+                "@font-face{src:url('f.otf')}".as_bytes().to_vec(),
+            )
+            .add_assets("f.otf", vec![4])
+            .cover("cover.jpg", vec![5])
+            .mem()
+            .unwrap();
+
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(data.clone())).unwrap();
+        for name in [
+            "EPUB/content.opf",
+            "EPUB/toc.ncx",
+            "EPUB/nav.xhtml",
+            "EPUB/cover.xhtml",
+            "EPUB/Text/chapter_0001.xhtml",
+            "EPUB/Text/chapter_0002.xhtml",
+            "EPUB/Images/cover.jpg",
+            "EPUB/Images/image_0001.png",
+            "EPUB/Styles/style_0001.css",
+            "EPUB/Fonts/font_0001.otf",
+        ] {
+            assert!(zip.by_name(name).is_ok(), "{name} not exist");
+        }
+        let mut container = String::new();
+        zip.by_name("META-INF/container.xml")
+            .unwrap()
+            .read_to_string(&mut container)
+            .unwrap();
+        assert!(container.contains(r#"full-path="EPUB/content.opf""#));
+
+        let mut css = String::new();
+        zip.by_name("EPUB/Styles/style_0001.css")
+            .unwrap()
+            .read_to_string(&mut css)
+            .unwrap();
+        assert_eq!("@font-face{src:url('../Fonts/font_0001.otf')}", css);
+
+        let mut book = read_from_vec(data).unwrap();
+        assert_eq!(
+            "Text/chapter_0001.xhtml",
+            book.nav().next().unwrap().file_name()
+        );
+        let chap = book.get_chapter_mut("Text/chapter_0001.xhtml").unwrap();
+        let html = chap.raw_data().unwrap().to_string();
+        assert!(html.contains(r#"<img src="../Images/image_0001.png"/>"#));
+        assert!(html.contains(r#"<a href="chapter_0002.xhtml#s">"#));
+        assert!(html.contains(r#"href="../Styles/style_0001.css""#));
+        assert_eq!(
+            vec![1, 2, 3],
+            book.get_assets_mut("Images/image_0001.png")
+                .unwrap()
+                .data_mut()
+                .unwrap()
+        );
+    }
+}
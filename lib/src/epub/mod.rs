@@ -3,5 +3,8 @@ pub(crate) mod builder;
 pub(crate) mod common;
 pub(crate) mod core;
 mod html;
+pub(crate) mod layout;
 pub(crate) mod reader;
+pub(crate) mod validate;
 pub(crate) mod writer;
+pub(crate) mod xhtml;
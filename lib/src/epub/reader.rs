@@ -10,27 +10,29 @@ use std::{
 };
 
 use super::core::EpubReaderTrait;
+use super::html::get_html_info;
+use crate::common::{ErrorContext, ResultContext};
 use crate::prelude::*;
 macro_rules! invalid {
-    ($x:tt) => {
-        Err(IError::InvalidArchive(Cow::from($x)))
+    ($reader:expr, $x:literal) => {
+        Err(IError::Parse(Cow::from($x))
+            .with_context(ErrorContext::default().with_position($reader.buffer_position())))
     };
-    ($x:expr,$y:expr) => {
-        $x.or(Err(IError::InvalidArchive(Cow::from($y))))?
+    ($reader:expr, $x:expr) => {
+        Err(IError::from($x)
+            .with_context(ErrorContext::default().with_position($reader.error_position())))
     };
 }
 
 macro_rules! read_from_zip {
     ($m:ident,$x:expr) => {{
-        // 读取 container.xml
-        let mut file = invalid!($m.by_name($x), stringify!($x not exist));
-        let mut content = String::new();
-        invalid!(file.read_to_string(&mut content), "read err");
-        content
+        let entry = $x;
+        String::from_utf8(read_entry_to_end($m, entry)?)
+            .context(|| ErrorContext::new("decode entry").with_entry(entry))?
     }};
 }
 
-fn get_opf_location(xml: &str) -> IResult<String> {
+pub(crate) fn get_opf_location(xml: &str) -> IResult<String> {
     use quick_xml::events::Event;
     use quick_xml::reader::Reader;
 
@@ -44,7 +46,7 @@ fn get_opf_location(xml: &str) -> IResult<String> {
                 return Ok(String::new());
             }
             Err(e) => {
-                return Err(IError::Xml(e));
+                return invalid!(reader, e);
             }
             Ok(Event::Empty(e)) => {
                 if e.name().as_ref() == b"rootfile" {
@@ -54,7 +56,7 @@ fn get_opf_location(xml: &str) -> IResult<String> {
                                 .map_or_else(|_| String::new(), |v| v.to_string())
                         }) {
                             Some(v) => Ok(v),
-                            None => Err(IError::InvalidArchive(Cow::from("has no opf"))),
+                            None => invalid!(reader, "has no opf"),
                         };
                     }
                 }
@@ -71,8 +73,12 @@ fn create_meta(xml: &BytesStart) -> IResult<EpubMetaData> {
     for ele in xml.attributes() {
         if let Ok(a) = ele {
             meta.push_attr(
-                String::from_utf8(a.key.0.to_vec()).unwrap().as_str(),
-                String::from_utf8(a.value.to_vec()).unwrap().as_str(),
+                String::from_utf8(a.key.0.to_vec())
+                    .map_err(IError::Utf8)?
+                    .as_str(),
+                String::from_utf8(a.value.to_vec())
+                    .map_err(IError::Utf8)?
+                    .as_str(),
             );
         }
     }
@@ -87,30 +93,41 @@ fn read_meta_xml(
 
     // 模拟 栈，记录当前的层级
     let mut parent: Vec<String> = vec!["package".to_string(), "metadata".to_string()];
+    // 当前 meta 对应的固定版式属性
+    let mut rendition: Option<String> = None;
     let mut buf = Vec::new();
     loop {
         match reader.read_event_into(&mut buf) {
             Ok(Event::Eof) => {
                 break;
             }
-            Err(_e) => {
-                return invalid!("err");
+            Err(e) => {
+                return invalid!(reader, e);
             }
             Ok(Event::Start(e)) => {
                 let name = String::from_utf8(e.name().as_ref().to_vec()).map_err(IError::Utf8)?;
 
                 if name == "meta" {
                     if parent.len() != 2 || parent[1] != "metadata" {
-                        return invalid!("not valid opf meta");
+                        return invalid!(reader, "not valid opf meta");
                     } else {
                         let meta = create_meta(&e);
-                        if let Ok(m) = meta {
-                            book.add_meta(m);
+                        rendition = None;
+                        match meta {
+                            // 固定版式的属性单独保存
+                            Ok(m)
+                                if m.get_attr("property")
+                                    .is_some_and(|f| f.starts_with("rendition:")) =>
+                            {
+                                rendition = m.get_attr("property").cloned();
+                            }
+                            Ok(m) => book.add_meta(m),
+                            Err(_) => {}
                         }
                         parent.push("meta".to_string());
                     }
                 } else if parent.len() != 2 || parent[1] != "metadata" {
-                    return invalid!("not valid opf identifier");
+                    return invalid!(reader, "not valid opf identifier");
                 } else {
                     parent.push(name);
                 }
@@ -118,11 +135,20 @@ fn read_meta_xml(
             Ok(Event::Empty(e)) => match e.name().as_ref() {
                 b"meta" => {
                     if parent.len() != 2 || parent[1] != "metadata" {
-                        return invalid!("not valid opf meta empty");
+                        return invalid!(reader, "not valid opf meta empty");
                     } else {
-                        let meta = create_meta(&e);
-                        if let Ok(m) = meta {
-                            book.add_meta(m);
+                        match create_meta(&e) {
+                            // 书写方向单独保存
+                            Ok(m)
+                                if m.get_attr("name").map(|f| f.as_str())
+                                    == Some("primary-writing-mode") =>
+                            {
+                                if let Some(v) = m.get_attr("content") {
+                                    book.set_writing_mode(v.trim());
+                                }
+                            }
+                            Ok(m) => book.add_meta(m),
+                            Err(_) => {}
                         }
                     }
                 }
@@ -131,8 +157,23 @@ fn read_meta_xml(
             Ok(Event::Text(txt)) => {
                 if !parent.is_empty() {
                     match parent[parent.len() - 1].as_str() {
+                        "meta" if rendition.is_some() => {
+                            let v = txt.unescape()?;
+                            match rendition.as_deref() {
+                                Some("rendition:layout") => book.set_rendition_layout(v.trim()),
+                                Some("rendition:orientation") => {
+                                    book.set_rendition_orientation(v.trim())
+                                }
+                                Some("rendition:spread") => book.set_rendition_spread(v.trim()),
+                                _ => {}
+                            }
+                        }
                         "meta" => {
-                            if let Some(m) = book.get_meta_mut(book.meta_len() - 1) {
+                            if let Some(m) = book
+                                .meta_len()
+                                .checked_sub(1)
+                                .and_then(|i| book.get_meta_mut(i))
+                            {
                                 m.set_text(txt.unescape()?.deref());
                             }
                         }
@@ -160,6 +201,9 @@ fn read_meta_xml(
                         "dc:contributor" => {
                             book.set_contributor(txt.unescape()?.deref());
                         }
+                        "dc:language" => {
+                            book.set_language(txt.unescape()?.trim());
+                        }
                         "dc:date" => {
                             book.set_date(txt.unescape()?.deref());
                         }
@@ -172,7 +216,7 @@ fn read_meta_xml(
 
                 if name == "metadata" {
                     if parent.len() != 2 || parent[0] != "package" {
-                        return invalid!("not valid opf metadata end");
+                        return invalid!(reader, "not valid opf metadata end");
                     }
                     break;
                 }
@@ -228,9 +272,15 @@ fn read_spine_xml(
                                 .enumerate()
                                 .find(|(_index, s)| s.id() == h.as_str());
                             if let Some((index, xh)) = xhtml {
-                                book.add_chapter(
-                                    EpubHtml::default().with_file_name(xh.file_name()),
-                                );
+                                let mut chap = EpubHtml::default().with_file_name(xh.file_name());
+                                chap.properties = xh.properties.clone();
+                                chap.generated = xh.generated;
+                                if let Ok(Some(v)) = e.try_get_attribute("properties") {
+                                    if let Ok(v) = v.unescape_value() {
+                                        chap.set_spine_properties(v.trim());
+                                    }
+                                }
+                                book.add_chapter(chap);
                                 if !xh.id().eq_ignore_ascii_case("toc")
                                     && xh.file_name().contains(".xhtml")
                                 {
@@ -265,9 +315,12 @@ fn read_manifest_xml(
     let mut buf = Vec::new();
     loop {
         match reader.read_event_into(&mut buf) {
-            Ok(Event::End(_e)) => {
+            Ok(Event::End(_)) | Ok(Event::Eof) => {
                 break;
             }
+            Err(e) => {
+                return invalid!(reader, e);
+            }
             Ok(Event::Empty(e)) => {
                 match e.name().as_ref() {
                     b"item" => {
@@ -288,6 +341,11 @@ fn read_manifest_xml(
                                 a.set_id(h.as_str());
                             }
                         }
+                        if let Ok(Some(v)) = e.try_get_attribute("properties") {
+                            if let Ok(v) = v.unescape_value() {
+                                a.properties = Some(v.trim().to_string());
+                            }
+                        }
                         assets.push(a);
                     }
                     _ => {
@@ -318,8 +376,8 @@ fn read_opf_xml(xml: &str, book: &mut EpubBook) -> IResult<()> {
             Ok(Event::Eof) => {
                 break;
             }
-            Err(_e) => {
-                return invalid!("err");
+            Err(e) => {
+                return invalid!(reader, e);
             }
             Ok(Event::Start(e)) => match e.name().as_ref() {
                 b"package" => {
@@ -339,7 +397,7 @@ fn read_opf_xml(xml: &str, book: &mut EpubBook) -> IResult<()> {
                 }
                 b"metadata" => {
                     if parent.len() != 1 || parent[0] != "package" {
-                        return invalid!("not valid opf metadata");
+                        return invalid!(reader, "not valid opf metadata");
                     } else {
                         parent.push("metadata".to_string());
                     }
@@ -349,6 +407,25 @@ fn read_opf_xml(xml: &str, book: &mut EpubBook) -> IResult<()> {
                     read_manifest_xml(&mut reader, book, &mut assets)?;
                 }
                 b"spine" => {
+                    let mut toc = None;
+                    for attr in e.attributes().flatten() {
+                        match attr.key.as_ref() {
+                            // 翻页方向
+                            b"page-progression-direction" => {
+                                book.set_page_direction(attr.unescape_value()?.trim())
+                            }
+                            b"toc" => toc = Some(attr.unescape_value()?.trim().to_string()),
+                            _ => {}
+                        }
+                    }
+                    // 导航文档和 toc.ncx 写入时会重新生成
+                    for ele in assets.iter_mut() {
+                        ele.generated = ele.is_nav()
+                            || match &toc {
+                                Some(toc) => ele.id() == toc,
+                                None => ele.file_name().to_lowercase().ends_with(".ncx"),
+                            };
+                    }
                     read_spine_xml(&mut reader, book, &mut assets)?;
                 }
                 _ => {}
@@ -416,8 +493,8 @@ fn read_opf_xml(xml: &str, book: &mut EpubBook) -> IResult<()> {
                 }
                 {
                     if let Some(pro) = meta.get_attr("property") {
-                        if pro == "dcterms:modified" && meta.text().is_some() {
-                            last_modify = Some(meta.text().unwrap().to_string());
+                        if let (true, Some(text)) = (pro == "dcterms:modified", meta.text()) {
+                            last_modify = Some(text.to_string());
                         }
                     }
                 }
@@ -430,6 +507,16 @@ fn read_opf_xml(xml: &str, book: &mut EpubBook) -> IResult<()> {
     }
     if let Some(co) = cover {
         book.set_cover(co);
+        // 不在 spine 中的封面页，写入时会重新生成
+        let chapters: Vec<String> = book.chapters().map(|f| f.file_name().to_string()).collect();
+        for ele in book.assets_mut() {
+            if ele.id() == "cover"
+                && ele.file_name().contains(".xhtml")
+                && !chapters.iter().any(|f| f == ele.file_name())
+            {
+                ele.generated = true;
+            }
+        }
     }
     if let Some(g) = generator {
         book.set_generator(g.as_str());
@@ -489,12 +576,12 @@ fn read_nav_point_xml(
                 }
             }
             Ok(Event::Text(e)) => {
-                if parent[parent.len() - 1] == "text" {
+                if parent.last().is_some_and(|p| p == "text") {
                     nav.set_title(e.unescape()?.deref());
                 }
             }
-            Err(_e) => {
-                return invalid!("err");
+            Err(e) => {
+                return invalid!(reader, e);
             }
             _ => {}
         }
@@ -520,8 +607,8 @@ fn read_nav_xml(xml: &str, book: &mut EpubBook) -> IResult<()> {
             Ok(Event::Eof) => {
                 break;
             }
-            Err(_e) => {
-                return invalid!("err");
+            Err(e) => {
+                return invalid!(reader, e);
             }
             Ok(Event::Start(e)) => match e.name().as_ref() {
                 b"ncx" => {
@@ -529,13 +616,13 @@ fn read_nav_xml(xml: &str, book: &mut EpubBook) -> IResult<()> {
                 }
                 b"navMap" => {
                     if parent.len() != 1 || parent[0] != "ncx" {
-                        return invalid!("err nav 1");
+                        return invalid!(reader, "navMap is not in ncx");
                     }
                     parent.push("navMap".to_string());
                 }
                 b"navPoint" => {
                     if parent.len() != 2 || parent[1] != "navMap" {
-                        return invalid!("err nav 2");
+                        return invalid!(reader, "navPoint is not in navMap");
                     }
                     let mut nav = EpubNav::default();
                     read_nav_point_xml(&mut reader, &mut nav)?;
@@ -576,29 +663,36 @@ fn read_nav_xhtml(xhtml: &str, root_path: String, book: &mut EpubBook) -> IResul
     let mut buffer = String::new();
     let mut in_label = false;
     loop {
-        match reader.read_event()? {
+        let event = match reader.read_event() {
+            Ok(v) => v,
+            Err(e) => return invalid!(reader, e),
+        };
+        match event {
             Event::Start(e) => match e.name().as_ref() {
                 b"nav" if has_epub_type(&e, "toc") => in_toc_nav = true,
                 b"ol" if in_toc_nav => stack.push_back(Vec::new()),
                 b"li" if in_toc_nav => current_item = Some(EpubNav::default()),
                 b"a" if in_toc_nav => {
-                    if let Some(href) = e
-                        .attributes()
-                        .find(|a| a.as_ref().unwrap().key.as_ref() == b"href")
-                    {
-                        let mut href = String::from_utf8_lossy(&href.unwrap().value).to_string();
+                    if let (Some(href), Some(item)) = (
+                        e.attributes()
+                            .filter_map(Result::ok)
+                            .find(|a| a.key.as_ref() == b"href"),
+                        current_item.as_mut(),
+                    ) {
+                        let mut href = String::from_utf8_lossy(&href.value).to_string();
                         if !href.starts_with(&root_path) {
                             href = format!("{}{}", root_path, href);
                         }
-                        current_item.as_mut().unwrap().set_file_name(&href);
+                        item.set_file_name(&href);
                     }
                 }
                 b"span" => {
                     if let Some(class) = e
                         .attributes()
-                        .find(|a| a.as_ref().unwrap().key.as_ref() == b"class")
+                        .filter_map(Result::ok)
+                        .find(|a| a.key.as_ref() == b"class")
                     {
-                        match class.unwrap().value.as_ref() {
+                        match class.value.as_ref() {
                             b"toc-label" => in_label = true,
                             _ => (),
                         }
@@ -617,8 +711,10 @@ fn read_nav_xhtml(xhtml: &str, root_path: String, book: &mut EpubBook) -> IResul
                 b"ol" => {
                     if let Some(children) = stack.pop_back() {
                         if let Some(last) = stack.back_mut() {
-                            for item in children {
-                                last.last_mut().unwrap().push(item);
+                            // ol 直接嵌套在 ol 中时没有上级条目，作为同级处理
+                            match last.last_mut() {
+                                Some(parent) => children.into_iter().for_each(|f| parent.push(f)),
+                                None => last.extend(children),
                             }
                         } else {
                             items = children;
@@ -636,7 +732,9 @@ fn read_nav_xhtml(xhtml: &str, root_path: String, book: &mut EpubBook) -> IResul
                 }
                 b"span" => {
                     if in_label {
-                        current_item.as_mut().unwrap().set_title(buffer.trim());
+                        if let Some(item) = current_item.as_mut() {
+                            item.set_title(buffer.trim());
+                        }
                         buffer.clear();
                         in_label = false;
                     }
@@ -654,161 +752,544 @@ fn read_nav_xhtml(xhtml: &str, root_path: String, book: &mut EpubBook) -> IResul
 }
 
 fn has_epub_type(e: &BytesStart, value: &str) -> bool {
-    e.attributes().any(|a| {
-        let attr = a.as_ref().unwrap();
-        attr.key.as_ref() == b"epub:type" && attr.value.as_ref() == value.as_bytes()
-    })
+    e.attributes()
+        .filter_map(Result::ok)
+        .any(|attr| attr.key.as_ref() == b"epub:type" && attr.value.as_ref() == value.as_bytes())
 }
 
-#[derive(Debug, Clone)]
-struct EpubReader<T> {
-    inner: zip::ZipArchive<T>,
+///
+/// 宽松模式读取时的一处修复
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    /// mimetype 缺失或者内容不正确
+    Mimetype,
+    /// container.xml 中的 opf 路径缺失或者无效，改为扫描得到的 opf
+    OpfLocation { from: Option<String>, to: String },
+    /// manifest 中声明但是不存在的文件，已移除
+    MissingFile(String),
+    /// 指向不存在文件的目录，已移除，下级目录上移一级
+    MissingNavTarget { title: String, file: String },
+    /// 目录缺失或者为空，根据 spine 生成的目录数
+    SynthesizedNav(usize),
 }
 
-// impl <T: Read + Seek> From<Vec<u8>> for EpubReader<T> {
-//     fn from(value: Vec<u8>) -> Self {
-
-//         EpubReader{
-//             inner:
-//         }
-
-//     }
-// }
-impl<T: Read + Seek> EpubReader<T> {
-    pub fn new(value: T) -> IResult<Self> {
-        let r = zip::ZipArchive::new(value)?;
-        Ok(EpubReader { inner: r })
+impl std::fmt::Display for Repair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Repair::Mimetype => write!(f, "mimetype 缺失或者内容不正确"),
+            Repair::OpfLocation {
+                from: Some(from),
+                to,
+            } => {
+                write!(f, "opf 路径 {} 不存在，改为 {}", from, to)
+            }
+            Repair::OpfLocation { from: None, to } => {
+                write!(f, "container.xml 中没有 opf 路径，改为 {}", to)
+            }
+            Repair::MissingFile(file) => write!(f, "文件 {} 不存在，已移除", file),
+            Repair::MissingNavTarget { title, file } => {
+                write!(f, "目录 {} 指向的文件 {} 不存在，已移除", title, file)
+            }
+            Repair::SynthesizedNav(len) => write!(f, "目录为空，根据 spine 生成了 {} 项目录", len),
+        }
     }
 }
-impl<T: Read + Seek + Sync + Send> EpubReaderTrait for EpubReader<T> {
-    fn read(&mut self, book: &mut EpubBook) -> IResult<()> {
-        let reader = &mut self.inner;
-
-        {
-            // 判断文件格式
-            let content = read_from_zip!(reader, "mimetype");
 
-            if content != "application/epub+zip" {
-                return invalid!("not a epub file");
+///
+/// 解析epub结构，填充元数据、目录、章节等信息
+///
+/// [repairs] 不为None时使用宽松模式，尽量修复而不是返回错误，修复记录写入其中
+///
+fn read_book<T: Read + Seek>(
+    reader: &mut zip::ZipArchive<T>,
+    book: &mut EpubBook,
+    mut repairs: Option<&mut Vec<Repair>>,
+) -> IResult<()> {
+    {
+        // 判断文件格式
+        let content = if repairs.is_some() {
+            read_entry_to_end(reader, "mimetype")
+                .map(|v| String::from_utf8_lossy(&v).to_string())
+                .unwrap_or_default()
+        } else {
+            read_from_zip!(reader, "mimetype")
+        };
+
+        if content != "application/epub+zip" {
+            match repairs.as_deref_mut() {
+                Some(r) => r.push(Repair::Mimetype),
+                None => {
+                    return Err(IError::Parse(Cow::from("not a epub file"))
+                        .with_context(ErrorContext::default().with_entry("mimetype")))
+                }
             }
         }
+    }
 
-        {
-            let content = read_from_zip!(reader, "META-INF/container.xml");
+    {
+        let opf_path = match repairs.as_deref_mut() {
+            Some(r) => {
+                let from = read_entry_to_end(reader, "META-INF/container.xml")
+                    .ok()
+                    .and_then(|v| String::from_utf8(v).ok())
+                    .and_then(|v| get_opf_location(v.as_str()).ok())
+                    .filter(|v| !v.is_empty());
+                match from.clone().filter(|v| reader.index_for_name(v).is_some()) {
+                    Some(v) => Ok(v),
+                    None => {
+                        let to =
+                            scan_opf(reader).ok_or(IError::MissingEntry("*.opf".to_string()))?;
+                        r.push(Repair::OpfLocation {
+                            from,
+                            to: to.clone(),
+                        });
+                        Ok(to)
+                    }
+                }
+            }
+            None => {
+                let content = read_from_zip!(reader, "META-INF/container.xml");
+                get_opf_location(content.as_str())
+            }
+        };
+        if let Ok(path) = opf_path {
+            let pp = crate::path::Path::system(path.as_str());
+            if pp.level_count() != 1 {
+                book.prefix.push_str(pp.pop().to_str().as_str());
+            }
+            let opf = read_from_zip!(reader, path.as_str());
+            read_opf_xml(opf.as_str(), book)
+                .context(|| ErrorContext::new("read opf").with_entry(path.as_str()))?;
+            // opf 本身不应出现在 manifest 中，出现时同样会重新生成
+            let opf_name = path.rsplit('/').next().unwrap_or_default();
+            for ele in book.assets_mut() {
+                if ele.file_name() == opf_name {
+                    ele.generated = true;
+                }
+            }
 
-            let opf_path = get_opf_location(content.as_str());
-            if let Ok(path) = opf_path {
-                let pp = crate::path::Path::system(path.as_str());
-                if pp.level_count() != 1 {
-                    book.prefix.push_str(pp.pop().to_str().as_str());
+            if let Some(r) = repairs.as_deref_mut() {
+                let prefix = book.prefix.clone();
+                for file in book.retain_files(|f| has_entry(reader, &prefix, f)) {
+                    r.push(Repair::MissingFile(file));
                 }
-                let opf = read_from_zip!(reader, path.as_str());
-                read_opf_xml(opf.as_str(), book)?;
+            }
 
-                {
-                    // 读取导航
-                    if let Some(toc) = book.assets().find(|s| s.id() == "ncx" || s.id() == "toc") {
-                        let t = crate::path::Path::system(path.as_str())
-                            .pop()
-                            .join(toc.file_name())
-                            .to_str();
-
-                        if reader.by_name(t.as_str()).is_ok() {
-                            let content = read_from_zip!(reader, t.as_str());
-                            if toc.file_name().contains(".xhtml") {
-                                let root = toc
-                                    .file_name()
-                                    .rfind("/")
-                                    .map(|f| {
-                                        return toc
-                                            .file_name()
-                                            .get(..(f + 1))
-                                            .unwrap_or_default()
-                                            .to_string();
-                                    })
-                                    .unwrap_or_default();
-                                read_nav_xhtml(content.as_str(), root, book)?;
-                            } else {
-                                read_nav_xml(content.as_str(), book)?;
-                            }
-                            book.update_chapter();
-                        }
+            let toc = book
+                .assets()
+                .find(|s| s.id() == "ncx" || s.id() == "toc")
+                .map(|f| f.file_name().to_string());
+            // 读取导航
+            if let Some(toc) = &toc {
+                let t = crate::path::Path::system(path.as_str())
+                    .pop()
+                    .join(toc)
+                    .to_str();
+
+                if reader.by_name(t.as_str()).is_ok() {
+                    let content = read_from_zip!(reader, t.as_str());
+                    if toc.contains(".xhtml") {
+                        let root = toc
+                            .rfind("/")
+                            .map(|f| toc.get(..(f + 1)).unwrap_or_default().to_string())
+                            .unwrap_or_default();
+                        read_nav_xhtml(content.as_str(), root, book)
+                    } else {
+                        read_nav_xml(content.as_str(), book)
                     }
+                    .context(|| ErrorContext::new("read nav").with_entry(t.as_str()))?;
                 }
             }
+
+            if let Some(r) = repairs.as_deref_mut() {
+                let prefix = book.prefix.clone();
+                for nav in book.retain_nav(|n| has_entry(reader, &prefix, n.file_name())) {
+                    r.push(Repair::MissingNavTarget {
+                        title: nav.title().to_string(),
+                        file: nav.file_name().to_string(),
+                    });
+                }
+                if book.nav().len() == 0 {
+                    let len = synthesize_nav(reader, book, toc.as_deref());
+                    if len != 0 {
+                        r.push(Repair::SynthesizedNav(len));
+                    }
+                }
+            }
+            book.update_chapter();
         }
-        book.update_assets();
+    }
+    book.update_assets();
+
+    Ok(())
+}
+
+///
+/// 扫描zip内的opf文件，存在多个时取路径最短的
+///
+fn scan_opf<T: Read + Seek>(reader: &zip::ZipArchive<T>) -> Option<String> {
+    reader
+        .file_names()
+        .filter(|f| f.to_lowercase().ends_with(".opf"))
+        .min_by_key(|f| (f.len(), *f))
+        .map(|f| f.to_string())
+}
+
+///
+/// 判断文件能否被读取，忽略 # 之后的部分
+///
+fn has_entry<T: Read + Seek>(reader: &zip::ZipArchive<T>, prefix: &str, file_name: &str) -> bool {
+    let file_name = file_name.split('#').next().unwrap_or_default();
+    !file_name.is_empty()
+        && reader
+            .index_for_name(resolve_entry(prefix, file_name).as_str())
+            .is_some()
+}
+
+///
+/// 根据 spine 生成目录，标题取自章节的 title，没有时使用文件名
+///
+/// [skip] 导航文件本身，不生成目录
+///
+fn synthesize_nav<T: Read + Seek>(
+    reader: &mut zip::ZipArchive<T>,
+    book: &mut EpubBook,
+    skip: Option<&str>,
+) -> usize {
+    let mut nav = Vec::new();
+    for chap in book.chapters().filter(|f| Some(f.file_name()) != skip) {
+        let title = read_entry_to_end(
+            reader,
+            resolve_entry(&book.prefix, chap.file_name()).as_str(),
+        )
+        .ok()
+        .and_then(|v| String::from_utf8(v).ok())
+        .and_then(|v| get_html_info(v.as_str(), None).ok())
+        .map(|(title, _)| title.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| {
+            let name = chap.file_name().rsplit('/').next().unwrap_or_default();
+            name.rsplit_once('.').map_or(name, |(v, _)| v).to_string()
+        });
+        nav.push(
+            EpubNav::default()
+                .with_title(title)
+                .with_file_name(chap.file_name()),
+        );
+    }
+    let len = nav.len();
+    for ele in nav {
+        book.add_nav(ele);
+    }
+    len
+}
+
+///
+/// 将相对于opf目录的路径转换为zip内的完整路径
+///
+pub(crate) fn resolve_entry(prefix: &str, file_name: &str) -> String {
+    if prefix.is_empty() {
+        file_name.to_string()
+    } else {
+        crate::path::Path::system(prefix).join(file_name).to_str()
+    }
+}
 
-        Ok(())
+fn read_entry_to_end<T: Read + Seek>(
+    reader: &mut zip::ZipArchive<T>,
+    entry: &str,
+) -> IResult<Vec<u8>> {
+    let mut file = open_entry(reader, entry)?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)
+        .context(|| ErrorContext::new("read entry").with_entry(entry))?;
+    Ok(content)
+}
+
+fn read_entry_to_path<T: Read + Seek>(
+    reader: &mut zip::ZipArchive<T>,
+    entry: &str,
+    file_path: &str,
+) -> IResult<()> {
+    let mut file = open_entry(reader, entry)?;
+    let output_file =
+        File::create(file_path).context(|| ErrorContext::new("create file").with_entry(entry))?;
+    let mut writer = BufWriter::new(output_file);
+    std::io::copy(&mut file, &mut writer)
+        .and_then(|_| writer.flush())
+        .context(|| ErrorContext::new("copy entry").with_entry(entry))?;
+    Ok(())
+}
+
+fn open_entry<'a, T: Read + Seek>(
+    reader: &'a mut zip::ZipArchive<T>,
+    entry: &str,
+) -> IResult<zip::read::ZipFile<'a, T>> {
+    reader.by_name(entry).map_err(|e| match e {
+        zip::result::ZipError::FileNotFound => IError::MissingEntry(entry.to_string()),
+        e => IError::from(e).with_context(ErrorContext::new("open entry").with_entry(entry)),
+    })
+}
+
+///
+/// 可廉价克隆的reader
+///
+/// 每次读取都会克隆一份 [zip::ZipArchive]，中央目录只解析一次并在克隆间共享，
+/// 因此多个线程可以同时读取不同的文件，无需加锁
+///
+struct EpubReader<T> {
+    inner: zip::ZipArchive<T>,
+    /// opf 所在目录
+    prefix: String,
+}
+
+impl<T: Read + Seek + Clone + Sync + Send> EpubReaderTrait for EpubReader<T> {
+    fn read_file(&self, file_name: &str) -> IResult<Vec<u8>> {
+        read_entry_to_end(
+            &mut self.inner.clone(),
+            resolve_entry(&self.prefix, file_name).as_str(),
+        )
     }
 
-    fn read_file(&mut self, file_name: &str) -> IResult<Vec<u8>> {
-        let mut file = self
-            .inner
-            .by_name(file_name)
-            .or(Err(IError::FileNotFound))?;
-        let mut content = Vec::new();
-        invalid!(file.read_to_end(&mut content), "read err");
-        Ok(content)
+    fn read_string(&self, file_name: &str) -> IResult<String> {
+        Ok(String::from_utf8(self.read_file(file_name)?)?)
     }
 
-    fn read_string(&mut self, file_name: &str) -> IResult<String> {
-        let mut file = self
-            .inner
-            .by_name(file_name)
-            .or(Err(IError::FileNotFound))?;
-        let mut content = String::new();
-        invalid!(file.read_to_string(&mut content), "read err");
-        Ok(content)
+    fn read_to_path(&self, file_name: &str, file_path: &str) -> IResult<()> {
+        read_entry_to_path(
+            &mut self.inner.clone(),
+            resolve_entry(&self.prefix, file_name).as_str(),
+            file_path,
+        )
     }
+}
 
-    fn read_to_path(&mut self, file_name: &str, file_path: &str) -> IResult<()> {
-        let mut file = self
-            .inner
-            .by_name(file_name)
-            .or(Err(IError::FileNotFound))?;
-        let output_file = File::create(file_path)?;
-        let mut writer = BufWriter::new(output_file);
-        let mut buffer = [0u8; 16384];
-        loop {
-            let bytes_read = file.read(&mut buffer)?;
-            if bytes_read == 0 {
-                break;
+///
+/// 无法克隆的reader，只能加锁串行读取
+///
+struct LockedEpubReader<T> {
+    inner: std::sync::Mutex<zip::ZipArchive<T>>,
+    /// opf 所在目录
+    prefix: String,
+}
+
+impl<T: Read + Seek + Sync + Send> LockedEpubReader<T> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, zip::ZipArchive<T>> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T: Read + Seek + Sync + Send> EpubReaderTrait for LockedEpubReader<T> {
+    fn read_file(&self, file_name: &str) -> IResult<Vec<u8>> {
+        read_entry_to_end(
+            &mut self.lock(),
+            resolve_entry(&self.prefix, file_name).as_str(),
+        )
+    }
+
+    fn read_string(&self, file_name: &str) -> IResult<String> {
+        Ok(String::from_utf8(self.read_file(file_name)?)?)
+    }
+
+    fn read_to_path(&self, file_name: &str, file_path: &str) -> IResult<()> {
+        read_entry_to_path(
+            &mut self.lock(),
+            resolve_entry(&self.prefix, file_name).as_str(),
+            file_path,
+        )
+    }
+}
+
+///
+/// 多个reader共享的内存数据
+///
+#[derive(Clone)]
+struct SharedBytes(std::sync::Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+///
+/// 多个reader共享的文件句柄
+///
+/// 使用按位置读取，每个克隆维护自己的读取位置，互不干扰
+///
+#[cfg(any(unix, windows))]
+#[derive(Clone)]
+struct SharedFile {
+    file: std::sync::Arc<File>,
+    len: u64,
+    pos: u64,
+}
+
+#[cfg(any(unix, windows))]
+impl SharedFile {
+    fn new(file: File) -> IResult<Self> {
+        let len = file.metadata()?.len();
+        Ok(SharedFile {
+            file: std::sync::Arc::new(file),
+            len,
+            pos: 0,
+        })
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl Read for SharedFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        #[cfg(unix)]
+        let size = std::os::unix::fs::FileExt::read_at(self.file.as_ref(), buf, self.pos)?;
+        #[cfg(windows)]
+        let size = std::os::windows::fs::FileExt::seek_read(self.file.as_ref(), buf, self.pos)?;
+        self.pos += size as u64;
+        Ok(size)
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl Seek for SharedFile {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            std::io::SeekFrom::Start(v) => Some(v),
+            std::io::SeekFrom::End(v) => self.len.checked_add_signed(v),
+            std::io::SeekFrom::Current(v) => self.pos.checked_add_signed(v),
+        };
+        match target {
+            Some(v) => {
+                self.pos = v;
+                Ok(v)
             }
-            writer.write_all(&buffer[..bytes_read])?;
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
         }
-        Ok(())
     }
 }
 
 ///
 /// 从内存读取epub
 ///
+/// 内容在所有章节和资源间共享，支持多线程并行读取
+///
 pub fn read_from_vec(data: Vec<u8>) -> IResult<EpubBook> {
-    read_from_reader(std::io::Cursor::new(data))
+    read_from_cloneable_reader(std::io::Cursor::new(SharedBytes(std::sync::Arc::new(data))))
 }
 
 ///
 /// 从文件读取epub
 ///
+/// 文件句柄在所有章节和资源间共享，支持多线程并行读取
+///
 pub fn read_from_file<P: AsRef<Path>>(file: P) -> IResult<EpubBook> {
-    read_from_reader(std::fs::File::open(file)?)
+    #[cfg(any(unix, windows))]
+    return read_from_cloneable_reader(SharedFile::new(std::fs::File::open(file)?)?);
+    #[cfg(not(any(unix, windows)))]
+    return read_from_reader(std::fs::File::open(file)?);
 }
 
 ///
 /// 从任意reader读取epub
 ///
+/// 延迟读取时需要加锁，多线程读取章节会串行执行，如果reader可以廉价克隆，
+/// 应该使用 [read_from_cloneable_reader]
+///
 pub fn read_from_reader<T: Read + Seek + Sync + Send + 'static>(value: T) -> IResult<EpubBook> {
-    let reader = EpubReader::new(value)?;
+    let mut inner = zip::ZipArchive::new(value)?;
+    let mut book = EpubBook::default();
+    read_book(&mut inner, &mut book, None)?;
+
+    let prefix = book.prefix.clone();
+    book.set_reader(std::sync::Arc::new(LockedEpubReader {
+        inner: std::sync::Mutex::new(inner),
+        prefix,
+    }));
+    Ok(book)
+}
+
+///
+/// 从可克隆的reader读取epub
+///
+/// 每次延迟读取都会克隆reader，不需要加锁，可以在多个线程中并行读取章节，
+/// 适用于 `Cursor<Arc<..>>` 或者按位置读取的文件句柄等克隆代价很小的reader
+///
+pub fn read_from_cloneable_reader<T: Read + Seek + Clone + Sync + Send + 'static>(
+    value: T,
+) -> IResult<EpubBook> {
+    read_cloneable(value, None)
+}
+
+fn read_cloneable<T: Read + Seek + Clone + Sync + Send + 'static>(
+    value: T,
+    repairs: Option<&mut Vec<Repair>>,
+) -> IResult<EpubBook> {
+    let mut inner = zip::ZipArchive::new(value)?;
     let mut book = EpubBook::default();
-    let re: std::sync::Arc<std::sync::Mutex<Box<dyn EpubReaderTrait + Sync + Send>>> =
-        std::sync::Arc::new(std::sync::Mutex::new(Box::new(reader)));
-    book.set_reader(std::sync::Arc::clone(&re));
+    read_book(&mut inner, &mut book, repairs)?;
 
-    re.lock().unwrap().read(&mut book)?;
+    let prefix = book.prefix.clone();
+    book.set_reader(std::sync::Arc::new(EpubReader { inner, prefix }));
     Ok(book)
 }
 
+///
+/// 使用宽松模式从内存读取epub
+///
+/// 遇到轻微损坏的文件时尽量修复而不是返回错误，例如缺失 mimetype、
+/// container.xml 中的 opf 路径错误、manifest 或者目录引用了不存在的文件等
+///
+/// # Returns
+///
+/// 读取的书本以及按发生顺序排列的修复记录
+///
+pub fn read_from_vec_lenient(data: Vec<u8>) -> IResult<(EpubBook, Vec<Repair>)> {
+    let mut repairs = Vec::new();
+    let book = read_cloneable(
+        std::io::Cursor::new(SharedBytes(std::sync::Arc::new(data))),
+        Some(&mut repairs),
+    )?;
+    Ok((book, repairs))
+}
+
+///
+/// 使用宽松模式从文件读取epub
+///
+/// 参考 [read_from_vec_lenient]
+///
+pub fn read_from_file_lenient<P: AsRef<Path>>(file: P) -> IResult<(EpubBook, Vec<Repair>)> {
+    #[cfg(any(unix, windows))]
+    {
+        let mut repairs = Vec::new();
+        let book = read_cloneable(
+            SharedFile::new(std::fs::File::open(file)?)?,
+            Some(&mut repairs),
+        )?;
+        Ok((book, repairs))
+    }
+    #[cfg(not(any(unix, windows)))]
+    return read_from_vec_lenient(std::fs::read(file)?);
+}
+
+///
+/// 从异步reader读取epub
+///
+/// zip 需要随机访问，所以会先把全部内容读入内存，之后与 [read_from_vec] 共用同一套解析流程
+///
+#[cfg(feature = "async")]
+pub async fn read_from_async_reader<T: tokio::io::AsyncRead + Unpin>(
+    mut value: T,
+) -> IResult<EpubBook> {
+    use tokio::io::AsyncReadExt;
+
+    let mut data = Vec::new();
+    value.read_to_end(&mut data).await?;
+    read_from_vec(data)
+}
+
 /// 判断是否是epub文件
 pub fn is_epub<T: Read>(value: &mut T) -> IResult<bool> {
     let mut v = Vec::new();
@@ -818,7 +1299,7 @@ pub fn is_epub<T: Read>(value: &mut T) -> IResult<bool> {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use crate::{common::tests::download_epub_file, prelude::*};
 
     use super::{is_epub, read_nav_xml};
@@ -934,6 +1415,165 @@ html
         // println!("{}",c.data().map(|f|String::from_utf8(f.to_vec())).unwrap().unwrap());
     }
 
+    #[test]
+    fn test_parallel_read() {
+        let mut builder = EpubBuilder::new().with_title("书名");
+        for i in 0..16 {
+            builder = builder.add_chapter(
+                EpubHtml::default()
+                    .with_title(format!("第{i}章"))
+                    .with_file_name(format!("chap/{i}.xhtml"))
+                    .with_data(format!("<p>内容{i}</p>").as_bytes().to_vec()),
+            );
+        }
+        let data = builder.mem().unwrap();
+
+        let file =
+            std::env::temp_dir().join(format!("{}.test_parallel_read.epub", std::process::id()));
+        std::fs::write(&file, &data).unwrap();
+
+        for mut book in [
+            read_from_vec(data.clone()).unwrap(),
+            read_from_file(&file).unwrap(),
+            read_from_reader(std::io::Cursor::new(data.clone())).unwrap(),
+        ] {
+            std::thread::scope(|s| {
+                for chap in book
+                    .chapters_mut()
+                    .filter(|c| c.file_name().starts_with("chap/"))
+                {
+                    s.spawn(move || {
+                        let index = chap.file_name()["chap/".len()..].replace(".xhtml", "");
+                        let data = String::from_utf8(chap.data_mut().unwrap().to_vec()).unwrap();
+                        assert!(data.contains(format!("<p>内容{index}</p>").as_str()));
+                    });
+                }
+            });
+            assert_eq!(16, book.chapters().filter(|c| c.data().is_some()).count());
+        }
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn test_read_fixed_layout() {
+        let data = EpubBuilder::new()
+            .with_title("漫画")
+            .with_rendition_layout("pre-paginated")
+            .with_rendition_orientation("portrait")
+            .with_rendition_spread("landscape")
+            .with_viewport(600, 800)
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("1")
+                    .with_file_name("1.xhtml")
+                    .with_spine_properties("page-spread-right")
+                    .with_data(b"<img src=\"1.png\"/>".to_vec()),
+            )
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("2")
+                    .with_file_name("2.xhtml")
+                    .with_viewport(1200, 800)
+                    .with_spine_properties("rendition:page-spread-center")
+                    .with_data(b"<img src=\"2.png\"/>".to_vec()),
+            )
+            .mem()
+            .unwrap();
+
+        let mut book = read_from_vec(data).unwrap();
+        assert_eq!("3.0", book.version());
+        assert!(book.is_fixed_layout());
+        assert_eq!(Some("portrait"), book.rendition_orientation());
+        assert_eq!(Some("landscape"), book.rendition_spread());
+        // 固定版式的属性不会作为自定义的meta
+        assert!(book.meta().iter().all(|f| !f
+            .get_attr("property")
+            .is_some_and(|p| p.starts_with("rendition:"))));
+
+        let chap = book.get_chapter_mut("1.xhtml").unwrap();
+        assert_eq!(Some("page-spread-right"), chap.spine_properties());
+        assert_eq!(None, chap.viewport());
+        assert!(!String::from_utf8_lossy(chap.data_mut().unwrap()).contains("<h1"));
+        assert_eq!(Some((600, 800)), chap.viewport());
+
+        let chap = book.get_chapter_mut("2.xhtml").unwrap();
+        chap.data_mut();
+        assert_eq!(Some((1200, 800)), chap.viewport());
+        assert_eq!(
+            Some("rendition:page-spread-center"),
+            chap.spine_properties()
+        );
+    }
+
+    #[test]
+    fn test_read_writing_mode() {
+        let data = EpubBuilder::new()
+            .with_title("竖排")
+            .with_writing_mode("vertical-rl")
+            .with_page_direction("rtl")
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("1")
+                    .with_file_name("1.xhtml")
+                    .with_data(b"<p>1</p>".to_vec()),
+            )
+            .mem()
+            .unwrap();
+
+        let mut book = read_from_vec(data).unwrap();
+        assert_eq!(Some("vertical-rl"), book.writing_mode());
+        assert_eq!(Some("rtl"), book.page_direction());
+        // 书写方向不会作为自定义的meta
+        assert!(book
+            .meta()
+            .iter()
+            .all(|f| f.get_attr("name").map(|f| f.as_str()) != Some("primary-writing-mode")));
+
+        let html = book
+            .get_chapter_mut("1.xhtml")
+            .unwrap()
+            .raw_data()
+            .unwrap()
+            .to_string();
+        assert!(html.contains("writing-mode:vertical-rl"));
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn test_async_read_write() {
+        let mut book = EpubBuilder::new()
+            .with_title("书名")
+            .with_creator("作者")
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("ok")
+                    .with_file_name("0.xhtml")
+                    .with_data("<p>html</p>".as_bytes().to_vec()),
+            )
+            .book()
+            .unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let data = runtime.block_on(async {
+            // 不支持 seek 的 writer
+            let mut writer = AsyncEpubWriter::new(Vec::new());
+            writer.write(&mut book).await.unwrap();
+            writer.into_inner()
+        });
+        let mut nb = runtime
+            .block_on(read_from_async_reader(std::io::Cursor::new(data)))
+            .unwrap();
+
+        assert_eq!("书名", nb.title());
+        assert_eq!(Some("作者"), nb.creator());
+        let chap = nb.get_chapter_mut("0.xhtml").unwrap();
+        assert!(String::from_utf8(chap.data_mut().unwrap().to_vec())
+            .unwrap()
+            .contains("<p>html</p>"));
+    }
+
     #[test]
     fn test_read_empty_toc() {
         let xml = r#"<?xml version='1.0' encoding='utf-8'?><ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><head><meta content="1394" name="dtb:uid"/><meta content="0" name="dtb:depth"/><meta content="0" name="dtb:totalPageCount"/><meta content="0" name="dtb:maxPageNumber"/></head><docTitle><text>book_title</text></docTitle><navMap><navPoint id="0-0"><navLabel><text></text></navLabel><content src="0.xhtml"></content><navPoint id="0-0"><navLabel><text></text></navLabel><content src="0.xhtml"></content></navPoint></navPoint></navMap></ncx>"#;
@@ -948,6 +1588,370 @@ html
         assert_eq!("", n[0].child().as_slice()[0].title());
     }
 
+    /// 按顺序写入文件生成压缩包
+    pub(crate) fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        use std::io::Write;
+        let mut v = std::io::Cursor::new(Vec::new());
+        let mut w = zip::ZipWriter::new(&mut v);
+        for (name, content) in files {
+            w.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            w.write_all(content.as_bytes()).unwrap();
+        }
+        w.finish().unwrap();
+        v.into_inner()
+    }
+
+    static OPF: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>书名</dc:title>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="c1" href="1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c2" href="2.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c3" href="3.xhtml" media-type="application/xhtml+xml"/>
+    <item id="css" href="style.css" media-type="text/css"/>
+  </manifest>
+  <spine toc="ncx">
+    <itemref idref="c1"/>
+    <itemref idref="c2"/>
+    <itemref idref="c3"/>
+  </spine>
+</package>"#;
+
+    static NCX: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap>
+<navPoint id="n1"><navLabel><text>一</text></navLabel><content src="1.xhtml"/></navPoint>
+<navPoint id="n3"><navLabel><text>三</text></navLabel><content src="3.xhtml"/>
+<navPoint id="n2"><navLabel><text>二</text></navLabel><content src="2.xhtml"/></navPoint>
+</navPoint>
+</navMap></ncx>"#;
+
+    fn html(title: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><html xmlns="http://www.w3.org/1999/xhtml"><head><title>{title}</title></head><body><p>{title}</p></body></html>"#
+        )
+    }
+
+    pub(crate) static EPUB3_OPF: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="uid">urn:uuid:5b0c4d9e-3f6a-4c1e-9d2b-7a8e6f1c0b3d</dc:identifier>
+    <dc:title>书名</dc:title>
+    <dc:language>zh</dc:language>
+    <meta property="dcterms:modified">2024-01-01T00:00:00Z</meta>
+    <meta name="generator" content="other"/>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="nav" href="toc.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="ch1" href="text/c1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="ch2" href="text/c2.xhtml" media-type="application/xhtml+xml"/>
+    <item id="ch3" href="text/c3.xhtml" media-type="application/xhtml+xml"/>
+    <item id="appendix" href="text/c4.xhtml" media-type="application/xhtml+xml"/>
+    <item id="css" href="style.css" media-type="text/css"/>
+  </manifest>
+  <spine toc="ncx">
+    <itemref idref="ch1"/>
+    <itemref idref="ch2"/>
+    <itemref idref="ch3"/>
+    <itemref idref="appendix"/>
+  </spine>
+</package>"#;
+
+    pub(crate) static EPUB3_NAV: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"><head><title>目录</title></head><body>
+<nav epub:type="toc" id="toc"><ol>
+<li><a href="text/c1.xhtml">第一卷</a><ol>
+<li><a href="text/c1.xhtml">第一章</a></li>
+<li><a href="text/c2.xhtml">第二章</a></li>
+</ol></li>
+<li><a href="text/c3.xhtml">第三章</a></li>
+</ol></nav>
+</body></html>"#;
+
+    static EPUB3_NCX: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap>
+<navPoint id="v1"><navLabel><text>第一卷</text></navLabel><content src="text/c1.xhtml"/>
+<navPoint id="n1"><navLabel><text>第一章</text></navLabel><content src="text/c1.xhtml"/></navPoint>
+<navPoint id="n2"><navLabel><text>第二章</text></navLabel><content src="text/c2.xhtml"/></navPoint>
+</navPoint>
+<navPoint id="n3"><navLabel><text>第三章</text></navLabel><content src="text/c3.xhtml"/></navPoint>
+</navMap></ncx>"#;
+
+    ///
+    /// 其他工具生成的 epub3
+    ///
+    /// opf 位于 EPUB/package.opf，导航为 toc.xhtml 和 toc.ncx，目录中有卷，附录不在目录中
+    ///
+    pub(crate) fn epub3() -> Vec<u8> {
+        epub3_with(&[])
+    }
+
+    /// 替换 [epub3] 中的部分文件
+    pub(crate) fn epub3_with(replace: &[(&str, &str)]) -> Vec<u8> {
+        let container = r#"<?xml version="1.0"?><container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles><rootfile full-path="EPUB/package.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+        let chapters: Vec<String> = ["第一章", "第二章", "第三章", "附录"]
+            .iter()
+            .map(|f| html(f))
+            .collect();
+        let files = [
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", container),
+            ("EPUB/package.opf", EPUB3_OPF),
+            ("EPUB/toc.ncx", EPUB3_NCX),
+            ("EPUB/toc.xhtml", EPUB3_NAV),
+            ("EPUB/text/c1.xhtml", chapters[0].as_str()),
+            ("EPUB/text/c2.xhtml", chapters[1].as_str()),
+            ("EPUB/text/c3.xhtml", chapters[2].as_str()),
+            ("EPUB/text/c4.xhtml", chapters[3].as_str()),
+            ("EPUB/style.css", "p{}"),
+        ];
+        let files: Vec<(&str, &str)> = files
+            .into_iter()
+            .map(|(name, content)| {
+                replace
+                    .iter()
+                    .find(|f| f.0 == name)
+                    .map_or((name, content), |f| (name, f.1))
+            })
+            .collect();
+        zip(&files)
+    }
+
+    #[test]
+    fn test_read_generated_by_manifest() {
+        let container = r#"<?xml version="1.0"?><container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+        // 正文使用 nav.xhtml、cover.xhtml 命名，导航文档为 toc.xhtml
+        let opf = r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>书名</dc:title>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="toc-page" href="toc.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="c1" href="nav.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c2" href="cover.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine toc="ncx">
+    <itemref idref="toc-page"/>
+    <itemref idref="c1"/>
+    <itemref idref="c2"/>
+  </spine>
+</package>"#;
+        let ncx = r#"<?xml version="1.0" encoding="utf-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap>
+<navPoint id="n1"><navLabel><text>航行</text></navLabel><content src="nav.xhtml"/></navPoint>
+<navPoint id="n2"><navLabel><text>封面故事</text></navLabel><content src="cover.xhtml"/></navPoint>
+</navMap></ncx>"#;
+        let (c1, c2) = (html("航行"), html("封面故事"));
+        let data = zip(&[
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", container),
+            ("OEBPS/content.opf", opf),
+            ("OEBPS/toc.ncx", ncx),
+            ("OEBPS/toc.xhtml", EPUB3_NAV),
+            ("OEBPS/nav.xhtml", c1.as_str()),
+            ("OEBPS/cover.xhtml", c2.as_str()),
+        ]);
+
+        let mut book = read_from_vec(data).unwrap();
+        let generated: Vec<(&str, bool)> = book
+            .chapters()
+            .map(|f| (f.file_name(), f.is_generated()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("toc.xhtml", true),
+                ("nav.xhtml", false),
+                ("cover.xhtml", false)
+            ],
+            generated
+        );
+        assert_eq!(
+            vec![("toc.ncx", true)],
+            book.assets()
+                .map(|f| (f.file_name(), f.is_generated()))
+                .collect::<Vec<_>>()
+        );
+
+        // 写入时生成的文件改名，不覆盖正文
+        let data = EpubWriter::write_to_mem(&mut book, false).unwrap();
+        let mut book = read_from_vec(data).unwrap();
+        let chapters: Vec<(&str, bool)> = book
+            .chapters()
+            .filter(|f| !f.is_generated())
+            .map(|f| (f.file_name(), f.title().is_empty()))
+            .collect();
+        assert_eq!(vec![("nav.xhtml", false), ("cover.xhtml", false)], chapters);
+        assert!(book.chapters().any(|f| f.file_name() == "nav_1.xhtml"));
+        assert!(String::from_utf8_lossy(
+            book.get_chapter_mut("nav.xhtml")
+                .unwrap()
+                .data_mut()
+                .unwrap()
+        )
+        .contains("<p>航行</p>"));
+    }
+
+    #[test]
+    fn test_read_lenient() {
+        let (c1, c2) = (html("一"), html("二"));
+        let container = r#"<?xml version="1.0"?><container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles><rootfile full-path="content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+        // 缺失 mimetype，opf 路径错误，3.xhtml 和 style.css 不存在
+        let data = zip(&[
+            ("META-INF/container.xml", container),
+            ("OEBPS/content.opf", OPF),
+            ("OEBPS/toc.ncx", NCX),
+            ("OEBPS/1.xhtml", c1.as_str()),
+            ("OEBPS/2.xhtml", c2.as_str()),
+        ]);
+
+        assert!(read_from_vec(data.clone()).is_err());
+
+        let (mut book, repairs) = read_from_vec_lenient(data).unwrap();
+        assert_eq!(
+            vec![
+                Repair::Mimetype,
+                Repair::OpfLocation {
+                    from: Some("content.opf".to_string()),
+                    to: "OEBPS/content.opf".to_string()
+                },
+                Repair::MissingFile("3.xhtml".to_string()),
+                Repair::MissingFile("style.css".to_string()),
+                Repair::MissingNavTarget {
+                    title: "三".to_string(),
+                    file: "3.xhtml".to_string()
+                },
+            ],
+            repairs
+        );
+        assert_eq!("书名", book.title());
+        assert_eq!(2, book.chapters().len());
+        // 下级目录上移一级
+        let nav: Vec<&str> = book.nav().map(|f| f.title()).collect();
+        assert_eq!(vec!["一", "二"], nav);
+        assert_eq!("二", book.get_chapter("2.xhtml").unwrap().title());
+        assert!(String::from_utf8(
+            book.get_chapter_mut("2.xhtml")
+                .unwrap()
+                .data_mut()
+                .unwrap()
+                .to_vec()
+        )
+        .unwrap()
+        .contains("<p>二</p>"));
+        assert!(book.get_assets("style.css").is_none());
+
+        let mut book = read_from_vec(EpubWriter::write_to_mem(&mut book, false).unwrap()).unwrap();
+        assert_eq!(2, book.nav().len());
+        assert!(book
+            .get_chapter_mut("1.xhtml")
+            .unwrap()
+            .data_mut()
+            .is_some());
+    }
+
+    #[test]
+    fn test_read_lenient_synthesize_nav() {
+        let (c1, c2, c3) = (html("一"), html(""), html("三"));
+        // 没有 container.xml 和目录文件
+        let data = zip(&[
+            ("mimetype", "application/epub+zip"),
+            ("content.opf", OPF.replace("style.css", "1.xhtml").as_str()),
+            ("1.xhtml", c1.as_str()),
+            ("2.xhtml", c2.as_str()),
+            ("3.xhtml", c3.as_str()),
+        ]);
+        let (book, repairs) = read_from_vec_lenient(data).unwrap();
+        assert_eq!(
+            vec![
+                Repair::OpfLocation {
+                    from: None,
+                    to: "content.opf".to_string()
+                },
+                Repair::MissingFile("toc.ncx".to_string()),
+                Repair::SynthesizedNav(3),
+            ],
+            repairs
+        );
+        let nav: Vec<(&str, &str)> = book.nav().map(|f| (f.title(), f.file_name())).collect();
+        assert_eq!(
+            vec![("一", "1.xhtml"), ("2", "2.xhtml"), ("三", "3.xhtml")],
+            nav
+        );
+        assert_eq!("三", book.get_chapter("3.xhtml").unwrap().title());
+
+        // 完好的书不需要修复
+        let data = EpubBuilder::new()
+            .with_title("书名")
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("ok")
+                    .with_file_name("0.xhtml")
+                    .with_data("html".as_bytes().to_vec()),
+            )
+            .mem()
+            .unwrap();
+        assert!(read_from_vec_lenient(data).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn test_read_error_context() {
+        let container = r#"<?xml version="1.0"?><container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+        let data = zip(&[
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", container),
+            ("OEBPS/content.opf", "<package><metadata></package>"),
+        ]);
+        let err = read_from_vec(data).err().unwrap();
+        let context = err.context().unwrap();
+        assert_eq!(Some("read opf"), context.operation());
+        assert_eq!(Some("OEBPS/content.opf"), context.entry());
+        assert!(context.position().is_some());
+        assert!(matches!(err.kind(), IError::Xml(_)));
+
+        let data = zip(&[
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", container),
+        ]);
+        let err = read_from_vec(data).err().unwrap();
+        assert!(matches!(err, IError::MissingEntry(ref v) if v == "OEBPS/content.opf"));
+        assert_eq!("missing entry: OEBPS/content.opf", err.to_string());
+    }
+
+    #[test]
+    fn test_read_no_panic() {
+        let container = r#"<?xml version="1.0"?><container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+        let (c1, c2) = (html("一"), html("二"));
+        let files = [
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", container),
+            ("OEBPS/content.opf", OPF),
+            ("OEBPS/toc.ncx", NCX),
+            ("OEBPS/1.xhtml", c1.as_str()),
+            ("OEBPS/2.xhtml", c2.as_str()),
+        ];
+
+        // 在任意位置截断其中一个文件，读取和写入只能返回错误，不能panic
+        for (index, (_, content)) in files.iter().enumerate() {
+            for (cut, _) in content.char_indices() {
+                let mut f = files.to_vec();
+                f[index].1 = &content[..cut];
+                let data = zip(&f);
+                if let Ok(mut book) = read_from_vec(data.clone()) {
+                    let _ = EpubWriter::write_to_mem(&mut book, true);
+                }
+                if let Ok((mut book, _)) = read_from_vec_lenient(data) {
+                    let _ = EpubWriter::write_to_mem(&mut book, true);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_no_oebps_prefix_path() {
         use crate::common::tests::download_zip_file;
@@ -0,0 +1,1002 @@
+//!
+//! epub 校验
+//!
+//! 类似 epubcheck，检查压缩包结构、opf、目录以及 xhtml 格式，返回带有级别、代码、文件和行号的诊断信息
+//!
+//! | 代码 | 说明 |
+//! | --- | --- |
+//! | PKG-001 | mimetype 不存在 |
+//! | PKG-002 | mimetype 不是压缩包中的第一个文件 |
+//! | PKG-003 | mimetype 被压缩 |
+//! | PKG-004 | mimetype 内容错误 |
+//! | PKG-005 | META-INF/container.xml 不存在 |
+//! | PKG-006 | container.xml 中没有 rootfile |
+//! | PKG-007 | rootfile 指向的 opf 不存在 |
+//! | OPF-001 | manifest 中的文件不存在 |
+//! | OPF-002 | 文件没有在 manifest 中声明 |
+//! | OPF-003 | spine 引用了 manifest 中不存在的 id |
+//! | OPF-004 | manifest 中的 id 重复 |
+//! | OPF-005 | manifest 中的文件没有 media-type |
+//! | OPF-006 | 无法识别的文件类型 |
+//! | OPF-007 | 缺少必需的元数据 |
+//! | OPF-008 | unique-identifier 没有对应的 dc:identifier |
+//! | OPF-009 | spine 为空 |
+//! | OPF-010 | 缺少导航文件 |
+//! | NAV-001 | 目录指向的文件不存在 |
+//! | NAV-002 | 目录指向的锚点不存在 |
+//! | XML-001 | xml/xhtml 格式错误 |
+//! | XML-002 | 文档中的 id 重复 |
+//!
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{Read, Seek},
+    path::Path,
+};
+
+use quick_xml::events::{BytesStart, Event};
+
+use super::layout::{is_external, normalize, parent, split_fragment};
+use crate::{common::get_media_type, prelude::*};
+
+///
+/// 诊断级别
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// 错误，阅读器可能无法打开
+    Error,
+    /// 警告，不影响阅读但是不符合规范
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+///
+/// 一条诊断信息
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    severity: Severity,
+    code: &'static str,
+    /// 压缩包内的文件路径，mobi 中为 record 序号
+    file: Option<String>,
+    /// 从1开始的行号
+    line: Option<usize>,
+    message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(
+        severity: Severity,
+        code: &'static str,
+        file: Option<String>,
+        line: Option<usize>,
+        message: String,
+    ) -> Self {
+        Diagnostic {
+            severity,
+            code,
+            file,
+            line,
+            message,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// 诊断代码，例如 PKG-001，参考模块文档
+    pub fn code(&self) -> &str {
+        self.code
+    }
+
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// 转为json对象
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"severity":"{}","code":"{}","file":{},"line":{},"message":{}}}"#,
+            self.severity,
+            self.code,
+            self.file
+                .as_deref()
+                .map_or_else(|| "null".to_string(), json_string),
+            self.line
+                .map_or_else(|| "null".to_string(), |v| v.to_string()),
+            json_string(&self.message)
+        )
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.severity, self.code)?;
+        if let Some(file) = &self.file {
+            write!(f, " {}", file)?;
+            if let Some(line) = self.line {
+                write!(f, ":{}", line)?;
+            }
+        }
+        write!(f, " {}", self.message)
+    }
+}
+
+/// 将诊断信息转为json数组
+pub fn to_json(diagnostics: &[Diagnostic]) -> String {
+    let items: Vec<String> = diagnostics.iter().map(|v| v.to_json()).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// 是否没有错误级别的诊断
+pub fn is_valid(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().all(|v| v.severity != Severity::Error)
+}
+
+pub(crate) fn json_string(v: &str) -> String {
+    let mut out = String::with_capacity(v.len() + 2);
+    out.push('"');
+    for c in v.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+///
+/// 校验 epub 文件
+///
+/// # Returns
+///
+/// 不是zip文件时返回错误，其他问题都以诊断信息的形式返回
+///
+pub fn validate_file<P: AsRef<Path>>(file: P) -> IResult<Vec<Diagnostic>> {
+    validate_reader(File::open(file)?)
+}
+
+/// 校验内存中的 epub
+pub fn validate_vec(data: Vec<u8>) -> IResult<Vec<Diagnostic>> {
+    validate_reader(std::io::Cursor::new(data))
+}
+
+/// 校验 epub
+pub fn validate_reader<T: Read + Seek>(reader: T) -> IResult<Vec<Diagnostic>> {
+    let zip = zip::ZipArchive::new(reader)?;
+    let entries = zip.file_names().map(String::from).collect();
+    let mut v = Validator {
+        zip,
+        entries,
+        diagnostics: Vec::new(),
+    };
+    v.run();
+    Ok(v.diagnostics)
+}
+
+///
+/// 校验 book
+///
+/// 按照 [EpubWriter] 的流程在内存中生成 epub 后再校验，文件和行号对应生成的文件
+///
+pub fn validate_book(book: &mut EpubBook) -> IResult<Vec<Diagnostic>> {
+    validate_vec(EpubWriter::write_to_mem(book, false)?)
+}
+
+/// 解析后的 xml 元素
+struct Element {
+    /// 带前缀的名称，例如 dc:title
+    name: String,
+    attrs: Vec<(String, String)>,
+    /// 直接包含的文本
+    text: String,
+    line: usize,
+}
+
+impl Element {
+    /// 去掉前缀的名称
+    fn local_name(&self) -> &str {
+        self.name.rsplit(':').next().unwrap_or_default()
+    }
+
+    fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// 行号计算
+struct Lines(Vec<usize>);
+
+impl Lines {
+    fn new(content: &str) -> Self {
+        Lines(
+            content
+                .bytes()
+                .enumerate()
+                .filter(|(_, b)| *b == b'\n')
+                .map(|(i, _)| i)
+                .collect(),
+        )
+    }
+
+    /// 字节偏移对应的行号
+    fn line(&self, offset: usize) -> usize {
+        match self.0.binary_search(&offset) {
+            Ok(v) | Err(v) => v + 1,
+        }
+    }
+}
+
+fn read_element(e: &BytesStart, line: usize) -> Result<Element, String> {
+    let mut attrs = Vec::new();
+    for attr in e.attributes() {
+        let attr = attr.map_err(|e| e.to_string())?;
+        attrs.push((
+            String::from_utf8_lossy(attr.key.as_ref()).to_string(),
+            attr.unescape_value()
+                .map_err(|e| e.to_string())?
+                .to_string(),
+        ));
+    }
+    Ok(Element {
+        name: String::from_utf8_lossy(e.name().as_ref()).to_string(),
+        attrs,
+        text: String::new(),
+        line,
+    })
+}
+
+///
+/// 解析 xml，要求格式正确
+///
+/// # Returns
+///
+/// 所有元素，或者错误所在行号以及原因
+///
+fn parse_xml(content: &str) -> Result<Vec<Element>, (usize, String)> {
+    let lines = Lines::new(content);
+    let mut reader = quick_xml::reader::Reader::from_str(content);
+    let mut elements: Vec<Element> = Vec::new();
+    let mut stack: Vec<usize> = Vec::new();
+    loop {
+        let line = lines.line(reader.buffer_position() as usize);
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                elements.push(read_element(&e, line).map_err(|e| (line, e))?);
+                stack.push(elements.len() - 1);
+            }
+            Ok(Event::Empty(e)) => {
+                elements.push(read_element(&e, line).map_err(|e| (line, e))?);
+            }
+            Ok(Event::End(_)) => {
+                stack.pop();
+            }
+            Ok(Event::Text(e)) => {
+                let text = e.unescape().map_err(|e| (line, e.to_string()))?;
+                match stack.last() {
+                    Some(index) => elements[*index].text.push_str(&text),
+                    None if text.trim().is_empty() => {}
+                    None => return Err((line, "text outside of root element".to_string())),
+                }
+            }
+            Ok(Event::CData(e)) => {
+                if let Some(index) = stack.last() {
+                    elements[*index]
+                        .text
+                        .push_str(&String::from_utf8_lossy(e.as_ref()));
+                }
+            }
+            Ok(Event::Eof) => {
+                if let Some(index) = stack.last() {
+                    let e = &elements[*index];
+                    return Err((e.line, format!("element <{}> is not closed", e.name)));
+                }
+                break;
+            }
+            Err(e) => {
+                return Err((lines.line(reader.error_position() as usize), e.to_string()));
+            }
+            _ => {}
+        }
+    }
+    if elements.is_empty() {
+        return Err((1, "no root element".to_string()));
+    }
+    Ok(elements)
+}
+
+/// 解码 href 中的 %XX
+fn decode_href(href: &str) -> String {
+    let bytes = href.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(v) = href
+                .get(i + 1..i + 3)
+                .and_then(|v| u8::from_str_radix(v, 16).ok())
+            {
+                out.push(v);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// manifest 中的一项
+struct Item {
+    id: String,
+    path: String,
+    media_type: String,
+    properties: String,
+}
+
+struct Validator<T: Read + Seek> {
+    zip: zip::ZipArchive<T>,
+    /// 压缩包内所有文件
+    entries: HashSet<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<T: Read + Seek> Validator<T> {
+    fn push(
+        &mut self,
+        severity: Severity,
+        code: &'static str,
+        file: Option<&str>,
+        line: Option<usize>,
+        message: String,
+    ) {
+        self.diagnostics.push(Diagnostic::new(
+            severity,
+            code,
+            file.map(String::from),
+            line,
+            message,
+        ));
+    }
+
+    fn error(&mut self, code: &'static str, file: &str, line: Option<usize>, message: String) {
+        self.push(Severity::Error, code, Some(file), line, message);
+    }
+
+    fn warning(&mut self, code: &'static str, file: &str, line: Option<usize>, message: String) {
+        self.push(Severity::Warning, code, Some(file), line, message);
+    }
+
+    /// 读取文本文件
+    fn read(&mut self, name: &str) -> Option<String> {
+        let mut data = Vec::new();
+        self.zip.by_name(name).ok()?.read_to_end(&mut data).ok()?;
+        match String::from_utf8(data) {
+            Ok(v) => Some(v.trim_start_matches('\u{FEFF}').to_string()),
+            Err(_) => {
+                self.error("XML-001", name, None, "file is not valid utf-8".to_string());
+                None
+            }
+        }
+    }
+
+    /// 读取并解析 xml，格式错误时记录诊断
+    fn read_xml(&mut self, name: &str) -> Option<Vec<Element>> {
+        let content = self.read(name)?;
+        match parse_xml(&content) {
+            Ok(v) => Some(v),
+            Err((line, message)) => {
+                self.error("XML-001", name, Some(line), message);
+                None
+            }
+        }
+    }
+
+    fn run(&mut self) {
+        self.check_mimetype();
+        let Some(opf) = self.check_container() else {
+            return;
+        };
+        self.check_opf(&opf);
+    }
+
+    fn check_mimetype(&mut self) {
+        let Some(index) = self.zip.index_for_name("mimetype") else {
+            self.push(
+                Severity::Error,
+                "PKG-001",
+                None,
+                None,
+                "mimetype file is missing".to_string(),
+            );
+            return;
+        };
+        let (first, stored) = match self.zip.by_index_raw(index) {
+            Ok(f) => (
+                f.header_start() == 0,
+                f.compression() == zip::CompressionMethod::Stored,
+            ),
+            Err(_) => (true, true),
+        };
+        if !first {
+            self.error(
+                "PKG-002",
+                "mimetype",
+                None,
+                "mimetype must be the first file in the archive".to_string(),
+            );
+        }
+        if !stored {
+            self.error(
+                "PKG-003",
+                "mimetype",
+                None,
+                "mimetype must not be compressed".to_string(),
+            );
+        }
+        let mut content = Vec::new();
+        if let Ok(mut f) = self.zip.by_index(index) {
+            let _ = f.read_to_end(&mut content);
+        }
+        if content != b"application/epub+zip" {
+            self.error(
+                "PKG-004",
+                "mimetype",
+                None,
+                format!(
+                    "mimetype content must be application/epub+zip, found {:?}",
+                    String::from_utf8_lossy(&content)
+                ),
+            );
+        }
+    }
+
+    /// 检查 container.xml，返回 opf 路径
+    fn check_container(&mut self) -> Option<String> {
+        let container = "META-INF/container.xml";
+        if !self.entries.contains(container) {
+            self.error(
+                "PKG-005",
+                container,
+                None,
+                "container.xml is missing".to_string(),
+            );
+            return None;
+        }
+        let elements = self.read_xml(container)?;
+        let rootfiles: Vec<&Element> = elements
+            .iter()
+            .filter(|e| e.local_name() == "rootfile" && e.attr("full-path").is_some())
+            .collect();
+        let Some(rootfile) = rootfiles
+            .iter()
+            .find(|e| e.attr("media-type") == Some("application/oebps-package+xml"))
+            .or(rootfiles.first())
+        else {
+            self.error(
+                "PKG-006",
+                container,
+                None,
+                "container.xml has no rootfile".to_string(),
+            );
+            return None;
+        };
+        let path = rootfile.attr("full-path").unwrap_or_default().to_string();
+        let line = rootfile.line;
+        if !self.entries.contains(&path) {
+            self.error(
+                "PKG-007",
+                container,
+                Some(line),
+                format!("package document {} is missing", path),
+            );
+            return None;
+        }
+        Some(path)
+    }
+
+    fn check_opf(&mut self, opf: &str) {
+        let Some(elements) = self.read_xml(opf) else {
+            return;
+        };
+        let dir = parent(opf);
+        let package = elements.iter().find(|e| e.local_name() == "package");
+        let version = package
+            .and_then(|e| e.attr("version"))
+            .unwrap_or_default()
+            .to_string();
+        let epub3 = version.starts_with('3');
+
+        self.check_metadata(opf, &elements, epub3);
+
+        // manifest
+        let mut items: Vec<Item> = Vec::new();
+        let mut ids: HashSet<String> = HashSet::new();
+        for e in elements.iter().filter(|e| e.local_name() == "item") {
+            let id = e.attr("id").unwrap_or_default().to_string();
+            if !ids.insert(id.clone()) {
+                self.error(
+                    "OPF-004",
+                    opf,
+                    Some(e.line),
+                    format!("duplicate manifest id {}", id),
+                );
+            }
+            let href = e.attr("href").unwrap_or_default();
+            if href.is_empty() {
+                self.error(
+                    "OPF-001",
+                    opf,
+                    Some(e.line),
+                    format!("manifest item {} has no href", id),
+                );
+                continue;
+            }
+            let media_type = e.attr("media-type").unwrap_or_default().to_string();
+            if media_type.is_empty() {
+                self.error(
+                    "OPF-005",
+                    opf,
+                    Some(e.line),
+                    format!("manifest item {} has no media-type", href),
+                );
+            }
+            if get_media_type(href).is_empty() {
+                self.warning(
+                    "OPF-006",
+                    opf,
+                    Some(e.line),
+                    format!("unknown media type of {}", href),
+                );
+            }
+            if href.contains("://") {
+                // 远程资源
+                continue;
+            }
+            let path = normalize(dir, &decode_href(href));
+            if !self.entries.contains(&path) {
+                self.error(
+                    "OPF-001",
+                    opf,
+                    Some(e.line),
+                    format!("manifest item {} is missing in archive", path),
+                );
+            }
+            items.push(Item {
+                id,
+                path,
+                media_type,
+                properties: e.attr("properties").unwrap_or_default().to_string(),
+            });
+        }
+
+        let declared: HashSet<&str> = items.iter().map(|v| v.path.as_str()).collect();
+        let mut undeclared: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|v| {
+                !v.ends_with('/')
+                    && v.as_str() != "mimetype"
+                    && v.as_str() != opf
+                    && !v.starts_with("META-INF/")
+                    && !declared.contains(v.as_str())
+            })
+            .cloned()
+            .collect();
+        undeclared.sort();
+        for ele in undeclared {
+            self.warning(
+                "OPF-002",
+                &ele,
+                None,
+                "file is not declared in manifest".to_string(),
+            );
+        }
+
+        // spine
+        let itemrefs: Vec<&Element> = elements
+            .iter()
+            .filter(|e| e.local_name() == "itemref")
+            .collect();
+        if itemrefs.is_empty() {
+            self.error("OPF-009", opf, None, "spine is empty".to_string());
+        }
+        for e in itemrefs {
+            let idref = e.attr("idref").unwrap_or_default();
+            if !ids.contains(idref) {
+                self.error(
+                    "OPF-003",
+                    opf,
+                    Some(e.line),
+                    format!("spine references unknown id {}", idref),
+                );
+            }
+        }
+
+        // 导航
+        let nav = items
+            .iter()
+            .find(|v| v.properties.split_whitespace().any(|p| p == "nav"))
+            .map(|v| v.path.clone());
+        let toc = elements
+            .iter()
+            .find(|e| e.local_name() == "spine")
+            .and_then(|e| e.attr("toc"))
+            .and_then(|toc| items.iter().find(|v| v.id == toc))
+            .or_else(|| {
+                items
+                    .iter()
+                    .find(|v| v.media_type == "application/x-dtbncx+xml")
+            })
+            .map(|v| v.path.clone());
+        if epub3 && nav.is_none() {
+            self.error(
+                "OPF-010",
+                opf,
+                None,
+                "epub 3 requires a nav document".to_string(),
+            );
+        } else if !epub3 && toc.is_none() {
+            self.error("OPF-010", opf, None, "epub 2 requires a ncx".to_string());
+        }
+
+        // xhtml
+        let mut docs: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut navs: Vec<(String, Vec<Element>)> = Vec::new();
+        for item in &items {
+            let is_xml = matches!(
+                item.media_type.as_str(),
+                "application/xhtml+xml" | "application/x-dtbncx+xml" | "image/svg+xml"
+            );
+            if !is_xml || !self.entries.contains(&item.path) {
+                continue;
+            }
+            let Some(elements) = self.read_xml(&item.path) else {
+                continue;
+            };
+            let mut doc_ids = HashSet::new();
+            for e in &elements {
+                for (k, v) in &e.attrs {
+                    if (k == "id" || k == "xml:id") && !doc_ids.insert(v.clone()) {
+                        self.error(
+                            "XML-002",
+                            &item.path,
+                            Some(e.line),
+                            format!("duplicate id {}", v),
+                        );
+                    }
+                }
+            }
+            docs.insert(item.path.clone(), doc_ids);
+            if Some(&item.path) == nav.as_ref() || Some(&item.path) == toc.as_ref() {
+                navs.push((item.path.clone(), elements));
+            }
+        }
+
+        for (path, elements) in navs {
+            self.check_nav(&path, &elements, &docs);
+        }
+    }
+
+    fn check_metadata(&mut self, opf: &str, elements: &[Element], epub3: bool) {
+        let has = |name: &str| {
+            elements.iter().any(|e| {
+                e.name.starts_with("dc:") && e.local_name() == name && !e.text.trim().is_empty()
+            })
+        };
+        for name in ["identifier", "title", "language"] {
+            if !has(name) {
+                self.error(
+                    "OPF-007",
+                    opf,
+                    None,
+                    format!("missing required metadata dc:{}", name),
+                );
+            }
+        }
+        if epub3
+            && !elements.iter().any(|e| {
+                e.local_name() == "meta"
+                    && e.attr("property") == Some("dcterms:modified")
+                    && !e.text.trim().is_empty()
+            })
+        {
+            self.error(
+                "OPF-007",
+                opf,
+                None,
+                "missing required metadata dcterms:modified".to_string(),
+            );
+        }
+
+        let package = elements.iter().find(|e| e.local_name() == "package");
+        let unique = package
+            .and_then(|e| e.attr("unique-identifier"))
+            .unwrap_or_default();
+        if !elements
+            .iter()
+            .any(|e| e.local_name() == "identifier" && e.attr("id") == Some(unique))
+        {
+            self.error(
+                "OPF-008",
+                opf,
+                package.map(|e| e.line),
+                format!("unique-identifier {} has no matching dc:identifier", unique),
+            );
+        }
+    }
+
+    /// 检查 nav.xhtml 或者 toc.ncx 中的链接
+    fn check_nav(
+        &mut self,
+        path: &str,
+        elements: &[Element],
+        docs: &HashMap<String, HashSet<String>>,
+    ) {
+        let dir = parent(path);
+        for e in elements {
+            let href = match e.local_name() {
+                "a" => e.attr("href"),
+                "content" => e.attr("src"),
+                _ => None,
+            };
+            let Some(href) = href else {
+                continue;
+            };
+            let href = decode_href(href);
+            let (file, fragment) = split_fragment(&href);
+            if is_external(file) {
+                continue;
+            }
+            let target = if file.is_empty() {
+                path.to_string()
+            } else {
+                normalize(dir, file)
+            };
+            if !self.entries.contains(&target) {
+                self.error(
+                    "NAV-001",
+                    path,
+                    Some(e.line),
+                    format!("navigation target {} is missing", target),
+                );
+                continue;
+            }
+            let fragment = fragment.trim_start_matches('#');
+            if fragment.is_empty() {
+                continue;
+            }
+            if let Some(ids) = docs.get(&target) {
+                if !ids.contains(fragment) {
+                    self.error(
+                        "NAV-002",
+                        path,
+                        Some(e.line),
+                        format!("fragment #{} is not found in {}", fragment, target),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// 按顺序写入文件生成压缩包
+    fn zip(files: &[(&str, &str)], compress_mimetype: bool) -> Vec<u8> {
+        let mut v = std::io::Cursor::new(Vec::new());
+        let mut w = zip::ZipWriter::new(&mut v);
+        for (name, content) in files {
+            let method = if *name == "mimetype" && !compress_mimetype {
+                zip::CompressionMethod::Stored
+            } else {
+                zip::CompressionMethod::Deflated
+            };
+            w.start_file(
+                *name,
+                zip::write::SimpleFileOptions::default().compression_method(method),
+            )
+            .unwrap();
+            w.write_all(content.as_bytes()).unwrap();
+        }
+        w.finish().unwrap();
+        v.into_inner()
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().map(|v| v.code()).collect()
+    }
+
+    static CONTAINER: &str = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+    static OPF: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="id">urn:uuid:1</dc:identifier>
+    <dc:title>书名</dc:title>
+    <dc:language>zh</dc:language>
+    <meta property="dcterms:modified">2024-01-01T00:00:00Z</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="c1" href="text/1%20a.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="c1"/>
+  </spine>
+</package>"#;
+
+    static NAV: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<body>
+<nav epub:type="toc"><ol>
+<li><a href="text/1%20a.xhtml#s1">1</a></li>
+</ol></nav>
+</body>
+</html>"#;
+
+    static CHAPTER: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<body>
+<p id="s1">1</p>
+</body>
+</html>"#;
+
+    fn valid() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", CONTAINER),
+            ("OEBPS/content.opf", OPF),
+            ("OEBPS/nav.xhtml", NAV),
+            ("OEBPS/text/1 a.xhtml", CHAPTER),
+        ]
+    }
+
+    #[test]
+    fn test_valid() {
+        let d = validate_vec(zip(&valid(), false)).unwrap();
+        assert!(d.is_empty(), "{:?}", d);
+        assert!(is_valid(&d));
+    }
+
+    #[test]
+    fn test_package() {
+        let mut files = valid();
+        files.swap(0, 1);
+        let d = validate_vec(zip(&files, true)).unwrap();
+        assert_eq!(vec!["PKG-002", "PKG-003"], codes(&d));
+
+        let mut files = valid();
+        files[0].1 = "application/zip";
+        files.remove(2);
+        let d = validate_vec(zip(&files, false)).unwrap();
+        assert_eq!(vec!["PKG-004", "PKG-007"], codes(&d));
+        assert_eq!(Some(4), d[1].line());
+
+        assert!(validate_vec(b"not a zip".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_opf() {
+        let opf = OPF
+            .replace("<dc:language>zh</dc:language>", "")
+            .replace(r#"id="id">"#, r#"id="x">"#)
+            .replace(r#"idref="c1""#, r#"idref="c2""#)
+            .replace(
+                r#"<item id="c1""#,
+                r#"<item id="c1" href="a.bin"/><item id="c1""#,
+            );
+        let mut files = valid();
+        files[2].1 = &opf;
+        files.push(("OEBPS/extra.css", ""));
+        let d = validate_vec(zip(&files, false)).unwrap();
+        assert_eq!(
+            vec![
+                "OPF-007", "OPF-008", "OPF-005", "OPF-006", "OPF-001", "OPF-004", "OPF-002",
+                "OPF-003"
+            ],
+            codes(&d)
+        );
+        assert_eq!(Some("OEBPS/extra.css"), d[6].file());
+        assert_eq!(Severity::Warning, d[6].severity());
+        assert_eq!(Some(11), d[5].line());
+    }
+
+    #[test]
+    fn test_xhtml() {
+        let nav = NAV.replace("#s1", "#s2").replace("1%20a", "2");
+        let chapter = CHAPTER.replace("<p id=\"s1\">1</p>", "<p id=\"a\"/><p id=\"a\"/>");
+        let mut files = valid();
+        files[3].1 = &nav;
+        files[4].1 = &chapter;
+        let d = validate_vec(zip(&files, false)).unwrap();
+        assert_eq!(vec!["XML-002", "NAV-001"], codes(&d));
+        assert_eq!(Some(4), d[0].line());
+        assert_eq!(Some(5), d[1].line());
+
+        let nav = NAV.replace("#s1", "#s2");
+        let mut files = valid();
+        files[3].1 = &nav;
+        let d = validate_vec(zip(&files, false)).unwrap();
+        assert_eq!(vec!["NAV-002"], codes(&d));
+
+        // 格式错误的文件不再检查锚点
+        let chapter = CHAPTER.replace("</body>", "<br></body>");
+        files[4].1 = &chapter;
+        let d = validate_vec(zip(&files, false)).unwrap();
+        assert_eq!(vec!["XML-001"], codes(&d));
+        assert_eq!(Some("OEBPS/text/1 a.xhtml"), d[0].file());
+        assert_eq!(Some(5), d[0].line());
+    }
+
+    #[test]
+    fn test_json() {
+        let d = Diagnostic {
+            severity: Severity::Error,
+            code: "XML-001",
+            file: Some("a\"b.xhtml".to_string()),
+            line: None,
+            message: "line\n\u{1}".to_string(),
+        };
+        assert_eq!(
+            r#"[{"severity":"error","code":"XML-001","file":"a\"b.xhtml","line":null,"message":"line\n\u0001"}]"#,
+            to_json(&[d.clone()])
+        );
+        assert_eq!("error XML-001 a\"b.xhtml line\n\u{1}", d.to_string());
+    }
+
+    #[test]
+    fn test_validate_book() {
+        let mut book = EpubBuilder::default()
+            .with_title("书名")
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("章节")
+                    .with_file_name("0.xhtml")
+                    .with_data("<p>锻炼</p>".as_bytes().to_vec()),
+            )
+            .book()
+            .unwrap();
+        let d = validate_book(&mut book).unwrap();
+        assert!(d.is_empty(), "{:?}", d);
+        assert_eq!(Some("zh"), book.language());
+        assert!(book.identifier().starts_with("urn:uuid:"));
+    }
+}
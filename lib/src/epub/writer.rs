@@ -6,12 +6,16 @@ use std::{
 
 use zip::ZipWriter;
 
-use crate::prelude::*;
+use crate::{
+    common::{escape_xml, gen_uuid},
+    prelude::*,
+};
 
 use super::{
-    common,
+    common::DEFAULT_LANGUAGE,
     core::info,
-    html::{to_html, to_nav_html, to_opf, to_toc_xml},
+    html::{to_html_with_writing_mode, to_nav_html, to_opf, to_toc_xml},
+    layout::{EpubLayout, GeneratedFiles},
 };
 
 impl From<zip::result::ZipError> for IError {
@@ -22,7 +26,7 @@ impl From<zip::result::ZipError> for IError {
             zip::result::ZipError::UnsupportedArchive(v) => IError::UnsupportedArchive(v),
             zip::result::ZipError::InvalidPassword => IError::InvalidPassword,
             zip::result::ZipError::FileNotFound => IError::FileNotFound,
-            _ => IError::Unknown,
+            e => IError::Unsupported(std::borrow::Cow::from(e.to_string())),
         }
     }
 }
@@ -44,6 +48,12 @@ pub(crate) trait EpubWriterTrait {
 pub struct EpubWriter<T: Write + Seek> {
     pub(crate) inner: zip::ZipWriter<T>,
     pub(crate) append_title: bool,
+    /// 文件布局
+    pub(crate) layout: EpubLayout,
+    /// 规范化策略，为None时不处理章节内容
+    pub(crate) normalize: Option<NormalizePolicy>,
+    /// 规范化的修改记录
+    pub(crate) report: Vec<NormalizeReport>,
 }
 static CONTAINER_XML: &str = r#"<?xml version='1.0' encoding='utf-8'?>
 <container xmlns="urn:oasis:names:tc:opendocument:xmlns:container" version="1.0">
@@ -85,11 +95,85 @@ impl EpubWriter<std::io::Cursor<Vec<u8>>> {
     }
 }
 
+///
+/// 写入到异步writer
+///
+/// 先在内存中按 [EpubWriter] 的流程生成完整的epub，再一次性写出，所以不要求 writer 支持 seek
+///
+#[cfg(feature = "async")]
+pub struct AsyncEpubWriter<T> {
+    inner: T,
+    append_title: bool,
+    layout: EpubLayout,
+    normalize: Option<NormalizePolicy>,
+    report: Vec<NormalizeReport>,
+}
+
+#[cfg(feature = "async")]
+impl<T: tokio::io::AsyncWrite + Unpin> AsyncEpubWriter<T> {
+    pub fn new(inner: T) -> Self {
+        AsyncEpubWriter {
+            inner,
+            append_title: true,
+            layout: EpubLayout::default(),
+            normalize: None,
+            report: Vec::new(),
+        }
+    }
+
+    pub fn with_append_title(mut self, append_title: bool) -> Self {
+        self.append_title = append_title;
+        self
+    }
+
+    /// 设置文件布局
+    pub fn with_layout(mut self, layout: EpubLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// 写入前将章节内容规范化为 xhtml
+    pub fn with_normalize(mut self, policy: NormalizePolicy) -> Self {
+        self.normalize = Some(policy);
+        self
+    }
+
+    /// 最近一次写入时规范化的修改记录
+    pub fn normalize_report(&self) -> &[NormalizeReport] {
+        &self.report
+    }
+
+    pub async fn write(&mut self, book: &mut EpubBook) -> IResult<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut data = std::io::Cursor::new(Vec::new());
+        let mut writer = EpubWriter::new(&mut data)
+            .with_append_title(self.append_title)
+            .with_layout(self.layout.clone());
+        writer.normalize = self.normalize.clone();
+        writer.write(book)?;
+        self.report = std::mem::take(&mut writer.report);
+        drop(writer);
+        let data = data.into_inner();
+        self.inner.write_all(&data).await?;
+        self.inner.flush().await?;
+        Ok(())
+    }
+
+    /// 获取内部的writer
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
 impl<T: Write + Seek> EpubWriter<T> {
     pub fn new(inner: T) -> Self {
         EpubWriter {
             inner: ZipWriter::new(inner),
             append_title: true,
+            layout: EpubLayout::default(),
+            normalize: None,
+            report: Vec::new(),
         }
     }
 
@@ -98,32 +182,74 @@ impl<T: Write + Seek> EpubWriter<T> {
         self
     }
 
+    ///
+    /// 设置文件布局
+    ///
+    /// 写入时会按照布局修改 book 中章节、资源、目录的路径以及相互引用
+    ///
+    pub fn with_layout(mut self, layout: EpubLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    ///
+    /// 写入前将章节内容规范化为 xhtml
+    ///
+    /// 修改记录可以通过 [EpubWriter::normalize_report] 获取
+    ///
+    pub fn with_normalize(mut self, policy: NormalizePolicy) -> Self {
+        self.normalize = Some(policy);
+        self
+    }
+
+    /// 最近一次写入时规范化的修改记录
+    pub fn normalize_report(&self) -> &[NormalizeReport] {
+        &self.report
+    }
+
     pub fn write(&mut self, book: &mut EpubBook) -> IResult<()> {
-        self.write_base(book)?;
+        if let Some(policy) = &self.normalize {
+            self.report = book.normalize(policy);
+        }
+        self.layout.apply(book);
+        let files = GeneratedFiles::new(book);
+        self.write_base(book, &files)?;
         self.write_assets(book)?;
         self.write_chapters(book)?;
-        self.write_nav(book)?;
-        self.write_cover(book)?;
+        self.write_nav(book, &files)?;
+        self.write_cover(book, &files)?;
 
         Ok(())
     }
 
     /// 写入基础的文件
-    fn write_base(&mut self, book: &mut EpubBook) -> IResult<()> {
+    fn write_base(&mut self, book: &mut EpubBook, files: &GeneratedFiles) -> IResult<()> {
         if book.version().is_empty() {
-            book.set_version("2.0");
+            // 固定版式需要 epub3
+            book.set_version(if book.is_fixed_layout() { "3.0" } else { "2.0" });
+        }
+        // identifier 和 language 是必需的元数据
+        if book.identifier().is_empty() {
+            book.set_identifier(format!("urn:uuid:{}", gen_uuid()));
         }
+        if book.language().is_none() {
+            book.set_language(DEFAULT_LANGUAGE);
+        }
+        // mimetype 必须是第一个文件
+        self.write_file("mimetype", "application/epub+zip".as_bytes())?;
         self.write_file(
             "META-INF/container.xml",
-            CONTAINER_XML.replace("{opf}", common::OPF).as_bytes(),
+            CONTAINER_XML
+                .replace("{opf}", self.layout.entry(&files.opf).as_str())
+                .as_bytes(),
         )?;
-        self.write_file("mimetype", "application/epub+zip".as_bytes())?;
 
         self.write_file(
-            common::OPF,
+            self.layout.entry(&files.opf).as_str(),
             to_opf(
                 book,
                 format!("{}-{}", info::PROJECT_NAME, info::PKG_VERSION).as_str(),
+                files,
             )
             .as_bytes(),
         )?;
@@ -135,43 +261,50 @@ impl<T: Write + Seek> EpubWriter<T> {
     fn write_assets(&mut self, book: &mut EpubBook) -> IResult<()> {
         let m = book.assets_mut();
         for ele in m {
-            if ele.data_mut().is_none() {
+            if ele.is_generated() {
                 continue;
             }
-            self.write_file(
-                format!("{}{}", common::EPUB, ele.file_name()).as_str(),
-                ele.data_mut().unwrap(),
-            )?;
+            let file = self.layout.entry(ele.file_name());
+            if let Some(data) = ele.data_mut() {
+                self.write_file(file.as_str(), data)?;
+            }
         }
         Ok(())
     }
 
     /// 写入章节文件
     fn write_chapters(&mut self, book: &mut EpubBook) -> IResult<()> {
+        let writing_mode = book.writing_mode().map(String::from);
+        let language = book.language().map(String::from);
         let chap = book.chapters_mut();
         for ele in chap {
-            if ele.data_mut().is_none() {
+            if ele.is_generated() || ele.data_mut().is_none() {
                 continue;
             }
 
-            let html = to_html(ele, self.append_title);
-
-            self.write_file(
-                format!("{}{}", common::EPUB, ele.file_name()).as_str(),
-                html.as_bytes(),
+            let html = to_html_with_writing_mode(
+                ele,
+                self.append_title,
+                writing_mode.as_deref(),
+                language.as_deref(),
             )?;
+
+            self.write_file(self.layout.entry(ele.file_name()).as_str(), html.as_bytes())?;
         }
 
         Ok(())
     }
     /// 写入目录
-    fn write_nav(&mut self, book: &mut EpubBook) -> IResult<()> {
+    fn write_nav(&mut self, book: &mut EpubBook, files: &GeneratedFiles) -> IResult<()> {
         // 目录包括两部分，一是自定义的用于书本导航的html，二是epub规范里的toc.ncx文件
         self.write_file(
-            common::NAV,
-            to_nav_html(book.title(), book.nav()).as_bytes(),
+            self.layout.entry(&files.nav).as_str(),
+            to_nav_html(book.title(), book.nav(), book.language()).as_bytes(),
+        )?;
+        self.write_file(
+            self.layout.entry(&files.toc).as_str(),
+            to_toc_xml(book.title(), book.nav()).as_bytes(),
         )?;
-        self.write_file(common::TOC, to_toc_xml(book.title(), book.nav()).as_bytes())?;
 
         Ok(())
     }
@@ -181,21 +314,29 @@ impl<T: Write + Seek> EpubWriter<T> {
     ///
     /// 拷贝资源文件以及生成对应的xhtml文件
     ///
-    fn write_cover(&mut self, book: &mut EpubBook) -> IResult<()> {
+    fn write_cover(&mut self, book: &mut EpubBook, files: &GeneratedFiles) -> IResult<()> {
+        let language = book.language().map(String::from);
         if let Some(cover) = book.cover_mut() {
-            self.write_file(
-                format!("{}{}", common::EPUB, cover.file_name()).as_str(),
-                cover.data_mut().as_ref().unwrap(),
-            )?;
+            let file = self.layout.entry(cover.file_name());
+            let Some(data) = cover.data_mut() else {
+                return Err(IError::MissingEntry(cover.file_name().to_string()));
+            };
+            self.write_file(file.as_str(), data)?;
 
             let mut html = EpubHtml::default();
             html.set_data(
-                format!("<img src=\"{}\" alt=\"Cover\"/>", cover.file_name())
-                    .as_bytes()
-                    .to_vec(),
+                format!(
+                    "<img src=\"{}\" alt=\"Cover\"/>",
+                    escape_xml(cover.file_name())
+                )
+                .as_bytes()
+                .to_vec(),
             );
             html.set_title("Cover");
-            self.write_file(common::COVER, to_html(&mut html, false).as_bytes())?;
+            self.write_file(
+                self.layout.entry(&files.cover).as_str(),
+                to_html_with_writing_mode(&mut html, false, None, language.as_deref())?.as_bytes(),
+            )?;
         }
         Ok(())
     }
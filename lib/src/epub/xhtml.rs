@@ -0,0 +1,1344 @@
+//!
+//! html 规范化为 xhtml
+//!
+//! 抓取到的 html 经常存在未闭合的标签、大写标签、html 命名实体以及脚本等，
+//! 直接写入 epub 会导致文件不合法，这里将其转换为格式正确的 xhtml，并记录所有修改
+//!
+use crate::common::is_xml_char;
+
+/// 不需要结束标签的元素
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// 内容为纯文本的元素，内容中的 < 不会被当作标签
+const RAW_TEXT: &[&str] = &[
+    "script", "style", "xmp", "iframe", "noembed", "noframes", "noscript",
+];
+
+/// 内容为纯文本，但是会解析实体的元素
+const ESCAPABLE_RAW_TEXT: &[&str] = &["textarea", "title"];
+
+/// 开始时会自动闭合 p 的元素
+const CLOSE_P: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "center",
+    "dd",
+    "details",
+    "dialog",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "li",
+    "listing",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+    "xmp",
+];
+
+/// 查找需要闭合的 p 时的边界
+const BUTTON_SCOPE: &[&str] = &[
+    "applet", "button", "caption", "html", "marquee", "object", "table", "td", "template", "th",
+];
+
+const HEADING: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// 隐式闭合规则，(开始的元素，会被闭合的元素，查找边界)
+const IMPLIED: &[(&[&str], &[&str], &[&str])] = &[
+    (&["li"], &["li"], &["ul", "ol", "menu", "table", "td", "th"]),
+    (&["dt", "dd"], &["dt", "dd"], &["dl", "table", "td", "th"]),
+    (&["tr"], &["tr"], &["table", "thead", "tbody", "tfoot"]),
+    (&["td", "th"], &["td", "th"], &["tr", "table"]),
+    (
+        &["thead", "tbody", "tfoot"],
+        &["thead", "tbody", "tfoot"],
+        &["table"],
+    ),
+    (
+        &["option"],
+        &["option"],
+        &["select", "datalist", "optgroup"],
+    ),
+    (&["optgroup"], &["optgroup", "option"], &["select"]),
+    (&["rt", "rp"], &["rt", "rp"], &["ruby"]),
+];
+
+/// 默认移除的元素
+const STRIP_ELEMENTS: &[&str] = &[
+    "script", "noscript", "iframe", "frame", "frameset", "object", "embed", "applet", "base",
+    "meta",
+];
+
+/// xml 预定义的实体，保持原样
+const XML_ENTITIES: &[&str] = &["amp", "lt", "gt", "quot", "apos"];
+
+/// html4 命名实体，按名称排序
+static ENTITIES: &[(&str, u32)] = &[
+    ("AElig", 198),
+    ("Aacute", 193),
+    ("Acirc", 194),
+    ("Agrave", 192),
+    ("Alpha", 913),
+    ("Aring", 197),
+    ("Atilde", 195),
+    ("Auml", 196),
+    ("Beta", 914),
+    ("Ccedil", 199),
+    ("Chi", 935),
+    ("Dagger", 8225),
+    ("Delta", 916),
+    ("ETH", 208),
+    ("Eacute", 201),
+    ("Ecirc", 202),
+    ("Egrave", 200),
+    ("Epsilon", 917),
+    ("Eta", 919),
+    ("Euml", 203),
+    ("Gamma", 915),
+    ("Iacute", 205),
+    ("Icirc", 206),
+    ("Igrave", 204),
+    ("Iota", 921),
+    ("Iuml", 207),
+    ("Kappa", 922),
+    ("Lambda", 923),
+    ("Mu", 924),
+    ("Ntilde", 209),
+    ("Nu", 925),
+    ("OElig", 338),
+    ("Oacute", 211),
+    ("Ocirc", 212),
+    ("Ograve", 210),
+    ("Omega", 937),
+    ("Omicron", 927),
+    ("Oslash", 216),
+    ("Otilde", 213),
+    ("Ouml", 214),
+    ("Phi", 934),
+    ("Pi", 928),
+    ("Prime", 8243),
+    ("Psi", 936),
+    ("Rho", 929),
+    ("Scaron", 352),
+    ("Sigma", 931),
+    ("THORN", 222),
+    ("Tau", 932),
+    ("Theta", 920),
+    ("Uacute", 218),
+    ("Ucirc", 219),
+    ("Ugrave", 217),
+    ("Upsilon", 933),
+    ("Uuml", 220),
+    ("Xi", 926),
+    ("Yacute", 221),
+    ("Yuml", 376),
+    ("Zeta", 918),
+    ("aacute", 225),
+    ("acirc", 226),
+    ("acute", 180),
+    ("aelig", 230),
+    ("agrave", 224),
+    ("alefsym", 8501),
+    ("alpha", 945),
+    ("amp", 38),
+    ("and", 8743),
+    ("ang", 8736),
+    ("aring", 229),
+    ("asymp", 8776),
+    ("atilde", 227),
+    ("auml", 228),
+    ("bdquo", 8222),
+    ("beta", 946),
+    ("brvbar", 166),
+    ("bull", 8226),
+    ("cap", 8745),
+    ("ccedil", 231),
+    ("cedil", 184),
+    ("cent", 162),
+    ("chi", 967),
+    ("circ", 710),
+    ("clubs", 9827),
+    ("cong", 8773),
+    ("copy", 169),
+    ("crarr", 8629),
+    ("cup", 8746),
+    ("curren", 164),
+    ("dArr", 8659),
+    ("dagger", 8224),
+    ("darr", 8595),
+    ("deg", 176),
+    ("delta", 948),
+    ("diams", 9830),
+    ("divide", 247),
+    ("eacute", 233),
+    ("ecirc", 234),
+    ("egrave", 232),
+    ("empty", 8709),
+    ("emsp", 8195),
+    ("ensp", 8194),
+    ("epsilon", 949),
+    ("equiv", 8801),
+    ("eta", 951),
+    ("eth", 240),
+    ("euml", 235),
+    ("euro", 8364),
+    ("exist", 8707),
+    ("fnof", 402),
+    ("forall", 8704),
+    ("frac12", 189),
+    ("frac14", 188),
+    ("frac34", 190),
+    ("frasl", 8260),
+    ("gamma", 947),
+    ("ge", 8805),
+    ("gt", 62),
+    ("hArr", 8660),
+    ("harr", 8596),
+    ("hearts", 9829),
+    ("hellip", 8230),
+    ("iacute", 237),
+    ("icirc", 238),
+    ("iexcl", 161),
+    ("igrave", 236),
+    ("image", 8465),
+    ("infin", 8734),
+    ("int", 8747),
+    ("iota", 953),
+    ("iquest", 191),
+    ("isin", 8712),
+    ("iuml", 239),
+    ("kappa", 954),
+    ("lArr", 8656),
+    ("lambda", 955),
+    ("lang", 9001),
+    ("laquo", 171),
+    ("larr", 8592),
+    ("lceil", 8968),
+    ("ldquo", 8220),
+    ("le", 8804),
+    ("lfloor", 8970),
+    ("lowast", 8727),
+    ("loz", 9674),
+    ("lrm", 8206),
+    ("lsaquo", 8249),
+    ("lsquo", 8216),
+    ("lt", 60),
+    ("macr", 175),
+    ("mdash", 8212),
+    ("micro", 181),
+    ("middot", 183),
+    ("minus", 8722),
+    ("mu", 956),
+    ("nabla", 8711),
+    ("nbsp", 160),
+    ("ndash", 8211),
+    ("ne", 8800),
+    ("ni", 8715),
+    ("not", 172),
+    ("notin", 8713),
+    ("nsub", 8836),
+    ("ntilde", 241),
+    ("nu", 957),
+    ("oacute", 243),
+    ("ocirc", 244),
+    ("oelig", 339),
+    ("ograve", 242),
+    ("oline", 8254),
+    ("omega", 969),
+    ("omicron", 959),
+    ("oplus", 8853),
+    ("or", 8744),
+    ("ordf", 170),
+    ("ordm", 186),
+    ("oslash", 248),
+    ("otilde", 245),
+    ("otimes", 8855),
+    ("ouml", 246),
+    ("para", 182),
+    ("part", 8706),
+    ("permil", 8240),
+    ("perp", 8869),
+    ("phi", 966),
+    ("pi", 960),
+    ("piv", 982),
+    ("plusmn", 177),
+    ("pound", 163),
+    ("prime", 8242),
+    ("prod", 8719),
+    ("prop", 8733),
+    ("psi", 968),
+    ("quot", 34),
+    ("rArr", 8658),
+    ("radic", 8730),
+    ("rang", 9002),
+    ("raquo", 187),
+    ("rarr", 8594),
+    ("rceil", 8969),
+    ("rdquo", 8221),
+    ("real", 8476),
+    ("reg", 174),
+    ("rfloor", 8971),
+    ("rho", 961),
+    ("rlm", 8207),
+    ("rsaquo", 8250),
+    ("rsquo", 8217),
+    ("sbquo", 8218),
+    ("scaron", 353),
+    ("sdot", 8901),
+    ("sect", 167),
+    ("shy", 173),
+    ("sigma", 963),
+    ("sigmaf", 962),
+    ("sim", 8764),
+    ("spades", 9824),
+    ("sub", 8834),
+    ("sube", 8838),
+    ("sum", 8721),
+    ("sup", 8835),
+    ("sup1", 185),
+    ("sup2", 178),
+    ("sup3", 179),
+    ("supe", 8839),
+    ("szlig", 223),
+    ("tau", 964),
+    ("there4", 8756),
+    ("theta", 952),
+    ("thetasym", 977),
+    ("thinsp", 8201),
+    ("thorn", 254),
+    ("tilde", 732),
+    ("times", 215),
+    ("trade", 8482),
+    ("uArr", 8657),
+    ("uacute", 250),
+    ("uarr", 8593),
+    ("ucirc", 251),
+    ("ugrave", 249),
+    ("uml", 168),
+    ("upsih", 978),
+    ("upsilon", 965),
+    ("uuml", 252),
+    ("weierp", 8472),
+    ("xi", 958),
+    ("yacute", 253),
+    ("yen", 165),
+    ("yuml", 255),
+    ("zeta", 950),
+    ("zwj", 8205),
+    ("zwnj", 8204),
+];
+
+///
+/// 规范化时的一处修改
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NormalizeChange {
+    /// 标签名或者属性名转为小写
+    Lowercase(String),
+    /// 补全未闭合的标签
+    ClosedTag(String),
+    /// 移除没有对应开始标签的结束标签
+    RemovedEndTag(String),
+    /// html 命名实体转为数字实体，例如 &nbsp; 转为 &#160;
+    Entity(String),
+    /// 无法识别的实体，其中的 & 被转义
+    UnknownEntity(String),
+    /// 按策略移除的元素，包括元素内容
+    RemovedElement(String),
+    /// 按策略移除的属性，或者不合法、重复的属性
+    RemovedAttribute { element: String, attribute: String },
+    /// 属性值补全引号
+    QuotedAttribute(String),
+    /// 转义文本中的 < 或者 &
+    EscapedChar(char),
+    /// 移除 xml 不允许出现的字符
+    InvalidChar,
+    /// 移除注释、doctype 等无法保留的标记
+    RemovedMarkup(String),
+}
+
+///
+/// 单个章节的规范化结果
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NormalizeReport {
+    file_name: String,
+    changes: Vec<(NormalizeChange, usize)>,
+}
+
+impl NormalizeReport {
+    pub(crate) fn new<T: Into<String>>(
+        file_name: T,
+        changes: Vec<(NormalizeChange, usize)>,
+    ) -> Self {
+        NormalizeReport {
+            file_name: file_name.into(),
+            changes,
+        }
+    }
+
+    /// 章节文件名
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// 修改以及出现的次数，按首次出现的顺序排列
+    pub fn changes(&self) -> &[(NormalizeChange, usize)] {
+        &self.changes
+    }
+
+    /// 是否没有任何修改
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+///
+/// 规范化策略
+///
+/// 默认移除 script、iframe、object 等元素，以及 on* 事件属性和 javascript: 链接
+///
+/// # Examples
+///
+/// ```rust
+/// use iepub::prelude::*;
+///
+/// let policy = NormalizePolicy::default()
+///     .with_strip_element("form")
+///     .with_keep_element("noscript")
+///     .with_strip_attribute("style");
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizePolicy {
+    /// 需要移除的元素
+    elements: Vec<String>,
+    /// 需要移除的属性
+    attributes: Vec<String>,
+    /// 是否移除脚本相关的属性
+    scripts: bool,
+}
+
+impl Default for NormalizePolicy {
+    fn default() -> Self {
+        NormalizePolicy {
+            elements: STRIP_ELEMENTS.iter().map(|v| v.to_string()).collect(),
+            attributes: Vec::new(),
+            scripts: true,
+        }
+    }
+}
+
+impl NormalizePolicy {
+    /// 移除指定元素，包括元素内容
+    pub fn with_strip_element<T: Into<String>>(mut self, name: T) -> Self {
+        let name = name.into().to_lowercase();
+        if !self.elements.contains(&name) {
+            self.elements.push(name);
+        }
+        self
+    }
+
+    /// 保留指定元素
+    pub fn with_keep_element<T: AsRef<str>>(mut self, name: T) -> Self {
+        let name = name.as_ref().to_lowercase();
+        self.elements.retain(|v| v != &name);
+        self
+    }
+
+    /// 移除指定属性
+    pub fn with_strip_attribute<T: Into<String>>(mut self, name: T) -> Self {
+        let name = name.into().to_lowercase();
+        if !self.attributes.contains(&name) {
+            self.attributes.push(name);
+        }
+        self
+    }
+
+    /// 保留指定属性
+    pub fn with_keep_attribute<T: AsRef<str>>(mut self, name: T) -> Self {
+        let name = name.as_ref().to_lowercase();
+        self.attributes.retain(|v| v != &name);
+        self
+    }
+
+    /// 是否移除 on* 事件属性以及 javascript: 链接，默认true
+    pub fn with_strip_scripts(mut self, value: bool) -> Self {
+        self.scripts = value;
+        self
+    }
+
+    fn strip_element(&self, name: &str) -> bool {
+        self.elements.iter().any(|v| v.eq_ignore_ascii_case(name))
+    }
+
+    fn strip_attribute(&self, name: &str, value: &str) -> bool {
+        if self.attributes.iter().any(|v| v.eq_ignore_ascii_case(name)) {
+            return true;
+        }
+        self.scripts
+            && (name.to_ascii_lowercase().starts_with("on")
+                || value
+                    .trim_start()
+                    .get(..11)
+                    .is_some_and(|v| v.eq_ignore_ascii_case("javascript:")))
+    }
+}
+
+///
+/// 将 html 片段规范化为 xhtml
+///
+/// # Returns
+///
+/// 规范化后的文本以及修改记录
+///
+pub(crate) fn normalize_html(
+    html: &str,
+    policy: &NormalizePolicy,
+) -> (String, Vec<(NormalizeChange, usize)>) {
+    Normalizer {
+        src: html,
+        lower: html.to_ascii_lowercase(),
+        pos: 0,
+        policy,
+        out: String::with_capacity(html.len()),
+        stack: Vec::new(),
+        changes: Vec::new(),
+    }
+    .run()
+}
+
+struct Normalizer<'a> {
+    src: &'a str,
+    /// 小写后的内容，用于不区分大小写的查找，长度和 src 一致
+    lower: String,
+    pos: usize,
+    policy: &'a NormalizePolicy,
+    out: String,
+    /// 未闭合的元素
+    stack: Vec<String>,
+    changes: Vec<(NormalizeChange, usize)>,
+}
+
+/// 一个属性，值为None说明没有值
+type RawAttr<'a> = (&'a str, Option<&'a str>, bool);
+
+impl<'a> Normalizer<'a> {
+    fn run(mut self) -> (String, Vec<(NormalizeChange, usize)>) {
+        let src: &'a str = self.src;
+        while self.pos < src.len() {
+            let rest = &src[self.pos..];
+            let handled = if rest.starts_with("<!--") {
+                self.comment();
+                true
+            } else if rest.starts_with("<![CDATA[") {
+                self.cdata()
+            } else if rest.starts_with("<!") || rest.starts_with("<?") {
+                self.markup()
+            } else if rest.starts_with("</") {
+                self.end_tag()
+            } else if rest.starts_with('<') {
+                self.start_tag()
+            } else {
+                let end = rest.find('<').unwrap_or(rest.len());
+                self.text(&rest[..end], true, false);
+                self.pos += end;
+                true
+            };
+            if !handled {
+                // 不是标签的 <
+                self.change(NormalizeChange::EscapedChar('<'));
+                self.out.push_str("&lt;");
+                self.pos += 1;
+            }
+        }
+        while let Some(name) = self.stack.pop() {
+            self.change(NormalizeChange::ClosedTag(name.clone()));
+            self.push_end(&name);
+        }
+        (self.out, self.changes)
+    }
+
+    fn change(&mut self, change: NormalizeChange) {
+        match self.changes.iter_mut().find(|(v, _)| v == &change) {
+            Some((_, count)) => *count += 1,
+            None => self.changes.push((change, 1)),
+        }
+    }
+
+    fn push_end(&mut self, name: &str) {
+        self.out.push_str("</");
+        self.out.push_str(name);
+        self.out.push('>');
+    }
+
+    /// svg、math 内的标签和属性区分大小写
+    fn in_foreign(&self) -> bool {
+        self.stack.iter().any(|v| v == "svg" || v == "math")
+    }
+
+    fn comment(&mut self) {
+        let start = self.pos + 4;
+        match self.src[start..].find("-->") {
+            Some(end) => {
+                let content = &self.src[start..start + end];
+                self.pos = start + end + 3;
+                if content.contains("--")
+                    || content.ends_with('-')
+                    || !content.chars().all(is_xml_char)
+                {
+                    self.change(NormalizeChange::RemovedMarkup("comment".to_string()));
+                } else {
+                    self.out.push_str("<!--");
+                    self.out.push_str(content);
+                    self.out.push_str("-->");
+                }
+            }
+            None => {
+                self.change(NormalizeChange::RemovedMarkup("comment".to_string()));
+                self.pos = self.src.len();
+            }
+        }
+    }
+
+    fn cdata(&mut self) -> bool {
+        let start = self.pos + 9;
+        let Some(end) = self.src[start..].find("]]>") else {
+            return false;
+        };
+        let content = &self.src[start..start + end];
+        self.out.push_str("<![CDATA[");
+        for c in content.chars() {
+            if is_xml_char(c) {
+                self.out.push(c);
+            } else {
+                self.change(NormalizeChange::InvalidChar);
+            }
+        }
+        self.out.push_str("]]>");
+        self.pos = start + end + 3;
+        true
+    }
+
+    /// doctype、处理指令等，直接移除
+    fn markup(&mut self) -> bool {
+        let Some(end) = self.src[self.pos..].find('>') else {
+            return false;
+        };
+        let name = if self.lower[self.pos..].starts_with("<!doctype") {
+            "doctype"
+        } else if self.src[self.pos..].starts_with("<?") {
+            "processing-instruction"
+        } else {
+            "declaration"
+        };
+        self.change(NormalizeChange::RemovedMarkup(name.to_string()));
+        self.pos += end + 1;
+        true
+    }
+
+    /// 读取标签名，返回标签名以及结束位置
+    fn tag_name(&self, start: usize) -> Option<(&'a str, usize)> {
+        let bytes = self.src.as_bytes();
+        if !bytes.get(start)?.is_ascii_alphabetic() {
+            return None;
+        }
+        let mut end = start;
+        while end < bytes.len()
+            && (bytes[end].is_ascii_alphanumeric()
+                || matches!(bytes[end], b'-' | b'_' | b'.' | b':'))
+        {
+            end += 1;
+        }
+        Some((&self.src[start..end], end))
+    }
+
+    fn end_tag(&mut self) -> bool {
+        let Some((raw, end)) = self.tag_name(self.pos + 2) else {
+            return false;
+        };
+        let Some(close) = self.src[end..].find('>') else {
+            return false;
+        };
+        self.pos = end + close + 1;
+
+        let name = if self.in_foreign()
+            && !raw.eq_ignore_ascii_case("svg")
+            && !raw.eq_ignore_ascii_case("math")
+        {
+            raw.to_string()
+        } else {
+            raw.to_ascii_lowercase()
+        };
+        if name != raw {
+            self.change(NormalizeChange::Lowercase(name.clone()));
+        }
+
+        match self.stack.iter().rposition(|v| v == &name) {
+            Some(index) if !VOID.contains(&name.as_str()) => {
+                self.close_from(index + 1);
+                self.stack.pop();
+                self.push_end(&name);
+            }
+            _ => self.change(NormalizeChange::RemovedEndTag(name)),
+        }
+        true
+    }
+
+    /// 闭合 index 及之后的元素
+    fn close_from(&mut self, index: usize) {
+        while self.stack.len() > index {
+            let name = self.stack.pop().unwrap_or_default();
+            self.change(NormalizeChange::ClosedTag(name.clone()));
+            self.push_end(&name);
+        }
+    }
+
+    /// 从栈顶开始查找元素，遇到边界时停止
+    fn find_open(&self, targets: &[&str], boundary: &[&str]) -> Option<usize> {
+        for (index, name) in self.stack.iter().enumerate().rev() {
+            if targets.contains(&name.as_str()) {
+                return Some(index);
+            }
+            if boundary.contains(&name.as_str()) {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// 按照 html 的规则隐式闭合元素，例如 <p>a<p>b
+    fn close_implied(&mut self, name: &str) {
+        if CLOSE_P.contains(&name) {
+            if let Some(index) = self.find_open(&["p"], BUTTON_SCOPE) {
+                self.close_from(index);
+            }
+        }
+        for (tags, targets, boundary) in IMPLIED {
+            if tags.contains(&name) {
+                if let Some(index) = self.find_open(targets, boundary) {
+                    self.close_from(index);
+                }
+            }
+        }
+        if HEADING.contains(&name)
+            && self
+                .stack
+                .last()
+                .is_some_and(|v| HEADING.contains(&v.as_str()))
+        {
+            self.close_from(self.stack.len() - 1);
+        }
+    }
+
+    /// 读取开始标签内的属性，返回属性以及是否自闭合
+    fn attributes(&mut self, start: usize) -> Option<(Vec<RawAttr<'a>>, bool)> {
+        let src: &'a str = self.src;
+        let bytes = src.as_bytes();
+        let mut i = start;
+        let mut attrs = Vec::new();
+        let skip_space = |i: &mut usize| {
+            while *i < bytes.len() && bytes[*i].is_ascii_whitespace() {
+                *i += 1;
+            }
+        };
+        loop {
+            skip_space(&mut i);
+            match bytes.get(i)? {
+                b'>' => {
+                    self.pos = i + 1;
+                    return Some((attrs, false));
+                }
+                b'/' if bytes.get(i + 1) == Some(&b'>') => {
+                    self.pos = i + 2;
+                    return Some((attrs, true));
+                }
+                b'/' | b'=' => {
+                    i += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            let name_start = i;
+            while i < bytes.len()
+                && !bytes[i].is_ascii_whitespace()
+                && !matches!(bytes[i], b'=' | b'>' | b'/')
+            {
+                i += 1;
+            }
+            let name = &src[name_start..i];
+            skip_space(&mut i);
+            if bytes.get(i) != Some(&b'=') {
+                attrs.push((name, None, false));
+                continue;
+            }
+            i += 1;
+            skip_space(&mut i);
+            match bytes.get(i)? {
+                q @ (b'"' | b'\'') => {
+                    let end = src[i + 1..].find(*q as char)?;
+                    attrs.push((name, Some(&src[i + 1..i + 1 + end]), true));
+                    i += end + 2;
+                }
+                _ => {
+                    let value_start = i;
+                    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                        i += 1;
+                    }
+                    attrs.push((name, Some(&src[value_start..i]), false));
+                }
+            }
+        }
+    }
+
+    fn start_tag(&mut self) -> bool {
+        let Some((raw, end)) = self.tag_name(self.pos + 1) else {
+            return false;
+        };
+        let start = self.pos;
+        let Some((attrs, self_closing)) = self.attributes(end) else {
+            self.pos = start;
+            return false;
+        };
+
+        let foreign = self.in_foreign();
+        let name = if foreign {
+            raw.to_string()
+        } else {
+            raw.to_ascii_lowercase()
+        };
+        if name != raw {
+            self.change(NormalizeChange::Lowercase(name.clone()));
+        }
+        let is_void = !foreign && VOID.contains(&name.as_str());
+
+        if self.policy.strip_element(&name) {
+            self.change(NormalizeChange::RemovedElement(name.clone()));
+            if !self_closing && !is_void {
+                self.skip_element(&name);
+            }
+            return true;
+        }
+
+        if !foreign {
+            self.close_implied(&name);
+        }
+
+        let mut tag = format!("<{}", name);
+        let mut seen: Vec<String> = Vec::new();
+        for (raw_attr, value, quoted) in attrs {
+            let attr = if foreign || name == "svg" || name == "math" {
+                raw_attr.to_string()
+            } else {
+                raw_attr.to_ascii_lowercase()
+            };
+            if attr != raw_attr {
+                self.change(NormalizeChange::Lowercase(attr.clone()));
+            }
+            let value = value.unwrap_or(raw_attr);
+            if !is_xml_name(&attr)
+                || seen.contains(&attr)
+                || self.policy.strip_attribute(&attr, value)
+            {
+                self.change(NormalizeChange::RemovedAttribute {
+                    element: name.clone(),
+                    attribute: attr,
+                });
+                continue;
+            }
+            if !quoted {
+                self.change(NormalizeChange::QuotedAttribute(attr.clone()));
+            }
+            tag.push(' ');
+            tag.push_str(&attr);
+            tag.push_str("=\"");
+            let mut v = String::new();
+            std::mem::swap(&mut v, &mut self.out);
+            self.text(value, true, true);
+            std::mem::swap(&mut v, &mut self.out);
+            tag.push_str(&v);
+            tag.push('"');
+            seen.push(attr);
+        }
+
+        if is_void || self_closing {
+            if is_void && !self_closing {
+                self.change(NormalizeChange::ClosedTag(name.clone()));
+            }
+            tag.push_str("/>");
+            self.out.push_str(&tag);
+            return true;
+        }
+        tag.push('>');
+        self.out.push_str(&tag);
+
+        let raw_text = RAW_TEXT.contains(&name.as_str());
+        if raw_text || ESCAPABLE_RAW_TEXT.contains(&name.as_str()) {
+            let src: &'a str = self.src;
+            let end = self
+                .find_end_tag(&name, self.pos)
+                .map_or(src.len(), |(v, _)| v);
+            self.text(&src[self.pos..end], !raw_text, false);
+            self.pos = end;
+        }
+        self.stack.push(name);
+        true
+    }
+
+    /// 查找结束标签，返回结束标签的开始和结束位置
+    fn find_end_tag(&self, name: &str, from: usize) -> Option<(usize, usize)> {
+        let pattern = format!("</{}", name.to_ascii_lowercase());
+        let mut from = from;
+        while let Some(index) = self.lower[from..].find(pattern.as_str()) {
+            let start = from + index;
+            let after = start + pattern.len();
+            match self.lower.as_bytes().get(after) {
+                None => return Some((start, self.src.len())),
+                Some(c) if c.is_ascii_whitespace() || matches!(c, b'>' | b'/') => {
+                    let end = self.src[after..]
+                        .find('>')
+                        .map_or(self.src.len(), |v| after + v + 1);
+                    return Some((start, end));
+                }
+                _ => from = after,
+            }
+        }
+        None
+    }
+
+    /// 跳过元素以及元素内容
+    fn skip_element(&mut self, name: &str) {
+        if RAW_TEXT.contains(&name) || ESCAPABLE_RAW_TEXT.contains(&name) {
+            self.pos = self
+                .find_end_tag(name, self.pos)
+                .map_or(self.src.len(), |(_, end)| end);
+            return;
+        }
+        // 需要考虑嵌套的情况
+        let open = format!("<{}", name.to_ascii_lowercase());
+        let mut depth = 1;
+        let mut from = self.pos;
+        while let Some((close_start, close_end)) = self.find_end_tag(name, from) {
+            depth += self.lower[from..close_start]
+                .match_indices(open.as_str())
+                .filter(|(index, _)| {
+                    self.lower
+                        .as_bytes()
+                        .get(from + index + open.len())
+                        .is_some_and(|c| c.is_ascii_whitespace() || matches!(c, b'>' | b'/'))
+                })
+                .count();
+            depth -= 1;
+            from = close_end;
+            if depth == 0 {
+                self.pos = close_end;
+                return;
+            }
+        }
+        self.pos = self.src.len();
+    }
+
+    ///
+    /// 输出文本
+    ///
+    /// [entity] 是否解析实体
+    /// [attr] 是否是属性值
+    ///
+    fn text(&mut self, text: &str, entity: bool, attr: bool) {
+        let mut i = 0;
+        while let Some(c) = text[i..].chars().next() {
+            i += c.len_utf8();
+            match c {
+                '&' if entity => i += self.entity(&text[i..]),
+                '&' | '<' => {
+                    self.change(NormalizeChange::EscapedChar(c));
+                    self.out.push_str(if c == '&' { "&amp;" } else { "&lt;" });
+                }
+                '>' => self.out.push_str("&gt;"),
+                '"' if attr => self.out.push_str("&quot;"),
+                // 属性值中的空白会被解析器替换为空格，所以需要使用字符引用
+                '\t' if attr => self.out.push_str("&#9;"),
+                '\n' if attr => self.out.push_str("&#10;"),
+                '\r' if attr => self.out.push_str("&#13;"),
+                c if !is_xml_char(c) => self.change(NormalizeChange::InvalidChar),
+                c => self.out.push(c),
+            }
+        }
+    }
+
+    /// 处理 & 之后的实体，返回消耗的长度
+    fn entity(&mut self, rest: &str) -> usize {
+        if let Some(num) = rest.strip_prefix('#') {
+            let (hex, digits) = match num.strip_prefix(['x', 'X']) {
+                Some(v) => (true, v),
+                None => (false, num),
+            };
+            let len = digits
+                .find(|c: char| {
+                    !(if hex {
+                        c.is_ascii_hexdigit()
+                    } else {
+                        c.is_ascii_digit()
+                    })
+                })
+                .unwrap_or(digits.len());
+            let prefix = 1 + usize::from(hex);
+            if len > 0 {
+                let name = &rest[..prefix + len];
+                let semicolon = digits[len..].starts_with(';');
+                let value = u32::from_str_radix(&digits[..len], if hex { 16 } else { 10 })
+                    .ok()
+                    .and_then(char::from_u32)
+                    .filter(|c| is_xml_char(*c));
+                match value {
+                    Some(_) if semicolon => {
+                        self.out.push('&');
+                        self.out.push_str(name);
+                        self.out.push(';');
+                        return name.len() + 1;
+                    }
+                    Some(c) => {
+                        self.change(NormalizeChange::Entity(name.to_string()));
+                        self.out.push_str(&format!("&#{};", c as u32));
+                        return name.len();
+                    }
+                    None => {
+                        self.change(NormalizeChange::UnknownEntity(name.to_string()));
+                        self.out.push_str("&amp;");
+                        return 0;
+                    }
+                }
+            }
+        } else {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            let name = &rest[..len];
+            let semicolon = rest[len..].starts_with(';');
+            if len > 0 && semicolon && XML_ENTITIES.contains(&name) {
+                self.out.push('&');
+                self.out.push_str(name);
+                self.out.push(';');
+                return len + 1;
+            }
+            if let Ok(index) = ENTITIES.binary_search_by(|(v, _)| v.cmp(&name)) {
+                self.change(NormalizeChange::Entity(name.to_string()));
+                self.out.push_str(&format!("&#{};", ENTITIES[index].1));
+                return len + usize::from(semicolon);
+            }
+            if len > 0 && semicolon {
+                self.change(NormalizeChange::UnknownEntity(name.to_string()));
+                self.out.push_str("&amp;");
+                return 0;
+            }
+        }
+        self.change(NormalizeChange::EscapedChar('&'));
+        self.out.push_str("&amp;");
+        0
+    }
+}
+
+/// 是否是合法的 xml 名称
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_html, NormalizeChange, NormalizePolicy};
+    use crate::prelude::*;
+
+    /// 检查是否是格式正确的xml
+    fn well_formed(xhtml: &str) -> bool {
+        let xml = format!("<body>{}</body>", xhtml);
+        let mut reader = quick_xml::reader::Reader::from_str(xml.as_str());
+        loop {
+            match reader.read_event() {
+                Ok(quick_xml::events::Event::Text(e)) => {
+                    if e.unescape().is_err() {
+                        return false;
+                    }
+                }
+                Ok(quick_xml::events::Event::Start(e) | quick_xml::events::Event::Empty(e)) => {
+                    for attr in e.attributes() {
+                        if attr.map(|v| v.unescape_value().is_ok()) != Ok(true) {
+                            return false;
+                        }
+                    }
+                }
+                Ok(quick_xml::events::Event::Eof) => return true,
+                Err(_) => return false,
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        let (v, changes) = normalize_html(
+            r#"<P>段落1<p>段落2<br>&nbsp;a &copy b & c<IMG SRC=a.png alt='x'><script>alert("<p>")</script><div onclick="x()">d</div>"#,
+            &NormalizePolicy::default(),
+        );
+        assert_eq!(
+            r#"<p>段落1</p><p>段落2<br/>&#160;a &#169; b &amp; c<img src="a.png" alt="x"/></p><div>d</div>"#,
+            v
+        );
+        assert!(well_formed(&v));
+        let has = |c: NormalizeChange| changes.iter().any(|(v, _)| v == &c);
+        assert!(has(NormalizeChange::Lowercase("p".to_string())));
+        assert!(has(NormalizeChange::Lowercase("img".to_string())));
+        assert!(has(NormalizeChange::Lowercase("src".to_string())));
+        assert!(has(NormalizeChange::ClosedTag("br".to_string())));
+        assert!(has(NormalizeChange::Entity("nbsp".to_string())));
+        assert!(has(NormalizeChange::Entity("copy".to_string())));
+        assert!(has(NormalizeChange::EscapedChar('&')));
+        assert!(has(NormalizeChange::QuotedAttribute("src".to_string())));
+        assert!(has(NormalizeChange::RemovedElement("script".to_string())));
+        assert!(has(NormalizeChange::RemovedAttribute {
+            element: "div".to_string(),
+            attribute: "onclick".to_string()
+        }));
+        assert_eq!(
+            Some(2),
+            changes
+                .iter()
+                .find(|(v, _)| v == &NormalizeChange::ClosedTag("p".to_string()))
+                .map(|(_, count)| *count)
+        );
+
+        // 已经是合法的xhtml时不修改
+        let (v, changes) = normalize_html(
+            r#"<p class="a">1 &amp; 2 &#160; &#x4e2d;<br/></p><!-- 注释 --><img src="1.png" alt=""/>"#,
+            &NormalizePolicy::default(),
+        );
+        assert!(changes.is_empty());
+        assert_eq!(
+            r#"<p class="a">1 &amp; 2 &#160; &#x4e2d;<br/></p><!-- 注释 --><img src="1.png" alt=""/>"#,
+            v
+        );
+    }
+
+    #[test]
+    fn test_implied_close() {
+        let (v, _) = normalize_html(
+            "<ul><li>a<li>b</ul><table><tr><td>1<td>2<tr><td>3</table><dl><dt>t<dd>d</dl><h1>1<h2>2</h2></span>",
+            &NormalizePolicy::default(),
+        );
+        assert_eq!(
+            "<ul><li>a</li><li>b</li></ul><table><tr><td>1</td><td>2</td></tr><tr><td>3</td></tr></table><dl><dt>t</dt><dd>d</dd></dl><h1>1</h1><h2>2</h2>",
+            v
+        );
+
+        let (v, changes) = normalize_html(
+            r#"<div><p>a < b<svg viewBox="0 0 1 1"><linearGradient id="g"/></svg><!DOCTYPE html><style>p > a { }</style>"#,
+            &NormalizePolicy::default(),
+        );
+        assert_eq!(
+            r#"<div><p>a &lt; b<svg viewBox="0 0 1 1"><linearGradient id="g"/></svg><style>p &gt; a { }</style></p></div>"#,
+            v
+        );
+        assert!(changes.contains(&(NormalizeChange::RemovedMarkup("doctype".to_string()), 1)));
+        assert!(changes.contains(&(NormalizeChange::EscapedChar('<'), 1)));
+    }
+
+    #[test]
+    fn test_policy() {
+        let html = r#"<p style="color:red"><a href=" javascript:void(0)">a</a><script>1</script><object><object></object>b</object>c</p>"#;
+        let (v, _) = normalize_html(html, &NormalizePolicy::default());
+        assert_eq!(r#"<p style="color:red"><a>a</a>c</p>"#, v);
+
+        let policy = NormalizePolicy::default()
+            .with_keep_element("script")
+            .with_strip_attribute("STYLE")
+            .with_strip_scripts(false);
+        let (v, _) = normalize_html(html, &policy);
+        assert_eq!(
+            r#"<p><a href=" javascript:void(0)">a</a><script>1</script>c</p>"#,
+            v
+        );
+    }
+
+    #[test]
+    fn test_entity() {
+        let (v, changes) = normalize_html(
+            "&lt;&#1;&#65;&#x41&foo;&amp&mdash;&;&#;&Alpha;",
+            &NormalizePolicy::default(),
+        );
+        assert_eq!(
+            "&lt;&amp;#1;&#65;&#65;&amp;foo;&#38;&#8212;&amp;;&amp;#;&#913;",
+            v
+        );
+        assert!(well_formed(&v));
+        assert!(changes.contains(&(NormalizeChange::UnknownEntity("#1".to_string()), 1)));
+        assert!(changes.contains(&(NormalizeChange::UnknownEntity("foo".to_string()), 1)));
+        assert!(changes.contains(&(NormalizeChange::Entity("#x41".to_string()), 1)));
+        assert!(changes.contains(&(NormalizeChange::Entity("amp".to_string()), 1)));
+    }
+
+    #[test]
+    fn test_well_formed_property() {
+        /// 由 html 片段组成的随机内容
+        #[derive(Debug, Clone)]
+        struct Soup(String);
+        impl quickcheck::Arbitrary for Soup {
+            fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+                const PARTS: &[&str] = &[
+                    "<p>",
+                    "</p>",
+                    "<P class=a>",
+                    "<div>",
+                    "</div>",
+                    "<br>",
+                    "</br>",
+                    "<li>",
+                    "<ul>",
+                    "</ul>",
+                    "<table>",
+                    "<td>",
+                    "<tr>",
+                    "</table>",
+                    "<script>",
+                    "</script>",
+                    "<style>",
+                    "</style>",
+                    "<!--",
+                    "-->",
+                    "<![CDATA[",
+                    "]]>",
+                    "<svg>",
+                    "</SVG>",
+                    "<a href=\"",
+                    "\">",
+                    "'",
+                    "\"",
+                    "=",
+                    "&nbsp;",
+                    "&",
+                    ";",
+                    "&#",
+                    "x",
+                    "<",
+                    ">",
+                    "/",
+                    " ",
+                    "\n",
+                    "\r",
+                    "\u{0}",
+                    "\u{1}",
+                    "中文",
+                    "&amp;",
+                    "<img src=1.png>",
+                    "<!DOCTYPE html>",
+                    "<?xml?>",
+                    "<b>",
+                    "</i>",
+                    "<object>",
+                    "</object>",
+                    "<h1>",
+                    "<h2>",
+                    "onclick=x",
+                ];
+                let len = usize::arbitrary(g) % 40;
+                let mut v = String::new();
+                for _ in 0..len {
+                    if bool::arbitrary(g) {
+                        v.push_str(g.choose(PARTS).unwrap_or(&""));
+                    } else {
+                        v.push(char::arbitrary(g));
+                    }
+                }
+                Soup(v)
+            }
+        }
+        use quickcheck::Arbitrary;
+
+        fn prop(soup: Soup) -> bool {
+            let (v, _) = normalize_html(&soup.0, &NormalizePolicy::default());
+            well_formed(&v)
+        }
+        quickcheck::QuickCheck::new()
+            .tests(2000)
+            .quickcheck(prop as fn(Soup) -> bool);
+    }
+
+    #[test]
+    fn test_write_normalize() {
+        let mut book = EpubBook::default();
+        book.set_title("title");
+        book.add_chapter(
+            EpubHtml::default()
+                .with_file_name("0.xhtml")
+                .with_data("<P>1<br>2&nbsp;".as_bytes().to_vec()),
+        );
+        book.add_chapter(
+            EpubHtml::default()
+                .with_file_name("1.xhtml")
+                .with_data("<p>ok</p>".as_bytes().to_vec()),
+        );
+
+        let mut data = std::io::Cursor::new(Vec::new());
+        let mut writer = EpubWriter::new(&mut data)
+            .with_append_title(false)
+            .with_normalize(NormalizePolicy::default());
+        writer.write(&mut book).unwrap();
+        assert_eq!(1, writer.normalize_report().len());
+        assert_eq!("0.xhtml", writer.normalize_report()[0].file_name());
+        drop(writer);
+
+        let mut book = read_from_vec(data.into_inner()).unwrap();
+        let chap = book
+            .chapters_mut()
+            .find(|v| v.file_name() == "0.xhtml")
+            .unwrap();
+        assert_eq!(
+            "<p>1<br/>2&#160;</p>",
+            String::from_utf8(chap.data_mut().unwrap().to_vec())
+                .unwrap()
+                .trim()
+        );
+
+        // 默认不处理
+        let mut builder = EpubBuilder::new().with_title("title").add_chapter(
+            EpubHtml::default()
+                .with_file_name("0.xhtml")
+                .with_data("<P>1".as_bytes().to_vec()),
+        );
+        builder = builder.with_normalize(NormalizePolicy::default());
+        let mut book = builder.book().unwrap();
+        assert_eq!(
+            "<p>1</p>",
+            String::from_utf8(book.chapters_mut().next().unwrap().data().unwrap().to_vec())
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//!
+//! fb2 的数据结构
+//!
+//! 章节内容统一保存为 xhtml 片段，读取时从 fb2 的标签转换，写入时再转换回 fb2，
+//! 图片使用 `<img src="binary id"/>` 引用
+//!
+
+/// 章节，对应 `<section>`
+#[derive(Debug, Default, Clone)]
+pub struct Fb2Section {
+    pub(crate) id: Option<String>,
+    pub(crate) title: String,
+    /// xhtml 片段，不包括标题和子章节
+    pub(crate) data: Vec<u8>,
+    pub(crate) children: Vec<Fb2Section>,
+}
+
+impl Fb2Section {
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = title.into();
+    }
+
+    pub fn with_title<T: Into<String>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    pub fn with_id<T: Into<String>>(mut self, id: T) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// 章节内容，xhtml 片段
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.data = data;
+    }
+
+    pub fn with_data(mut self, data: Vec<u8>) -> Self {
+        self.set_data(data);
+        self
+    }
+
+    /// 子章节
+    pub fn children(&self) -> std::slice::Iter<'_, Fb2Section> {
+        self.children.iter()
+    }
+
+    pub fn push(&mut self, child: Fb2Section) {
+        self.children.push(child);
+    }
+
+    /// 是否有正文，只有空白时视为没有
+    pub(crate) fn has_data(&self) -> bool {
+        self.data.iter().any(|c| !c.is_ascii_whitespace())
+    }
+}
+
+/// 二进制资源，对应 `<binary>`，一般是图片
+#[derive(Debug, Default, Clone)]
+pub struct Fb2Binary {
+    pub(crate) id: String,
+    pub(crate) content_type: String,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Fb2Binary {
+    pub fn new<T: Into<String>, C: Into<String>>(id: T, content_type: C, data: Vec<u8>) -> Self {
+        Fb2Binary {
+            id: id.into(),
+            content_type: content_type.into(),
+            data,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Fb2Book {
+    pub(crate) info: crate::common::BookInfo,
+    /// 语言
+    pub(crate) lang: Option<String>,
+    /// 电子书创建者信息
+    pub(crate) generator: Option<String>,
+    /// 章节
+    pub(crate) sections: Vec<Fb2Section>,
+    /// 图片等资源
+    pub(crate) binaries: Vec<Fb2Binary>,
+    /// 封面对应的 binary id
+    pub(crate) cover: Option<String>,
+}
+
+impl Fb2Book {
+    iepub_derive::option_string_method!(info, creator);
+    iepub_derive::option_string_method!(info, description);
+    iepub_derive::option_string_method!(info, contributor);
+    iepub_derive::option_string_method!(info, date);
+    iepub_derive::option_string_method!(info, publisher);
+    iepub_derive::option_string_method!(info, subject);
+    iepub_derive::option_string_method!(lang);
+    iepub_derive::option_string_method!(generator);
+
+    pub fn set_title<T: AsRef<str>>(&mut self, title: T) {
+        self.info.title.clear();
+        self.info.title.push_str(title.as_ref());
+    }
+    pub fn title(&self) -> &str {
+        self.info.title.as_str()
+    }
+    pub fn with_title<T: AsRef<str>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+    pub fn identifier(&self) -> &str {
+        self.info.identifier.as_str()
+    }
+    pub fn set_identifier<T: AsRef<str>>(&mut self, identifier: T) {
+        self.info.identifier.clear();
+        self.info.identifier.push_str(identifier.as_ref());
+    }
+    pub fn with_identifier<T: AsRef<str>>(mut self, identifier: T) -> Self {
+        self.set_identifier(identifier);
+        self
+    }
+
+    pub fn sections(&self) -> std::slice::Iter<'_, Fb2Section> {
+        self.sections.iter()
+    }
+
+    pub fn sections_mut(&mut self) -> std::slice::IterMut<'_, Fb2Section> {
+        self.sections.iter_mut()
+    }
+
+    pub fn add_section(&mut self, section: Fb2Section) {
+        self.sections.push(section);
+    }
+
+    pub fn binaries(&self) -> std::slice::Iter<'_, Fb2Binary> {
+        self.binaries.iter()
+    }
+
+    pub fn add_binary(&mut self, binary: Fb2Binary) {
+        self.binaries.push(binary);
+    }
+
+    pub fn get_binary(&self, id: &str) -> Option<&Fb2Binary> {
+        self.binaries.iter().find(|f| f.id == id)
+    }
+
+    /// 封面，需要先通过 [Fb2Book::add_binary] 添加图片
+    pub fn set_cover<T: Into<String>>(&mut self, id: T) {
+        self.cover = Some(id.into());
+    }
+
+    pub fn cover(&self) -> Option<&Fb2Binary> {
+        self.cover.as_deref().and_then(|id| self.get_binary(id))
+    }
+}
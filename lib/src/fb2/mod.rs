@@ -0,0 +1,4 @@
+//! fb2 读写
+pub(crate) mod core;
+pub(crate) mod reader;
+pub(crate) mod writer;
@@ -0,0 +1,571 @@
+//!
+//! 读取 fb2
+//!
+//! 支持未压缩的 `.fb2` 以及 zip 压缩的 `.fb2.zip`，编码按照 BOM 以及 xml 声明识别
+//!
+use std::{borrow::Cow, io::Read, path::Path};
+
+use base64::Engine;
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use crate::{
+    common::{escape_xml, ErrorContext, IError, IResult, ResultContext},
+    epub::reader::is_epub,
+};
+
+use super::core::{Fb2Binary, Fb2Book, Fb2Section};
+
+/// 行内标签，读取元数据时不会打断文本
+const INLINE: &[&str] = &[
+    "emphasis",
+    "strong",
+    "style",
+    "a",
+    "strikethrough",
+    "sub",
+    "sup",
+    "code",
+];
+
+///
+/// 是否是fb2
+///
+/// 只检查未压缩的文件，判断开头是否有 `<FictionBook` 标签
+///
+pub fn is_fb2<T: Read>(value: &mut T) -> IResult<bool> {
+    let mut v = Vec::new();
+    value.take(1024).read_to_end(&mut v)?;
+    Ok(v.windows(12).any(|f| f == b"<FictionBook"))
+}
+
+/// 从文件读取fb2
+pub fn read_from_file<P: AsRef<Path>>(file: P) -> IResult<Fb2Book> {
+    read_from_vec(std::fs::read(file)?)
+}
+
+///
+/// 从内存读取fb2
+///
+/// 数据是 zip 时读取其中第一个 `.fb2` 文件
+///
+pub fn read_from_vec(data: Vec<u8>) -> IResult<Fb2Book> {
+    if is_epub(&mut data.as_slice())? {
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(data))?;
+        let name = zip
+            .file_names()
+            .find(|f| f.to_lowercase().ends_with(".fb2"))
+            .map(|f| f.to_string())
+            .ok_or_else(|| IError::MissingEntry("*.fb2".to_string()))?;
+        let mut entry = zip.by_name(&name)?;
+        let mut v = Vec::new();
+        entry
+            .read_to_end(&mut v)
+            .context(|| ErrorContext::new("read entry").with_entry(name.as_str()))?;
+        return read_xml(&decode(&v)?);
+    }
+    read_xml(&decode(&data)?)
+}
+
+///
+/// 解码，优先使用 BOM，其次是 xml 声明中的 encoding，默认 utf-8
+///
+fn decode(data: &[u8]) -> IResult<String> {
+    if let Some((encoding, len)) = encoding_rs::Encoding::for_bom(data) {
+        return Ok(encoding
+            .decode_without_bom_handling(&data[len..])
+            .0
+            .into_owned());
+    }
+    let encoding = declared_encoding(data)
+        .map(|label| {
+            encoding_rs::Encoding::for_label(label.as_bytes()).ok_or_else(|| {
+                IError::Encoding(Cow::from(format!("unsupported encoding {}", label)))
+            })
+        })
+        .transpose()?
+        .unwrap_or(encoding_rs::UTF_8);
+    if encoding == encoding_rs::UTF_8 {
+        return String::from_utf8(data.to_vec())
+            .map_err(IError::Utf8)
+            .context(|| ErrorContext::new("decode fb2"));
+    }
+    Ok(encoding.decode_without_bom_handling(data).0.into_owned())
+}
+
+/// xml 声明中的编码
+fn declared_encoding(data: &[u8]) -> Option<String> {
+    let head = &data[..data.len().min(200)];
+    let end = head.windows(2).position(|f| f == b"?>")?;
+    let decl = String::from_utf8_lossy(&head[..end]);
+    let start = decl.find("encoding")? + "encoding".len();
+    let value = decl[start..].trim_start().strip_prefix('=')?.trim_start();
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    value[1..].split(quote).next().map(|f| f.to_string())
+}
+
+fn attr(e: &BytesStart, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .filter_map(Result::ok)
+        .find(|a| a.key.local_name().as_ref() == name)
+        .and_then(|a| a.unescape_value().ok().map(|v| v.to_string()))
+}
+
+/// 在第一个标签上添加 id 属性
+fn with_id(html: &str, id: &str) -> String {
+    let Some(end) = html.find('>') else {
+        return html.to_string();
+    };
+    let end = if html[..end].ends_with('/') {
+        end - 1
+    } else {
+        end
+    };
+    format!(
+        r#"{} id="{}"{}"#,
+        &html[..end],
+        escape_xml(id),
+        &html[end..]
+    )
+}
+
+#[derive(Default)]
+struct Fb2Parser {
+    book: Fb2Book,
+    /// 标签路径
+    path: Vec<String>,
+    /// 标签结束时需要输出的 html
+    close: Vec<Option<&'static str>>,
+    /// 正在读取的章节，第一个是 body
+    sections: Vec<Fb2Section>,
+    /// body 的 name 属性，一般是注释
+    body_name: Option<String>,
+    /// 是否已经读取过正文
+    has_main_body: bool,
+    /// 正在读取的标题
+    title: Option<String>,
+    /// 元数据以及 binary 的文本
+    text: String,
+    /// 作者姓名的各个部分
+    author: Vec<String>,
+    binary: Option<(String, String)>,
+}
+
+impl Fb2Parser {
+    fn parent(&self) -> &str {
+        self.path
+            .len()
+            .checked_sub(2)
+            .and_then(|i| self.path.get(i))
+            .map_or("", |f| f.as_str())
+    }
+
+    fn in_path(&self, name: &str) -> bool {
+        self.path.iter().any(|f| f == name)
+    }
+
+    fn push_html(&mut self, html: &str) {
+        if let Some(section) = self.sections.last_mut() {
+            section.data.extend_from_slice(html.as_bytes());
+        }
+    }
+
+    fn start(&mut self, e: &BytesStart) {
+        let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+        self.path.push(name);
+        let name = self.path.last().map_or("", |f| f.as_str());
+        let mut close = None;
+
+        if self.in_path("description") {
+            match name {
+                "author" => self.author.clear(),
+                "date" if self.parent() == "title-info" => {
+                    if let Some(v) = attr(e, b"value") {
+                        self.book.set_date(v);
+                    }
+                }
+                "image" if self.parent() == "coverpage" => {
+                    if let Some(href) = attr(e, b"href") {
+                        self.book.cover = Some(href.trim_start_matches('#').to_string());
+                    }
+                }
+                _ => {}
+            }
+        } else if name == "binary" {
+            self.text.clear();
+            self.binary = Some((
+                attr(e, b"id").unwrap_or_default(),
+                attr(e, b"content-type").unwrap_or_default(),
+            ));
+        } else if name == "body" {
+            self.body_name = attr(e, b"name");
+            self.sections.push(Fb2Section::default());
+        } else if self.sections.is_empty() {
+        } else if name == "section" {
+            self.sections.push(Fb2Section {
+                id: attr(e, b"id"),
+                ..Default::default()
+            });
+        } else if name == "title" && matches!(self.parent(), "section" | "body") {
+            self.title = Some(String::new());
+        } else if let Some(title) = &mut self.title {
+            if name == "p" && !title.is_empty() {
+                title.push(' ');
+            }
+        } else {
+            let block = matches!(
+                self.parent(),
+                "section" | "body" | "epigraph" | "cite" | "annotation" | "poem"
+            );
+            let open = match name {
+                "p" | "v" => Cow::from("<p>"),
+                "subtitle" => Cow::from(r#"<p class="subtitle">"#),
+                "text-author" => Cow::from(r#"<p class="text-author">"#),
+                "date" => Cow::from(r#"<p class="date">"#),
+                "emphasis" => Cow::from("<em>"),
+                "strong" => Cow::from("<strong>"),
+                "strikethrough" => Cow::from("<del>"),
+                "sub" => Cow::from("<sub>"),
+                "sup" => Cow::from("<sup>"),
+                "code" => Cow::from("<code>"),
+                "style" => Cow::from("<span>"),
+                "epigraph" => Cow::from(r#"<blockquote class="epigraph">"#),
+                "cite" => Cow::from("<blockquote>"),
+                "poem" => Cow::from(r#"<div class="poem">"#),
+                "stanza" => Cow::from(r#"<div class="stanza">"#),
+                "annotation" => Cow::from(r#"<div class="annotation">"#),
+                "title" => Cow::from(r#"<div class="title">"#),
+                "table" | "tr" | "td" | "th" => Cow::from(format!("<{}>", name)),
+                "a" => Cow::from(format!(
+                    r#"<a href="{}">"#,
+                    escape_xml(&attr(e, b"href").unwrap_or_default())
+                )),
+                "image" => {
+                    let img = format!(
+                        r#"<img src="{}" alt="{}"/>"#,
+                        escape_xml(attr(e, b"href").unwrap_or_default().trim_start_matches('#')),
+                        escape_xml(&attr(e, b"alt").unwrap_or_default())
+                    );
+                    if block {
+                        close = Some("</p>");
+                        Cow::from(format!("<p>{}", img))
+                    } else {
+                        Cow::from(img)
+                    }
+                }
+                "empty-line" => Cow::from("<p><br/></p>"),
+                _ => Cow::from(""),
+            };
+            // 保留元素的 id，链接可能指向这些元素
+            let open = match attr(e, b"id") {
+                Some(id) if !open.is_empty() => Cow::from(with_id(&open, &id)),
+                _ => open,
+            };
+            if close.is_none() && !open.is_empty() {
+                close = match name {
+                    "p" | "v" | "subtitle" | "text-author" | "date" => Some("</p>"),
+                    "emphasis" => Some("</em>"),
+                    "strong" => Some("</strong>"),
+                    "strikethrough" => Some("</del>"),
+                    "sub" => Some("</sub>"),
+                    "sup" => Some("</sup>"),
+                    "code" => Some("</code>"),
+                    "style" => Some("</span>"),
+                    "epigraph" | "cite" => Some("</blockquote>"),
+                    "poem" | "stanza" | "annotation" | "title" => Some("</div>"),
+                    "table" => Some("</table>"),
+                    "tr" => Some("</tr>"),
+                    "td" => Some("</td>"),
+                    "th" => Some("</th>"),
+                    "a" => Some("</a>"),
+                    _ => None,
+                };
+            }
+            self.push_html(&open);
+        }
+        self.close.push(close);
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some(title) = &mut self.title {
+            title.push_str(text);
+        } else if self.in_path("description") || self.binary.is_some() {
+            self.text.push_str(text);
+        } else if !self.sections.is_empty() {
+            self.push_html(&escape_xml(text));
+        }
+    }
+
+    fn end(&mut self) -> IResult<()> {
+        if let Some(Some(close)) = self.close.pop() {
+            self.push_html(close);
+        }
+        let parent = self.parent().to_string();
+        let Some(name) = self.path.pop() else {
+            return Ok(());
+        };
+
+        if self.in_path("description") {
+            let text = self.text.trim();
+            let in_title_info = self.in_path("title-info");
+            match name.as_str() {
+                "book-title" if in_title_info => self.book.set_title(text),
+                "first-name" | "middle-name" | "last-name" | "nickname"
+                    if in_title_info && !text.is_empty() =>
+                {
+                    self.author.push(text.to_string());
+                }
+                "author" if in_title_info && !self.author.is_empty() => {
+                    self.book.info.append_creator(&self.author.join(" "));
+                }
+                "genre" if in_title_info && !text.is_empty() => {
+                    let subject = match self.book.subject() {
+                        Some(v) => format!("{},{}", v, text),
+                        None => text.to_string(),
+                    };
+                    self.book.set_subject(subject);
+                }
+                "p" if in_title_info && self.in_path("annotation") => {
+                    let desc = match self.book.description() {
+                        Some(v) => format!("{}\n{}", v, text),
+                        None => text.to_string(),
+                    };
+                    self.book.set_description(desc);
+                }
+                "date" if in_title_info && self.book.date().is_none() && !text.is_empty() => {
+                    self.book.set_date(text)
+                }
+                "lang" if in_title_info => self.book.set_lang(text),
+                "id" if parent == "document-info" && self.book.identifier().is_empty() => {
+                    self.book.set_identifier(text)
+                }
+                "program-used" if parent == "document-info" => self.book.set_generator(text),
+                "publisher" if parent == "publish-info" => self.book.set_publisher(text),
+                "year" if parent == "publish-info" && self.book.date().is_none() => {
+                    self.book.set_date(text)
+                }
+                "isbn" if parent == "publish-info" && !text.is_empty() => {
+                    self.book.set_identifier(text)
+                }
+                _ => {}
+            }
+            if !INLINE.contains(&name.as_str()) {
+                self.text.clear();
+            }
+            return Ok(());
+        }
+
+        match name.as_str() {
+            "binary" => {
+                if let Some((id, content_type)) = self.binary.take() {
+                    let text: String = self
+                        .text
+                        .chars()
+                        .filter(|c| !c.is_ascii_whitespace())
+                        .collect();
+                    let data = base64::engine::general_purpose::STANDARD
+                        .decode(text)
+                        .map_err(|e| IError::Parse(Cow::from(e.to_string())))
+                        .context(|| ErrorContext::new("decode binary").with_entry(id.as_str()))?;
+                    self.book.add_binary(Fb2Binary::new(id, content_type, data));
+                }
+                self.text.clear();
+            }
+            "title" if self.title.is_some() => {
+                let title = self.title.take().unwrap_or_default();
+                if let Some(section) = self.sections.last_mut() {
+                    section.title = title.split_whitespace().collect::<Vec<&str>>().join(" ");
+                }
+            }
+            "section" if self.sections.len() > 1 => {
+                if let Some(section) = self.sections.pop() {
+                    if let Some(parent) = self.sections.last_mut() {
+                        parent.children.push(section);
+                    }
+                }
+            }
+            "body" => {
+                let Some(mut body) = self.sections.pop() else {
+                    return Ok(());
+                };
+                match self.body_name.take() {
+                    // 注释等附加内容，整体作为一个章节
+                    Some(name) if self.has_main_body => {
+                        if body.title.is_empty() {
+                            body.title = name;
+                        }
+                        self.book.sections.push(body);
+                    }
+                    _ => {
+                        self.has_main_body = true;
+                        let children = std::mem::take(&mut body.children);
+                        if body.has_data() {
+                            if body.title.is_empty() {
+                                body.title = self.book.title().to_string();
+                            }
+                            self.book.sections.push(body);
+                        }
+                        self.book.sections.extend(children);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn read_xml(xml: &str) -> IResult<Fb2Book> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().expand_empty_elements = true;
+    let mut parser = Fb2Parser::default();
+    loop {
+        let event = reader.read_event().map_err(|e| {
+            IError::from(e)
+                .with_context(ErrorContext::new("read fb2").with_position(reader.error_position()))
+        })?;
+        match event {
+            Event::Start(e) => parser.start(&e),
+            Event::End(_) => parser.end()?,
+            Event::Text(e) => {
+                let text = e.unescape().context(|| {
+                    ErrorContext::new("read fb2").with_position(reader.buffer_position())
+                })?;
+                parser.text(&text);
+            }
+            Event::CData(e) => parser.text(&String::from_utf8_lossy(&e)),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !parser.has_main_body {
+        return Err(IError::Parse(Cow::from("fb2 has no body")));
+    }
+    Ok(parser.book)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{declared_encoding, is_fb2, read_from_vec};
+
+    pub(crate) const FB2: &str = r##"<?xml version="1.0" encoding="utf-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">
+  <description>
+    <title-info>
+      <genre>sf</genre>
+      <genre>adventure</genre>
+      <author><first-name>Лев</first-name><last-name>Толстой</last-name></author>
+      <author><nickname>佚名</nickname></author>
+      <book-title>Война и мир</book-title>
+      <annotation><p>первая <emphasis>строка</emphasis></p><p>вторая</p></annotation>
+      <date value="1869-01-01">1869</date>
+      <coverpage><image l:href="#cover.jpg"/></coverpage>
+      <lang>ru</lang>
+    </title-info>
+    <document-info>
+      <id>doc-id</id>
+      <program-used>test</program-used>
+    </document-info>
+    <publish-info><publisher>Москва</publisher><isbn>978-0</isbn></publish-info>
+  </description>
+  <body>
+    <title><p>Война и мир</p></title>
+    <section id="s1">
+      <title><p>Том 1</p><p>Часть 1</p></title>
+      <section>
+        <title><p>Глава 1</p></title>
+        <epigraph><p>эпиграф</p><text-author>автор</text-author></epigraph>
+        <p>Текст <strong>жирный</strong> &amp; <a l:href="#n1" type="note">[1]</a></p>
+        <empty-line/>
+        <image l:href="#img1.png"/>
+      </section>
+      <section>
+        <title><p>Глава 2</p></title>
+        <poem><stanza><v>строка</v></stanza></poem>
+        <p id="p2">абзац</p>
+      </section>
+    </section>
+  </body>
+  <body name="notes">
+    <section id="n1"><title><p>1</p></title><p>примечание, <a l:href="#p2">см.</a></p></section>
+  </body>
+  <binary id="cover.jpg" content-type="image/jpeg">AQID</binary>
+  <binary id="img1.png" content-type="image/png">
+    BAUG
+  </binary>
+</FictionBook>
+"##;
+
+    #[test]
+    fn test_read() {
+        assert!(is_fb2(&mut FB2.as_bytes()).unwrap());
+        assert!(!is_fb2(&mut "<html/>".as_bytes()).unwrap());
+
+        let book = read_from_vec(FB2.as_bytes().to_vec()).unwrap();
+        assert_eq!("Война и мир", book.title());
+        assert_eq!(Some("Лев Толстой,佚名"), book.creator());
+        assert_eq!(Some("sf,adventure"), book.subject());
+        assert_eq!(Some("первая строка\nвторая"), book.description());
+        assert_eq!(Some("1869-01-01"), book.date());
+        assert_eq!(Some("ru"), book.lang());
+        assert_eq!("978-0", book.identifier());
+        assert_eq!(Some("Москва"), book.publisher());
+        assert_eq!(Some("test"), book.generator());
+        assert_eq!(&[1, 2, 3], book.cover().unwrap().data());
+        assert_eq!(&[4, 5, 6], book.get_binary("img1.png").unwrap().data());
+        assert_eq!(
+            "image/png",
+            book.get_binary("img1.png").unwrap().content_type()
+        );
+
+        let titles: Vec<&str> = book.sections().map(|f| f.title()).collect();
+        assert_eq!(vec!["Том 1 Часть 1", "notes"], titles);
+        let volume = book.sections().next().unwrap();
+        assert_eq!(Some("s1"), volume.id());
+        assert!(!volume.has_data());
+        let chap = volume.children().next().unwrap();
+        assert_eq!("Глава 1", chap.title());
+        let html = String::from_utf8(chap.data().to_vec()).unwrap();
+        assert!(html.contains(r#"<blockquote class="epigraph"><p>эпиграф</p><p class="text-author">автор</p></blockquote>"#));
+        assert!(
+            html.contains(r##"<p>Текст <strong>жирный</strong> &amp; <a href="#n1">[1]</a></p>"##)
+        );
+        assert!(html.contains(r#"<p><br/></p>"#));
+        assert!(html.contains(r#"<p><img src="img1.png" alt=""/></p>"#));
+        assert!(!html.contains("Глава 1"));
+        let chap = volume.children().nth(1).unwrap();
+        let html = String::from_utf8(chap.data().to_vec()).unwrap();
+        assert!(html.contains(r#"<p id="p2">абзац</p>"#));
+
+        let notes = book.sections().nth(1).unwrap();
+        assert_eq!("1", notes.children().next().unwrap().title());
+    }
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(
+            Some("windows-1251".to_string()),
+            declared_encoding(br#"<?xml version="1.0" encoding='windows-1251'?><a/>"#)
+        );
+        assert_eq!(None, declared_encoding(br#"<?xml version="1.0"?>"#));
+
+        let xml = FB2.replace("utf-8", "windows-1251");
+        let (data, _, _) = encoding_rs::WINDOWS_1251.encode(&xml);
+        let book = read_from_vec(data.to_vec()).unwrap();
+        assert_eq!("Война и мир", book.title());
+
+        // zip
+        let mut v = std::io::Cursor::new(Vec::new());
+        let mut zip = zip::ZipWriter::new(&mut v);
+        zip.start_file("book.fb2", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut zip, FB2.as_bytes()).unwrap();
+        zip.finish().unwrap();
+        let book = read_from_vec(v.into_inner()).unwrap();
+        assert_eq!("Война и мир", book.title());
+
+        assert!(read_from_vec(b"<FictionBook><description/></FictionBook>".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,502 @@
+//!
+//! 写入 fb2
+//!
+use std::{
+    fs::File,
+    io::{Seek, Write},
+    path::Path,
+};
+
+use base64::Engine;
+use quick_xml::{events::Event, Reader};
+
+use crate::{
+    common::{escape_xml, IResult},
+    epub::{
+        core::info,
+        xhtml::{normalize_html, NormalizePolicy},
+    },
+};
+
+use super::core::{Fb2Book, Fb2Section};
+
+///
+/// 写入fb2
+///
+/// 设置 [Fb2Writer::with_zip] 后输出为 `.fb2.zip`
+///
+pub struct Fb2Writer<T: Write + Seek> {
+    inner: T,
+    /// 压缩包内的文件名
+    zip: Option<String>,
+}
+
+impl Fb2Writer<File> {
+    ///
+    /// 写入文件
+    ///
+    /// 文件名以 `.zip` 结尾时压缩，压缩包内的文件名为去掉 `.zip` 之后的文件名
+    ///
+    pub fn write_to_file<P: AsRef<Path>>(file: P, book: &Fb2Book) -> IResult<()> {
+        let path = file.as_ref();
+        let zip = path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .filter(|f| f.to_lowercase().ends_with(".zip"))
+            .map(|f| f[..f.len() - 4].to_string());
+        let mut writer = Fb2Writer::new(File::create(path)?);
+        if let Some(name) = zip {
+            writer = writer.with_zip(name);
+        }
+        writer.write(book)
+    }
+}
+
+impl Fb2Writer<std::io::Cursor<Vec<u8>>> {
+    /// 写入内存
+    pub fn write_to_mem(book: &Fb2Book) -> IResult<Vec<u8>> {
+        let mut v = std::io::Cursor::new(Vec::new());
+        Fb2Writer::new(&mut v).write(book)?;
+        Ok(v.into_inner())
+    }
+}
+
+impl<T: Write + Seek> Fb2Writer<T> {
+    pub fn new(inner: T) -> Self {
+        Fb2Writer { inner, zip: None }
+    }
+
+    /// 压缩为zip，name 为压缩包内的文件名
+    pub fn with_zip<N: Into<String>>(mut self, name: N) -> Self {
+        self.zip = Some(name.into());
+        self
+    }
+
+    pub fn write(&mut self, book: &Fb2Book) -> IResult<()> {
+        let xml = to_xml(book);
+        match &self.zip {
+            Some(name) => {
+                let mut zip = zip::ZipWriter::new(&mut self.inner);
+                zip.start_file(
+                    name.as_str(),
+                    zip::write::SimpleFileOptions::default()
+                        .compression_method(zip::CompressionMethod::Deflated),
+                )?;
+                zip.write_all(xml.as_bytes())?;
+                zip.finish()?;
+            }
+            None => self.inner.write_all(xml.as_bytes())?,
+        }
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+fn tag(out: &mut String, name: &str, value: &str) {
+    if !value.trim().is_empty() {
+        out.push_str(&format!("<{name}>{}</{name}>\n", escape_xml(value.trim())));
+    }
+}
+
+/// 作者，有空格时拆分为名和姓，否则作为昵称
+fn author(out: &mut String, name: &str) {
+    let v: Vec<&str> = name.split_whitespace().collect();
+    out.push_str("<author>");
+    match v.as_slice() {
+        [] => {}
+        [nickname] => tag(out, "nickname", nickname),
+        [first, middle @ .., last] => {
+            tag(out, "first-name", first);
+            tag(out, "middle-name", &middle.join(" "));
+            tag(out, "last-name", last);
+        }
+    }
+    out.push_str("</author>\n");
+}
+
+fn to_xml(book: &Fb2Book) -> String {
+    let mut out = String::from(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">
+<description>
+<title-info>
+"#,
+    );
+    for genre in book.subject().unwrap_or_default().split(',') {
+        tag(&mut out, "genre", genre);
+    }
+    for name in book.creator().unwrap_or_default().split(',') {
+        if !name.trim().is_empty() {
+            author(&mut out, name);
+        }
+    }
+    tag(&mut out, "book-title", book.title());
+    if let Some(desc) = book.description().filter(|f| !f.trim().is_empty()) {
+        out.push_str("<annotation>\n");
+        for line in desc.lines() {
+            tag(&mut out, "p", line);
+        }
+        out.push_str("</annotation>\n");
+    }
+    tag(&mut out, "date", book.date().unwrap_or_default());
+    if let Some(cover) = book.cover() {
+        out.push_str(&format!(
+            "<coverpage><image l:href=\"#{}\"/></coverpage>\n",
+            escape_xml(cover.id())
+        ));
+    }
+    tag(&mut out, "lang", book.lang().unwrap_or_default());
+    out.push_str("</title-info>\n<document-info>\n");
+    tag(
+        &mut out,
+        "program-used",
+        &format!("{}-{}", info::PROJECT_NAME, info::PKG_VERSION),
+    );
+    tag(&mut out, "id", book.identifier());
+    out.push_str("<version>1.0</version>\n</document-info>\n");
+    if let Some(publisher) = book.publisher() {
+        out.push_str("<publish-info>\n");
+        tag(&mut out, "publisher", publisher);
+        out.push_str("</publish-info>\n");
+    }
+    out.push_str("</description>\n<body>\n");
+    if !book.title().is_empty() {
+        out.push_str(&format!(
+            "<title><p>{}</p></title>\n",
+            escape_xml(book.title())
+        ));
+    }
+    for section in book.sections() {
+        write_section(&mut out, section);
+    }
+    out.push_str("</body>\n");
+    for binary in book.binaries() {
+        out.push_str(&format!(
+            "<binary id=\"{}\" content-type=\"{}\">{}</binary>\n",
+            escape_xml(binary.id()),
+            escape_xml(binary.content_type()),
+            base64::engine::general_purpose::STANDARD.encode(binary.data())
+        ));
+    }
+    out.push_str("</FictionBook>\n");
+    out
+}
+
+///
+/// 写入章节
+///
+/// fb2 中有子章节的 section 不能再有正文，所以正文放到一个没有标题的子章节中
+///
+fn write_section(out: &mut String, section: &Fb2Section) {
+    match section.id() {
+        Some(id) => out.push_str(&format!("<section id=\"{}\">\n", escape_xml(id))),
+        None => out.push_str("<section>\n"),
+    }
+    if !section.title().is_empty() {
+        out.push_str(&format!(
+            "<title><p>{}</p></title>\n",
+            escape_xml(section.title())
+        ));
+    }
+    let body = to_fb2(&String::from_utf8_lossy(section.data()), section.title());
+    if section.children.is_empty() {
+        if body.trim().is_empty() {
+            out.push_str("<empty-line/>\n");
+        } else {
+            out.push_str(&body);
+        }
+    } else {
+        if !body.trim().is_empty() {
+            out.push_str("<section>\n");
+            out.push_str(&body);
+            out.push_str("</section>\n");
+        }
+        for child in section.children() {
+            write_section(out, child);
+        }
+    }
+    out.push_str("</section>\n");
+}
+
+/// 行内标签
+struct Inline {
+    name: String,
+    open: String,
+    close: &'static str,
+}
+
+///
+/// html 转换为 fb2 的段落
+///
+/// 块级标签转换为段落，标题转换为 subtitle，不支持的标签只保留文本，
+/// 和章节标题相同的第一个标题会被去掉
+///
+#[derive(Default)]
+struct Fb2Renderer<'a> {
+    out: String,
+    title: &'a str,
+    /// 是否有未关闭的段落
+    in_p: bool,
+    /// 正在读取标题，值为标题开始的位置
+    heading: Option<usize>,
+    inline: Vec<Inline>,
+    /// 跳过的标签层数
+    skip: usize,
+}
+
+impl Fb2Renderer<'_> {
+    fn open_p(&mut self) {
+        if self.in_p {
+            return;
+        }
+        self.out.push_str(if self.heading.is_some() {
+            "<subtitle>"
+        } else {
+            "<p>"
+        });
+        for ele in &self.inline {
+            self.out.push_str(&ele.open);
+        }
+        self.in_p = true;
+    }
+
+    fn close_p(&mut self) {
+        if !self.in_p {
+            return;
+        }
+        for ele in self.inline.iter().rev() {
+            self.out.push_str(ele.close);
+        }
+        self.out.push_str(if self.heading.is_some() {
+            "</subtitle>\n"
+        } else {
+            "</p>\n"
+        });
+        self.in_p = false;
+    }
+
+    fn start(&mut self, name: &str, e: &quick_xml::events::BytesStart) {
+        if self.skip > 0 || matches!(name, "head" | "script" | "style") {
+            self.skip += 1;
+            return;
+        }
+        let attr = |key: &[u8]| {
+            e.attributes()
+                .filter_map(Result::ok)
+                .find(|a| a.key.local_name().as_ref() == key)
+                .and_then(|a| a.unescape_value().ok().map(|v| v.to_string()))
+        };
+        let inline = match name {
+            "em" | "i" | "cite" | "dfn" => Some(("<emphasis>".to_string(), "</emphasis>")),
+            "strong" | "b" => Some(("<strong>".to_string(), "</strong>")),
+            "s" | "del" | "strike" => Some(("<strikethrough>".to_string(), "</strikethrough>")),
+            "sub" => Some(("<sub>".to_string(), "</sub>")),
+            "sup" => Some(("<sup>".to_string(), "</sup>")),
+            "code" | "tt" | "kbd" => Some(("<code>".to_string(), "</code>")),
+            "a" => match attr(b"href") {
+                Some(href) if href.contains("://") || href.starts_with('#') => {
+                    Some((format!("<a l:href=\"{}\">", escape_xml(&href)), "</a>"))
+                }
+                _ => Some((String::new(), "")),
+            },
+            _ => None,
+        };
+        if let Some((open, close)) = inline {
+            if self.in_p {
+                self.out.push_str(&open);
+            }
+            self.inline.push(Inline {
+                name: name.to_string(),
+                open,
+                close,
+            });
+            return;
+        }
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.close_p();
+                self.heading = Some(self.out.len());
+            }
+            "img" | "image" => {
+                self.close_p();
+                if let Some(src) = attr(b"src").or_else(|| attr(b"href")) {
+                    self.out.push_str(&format!(
+                        "<image l:href=\"#{}\"/>\n",
+                        escape_xml(src.trim_start_matches('#'))
+                    ));
+                }
+            }
+            "hr" => {
+                self.close_p();
+                self.out.push_str("<empty-line/>\n");
+            }
+            "span" | "font" | "small" | "big" | "u" | "ruby" | "rb" | "abbr" => {}
+            "rt" | "rp" => self.skip += 1,
+            _ => self.close_p(),
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return;
+        }
+        if let Some(index) = self.inline.iter().rposition(|f| f.name == name) {
+            let ele = self.inline.remove(index);
+            if self.in_p {
+                self.out.push_str(ele.close);
+            }
+            return;
+        }
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.close_p();
+                if let Some(start) = self.heading.take() {
+                    // 和章节标题相同的第一个标题
+                    let text = crate::common::unescape_html(&self.out[start..]);
+                    if start == 0 && text.trim() == self.title.trim() {
+                        self.out.clear();
+                    }
+                }
+            }
+            "span" | "font" | "small" | "big" | "u" | "ruby" | "rb" | "abbr" | "img" | "image"
+            | "hr" => {}
+            _ => self.close_p(),
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.skip > 0 {
+            return;
+        }
+        if !self.in_p {
+            let text = text.trim_start();
+            if text.is_empty() {
+                return;
+            }
+            self.open_p();
+            self.out.push_str(&escape_xml(text));
+            return;
+        }
+        self.out.push_str(&escape_xml(text));
+    }
+}
+
+/// 转换 html 片段，图片的 src 作为 binary id
+fn to_fb2(html: &str, title: &str) -> String {
+    let (html, _) = normalize_html(html, &NormalizePolicy::default());
+    let mut reader = Reader::from_str(&html);
+    reader.config_mut().expand_empty_elements = true;
+    reader.config_mut().check_end_names = false;
+    let mut renderer = Fb2Renderer {
+        title,
+        ..Default::default()
+    };
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_lowercase();
+                renderer.start(&name, &e);
+            }
+            Ok(Event::End(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_lowercase();
+                renderer.end(&name);
+            }
+            Ok(Event::Text(e)) => {
+                if let Ok(t) = e.unescape() {
+                    renderer.text(&t);
+                }
+            }
+            Ok(Event::CData(e)) => renderer.text(&String::from_utf8_lossy(&e)),
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    renderer.close_p();
+    renderer.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_fb2, Fb2Writer};
+    use crate::fb2::{
+        core::{Fb2Binary, Fb2Book, Fb2Section},
+        reader::read_from_vec,
+    };
+
+    #[test]
+    fn test_to_fb2() {
+        assert_eq!(
+            "<p>a <strong>b</strong></p>\n<p><strong>c</strong> d</p>\n",
+            to_fb2("<div>a <b>b<br/>c</b> d</div>", "")
+        );
+        assert_eq!(
+            "<p>text</p>\n<subtitle>sub</subtitle>\n",
+            to_fb2("<h1>标题</h1><p>text</p><h2>sub</h2>", "标题")
+        );
+        assert_eq!(
+            "<p><a l:href=\"https://a.com\">x</a>y</p>\n<image l:href=\"#1.png\"/>\n<p>z</p>\n",
+            to_fb2(
+                r#"<html><head><title>t</title></head><body><p><a href="https://a.com">x</a><a href="b.xhtml">y</a><img src="1.png"/>z</p></body></html>"#,
+                ""
+            )
+        );
+        assert_eq!(
+            "<p>漢字</p>\n",
+            to_fb2("<p><ruby>漢字<rt>かんじ</rt></ruby></p>", "")
+        );
+    }
+
+    #[test]
+    fn test_write() {
+        let mut book = Fb2Book::default()
+            .with_title("书名 & <1>")
+            .with_creator("Лев Николаевич Толстой,佚名")
+            .with_description("a\nb")
+            .with_subject("sf,prose")
+            .with_lang("zh");
+        let mut volume = Fb2Section::default()
+            .with_title("卷一")
+            .with_data(b"<p>intro</p>".to_vec());
+        volume.push(
+            Fb2Section::default()
+                .with_title("第一章")
+                .with_data(br#"<p>text</p><p><img src="1.png"/></p>"#.to_vec()),
+        );
+        book.add_section(volume);
+        book.add_section(Fb2Section::default().with_title("空"));
+        book.add_binary(Fb2Binary::new("1.png", "image/png", vec![1, 2, 3]));
+        book.set_cover("1.png");
+
+        let data = Fb2Writer::write_to_mem(&book).unwrap();
+        let xml = String::from_utf8(data.clone()).unwrap();
+        assert!(xml.contains("<first-name>Лев</first-name>\n<middle-name>Николаевич</middle-name>\n<last-name>Толстой</last-name>"));
+        assert!(xml.contains("<section>\n<title><p>空</p></title>\n<empty-line/>\n</section>"));
+
+        let read = read_from_vec(data).unwrap();
+        assert_eq!("书名 & <1>", read.title());
+        assert_eq!(Some("Лев Николаевич Толстой,佚名"), read.creator());
+        assert_eq!(Some("a\nb"), read.description());
+        assert_eq!(Some("sf,prose"), read.subject());
+        assert_eq!(Some("zh"), read.lang());
+        assert_eq!(&[1, 2, 3], read.cover().unwrap().data());
+        let volume = read.sections().next().unwrap();
+        assert_eq!("卷一", volume.title());
+        let titles: Vec<&str> = volume.children().map(|f| f.title()).collect();
+        assert_eq!(vec!["", "第一章"], titles);
+        assert_eq!(
+            r#"<p>text</p><p><img src="1.png" alt=""/></p>"#,
+            String::from_utf8(volume.children().nth(1).unwrap().data().to_vec())
+                .unwrap()
+                .replace('\n', "")
+        );
+
+        // zip
+        let mut v = std::io::Cursor::new(Vec::new());
+        Fb2Writer::new(&mut v)
+            .with_zip("book.fb2")
+            .write(&book)
+            .unwrap();
+        assert_eq!("书名 & <1>", read_from_vec(v.into_inner()).unwrap().title());
+    }
+}
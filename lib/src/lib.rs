@@ -2,14 +2,18 @@
 #[allow(dead_code)]
 extern crate iepub_derive;
 mod adapter;
+mod cbz;
 mod common;
 mod cover;
 mod epub;
+mod fb2;
 mod mobi;
 pub mod path;
+mod text;
 pub use crate::common::DateTimeFormater;
 
 pub mod prelude {
+    pub use crate::common::ErrorContext;
     pub use crate::common::IError;
     pub use crate::common::IResult;
 
@@ -21,12 +25,38 @@ pub mod prelude {
     pub use crate::epub::core::EpubLink;
     pub use crate::epub::core::EpubMetaData;
     pub use crate::epub::core::EpubNav;
+    pub use crate::epub::layout::EpubLayout;
+    pub use crate::epub::layout::LayoutNaming;
+    #[cfg(feature = "async")]
+    pub use crate::epub::reader::read_from_async_reader;
+    pub use crate::epub::reader::read_from_cloneable_reader;
     pub use crate::epub::reader::read_from_file;
+    pub use crate::epub::reader::read_from_file_lenient;
+    pub use crate::epub::reader::read_from_reader;
     pub use crate::epub::reader::read_from_vec;
+    pub use crate::epub::reader::read_from_vec_lenient;
+    pub use crate::epub::reader::Repair;
+    #[cfg(feature = "async")]
+    pub use crate::epub::writer::AsyncEpubWriter;
     pub use crate::epub::writer::EpubWriter;
+    pub use crate::epub::xhtml::NormalizeChange;
+    pub use crate::epub::xhtml::NormalizePolicy;
+    pub use crate::epub::xhtml::NormalizeReport;
 
     pub mod appender {
-        pub use crate::epub::appender::write_metadata;
+        pub use crate::epub::appender::{append_chapters, write_metadata};
+        pub use crate::mobi::appender::append_mobi_chapters;
+    }
+
+    pub mod validate {
+        pub use crate::epub::validate::is_valid;
+        pub use crate::epub::validate::to_json;
+        pub use crate::epub::validate::validate_book;
+        pub use crate::epub::validate::validate_file;
+        pub use crate::epub::validate::validate_reader;
+        pub use crate::epub::validate::validate_vec;
+        pub use crate::epub::validate::Diagnostic;
+        pub use crate::epub::validate::Severity;
     }
 
     pub use crate::mobi::builder::MobiBuilder;
@@ -36,14 +66,96 @@ pub mod prelude {
     pub use crate::mobi::reader::MobiReader;
     pub use crate::mobi::writer::MobiWriter;
 
+    pub mod inspect {
+        pub use crate::mobi::inspect::inspect_file;
+        pub use crate::mobi::inspect::inspect_reader;
+        pub use crate::mobi::inspect::inspect_vec;
+        pub use crate::mobi::inspect::MobiInspection;
+    }
+
+    pub mod cbz {
+        pub use crate::cbz::core::CbzBook;
+        pub use crate::cbz::core::CbzPage;
+        pub use crate::cbz::reader::read_from_file;
+        pub use crate::cbz::reader::read_from_vec;
+        pub use crate::cbz::writer::CbzWriter;
+    }
+
+    pub mod fb2 {
+        pub use crate::fb2::core::Fb2Binary;
+        pub use crate::fb2::core::Fb2Book;
+        pub use crate::fb2::core::Fb2Section;
+        pub use crate::fb2::reader::read_from_file;
+        pub use crate::fb2::reader::read_from_vec;
+        pub use crate::fb2::writer::Fb2Writer;
+    }
+
+    pub mod diff {
+        pub use crate::text::diff::AssetChange;
+        pub use crate::text::diff::BookDiff;
+        pub use crate::text::diff::BookSnapshot;
+        pub use crate::text::diff::ChangeKind;
+        pub use crate::text::diff::ChapterChange;
+        pub use crate::text::diff::ChapterRef;
+        pub use crate::text::diff::LineChange;
+        pub use crate::text::diff::MetadataChange;
+    }
+
+    pub mod export {
+        pub use crate::text::export::ExportFormat;
+        pub use crate::text::export::TextExport;
+        pub use crate::text::export::TextExporter;
+    }
+
+    pub mod markdown {
+        pub use crate::text::markdown::MarkdownImporter;
+    }
+    pub mod rewrite {
+        pub use crate::text::rewrite::RewriteReport;
+        pub use crate::text::rewrite::RewriteRules;
+    }
+    #[cfg(feature = "pinyin")]
+    pub mod ruby {
+        pub use crate::text::ruby::RubyAnnotator;
+    }
+    pub mod search {
+        pub use crate::text::search::SearchMatch;
+        pub use crate::text::search::SearchQuery;
+    }
+    pub mod stats {
+        pub use crate::text::stats::AssetStats;
+        pub use crate::text::stats::BookStats;
+        pub use crate::text::stats::ChapterStats;
+    }
+    pub mod typography {
+        pub use crate::text::typography::TypographyPolicy;
+    }
+    #[cfg(feature = "zh_convert")]
+    pub mod zh {
+        pub use crate::text::zh::ChineseConverter;
+        pub use crate::text::zh::ChineseScript;
+    }
+    pub mod txt {
+        pub use crate::text::txt::detect_encoding;
+        pub use crate::text::txt::TxtImporter;
+        pub use crate::text::txt::TxtPreset;
+        pub use crate::text::txt::TxtSection;
+    }
+
     pub mod check {
         pub use crate::epub::reader::is_epub;
+        pub use crate::fb2::reader::is_fb2;
         pub use crate::mobi::reader::is_mobi;
     }
 
     pub mod adapter {
+        pub use crate::adapter::core::cbz_to_epub;
         pub use crate::adapter::core::concat::add_into_epub;
+        pub use crate::adapter::core::epub_to_cbz;
+        pub use crate::adapter::core::epub_to_fb2;
         pub use crate::adapter::core::epub_to_mobi;
+        pub use crate::adapter::core::fb2_to_epub;
+        pub use crate::adapter::core::fb2_to_mobi;
         pub use crate::adapter::core::mobi_to_epub;
     }
 }
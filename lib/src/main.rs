@@ -8,7 +8,7 @@ mod cli;
 use std::{env, fs::File};
 
 use cli::arg::{Arg, ArgOption, OptionDef, OptionType};
-use commands::{epub, mobi};
+use commands::{epub, mobi, text};
 use iepub::prelude::*;
 
 /// 支持的全局参数
@@ -23,11 +23,11 @@ fn create_option_def() -> Vec<OptionDef> {
 
 mod commands {
     macro_rules! register_command {
-        ($($cmd_type:ident),*) => {
+        ($($(#[$meta:meta])* $cmd_type:ident),*) => {
             pub(crate) fn create_command_option_def() -> Vec<$crate::cli::arg::CommandOptionDef> {
                 vec![
                 $(
-                $cmd_type::def(),
+                $(#[$meta])* $cmd_type::def(),
                 )*
                 ]
 
@@ -36,7 +36,7 @@ mod commands {
             pub(crate) fn support_command() -> Vec<Box<dyn $crate::Command>> {
                 vec![
                     $(
-                        Box::<$cmd_type>::default(),
+                        $(#[$meta])* Box::<$cmd_type>::default(),
                     )*
                 ]
             }
@@ -44,6 +44,11 @@ mod commands {
     }
     pub(crate) mod epub {
         use crate::cli::command::epub::*;
+        #[cfg(feature = "zh_convert")]
+        use crate::cli::command::text::ConvertScript;
+        use crate::cli::command::text::{
+            Append, Diff, Grep, Normalize, Replace, Stats, ToMarkdown, ToText,
+        };
 
         // 注册子命令
         register_command!(
@@ -54,12 +59,59 @@ mod commands {
             GetImage,
             GetChapter,
             FormatConvert,
-            Concat
+            Concat,
+            Validate,
+            Repair,
+            ToText,
+            ToMarkdown,
+            Normalize,
+            Replace,
+            Grep,
+            Stats,
+            Diff,
+            Append,
+            #[cfg(feature = "zh_convert")]
+            ConvertScript
         );
     }
     pub(crate) mod mobi {
         use crate::cli::command::mobi::*;
-        register_command!(BookInfoGetter, GetImage, GetCover, Unpack, FormatConvert);
+        #[cfg(feature = "zh_convert")]
+        use crate::cli::command::text::ConvertScript;
+        use crate::cli::command::text::{
+            Append, Diff, Grep, Normalize, Replace, Stats, ToMarkdown, ToText,
+        };
+        register_command!(
+            BookInfoGetter,
+            GetImage,
+            GetCover,
+            Unpack,
+            FormatConvert,
+            Inspect,
+            ToText,
+            ToMarkdown,
+            Normalize,
+            Replace,
+            Grep,
+            Stats,
+            Diff,
+            Append,
+            #[cfg(feature = "zh_convert")]
+            ConvertScript
+        );
+    }
+    pub(crate) mod fb2 {
+        use crate::cli::command::fb2::*;
+        use crate::cli::command::text::{ToMarkdown, ToText};
+        register_command!(BookInfoGetter, FormatConvert, ToText, ToMarkdown);
+    }
+    pub(crate) mod cbz {
+        use crate::cli::command::cbz::*;
+        register_command!(FormatConvert);
+    }
+    pub(crate) mod text {
+        use crate::cli::command::text::*;
+        register_command!(FromTxt, FromMarkdown, Grep);
     }
 }
 
@@ -80,11 +132,15 @@ pub(crate) trait Command {
 pub(crate) enum Book<'a> {
     EPUB(&'a mut EpubBook),
     MOBI(&'a mut MobiBook),
+    FB2(&'a mut fb2::Fb2Book),
+    CBZ(&'a mut cbz::CbzBook),
+    /// 文本文件路径
+    TEXT(&'a str),
 }
 
 /// 检查文件类型
 ///
-/// [return] 0 epub 1 mobi,None 没有指定文件参数
+/// [return] 0 epub 1 mobi 2 txt 3 fb2 4 cbz,None 没有指定文件参数
 fn check_input_type(arg: &Arg) -> Option<(usize, String)> {
     let check_method: Vec<fn(&mut File) -> IResult<bool>> = vec![
         iepub::prelude::check::is_epub,
@@ -93,6 +149,17 @@ fn check_input_type(arg: &Arg) -> Option<(usize, String)> {
 
     if let Some(opt) = arg.find_opt("i") {
         let path = opt.value.as_ref().unwrap().as_str();
+        if std::path::Path::new(path).is_dir() {
+            return Some((2, path.to_string()));
+        }
+        let lower = path.to_lowercase();
+        // fb2.zip 也是 zip 文件，需要在检查 epub 之前判断
+        if lower.ends_with(".fb2") || lower.ends_with(".fb2.zip") {
+            return Some((3, path.to_string()));
+        }
+        if lower.ends_with(".cbz") {
+            return Some((4, path.to_string()));
+        }
         msg!("opening file {}", path);
         let v = std::fs::File::open(path);
         if let Err(e) = v {
@@ -105,6 +172,14 @@ fn check_input_type(arg: &Arg) -> Option<(usize, String)> {
                 return Some((index, path.to_string()));
             }
         }
+        if std::io::Seek::rewind(&mut fs).is_ok()
+            && iepub::prelude::check::is_fb2(&mut fs).unwrap_or(false)
+        {
+            return Some((3, path.to_string()));
+        }
+        if [".txt", ".md", ".markdown"].iter().any(|f| lower.ends_with(f)) {
+            return Some((2, path.to_string()));
+        }
         exec_err!("unsupport file format");
     }
 
@@ -136,6 +211,21 @@ fn print_useage(arg: &Arg, exe_file_name: &str) -> bool {
         for ele in commands::mobi::create_command_option_def() {
             println!("{}", ele);
         }
+
+        println!("\nsupported sub command for fb2:\n");
+        for ele in commands::fb2::create_command_option_def() {
+            println!("{}", ele);
+        }
+
+        println!("\nsupported sub command for cbz:\n");
+        for ele in commands::cbz::create_command_option_def() {
+            println!("{}", ele);
+        }
+
+        println!("\nsupported sub command for txt, markdown:\n");
+        for ele in commands::text::create_command_option_def() {
+            println!("{}", ele);
+        }
         println!("version: {}", info::PKG_VERSION);
         return true;
     }
@@ -166,10 +256,12 @@ fn main() {
         cli::arg::parse_command_arg(
             &mut arg,
             env::args().skip(index + 1).map(|f| f.to_string()).collect(),
-            if input_type == 0 {
-                epub::create_command_option_def()
-            } else {
-                mobi::create_command_option_def()
+            match input_type {
+                0 => epub::create_command_option_def(),
+                1 => mobi::create_command_option_def(),
+                3 => commands::fb2::create_command_option_def(),
+                4 => commands::cbz::create_command_option_def(),
+                _ => text::create_command_option_def(),
             },
         );
     }
@@ -181,12 +273,26 @@ fn main() {
             Ok(mut book) => {
                 exec_epub(&arg, &mut book, exe_file_name.as_str());
             }
+            // 无法解析的文件仍然可以校验和修复
+            Err(_)
+                if arg
+                    .group
+                    .iter()
+                    .any(|s| s.command == "validate" || s.command == "repair") =>
+            {
+                exec_epub(&arg, &mut EpubBook::default(), exe_file_name.as_str());
+            }
             Err(e) => {
                 exec_err!("err: {}", e);
             }
         }
     } else if res == 1 {
         // mobi
+        // 只检查结构时不需要解析，文件损坏时也可以执行
+        if !arg.group.is_empty() && arg.group.iter().all(|s| s.command == "inspect") {
+            exec_mobi(&arg, &mut MobiBook::default(), exe_file_name.as_str());
+            return;
+        }
         match iepub::prelude::MobiReader::new(std::fs::File::open(path).unwrap_or_else(|s| {
             exec_err!("err: {}", s);
         }))
@@ -199,6 +305,29 @@ fn main() {
                 exec_err!("err: {}", e);
             }
         }
+    } else if res == 2 {
+        // txt
+        exec_text(&arg, path.as_str(), exe_file_name.as_str());
+    } else if res == 3 {
+        // fb2
+        match fb2::read_from_file(path.as_str()) {
+            Ok(mut book) => {
+                exec_fb2(&arg, &mut book, exe_file_name.as_str());
+            }
+            Err(e) => {
+                exec_err!("err: {}", e);
+            }
+        }
+    } else if res == 4 {
+        // cbz
+        match cbz::read_from_file(path.as_str()) {
+            Ok(mut book) => {
+                exec_cbz(&arg, &mut book, exe_file_name.as_str());
+            }
+            Err(e) => {
+                exec_err!("err: {}", e);
+            }
+        }
     }
 }
 
@@ -272,3 +401,111 @@ fn exec_mobi(arg: &Arg, book: &mut MobiBook, exe_file_name: &str) {
         }
     }
 }
+
+fn exec_fb2(arg: &Arg, book: &mut fb2::Fb2Book, exe_file_name: &str) {
+    let global_opts = arg.opts.as_slice();
+
+    let commands = commands::fb2::support_command();
+
+    // 执行 command
+    for ele in &arg.group {
+        let m = commands.iter().find(|s| s.name() == ele.command);
+        if let Some(com) = m {
+            if ele.opts.iter().any(|s| s.key == "h") {
+                if let Some(def) = commands::fb2::create_command_option_def()
+                    .iter()
+                    .find(|s| s.command == com.name())
+                {
+                    println!(
+                        "Usage: {} {} {}",
+                        exe_file_name,
+                        com.name(),
+                        if def.support_args != 0 {
+                            "[file_path]"
+                        } else {
+                            ""
+                        }
+                    );
+                    for ele in &def.opts {
+                        println!("-{:10} {}", ele.key, ele.desc);
+                    }
+                }
+
+                continue;
+            }
+            com.exec(&mut Book::FB2(book), global_opts, &ele.opts, &ele.args);
+        }
+    }
+}
+
+fn exec_cbz(arg: &Arg, book: &mut cbz::CbzBook, exe_file_name: &str) {
+    let global_opts = arg.opts.as_slice();
+
+    let commands = commands::cbz::support_command();
+
+    // 执行 command
+    for ele in &arg.group {
+        let m = commands.iter().find(|s| s.name() == ele.command);
+        if let Some(com) = m {
+            if ele.opts.iter().any(|s| s.key == "h") {
+                if let Some(def) = commands::cbz::create_command_option_def()
+                    .iter()
+                    .find(|s| s.command == com.name())
+                {
+                    println!(
+                        "Usage: {} {} {}",
+                        exe_file_name,
+                        com.name(),
+                        if def.support_args != 0 {
+                            "[file_path]"
+                        } else {
+                            ""
+                        }
+                    );
+                    for ele in &def.opts {
+                        println!("-{:10} {}", ele.key, ele.desc);
+                    }
+                }
+
+                continue;
+            }
+            com.exec(&mut Book::CBZ(book), global_opts, &ele.opts, &ele.args);
+        }
+    }
+}
+
+fn exec_text(arg: &Arg, path: &str, exe_file_name: &str) {
+    let global_opts = arg.opts.as_slice();
+
+    let commands = commands::text::support_command();
+
+    // 执行 command
+    for ele in &arg.group {
+        let m = commands.iter().find(|s| s.name() == ele.command);
+        if let Some(com) = m {
+            if ele.opts.iter().any(|s| s.key == "h") {
+                if let Some(def) = commands::text::create_command_option_def()
+                    .iter()
+                    .find(|s| s.command == com.name())
+                {
+                    println!(
+                        "Usage: {} {} {}",
+                        exe_file_name,
+                        com.name(),
+                        if def.support_args != 0 {
+                            "[file_path]"
+                        } else {
+                            ""
+                        }
+                    );
+                    for ele in &def.opts {
+                        println!("-{:10} {}", ele.key, ele.desc);
+                    }
+                }
+
+                continue;
+            }
+            com.exec(&mut Book::TEXT(path), global_opts, &ele.opts, &ele.args);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//!
+//! 修改现有mobi文件
+//!
+//! mobi 的文本和索引是一个整体，无法只追加部分内容，需要读取后重新生成
+//!
+use std::path::Path;
+
+use crate::{
+    common::{escape_xml, replace_file, DateTimeFormater},
+    epub::appender::is_numbered,
+    prelude::*,
+};
+
+///
+/// 向现有mobi追加章节
+///
+/// 读取原文件后添加新章节和目录，目录与现有目录的编号方式保持一致，并更新修改时间，然后重新生成整个文件
+///
+/// # Arguments
+///
+/// * `file` - 原文件路径
+/// * `chapters` - 新章节
+/// * `append_title` - 是否在新章节正文前添加标题，原有章节保持不变
+///
+/// # Examples
+///
+/// ```no_run
+/// use iepub::prelude::*;
+/// use iepub::prelude::appender::append_mobi_chapters;
+///
+/// append_mobi_chapters(
+///     "1.mobi",
+///     vec![MobiHtml::new(0)
+///         .with_title("第三章")
+///         .with_data("<p>新的章节</p>".as_bytes().to_vec())],
+///     true,
+/// )
+/// .unwrap();
+/// ```
+///
+pub fn append_mobi_chapters<P: AsRef<Path> + ?Sized>(
+    file: &P,
+    chapters: Vec<MobiHtml>,
+    append_title: bool,
+) -> IResult<()> {
+    let mut book = MobiReader::new(std::fs::File::open(file)?)?.load()?;
+    append_to_book(&mut book, chapters, append_title);
+
+    // 原有章节已经包含标题
+    let data = MobiWriter::write_to_mem(&book, false)?;
+    replace_file(file.as_ref(), |f| Ok(std::io::Write::write_all(f, &data)?))
+}
+
+/// 添加章节和目录，章节 id 和目录 id 接着现有的最大值
+fn append_to_book(book: &mut MobiBook, chapters: Vec<MobiHtml>, append_title: bool) {
+    fn max_nav_id<'a>(nav: impl Iterator<Item = &'a MobiNav>) -> usize {
+        nav.map(|f| f.id.max(max_nav_id(f.children())))
+            .max()
+            .unwrap_or(0)
+    }
+    let mut chap_id = book.chapters().map(|f| f.id).max().unwrap_or(0);
+    let mut nav_id = max_nav_id(book.nav());
+    let numbered = is_numbered(book.nav().map(|f| f.title()));
+
+    for mut chap in chapters {
+        chap_id += 1;
+        nav_id += 1;
+        chap.id = chap_id;
+        chap.nav_id = nav_id;
+        if append_title && !chap.title().is_empty() {
+            let mut data = format!(
+                r#"<h1 style="text-align: center">{}</h1>"#,
+                escape_xml(chap.title())
+            )
+            .into_bytes();
+            data.extend_from_slice(chap.data().unwrap_or_default());
+            chap.set_data(data);
+        }
+        let title = if numbered {
+            format!("{}. {}", book.nav().len() + 1, chap.title())
+        } else {
+            chap.title().to_string()
+        };
+        book.add_nav(
+            MobiNav::default(nav_id)
+                .with_chap_id(chap_id)
+                .with_title(title),
+        );
+        book.add_chapter(chap);
+    }
+    book.set_last_modify(DateTimeFormater::default().default_format());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::append_mobi_chapters;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_append_mobi_chapters() {
+        let file = std::env::temp_dir().join(format!("{}.test_append.mobi", std::process::id()));
+        MobiBuilder::new()
+            .with_title("书名")
+            .with_identifier("isbn")
+            .cover(crate::cbz::core::tests::png(1, 1))
+            .add_chapter(
+                MobiHtml::new(1)
+                    .with_title("第一章")
+                    .with_data("<p>第一章的内容</p>".as_bytes().to_vec()),
+            )
+            .add_chapter(
+                MobiHtml::new(2)
+                    .with_title("第二章")
+                    .with_data("<p>第二章的内容</p>".as_bytes().to_vec()),
+            )
+            .file(&file)
+            .unwrap();
+
+        append_mobi_chapters(
+            &file,
+            vec![MobiHtml::new(0)
+                .with_title("第三章")
+                .with_data("<p>新的章节</p>".as_bytes().to_vec())],
+            true,
+        )
+        .unwrap();
+
+        let book = MobiReader::new(std::fs::File::open(&file).unwrap())
+            .unwrap()
+            .load()
+            .unwrap();
+        assert_eq!("书名", book.title());
+        assert_eq!(
+            vec!["1. 第一章", "2. 第二章", "3. 第三章"],
+            book.nav().map(|f| f.title()).collect::<Vec<_>>()
+        );
+        let chapters: Vec<String> = book.chapters().map(|f| f.string_data()).collect();
+        assert_eq!(3, chapters.len());
+        assert!(chapters[0].contains("第一章的内容"), "{}", chapters[0]);
+        assert!(chapters[2].contains("第三章</h1>"), "{}", chapters[2]);
+        assert!(chapters[2].contains("新的章节"), "{}", chapters[2]);
+        assert!(book.cover().is_some());
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_append_mobi_volumes() {
+        let file =
+            std::env::temp_dir().join(format!("{}.test_append_volumes.mobi", std::process::id()));
+        MobiBuilder::new()
+            .with_title("书名")
+            .with_identifier("isbn")
+            .cover(crate::cbz::core::tests::png(1, 1))
+            .add_chapter(
+                MobiHtml::new(1)
+                    .with_title("第一章")
+                    .with_data("<p>第一章的内容</p>".as_bytes().to_vec()),
+            )
+            .add_chapter(
+                MobiHtml::new(2)
+                    .with_title("第二章")
+                    .with_data("<p>第二章的内容</p>".as_bytes().to_vec()),
+            )
+            .custome_nav(true)
+            .add_nav({
+                let mut nav = MobiNav::new(1, 1).with_title("第一卷");
+                nav.add_child(MobiNav::new(2, 1).with_title("第一章"));
+                nav.add_child(MobiNav::new(3, 2).with_title("第二章"));
+                nav
+            })
+            .file(&file)
+            .unwrap();
+
+        append_mobi_chapters(
+            &file,
+            vec![MobiHtml::new(0)
+                .with_title("后记")
+                .with_data("<p>新的章节</p>".as_bytes().to_vec())],
+            false,
+        )
+        .unwrap();
+
+        let book = MobiReader::new(std::fs::File::open(&file).unwrap())
+            .unwrap()
+            .load()
+            .unwrap();
+        let _ = std::fs::remove_file(&file);
+        assert_eq!(
+            vec!["第一卷", "后记"],
+            book.nav().map(|f| f.title()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["第一章", "第二章"],
+            book.nav()
+                .next()
+                .unwrap()
+                .children()
+                .map(|f| f.title())
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use std::path::Path;
 
 use crate::common::{IError, IResult};
+use crate::text::typography::TypographyPolicy;
 
 use super::{
     core::{MobiAssets, MobiBook, MobiHtml, MobiNav},
@@ -50,6 +51,8 @@ pub struct MobiBuilder {
     /// 字体文件内容
     /// 用于生成封面图片
     font_byte: Option<Vec<u8>>,
+    /// 排版规则
+    typography: Option<TypographyPolicy>,
 }
 
 impl Default for MobiBuilder {
@@ -68,6 +71,7 @@ impl MobiBuilder {
             auto_gen_cover: false,
             font: None,
             font_byte: None,
+            typography: None,
         }
     }
 
@@ -109,11 +113,32 @@ impl MobiBuilder {
         self
     }
 
+    pub fn with_language<T: AsRef<str>>(mut self, language: T) -> Self {
+        self.book.set_language(language);
+        self
+    }
+
     pub fn with_last_modify<T: AsRef<str>>(mut self, last_modify: T) -> Self {
         self.book.set_last_modify(last_modify);
         self
     }
 
+    ///
+    /// 设置书写方向
+    ///
+    /// 取值同 css 的 writing-mode，例如 horizontal-tb、vertical-rl，写入 EXTH 525
+    ///
+    pub fn with_writing_mode<T: AsRef<str>>(mut self, writing_mode: T) -> Self {
+        self.book.set_writing_mode(writing_mode);
+        self
+    }
+
+    /// 设置翻页方向，ltr 或 rtl，写入 EXTH 527
+    pub fn with_page_direction<T: AsRef<str>>(mut self, page_direction: T) -> Self {
+        self.book.set_page_direction(page_direction);
+        self
+    }
+
     pub fn custome_nav(mut self, value: bool) -> Self {
         self.custome_nav = value;
         self
@@ -142,6 +167,16 @@ impl MobiBuilder {
         self
     }
 
+    ///
+    /// 生成时按规则调整章节的排版
+    ///
+    /// 只在 book()、file()、mem() 时生效
+    ///
+    pub fn with_typography(mut self, policy: TypographyPolicy) -> Self {
+        self.typography = Some(policy);
+        self
+    }
+
     ///
     /// 添加资源文件
     /// [file_name] 可以随便填写，但是务必和章节里的img标签的src属性值保持一致，否则会导致图片不显示
@@ -222,6 +257,13 @@ impl MobiBuilder {
         }
     }
 
+    fn gen_typography(&mut self) -> IResult<()> {
+        if let Some(policy) = &self.typography {
+            self.book.typeset(policy)?;
+        }
+        Ok(())
+    }
+
     fn gen_cover(&mut self) -> IResult<()> {
         if self.auto_gen_cover && self.book.cover().is_none() {
             let font_bytes = match self.font_byte.clone() {
@@ -249,6 +291,7 @@ impl MobiBuilder {
     pub fn book(mut self) -> IResult<MobiBook> {
         self.gen_last_modify();
         self.gen_nav();
+        self.gen_typography()?;
         self.gen_cover()?;
         Ok(self.book)
     }
@@ -259,6 +302,7 @@ impl MobiBuilder {
     pub fn file<T: AsRef<Path>>(mut self, file: T) -> IResult<()> {
         self.gen_last_modify();
         self.gen_nav();
+        self.gen_typography()?;
         self.gen_cover()?;
 
         let fs = std::fs::OpenOptions::new()
@@ -278,6 +322,7 @@ impl MobiBuilder {
     pub fn mem(mut self) -> IResult<Vec<u8>> {
         self.gen_last_modify();
         self.gen_nav();
+        self.gen_typography()?;
         self.gen_cover()?;
 
         let mut out = std::io::Cursor::new(Vec::new());
@@ -289,7 +334,7 @@ impl MobiBuilder {
 
     #[cfg(feature = "cache")]
     pub fn cache<T: AsRef<std::path::Path>>(&self, file: T) -> IResult<()> {
-        std::fs::write(file, serde_json::to_string(&self.book).unwrap())?;
+        std::fs::write(file, serde_json::to_string(&self.book)?)?;
         Ok(())
     }
 
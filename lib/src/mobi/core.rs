@@ -49,6 +49,27 @@ impl MobiHtml {
         self.title = value.into();
         self
     }
+
+    pub fn set_title<T: Into<String>>(&mut self, value: T) {
+        self.title = value.into();
+    }
+
+    ///
+    /// 按规则调整章节的排版
+    ///
+    /// mobi 不支持 css，设置了首行缩进时给 p 标签添加 width 属性
+    ///
+    pub fn typeset(&mut self, policy: &TypographyPolicy) -> IResult<()> {
+        let html = String::from_utf8(self.data.clone())
+            .map_err(IError::Utf8)
+            .context(|| ErrorContext::new("typeset chapter").with_entry(self.title.as_str()))?;
+        let mut html = typeset_html(&html, policy);
+        if let Some(indent) = policy.text_indent() {
+            html = set_paragraph_width(&html, indent);
+        }
+        self.data = html.into_bytes();
+        Ok(())
+    }
 }
 cache_struct! {
 #[derive(Debug, Clone)]
@@ -96,6 +117,10 @@ impl MobiNav {
         self.children.iter()
     }
 
+    pub fn children_mut(&mut self) -> std::slice::IterMut<'_, MobiNav> {
+        self.children.iter_mut()
+    }
+
     pub fn with_chap_id(mut self, chap_id: usize) -> Self {
         self.chap_id = chap_id;
         self
@@ -106,6 +131,10 @@ impl MobiNav {
         self
     }
 
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = title.into();
+    }
+
     pub fn add_child(&mut self, child: MobiNav) {
         self.children.push(child);
     }
@@ -197,6 +226,9 @@ impl MobiBook {
     iepub_derive::option_string_method!(info, format);
     iepub_derive::option_string_method!(info, publisher);
     iepub_derive::option_string_method!(info, subject);
+    iepub_derive::option_string_method!(info, language);
+    iepub_derive::option_string_method!(info, writing_mode);
+    iepub_derive::option_string_method!(info, page_direction);
     // /
     // / 设置epub最后修改时间
     // /
@@ -268,17 +300,111 @@ impl MobiBook {
         self.chapters.push(chap);
     }
 
+    /// 按规则调整所有章节的排版
+    pub fn typeset(&mut self, policy: &TypographyPolicy) -> IResult<()> {
+        for chap in self.chapters.iter_mut() {
+            chap.typeset(policy)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// 按规则替换所有章节的文本节点
+    ///
+    /// # Returns
+    ///
+    /// 有替换的章节的替换次数，只有这些章节的数据会被修改
+    ///
+    pub fn rewrite(&mut self, rules: &RewriteRules) -> IResult<Vec<RewriteReport>> {
+        let mut out = Vec::new();
+        for (index, chap) in self.chapters.iter_mut().enumerate() {
+            let html = String::from_utf8(chap.data.clone())
+                .map_err(IError::Utf8)
+                .context(|| ErrorContext::new("rewrite chapter").with_entry(chap.title.as_str()))?;
+            let (value, count) = rules.rewrite_html(&html);
+            if count > 0 {
+                chap.data = value.into_bytes();
+                out.push(RewriteReport::new(index, None, chap.title(), count));
+            }
+        }
+        Ok(out)
+    }
+
+    /// 搜索所有章节的文本
+    pub fn search(&self, query: &SearchQuery) -> IResult<Vec<SearchMatch>> {
+        let mut out = Vec::new();
+        for (index, chap) in self.chapters.iter().enumerate() {
+            let nav = get_mobi_chapter_nav(chap, &self.nav)
+                .map(|v| v.iter().map(|n| n.title().to_string()).collect())
+                .unwrap_or_else(|| vec![chap.title().to_string()]);
+            for mut m in query.find(&html_text(&String::from_utf8_lossy(&chap.data)))? {
+                m.chapter = index;
+                m.nav = nav.clone();
+                out.push(m);
+            }
+        }
+        Ok(out)
+    }
+
+    /// 统计字数、段落、图片以及图片大小
+    pub fn stats(&self) -> IResult<BookStats> {
+        let chapters = self
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(index, chap)| {
+                ChapterStats::new(
+                    index,
+                    None,
+                    chap.title(),
+                    &String::from_utf8_lossy(&chap.data),
+                )
+            })
+            .collect();
+        // 读取的封面同时在图片中
+        let mut files = std::collections::HashSet::new();
+        let assets = self
+            .images
+            .iter()
+            .chain(self.cover.as_ref())
+            .filter(|f| f.file_name().is_empty() || files.insert(f.file_name().to_string()))
+            .filter_map(|f| {
+                let data = f.data()?;
+                let media_type = Some(get_media_type(f.file_name()))
+                    .filter(|v| !v.is_empty())
+                    .unwrap_or_else(|| match get_suffix(data).as_str() {
+                        "jpe" | "jpeg" => "image/jpeg".to_string(),
+                        v => format!("image/{}", v),
+                    });
+                Some((media_type, data.len()))
+            })
+            .collect::<Vec<_>>();
+        Ok(BookStats::new(chapters, assets))
+    }
+
+    /// 比较两本书的差异，当前书为旧书
+    pub fn diff(&self, other: &MobiBook) -> IResult<BookDiff> {
+        Ok(BookDiff::new(
+            &BookSnapshot::from_mobi(self)?,
+            &BookSnapshot::from_mobi(other)?,
+        ))
+    }
+
     pub fn nav(&self) -> std::slice::Iter<MobiNav> {
         self.nav.iter()
     }
 
+    pub fn nav_mut(&mut self) -> std::slice::IterMut<'_, MobiNav> {
+        self.nav.iter_mut()
+    }
+
     pub fn add_nav(&mut self, value: MobiNav) {
         self.nav.push(value);
     }
 
     #[cfg(feature = "cache")]
     pub fn cache<T: AsRef<std::path::Path>>(&self, file: T) -> IResult<()> {
-        std::fs::write(file, serde_json::to_string(self).unwrap())?;
+        std::fs::write(file, serde_json::to_string(self)?)?;
         Ok(())
     }
 
@@ -301,9 +427,21 @@ use std::{
     sync::atomic::AtomicUsize,
 };
 
-use crate::{cache_struct, common::IResult};
+use crate::{
+    adapter::core::get_mobi_chapter_nav,
+    cache_struct,
+    common::{get_media_type, ErrorContext, IError, IResult, ResultContext},
+    text::{
+        diff::{BookDiff, BookSnapshot},
+        export::html_text,
+        rewrite::{RewriteReport, RewriteRules},
+        search::{SearchMatch, SearchQuery},
+        stats::{BookStats, ChapterStats},
+        typography::{set_paragraph_width, typeset_html, TypographyPolicy},
+    },
+};
 
-use super::{common::do_time_format, reader::MobiReader};
+use super::{common::do_time_format, image::get_suffix, reader::MobiReader};
 
 impl<T: Read + Seek> MobiReader<T> {
     pub fn load(&mut self) -> IResult<MobiBook> {
@@ -320,9 +458,7 @@ impl<T: Read + Seek> MobiReader<T> {
             chapters.append(
                 &mut flatten_nav(n)
                     .iter()
-                    .map(|f| (sec.iter().find(|m| m.end > f.href), f))
-                    .filter(|s| s.0.is_some())
-                    .map(|f| (f.0.unwrap(), f.1))
+                    .filter_map(|f| sec.iter().find(|m| m.end > f.href).map(|s| (s, f)))
                     .map(|(sec, nav)| MobiHtml {
                         id: id.fetch_add(1, std::sync::atomic::Ordering::Release),
                         nav_id: nav.id,
@@ -377,11 +513,55 @@ impl<T: Read + Seek> MobiReader<T> {
     }
 }
 
+#[cfg(feature = "async")]
+impl MobiReader<std::io::Cursor<Vec<u8>>> {
+    ///
+    /// 从异步reader读取mobi
+    ///
+    /// 先把全部内容读入内存，之后与 [MobiReader::load] 共用同一套解析流程
+    ///
+    pub async fn load_async<R: tokio::io::AsyncRead + Unpin>(mut value: R) -> IResult<MobiBook> {
+        use tokio::io::AsyncReadExt;
+
+        let mut data = Vec::new();
+        value.read_to_end(&mut data).await?;
+        MobiReader::new(std::io::Cursor::new(data))?.load()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::common::tests::download_zip_file;
     use crate::mobi::reader::MobiReader;
 
+    #[test]
+    #[cfg(feature = "async")]
+    fn test_load_async() {
+        use crate::prelude::{MobiBuilder, MobiHtml};
+
+        let data = MobiBuilder::default()
+            .with_title("书名")
+            .with_creator("作者")
+            .cover(b"\xFF\xD8\xFF\xE0\x00\x10JFIF\x00\x01\x01".to_vec())
+            .add_chapter(
+                MobiHtml::new(1)
+                    .with_title("标题")
+                    .with_data("<p>锻炼</p>".as_bytes().to_vec()),
+            )
+            .mem()
+            .unwrap();
+
+        let book = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(MobiReader::load_async(std::io::Cursor::new(data)))
+            .unwrap();
+
+        assert_eq!("书名", book.title());
+        assert_eq!(Some("作者"), book.creator());
+        assert_eq!(1, book.chapters().len());
+    }
+
     #[test]
     fn test_load() {
         let name = "3252.mobi";
@@ -441,8 +621,6 @@ mod tests {
 
         let book2 = MobiBook::load_from_cache(f).unwrap();
 
-
-
         assert_eq!(book.chapters.len(), book2.chapters.len());
         assert_eq!(book.chapters[0].data, book2.chapters[0].data);
         assert_eq!(book.images[0]._data, book2.images[0]._data);
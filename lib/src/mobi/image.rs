@@ -1,6 +1,4 @@
-use std::borrow::Cow;
-
-use crate::common::{IError, IResult};
+use crate::common::{ErrorContext, IError, IResult};
 
 pub(crate) struct Cover(pub Vec<u8>);
 
@@ -20,13 +18,13 @@ impl Cover {
 pub(crate) fn get_suffix(image: &[u8]) -> String {
     let mut suffix = "jpe";
 
-    if b"JFIF" == &image[6..10] {
+    if image.get(6..10) == Some(b"JFIF") {
         suffix = "jpeg";
-    } else if b"PNG" == &image[1..4] {
+    } else if image.get(1..4) == Some(b"PNG") {
         suffix = "png";
-    } else if b"GIF" == &image[0..3] {
+    } else if image.get(0..3) == Some(b"GIF") {
         suffix = "gif";
-    } else if b"WEBP" == &image[8..12] {
+    } else if image.get(8..12) == Some(b"WEBP") {
         suffix = "webp";
     }
     suffix.to_string()
@@ -47,8 +45,7 @@ pub(crate) fn read_image_recindex_from_html(html: &[u8]) -> IResult<Vec<usize>>
     loop {
         match reader.read_event_into(&mut buf) {
             Ok(Event::Start(e)) => {
-                let name = String::from_utf8(e.name().as_ref().to_vec())
-                    .map_err(|_| IError::InvalidArchive(Cow::from("not a img")))?;
+                let name = String::from_utf8(e.name().as_ref().to_vec()).map_err(IError::Utf8)?;
 
                 if name == "img" {
                     let recindex = e.get_recindex();
@@ -61,7 +58,8 @@ pub(crate) fn read_image_recindex_from_html(html: &[u8]) -> IResult<Vec<usize>>
                 break;
             }
             Err(e) => {
-                return Err(IError::Xml(e));
+                return Err(IError::Xml(e)
+                    .with_context(ErrorContext::default().with_position(reader.error_position())));
             }
             _ => (),
         }
@@ -83,7 +81,10 @@ pub(crate) fn generate_text_img_xml(html: &str, assets: &[String]) -> Vec<u8> {
         let mut j = 0;
         while j < key.len() {
             if now == key[j] {
-                now = chars[index + j + 1];
+                match chars.get(index + j + 1) {
+                    Some(v) => now = *v,
+                    None => break,
+                }
             } else {
                 break;
             }
@@ -106,8 +107,8 @@ pub(crate) fn generate_text_img_xml(html: &str, assets: &[String]) -> Vec<u8> {
                 index += start + 1;
                 // 查找对应的 assets
                 let len = att.2;
-                let path = String::from_utf8(v).unwrap();
-                let p = path.as_str();
+                let path = String::from_utf8_lossy(&v);
+                let p = path.as_ref();
                 let ass = assets.iter().enumerate().find(|(_, v)| v == &p);
                 if let Some(ass) = ass {
                     // 有对应的src，则去除src值，然后加入recindex
@@ -115,10 +116,8 @@ pub(crate) fn generate_text_img_xml(html: &str, assets: &[String]) -> Vec<u8> {
 
                     index += len - 1;
                 } else {
-                    // 如果没有，则继续原样添加
-                    for i in index - 1..index + len {
-                        text.push(chars[i]);
-                    }
+                    // 如果没有，则继续原样添加，引号未闭合时 len 会超出末尾
+                    text.extend_from_slice(&chars[index - 1..(index + len).min(chars.len())]);
                     index += len;
                 }
                 continue;
@@ -179,7 +178,10 @@ pub(crate) fn get_attr_value(attr: &[u8], key: &str) -> (Option<Vec<u8>>, usize,
         while j < key.len() {
             if now == key[j] {
                 index += 1;
-                now = attr[index];
+                match attr.get(index) {
+                    Some(v) => now = *v,
+                    None => break,
+                }
             } else {
                 break;
             }
@@ -193,8 +195,8 @@ pub(crate) fn get_attr_value(attr: &[u8], key: &str) -> (Option<Vec<u8>>, usize,
         }
         index += 1;
     }
-    if index == attr.len() {
-        return (None, index, 0);
+    if index >= attr.len() {
+        return (None, attr.len(), 0);
     }
     let mut now = attr[index];
     let mut len = key.len();
@@ -272,7 +274,10 @@ impl<'a> AttrExt for quick_xml::events::BytesStart<'a> {
             while j < key.len() {
                 if now == key[j] {
                     index += 1;
-                    now = attr[index];
+                    match attr.get(index) {
+                        Some(v) => now = *v,
+                        None => return None,
+                    }
                 } else {
                     break;
                 }
@@ -302,8 +307,7 @@ impl<'a> AttrExt for quick_xml::events::BytesStart<'a> {
                     now = attr[index];
                     if now >= 0x30 && now <= 0x39 {
                         // 数字0-9
-                        res = res * 10;
-                        res += (now - 0x30) as usize;
+                        res = res.checked_mul(10)?.checked_add((now - 0x30) as usize)?;
                     } else if now == 0x20 || now == 0x27 || now == 0x22 {
                         break;
                     } else {
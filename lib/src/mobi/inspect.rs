@@ -0,0 +1,1085 @@
+//!
+//! mobi 结构检查
+//!
+//! 输出 pdb record 列表、各个 header 的字段、EXTH、索引以及 text record 的尾巴，同时检查其中不一致的地方，主要用于调试 [MobiWriter](crate::prelude::MobiWriter) 的输出
+//!
+//! | 代码 | 说明 |
+//! | --- | --- |
+//! | PDB-001 | 没有 record |
+//! | PDB-002 | record 偏移超出文件、与 header 重叠或者没有递增 |
+//! | MOBI-001 | record 0 无法解析 |
+//! | MOBI-002 | text record 数量与 record 总数不符 |
+//! | MOBI-003 | header 中的 record 序号或者偏移超出范围 |
+//! | MOBI-004 | 文本长度与 header 不符 |
+//! | MOBI-005 | text record 尾巴或者压缩数据错误 |
+//! | MOBI-006 | filepos 超出文本范围 |
+//! | MOBI-007 | 图片 recindex 超出范围 |
+//! | EXTH-001 | EXTH 无法解析或者长度不符 |
+//! | EXTH-002 | 封面序号超出范围 |
+//! | INDX-001 | 索引无法解析或者数量不符 |
+//!
+use std::{
+    fmt::Write as _,
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use super::{
+    common::{do_time_format, EXTHHeader, INDXRecord, MOBIDOCHeader, MOBIHeader, PDBHeader},
+    reader::{count_bit, count_unset_end, get_var_len, read_trailing_entries, uncompression_lz77},
+};
+use crate::{
+    common::IResult,
+    epub::validate::{Diagnostic, Severity},
+};
+
+/// 表示不存在的 record 序号
+const NONE: u32 = 0xffffffff;
+
+/// pdb 中的一个 record
+struct Record {
+    offset: u32,
+    size: usize,
+    attribute: u8,
+    unique_id: u32,
+}
+
+/// 索引中的一项
+struct IndexEntry {
+    name: String,
+    tags: Vec<(u8, Vec<usize>)>,
+}
+
+/// 一个 INDX record
+struct IndexRecord {
+    index: usize,
+    header: INDXRecord,
+    entries: Vec<IndexEntry>,
+}
+
+/// 一个 text record
+struct TextRecord {
+    index: usize,
+    size: usize,
+    /// 尾巴长度，从后往前
+    trailers: Vec<usize>,
+    /// 解压后的文本长度
+    text: Option<usize>,
+}
+
+///
+/// mobi 结构检查结果
+///
+/// 通过 [std::fmt::Display] 输出完整的结构
+///
+pub struct MobiInspection {
+    file_size: usize,
+    pdb: PDBHeader,
+    records: Vec<Record>,
+    doc: Option<MOBIDOCHeader>,
+    mobi: Option<MOBIHeader>,
+    full_name: Option<String>,
+    exth: Option<EXTHHeader>,
+    tagx: Vec<[u8; 4]>,
+    indexes: Vec<IndexRecord>,
+    texts: Vec<TextRecord>,
+    /// 解压后的文本长度，无法解压时为None
+    text_length: Option<usize>,
+    filepos: Vec<usize>,
+    issues: Vec<Diagnostic>,
+}
+
+/// 检查 mobi 文件
+///
+/// # Returns
+///
+/// 不是mobi文件时返回错误，其他问题都以诊断信息的形式返回
+///
+pub fn inspect_file<P: AsRef<Path>>(file: P) -> IResult<MobiInspection> {
+    inspect_vec(std::fs::read(file)?)
+}
+
+/// 检查 mobi
+pub fn inspect_reader<T: Read + Seek>(mut reader: T) -> IResult<MobiInspection> {
+    let mut data = Vec::new();
+    reader.seek(SeekFrom::Start(0))?;
+    reader.read_to_end(&mut data)?;
+    inspect_vec(data)
+}
+
+/// 检查内存中的 mobi
+pub fn inspect_vec(data: Vec<u8>) -> IResult<MobiInspection> {
+    let pdb = PDBHeader::load(&mut Cursor::new(data.as_slice()))?;
+    let mut v = MobiInspection {
+        file_size: data.len(),
+        pdb,
+        records: Vec::new(),
+        doc: None,
+        mobi: None,
+        full_name: None,
+        exth: None,
+        tagx: Vec::new(),
+        indexes: Vec::new(),
+        texts: Vec::new(),
+        text_length: None,
+        filepos: Vec::new(),
+        issues: Vec::new(),
+    };
+    v.inspect(&data);
+    Ok(v)
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    data.get(pos..pos + 2)
+        .map(|v| u16::from_be_bytes([v[0], v[1]]))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    data.get(pos..pos + 4)
+        .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+}
+
+/// 读取一个变长整数，返回(值，字节数)
+fn read_var(data: &[u8], pos: usize) -> Option<(usize, usize)> {
+    let bytes = data.get(pos..)?;
+    let (value, length) = get_var_len(&bytes[..bytes.len().min(4)]);
+    if length == 0 {
+        return None;
+    }
+    Some((value, length))
+}
+
+///
+/// 解析索引项的 tag
+///
+/// [entry] 索引项名称之后的数据
+/// [control_count] TAGX 中 control byte 的数量
+///
+fn read_tags(
+    entry: &[u8],
+    tagx: &[[u8; 4]],
+    control_count: usize,
+) -> Option<Vec<(u8, Vec<usize>)>> {
+    let mut control_index = 0;
+    let mut pos = control_count;
+    let mut tags = Vec::new();
+    for [tag, num_values, mask, end] in tagx.iter().copied() {
+        if end & 1 == 1 {
+            control_index += 1;
+            continue;
+        }
+        if mask == 0 {
+            continue;
+        }
+        let value = entry.get(control_index)? & mask;
+        if value == 0 {
+            continue;
+        }
+        let mut values = Vec::new();
+        if value == mask && count_bit(mask as u32) > 1 {
+            // 值的总字节数
+            let (len, l) = read_var(entry, pos)?;
+            pos += l;
+            let mut count = 0;
+            while count < len {
+                let (v, l) = read_var(entry, pos)?;
+                values.push(v);
+                pos += l;
+                count += l;
+            }
+        } else {
+            let count = if value == mask {
+                1
+            } else {
+                (value >> count_unset_end(mask)) as usize
+            };
+            for _ in 0..(count * num_values as usize) {
+                let (v, l) = read_var(entry, pos)?;
+                values.push(v);
+                pos += l;
+            }
+        }
+        tags.push((tag, values));
+    }
+    Some(tags)
+}
+
+/// 查找文本中 key 后面的数字，例如 filepos=0000001234
+fn find_numbers(text: &[u8], key: &[u8]) -> Vec<usize> {
+    let mut res = Vec::new();
+    let mut i = 0;
+    while i + key.len() <= text.len() {
+        if &text[i..i + key.len()] != key {
+            i += 1;
+            continue;
+        }
+        i += key.len();
+        while i < text.len() && (text[i] == b'"' || text[i] == b'\'') {
+            i += 1;
+        }
+        let start = i;
+        while i < text.len() && text[i].is_ascii_digit() {
+            i += 1;
+        }
+        if let Some(v) = std::str::from_utf8(&text[start..i])
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+        {
+            res.push(v);
+        }
+    }
+    res
+}
+
+/// EXTH 类型名称
+fn exth_name(t: u32) -> &'static str {
+    match t {
+        100 => "creator",
+        101 => "publisher",
+        103 => "description",
+        104 => "isbn",
+        105 => "subject",
+        106 => "date",
+        108 => "contributor",
+        109 => "rights",
+        112 => "source",
+        113 => "asin",
+        201 => "cover offset",
+        202 => "thumbnail offset",
+        203 => "has fake cover",
+        204 => "creator software",
+        205 => "creator major version",
+        206 => "creator minor version",
+        207 => "creator build number",
+        501 => "cde type",
+        503 => "updated title",
+        524 => "language",
+        525 => "primary writing mode",
+        527 => "page progression direction",
+        _ => "",
+    }
+}
+
+impl MobiInspection {
+    /// 发现的问题
+    pub fn issues(&self) -> &[Diagnostic] {
+        &self.issues
+    }
+
+    fn issue(
+        &mut self,
+        severity: Severity,
+        code: &'static str,
+        record: Option<usize>,
+        message: String,
+    ) {
+        self.issues.push(Diagnostic::new(
+            severity,
+            code,
+            record.map(|v| format!("record {}", v)),
+            None,
+            message,
+        ));
+    }
+
+    fn error(&mut self, code: &'static str, record: Option<usize>, message: String) {
+        self.issue(Severity::Error, code, record, message);
+    }
+
+    fn warning(&mut self, code: &'static str, record: Option<usize>, message: String) {
+        self.issue(Severity::Warning, code, record, message);
+    }
+
+    fn record<'a>(&self, data: &'a [u8], index: usize) -> Option<&'a [u8]> {
+        let r = self.records.get(index)?;
+        data.get(r.offset as usize..r.offset as usize + r.size)
+    }
+
+    fn inspect(&mut self, data: &[u8]) {
+        self.inspect_records(data);
+        if self.records.is_empty() || !self.inspect_headers(data) {
+            return;
+        }
+        self.inspect_exth();
+        self.inspect_text(data);
+        self.inspect_index(data);
+    }
+
+    fn inspect_records(&mut self, data: &[u8]) {
+        let list = std::mem::take(&mut self.pdb.record_info_list);
+        if list.is_empty() {
+            self.error("PDB-001", None, "pdb has no record".to_string());
+        }
+        // 78字节的header，每个record info 8个字节
+        let min = 78 + 8 * list.len();
+        for (i, info) in list.iter().enumerate() {
+            let offset = info.offset as usize;
+            if offset > data.len() {
+                self.error(
+                    "PDB-002",
+                    Some(i),
+                    format!("offset {} is outside of file (size {})", offset, data.len()),
+                );
+            } else if offset < min {
+                self.error(
+                    "PDB-002",
+                    Some(i),
+                    format!("offset {} overlaps pdb header (size {})", offset, min),
+                );
+            } else if i > 0 && info.offset < list[i - 1].offset {
+                self.error(
+                    "PDB-002",
+                    Some(i),
+                    format!(
+                        "offset {} is smaller than previous record {}",
+                        offset,
+                        list[i - 1].offset
+                    ),
+                );
+            }
+            let next = list
+                .get(i + 1)
+                .map_or(data.len(), |v| v.offset as usize)
+                .min(data.len());
+            self.records.push(Record {
+                offset: info.offset,
+                size: next.saturating_sub(offset),
+                attribute: info.attribute,
+                unique_id: info.unique_id,
+            });
+        }
+        self.pdb.record_info_list = list;
+    }
+
+    /// 解析 record 0，失败时返回false
+    fn inspect_headers(&mut self, data: &[u8]) -> bool {
+        let Some(r0) = self.record(data, 0) else {
+            self.error("MOBI-001", Some(0), "record 0 is empty".to_string());
+            return false;
+        };
+        let mut cur = Cursor::new(r0);
+        let doc = match MOBIDOCHeader::load(&mut cur, 0) {
+            Ok(v) => v,
+            Err(e) => {
+                self.error(
+                    "MOBI-001",
+                    Some(0),
+                    format!("invalid palmdoc header: {}", e),
+                );
+                return false;
+            }
+        };
+        let mobi = match MOBIHeader::load(&mut cur) {
+            Ok(v) => v,
+            Err(e) => {
+                self.error("MOBI-001", Some(0), format!("invalid mobi header: {}", e));
+                self.doc = Some(doc);
+                return false;
+            }
+        };
+        match EXTHHeader::load(&mut cur, mobi.exth_flags) {
+            Ok(v) => self.exth = v,
+            Err(e) => self.error("EXTH-001", Some(0), format!("invalid exth header: {}", e)),
+        }
+
+        // 读取时已经将 offset 改为相对于 record 0
+        let name_start = mobi.full_name_offset as usize;
+        match r0.get(name_start..name_start + mobi.full_name_length as usize) {
+            Some(v) => self.full_name = Some(String::from_utf8_lossy(v).to_string()),
+            None => self.error(
+                "MOBI-003",
+                Some(0),
+                format!(
+                    "full name (offset {}, length {}) is outside of record 0 (size {})",
+                    mobi.full_name_offset,
+                    mobi.full_name_length,
+                    r0.len()
+                ),
+            ),
+        }
+
+        let n = self.records.len();
+        if doc.record_count as usize + 1 > n {
+            self.error(
+                "MOBI-002",
+                Some(0),
+                format!(
+                    "text record count {} exceeds record count {}",
+                    doc.record_count, n
+                ),
+            );
+        } else if mobi.first_non_book_index != NONE
+            && doc.record_count as u32 + 1 > mobi.first_non_book_index
+        {
+            self.error(
+                "MOBI-002",
+                Some(0),
+                format!(
+                    "text record count {} overlaps first_non_book_index {}",
+                    doc.record_count, mobi.first_non_book_index
+                ),
+            );
+        }
+
+        // 允许等于 record 数量，表示没有对应的 record
+        for (name, value) in [
+            ("first_non_book_index", mobi.first_non_book_index),
+            ("first_image_index", mobi.first_image_index),
+        ] {
+            if value != NONE && value as usize > n {
+                self.error(
+                    "MOBI-003",
+                    Some(0),
+                    format!("{} {} is out of range (records {})", name, value, n),
+                );
+            }
+        }
+        for (name, value) in [
+            (
+                "last_content_record_number",
+                mobi.last_content_record_number as u32,
+            ),
+            ("indx_record_offset", mobi.indx_record_offset),
+            ("fcis_record_number", mobi.fcis_record_number),
+            ("flis_record_number", mobi.flis_record_number),
+        ] {
+            if value != NONE && value as usize >= n {
+                self.error(
+                    "MOBI-003",
+                    Some(0),
+                    format!("{} {} is out of range (records {})", name, value, n),
+                );
+            }
+        }
+        if mobi.first_content_record_number > mobi.last_content_record_number {
+            self.warning(
+                "MOBI-003",
+                Some(0),
+                format!(
+                    "first_content_record_number {} is greater than last_content_record_number {}",
+                    mobi.first_content_record_number, mobi.last_content_record_number
+                ),
+            );
+        }
+        if doc.compression == 17480
+            && (mobi.huffman_record_offset as usize + mobi.huffman_record_count as usize) > n
+        {
+            self.error(
+                "MOBI-003",
+                Some(0),
+                format!(
+                    "huffman records {}..{} are out of range (records {})",
+                    mobi.huffman_record_offset,
+                    mobi.huffman_record_offset as usize + mobi.huffman_record_count as usize,
+                    n
+                ),
+            );
+        }
+
+        self.doc = Some(doc);
+        self.mobi = Some(mobi);
+        true
+    }
+
+    fn inspect_exth(&mut self) {
+        let Some(exth) = self.exth.take() else {
+            return;
+        };
+        let first_image = self.mobi.as_ref().map_or(NONE, |v| v.first_image_index);
+        let n = self.records.len();
+        let len: usize = 12
+            + exth
+                .record_list
+                .iter()
+                .map(|v| v.len as usize)
+                .sum::<usize>();
+        if len != exth.len as usize {
+            self.warning(
+                "EXTH-001",
+                Some(0),
+                format!(
+                    "exth length {} does not match records length {}",
+                    exth.len, len
+                ),
+            );
+        }
+        for ele in &exth.record_list {
+            if ele.data.len() + 8 != ele.len as usize {
+                self.error(
+                    "EXTH-001",
+                    Some(0),
+                    format!("exth record {} is truncated", ele._type),
+                );
+                continue;
+            }
+            if ele._type == 201 || ele._type == 202 {
+                let Some(offset) = read_u32(&ele.data, 0) else {
+                    continue;
+                };
+                if offset != NONE
+                    && (first_image == NONE || first_image as usize + offset as usize >= n)
+                {
+                    self.error(
+                        "EXTH-002",
+                        Some(0),
+                        format!(
+                            "{} {} points outside of records (first_image_index {}, records {})",
+                            exth_name(ele._type),
+                            offset,
+                            first_image,
+                            n
+                        ),
+                    );
+                }
+            }
+        }
+        self.exth = Some(exth);
+    }
+
+    fn inspect_text(&mut self, data: &[u8]) {
+        let (Some(doc), Some(mobi)) = (&self.doc, &self.mobi) else {
+            return;
+        };
+        let compression = doc.compression;
+        let length = doc.length as usize;
+        let count = (doc.record_count as usize).min(self.records.len().saturating_sub(1));
+        let flags = mobi.extra_record_data_flags;
+        let first_image = mobi.first_image_index;
+
+        if compression == 17480 {
+            self.warning(
+                "MOBI-005",
+                None,
+                "HUFF/CDIC compression is not supported, text is not checked".to_string(),
+            );
+        } else if compression != 1 && compression != 2 {
+            self.error(
+                "MOBI-005",
+                Some(0),
+                format!("unknown compression {}", compression),
+            );
+        }
+
+        let mut text = Some(Vec::new());
+        for i in 1..=count {
+            let raw = self.record(data, i).unwrap_or_default();
+            let Some(trailers) = read_trailing_entries(raw, flags) else {
+                self.error(
+                    "MOBI-005",
+                    Some(i),
+                    format!("trailing entries exceed record size {}", raw.len()),
+                );
+                self.texts.push(TextRecord {
+                    index: i,
+                    size: raw.len(),
+                    trailers: Vec::new(),
+                    text: None,
+                });
+                text = None;
+                continue;
+            };
+            let body = &raw[..raw.len() - trailers.iter().sum::<usize>()];
+            let decoded = match compression {
+                1 => Some(body.to_vec()),
+                2 => uncompression_lz77(body),
+                _ => None,
+            };
+            if compression == 2 && decoded.is_none() {
+                self.error("MOBI-005", Some(i), "invalid lz77 data".to_string());
+            }
+            self.texts.push(TextRecord {
+                index: i,
+                size: raw.len(),
+                trailers,
+                text: decoded.as_ref().map(|v| v.len()),
+            });
+            text = text.zip(decoded).map(|(mut t, mut d)| {
+                t.append(&mut d);
+                t
+            });
+        }
+
+        let Some(text) = text else {
+            return;
+        };
+        self.text_length = Some(text.len());
+        if text.len() != length {
+            self.error(
+                "MOBI-004",
+                None,
+                format!(
+                    "text length {} does not match header length {}",
+                    text.len(),
+                    length
+                ),
+            );
+        }
+
+        self.filepos = find_numbers(&text, b"filepos=");
+        let mut outside: Vec<usize> = self
+            .filepos
+            .iter()
+            .filter(|v| **v > text.len())
+            .copied()
+            .collect();
+        outside.dedup();
+        for ele in outside {
+            self.error(
+                "MOBI-006",
+                None,
+                format!("filepos {} is outside of text (length {})", ele, text.len()),
+            );
+        }
+
+        let n = self.records.len();
+        let mut recindex = find_numbers(&text, b"recindex=");
+        recindex.sort();
+        recindex.dedup();
+        for ele in recindex {
+            if first_image == NONE || first_image as usize + ele >= n {
+                self.error(
+                    "MOBI-007",
+                    None,
+                    format!(
+                        "recindex {} points outside of records (first_image_index {}, records {})",
+                        ele, first_image, n
+                    ),
+                );
+            }
+        }
+    }
+
+    fn inspect_index(&mut self, data: &[u8]) {
+        let Some(indx) = self
+            .mobi
+            .as_ref()
+            .map(|v| v.indx_record_offset)
+            .filter(|v| *v != NONE && (*v as usize) < self.records.len())
+        else {
+            return;
+        };
+        let indx = indx as usize;
+        let primary = self.record(data, indx).unwrap_or_default();
+        let mut cur = Cursor::new(primary);
+        let header = match INDXRecord::load(&mut cur) {
+            Ok(v) => v,
+            Err(e) => {
+                self.error("INDX-001", Some(indx), format!("invalid indx: {}", e));
+                return;
+            }
+        };
+
+        // TAGX 紧跟在 INDX header 之后
+        let pos = header.len as usize;
+        let mut control_count = 0;
+        if primary.get(pos..pos + 4) == Some(b"TAGX".as_slice()) {
+            let len = read_u32(primary, pos + 4).unwrap_or(0) as usize;
+            control_count = read_u32(primary, pos + 8).unwrap_or(0) as usize;
+            let mut p = pos + 12;
+            while p + 4 <= pos + len {
+                match primary.get(p..p + 4) {
+                    Some(v) => self.tagx.push([v[0], v[1], v[2], v[3]]),
+                    None => {
+                        self.error("INDX-001", Some(indx), "TAGX is truncated".to_string());
+                        break;
+                    }
+                }
+                p += 4;
+            }
+        } else {
+            self.error("INDX-001", Some(indx), "TAGX is missing".to_string());
+        }
+
+        let total = header.total_index_count as usize;
+        let count = header.index_count as usize;
+        self.indexes.push(IndexRecord {
+            index: indx,
+            header,
+            entries: Vec::new(),
+        });
+
+        let mut found = 0;
+        for i in (indx + 1)..=(indx + count) {
+            let Some(r) = self.record(data, i) else {
+                self.error(
+                    "INDX-001",
+                    Some(i),
+                    format!(
+                        "index record is out of range (records {})",
+                        self.records.len()
+                    ),
+                );
+                break;
+            };
+            let header = match INDXRecord::load(&mut Cursor::new(r)) {
+                Ok(v) => v,
+                Err(e) => {
+                    self.error("INDX-001", Some(i), format!("invalid indx: {}", e));
+                    continue;
+                }
+            };
+            let mut entries = Vec::new();
+            for j in 0..header.index_count as usize {
+                let entry = read_u16(r, header.idxt_start as usize + 4 + 2 * j)
+                    .map(|v| v as usize)
+                    .and_then(|offset| {
+                        let len = *r.get(offset)? as usize;
+                        let name = r.get(offset + 1..offset + 1 + len)?;
+                        let tags =
+                            read_tags(r.get(offset + 1 + len..)?, &self.tagx, control_count)?;
+                        Some(IndexEntry {
+                            name: String::from_utf8_lossy(name).to_string(),
+                            tags,
+                        })
+                    });
+                match entry {
+                    Some(v) => entries.push(v),
+                    None => {
+                        self.error(
+                            "INDX-001",
+                            Some(i),
+                            format!("index entry {} is outside of record", j),
+                        );
+                        break;
+                    }
+                }
+            }
+            found += entries.len();
+            self.indexes.push(IndexRecord {
+                index: i,
+                header,
+                entries,
+            });
+        }
+        if found != total {
+            self.warning(
+                "INDX-001",
+                Some(indx),
+                format!(
+                    "total_index_count {} does not match entries {}",
+                    total, found
+                ),
+            );
+        }
+    }
+}
+
+/// 输出结构体的字段
+macro_rules! write_fields {
+    ($f:expr, $v:expr, $($field:ident),*) => {
+        $(
+            writeln!($f, "  {}: {}", stringify!($field), $v.$field)?;
+        )*
+    };
+}
+
+impl std::fmt::Display for MobiInspection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pdb = &self.pdb;
+        writeln!(f, "PDB header")?;
+        writeln!(
+            f,
+            "  name: {}",
+            String::from_utf8_lossy(&pdb.name).trim_end_matches('\0')
+        )?;
+        write_fields!(f, pdb, attribute, version);
+        writeln!(
+            f,
+            "  createion_date: {}",
+            do_time_format(pdb.createion_date)
+        )?;
+        writeln!(f, "  modify_date: {}", do_time_format(pdb.modify_date))?;
+        writeln!(
+            f,
+            "  last_backup_date: {}",
+            do_time_format(pdb.last_backup_date)
+        )?;
+        write_fields!(
+            f,
+            pdb,
+            modification_number,
+            app_info_id,
+            sort_info_id,
+            unique_id_seed,
+            next_record_list_id,
+            number_of_records
+        );
+        writeln!(f, "  file_size: {}", self.file_size)?;
+
+        writeln!(f, "\nRecords")?;
+        writeln!(
+            f,
+            "  {:>6} {:>10} {:>8} {:>9} {:>9}",
+            "index", "offset", "size", "attribute", "unique_id"
+        )?;
+        for (i, r) in self.records.iter().enumerate() {
+            writeln!(
+                f,
+                "  {:>6} {:>10} {:>8} {:>9} {:>9}",
+                i, r.offset, r.size, r.attribute, r.unique_id
+            )?;
+        }
+
+        if let Some(doc) = &self.doc {
+            writeln!(f, "\nPalmDOC header")?;
+            write_fields!(
+                f,
+                doc,
+                compression,
+                length,
+                record_count,
+                record_size,
+                position,
+                encrypt_type
+            );
+        }
+
+        if let Some(mobi) = &self.mobi {
+            writeln!(f, "\nMOBI header")?;
+            write_fields!(
+                f,
+                mobi,
+                header_len,
+                mobi_type,
+                text_encoding,
+                unique_id,
+                file_version,
+                ortographic_index,
+                inflection_index,
+                index_names,
+                index_keys
+            );
+            writeln!(f, "  extra_index: {:?}", mobi.extra_index)?;
+            write_fields!(
+                f,
+                mobi,
+                first_non_book_index,
+                full_name_offset,
+                full_name_length
+            );
+            if let Some(name) = &self.full_name {
+                writeln!(f, "  full_name: {}", name)?;
+            }
+            write_fields!(
+                f,
+                mobi,
+                locale,
+                input_language,
+                output_language,
+                min_version,
+                first_image_index,
+                huffman_record_offset,
+                huffman_record_count,
+                huffman_table_offset,
+                huffman_table_length,
+                exth_flags,
+                drm_offset,
+                drm_count,
+                drm_size,
+                drm_flags,
+                first_content_record_number,
+                last_content_record_number,
+                fcis_record_number,
+                flis_record_number,
+                first_compilation_data_section_count,
+                number_of_compilation_data_sections,
+                extra_record_data_flags,
+                indx_record_offset
+            );
+        }
+
+        if let Some(exth) = &self.exth {
+            writeln!(
+                f,
+                "\nEXTH header (len: {}, record_count: {})",
+                exth.len, exth.record_count
+            )?;
+            for ele in &exth.record_list {
+                let value = match (ele._type, read_u32(&ele.data, 0)) {
+                    (115 | 116 | 121 | 125 | 131 | 201..=207 | 401..=406, Some(v))
+                        if ele.data.len() == 4 =>
+                    {
+                        v.to_string()
+                    }
+                    _ => format!("{:?}", String::from_utf8_lossy(&ele.data)),
+                };
+                writeln!(f, "  {:>4} {}: {}", ele._type, exth_name(ele._type), value)?;
+            }
+        }
+
+        if !self.indexes.is_empty() {
+            writeln!(f, "\nIndex")?;
+            writeln!(f, "  TAGX: {:?}", self.tagx)?;
+        }
+        for ele in &self.indexes {
+            let h = &ele.header;
+            writeln!(
+                f,
+                "  record {}: type {}, idxt_start {}, index_count {}, total_index_count {}, encoding {}, cncx_count {}",
+                ele.index,
+                h._type,
+                h.idxt_start,
+                h.index_count,
+                h.total_index_count,
+                h.index_encoding,
+                h.cncx_count
+            )?;
+            for entry in &ele.entries {
+                let mut tags = String::new();
+                for (tag, values) in &entry.tags {
+                    let _ = write!(tags, " {}={:?}", tag, values);
+                }
+                writeln!(f, "    {}:{}", entry.name, tags)?;
+            }
+        }
+
+        if !self.texts.is_empty() {
+            writeln!(f, "\nText records")?;
+            for ele in &self.texts {
+                writeln!(
+                    f,
+                    "  record {}: size {}, trailers {:?}, text {}",
+                    ele.index,
+                    ele.size,
+                    ele.trailers,
+                    ele.text.map_or_else(|| "-".to_string(), |v| v.to_string())
+                )?;
+            }
+        }
+        if let Some(length) = self.text_length {
+            writeln!(
+                f,
+                "  text length: {}, filepos count: {}",
+                length,
+                self.filepos.len()
+            )?;
+        }
+
+        writeln!(f, "\nIssues")?;
+        if self.issues.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for ele in &self.issues {
+            writeln!(f, "  {}", ele)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    fn book() -> Vec<u8> {
+        MobiBuilder::default()
+            .with_title("书名")
+            .with_creator("作者")
+            .add_chapter(
+                MobiHtml::new(1)
+                    .with_title("标题")
+                    .with_data("<p>锻炼</p><img src='1.jpg'/>".as_bytes().to_vec()),
+            )
+            .add_chapter(
+                MobiHtml::new(2)
+                    .with_title("标题2")
+                    .with_data("<p>锻炼2</p>".repeat(500).as_bytes().to_vec()),
+            )
+            .add_assets("1.jpg", vec![0xff, 0xd8, 0xff, 0xe0, 1, 2, 3])
+            .cover(vec![0x89, b'P', b'N', b'G', 1, 2, 3])
+            .mem()
+            .unwrap()
+    }
+
+    fn codes(v: &MobiInspection) -> Vec<&str> {
+        v.issues().iter().map(|v| v.code()).collect()
+    }
+
+    /// 修改 record 0 中的 u32
+    fn patch_u32(data: &mut [u8], offset: usize, value: u32) {
+        let r0 = read_u32(data, 78).unwrap() as usize;
+        data[r0 + offset..r0 + offset + 4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    #[test]
+    fn test_inspect() {
+        let v = inspect_vec(book()).unwrap();
+        assert!(v.issues().is_empty(), "{}", v);
+
+        let out = v.to_string();
+        assert!(out.contains("  name: 书名\n"));
+        assert!(out.contains("  number_of_records: 6\n"));
+        assert!(out.contains("  first_image_index: 4\n"));
+        assert!(out.contains("   503 updated title: \"书名\"\n"));
+        assert!(out.contains("   201 cover offset: 0\n"));
+        assert!(out.contains("  record 1: size 4096, trailers [], text 4096\n"));
+        assert!(out.ends_with("Issues\n  none\n"));
+
+        assert!(inspect_vec(vec![0u8; 100]).is_err());
+    }
+
+    #[test]
+    fn test_broken() {
+        // first_image_index
+        let mut data = book();
+        patch_u32(&mut data, 108, 100);
+        let v = inspect_vec(data).unwrap();
+        assert_eq!(
+            vec!["MOBI-003", "EXTH-002", "EXTH-002", "MOBI-007"],
+            codes(&v)
+        );
+        assert_eq!(Some("record 0"), v.issues()[0].file());
+
+        // text record 数量
+        let mut data = book();
+        let r0 = read_u32(&data, 78).unwrap() as usize;
+        data[r0 + 8..r0 + 10].copy_from_slice(&50u16.to_be_bytes());
+        let v = inspect_vec(data).unwrap();
+        assert_eq!("MOBI-002", v.issues()[0].code());
+        assert!(codes(&v).contains(&"MOBI-004"));
+
+        // record 偏移
+        let mut data = book();
+        data[78 + 8 * 5..78 + 8 * 5 + 4].copy_from_slice(&0xffffffu32.to_be_bytes());
+        let v = inspect_vec(data).unwrap();
+        assert_eq!(vec!["PDB-002"], codes(&v));
+        assert_eq!(Some("record 5"), v.issues()[0].file());
+
+        // filepos
+        let mut data = book();
+        let pos = data.windows(8).position(|v| v == b"filepos=").unwrap() + 8;
+        let start = pos + data[pos..].iter().position(|v| v.is_ascii_digit()).unwrap();
+        let end = start
+            + data[start..]
+                .iter()
+                .position(|v| !v.is_ascii_digit())
+                .unwrap();
+        data[start..end].fill(b'9');
+        let v = inspect_vec(data).unwrap();
+        assert_eq!(vec!["MOBI-006"], codes(&v));
+
+        // 尾巴
+        let mut data = book();
+        patch_u32(&mut data, 240, 2);
+        let v = inspect_vec(data).unwrap();
+        assert!(codes(&v).contains(&"MOBI-005"), "{}", v);
+    }
+
+    #[test]
+    fn test_read_tags() {
+        let tagx = [[1, 1, 1, 0], [2, 1, 2, 0], [3, 1, 4, 0], [0, 0, 0, 1]];
+        assert_eq!(
+            Some(vec![(1, vec![5]), (2, vec![200])]),
+            read_tags(&[0b011, 0x85, 0x01, 0xc8], &tagx, 1)
+        );
+        assert_eq!(None, read_tags(&[0b011, 0x85], &tagx, 1));
+
+        assert_eq!(
+            vec![12, 34],
+            find_numbers(
+                b"<a filepos=0000000012/><a filepos='34'><a filepos=>",
+                b"filepos="
+            )
+        );
+    }
+}
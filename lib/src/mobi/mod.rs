@@ -1,7 +1,9 @@
+pub(crate) mod appender;
 pub(crate) mod builder;
 pub(crate) mod common;
 pub(crate) mod core;
 pub(crate) mod image;
+pub(crate) mod inspect;
 pub(crate) mod nav;
 pub(crate) mod reader;
 pub(crate) mod writer;
@@ -7,7 +7,7 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use quick_xml::events::{BytesStart, Event};
 use quick_xml::reader::Reader;
 
-use crate::common::{IError, IResult};
+use crate::common::{escape_xml, ErrorContext, IError, IResult};
 
 use super::core::MobiNav;
 
@@ -20,11 +20,7 @@ trait VecExt<T> {
 
 impl<T> VecExt<T> for Vec<T> {
     fn rget(&mut self, r_index: usize) -> Option<&mut T> {
-        if self.is_empty() {
-            return None;
-        }
-
-        let index = self.len() - r_index - 1;
+        let index = self.len().checked_sub(r_index + 1)?;
         self.get_mut(index)
     }
 }
@@ -45,7 +41,7 @@ pub(crate) fn read_nav_xml(xml: Vec<u8>) -> IResult<Vec<MobiNav>> {
 
                 if name == "a" {
                     // 这里的上一级应该只有 p, 上一级是 blockquote 的情况 在text的时候交给其他方法处理了
-                    let pa = &parent[parent.len() - 1];
+                    let pa = parent.last().map_or("", String::as_str);
                     if pa == "p" {
                         let mut n = MobiNav::default(unsafe { ID.fetch_add(1, Ordering::SeqCst) });
                         if let Some(pos) = e.get_file_pos() {
@@ -68,24 +64,26 @@ pub(crate) fn read_nav_xml(xml: Vec<u8>) -> IResult<Vec<MobiNav>> {
                     std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a tagx")
                 })?;
                 parent.pop();
-                if name == "a" && now.is_some() {
-                    // 如果a标签内容为空或者只有空白， text不会被触发，只有end
-                    let pa = &parent[parent.len() - 1];
-                    if pa == "p" {
-                        // 读取这一卷下的目录
-                        let has_more = read_blockquote(&mut reader, now.as_mut().unwrap())?;
-                        nav.push(now.clone().unwrap());
-                        now = None;
+                if name == "a" {
+                    if let Some(mut n) = now.take() {
+                        // 如果a标签内容为空或者只有空白， text不会被触发，只有end
+                        let pa = parent.last().map_or("", String::as_str);
+                        if pa == "p" {
+                            // 读取这一卷下的目录
+                            let has_more = read_blockquote(&mut reader, &mut n)?;
+                            nav.push(n);
 
-                        if has_more {
-                            parent.push("p".to_string());
+                            if has_more {
+                                parent.push("p".to_string());
+                            } else {
+                                // 没有东西，直接结束
+                                return Ok(nav);
+                            }
+                        } else if pa == "blockquote" {
+                            nav.push(n);
                         } else {
-                            // 没有东西，直接结束
-                            return Ok(nav);
+                            now = Some(n);
                         }
-                    } else if pa == "blockquote" {
-                        nav.push(now.unwrap());
-                        now = None;
                     }
                 }
             }
@@ -97,16 +95,15 @@ pub(crate) fn read_nav_xml(xml: Vec<u8>) -> IResult<Vec<MobiNav>> {
                     if ppa == "p" {
                         // 新的一卷
 
-                        if now.is_some() {
-                            now.as_mut().unwrap().title = match e.unescape() {
+                        if let Some(mut n) = now.take() {
+                            n.title = match e.unescape() {
                                 Ok(v) => v.deref().to_string(),
                                 Err(e) => return Err(crate::common::IError::Xml(e)),
                             };
 
                             // 读取这一卷下的目录
-                            let has_more = read_blockquote(&mut reader, now.as_mut().unwrap())?;
-                            nav.push(now.clone().unwrap());
-                            now = None;
+                            let has_more = read_blockquote(&mut reader, &mut n)?;
+                            nav.push(n);
 
                             if has_more {
                                 parent.push("p".to_string());
@@ -117,13 +114,12 @@ pub(crate) fn read_nav_xml(xml: Vec<u8>) -> IResult<Vec<MobiNav>> {
                         }
                     } else if ppa == "blockquote" {
                         // 一级目录
-                        if now.is_some() {
-                            now.as_mut().unwrap().title = match e.unescape() {
+                        if let Some(mut n) = now.take() {
+                            n.title = match e.unescape() {
                                 Ok(v) => v.deref().to_string(),
                                 Err(e) => return Err(crate::common::IError::Xml(e)),
                             };
-                            nav.push(now.unwrap());
-                            now = None;
+                            nav.push(n);
 
                             let mut temp = MobiNav::default(0);
                             let has_more = read_blockquote(&mut reader, &mut temp)?;
@@ -196,10 +192,10 @@ fn generate_human_nav_item_xml(start: usize, nav: &[MobiNav]) -> (Vec<u8>, Vec<N
             child: Vec::new(),
             chap_id: ele.chap_id,
         };
-        let mut title = ele.title();
+        let mut title = escape_xml(ele.title());
         if title.len() == 0 {
             // 如果没有title的话，读取xml的时候这个章节就读不到了
-            title = "&#x20;"
+            title = Cow::Borrowed("&#x20;")
         }
         text.append(
             &mut format!(r#"{:0width$}>{}</a>"#, 0, title, width = len)
@@ -233,6 +229,7 @@ pub(crate) fn generate_human_nav_xml(
     title: &str,
 ) -> (Vec<u8>, Vec<NavFilePos>) {
     let mut text = Vec::new();
+    let title = escape_xml(title);
     text.append(&mut format!(r#"<p height="1em" width="0pt"><font size="6"><b>{title}</b></font></p><div height="1em"></div><ol width="0pt">"#).as_bytes().to_vec());
 
     let (mut n_text, pos) = generate_human_nav_item_xml(start + text.len(), nav);
@@ -242,27 +239,35 @@ pub(crate) fn generate_human_nav_xml(
     (text, pos)
 }
 
+///
+/// 有下级的目录使用 p，没有下级的使用 blockquote
+///
+/// 读取时 p 之后的 blockquote 都属于这个 p，所以第一层中位于卷之后的目录也要使用 p，否则会被读取为上一卷的下级
+///
 fn generate_reader_nav_item_xml(
     start: usize,
     nav: &[MobiNav],
     pos_value: &HashMap<usize, usize>,
+    top_level: bool,
 ) -> Vec<u8> {
     let mut text = Vec::new();
     let len = 10;
+    let mut after_volume = false;
     for ele in nav {
-        if ele.children().len() == 0 {
+        if ele.children().len() == 0 && !(top_level && after_volume) {
             let t = format!(
                 r#"<blockquote height="0pt" width="0pt"><a filepos={:0width$}>{}</a></blockquote>"#,
                 pos_value.get(&ele.chap_id).unwrap_or(&0),
-                ele.title(),
+                escape_xml(ele.title()),
                 width = len
             );
             text.append(&mut t.as_bytes().to_vec());
         } else {
-            let mut title = ele.title();
+            after_volume = true;
+            let mut title = escape_xml(ele.title());
             if title.len() == 0 {
                 // 如果没有title的话，读取xml的时候这个章节就读不到了
-                title = "&#x20;"
+                title = Cow::Borrowed("&#x20;")
             }
             let t = format!(
                 r#"<p height="1em" width="-19pt"><a filepos={:0width$}>{}</a></p>"#,
@@ -276,6 +281,7 @@ fn generate_reader_nav_item_xml(
                 start + text.len(),
                 ele.children().as_slice(),
                 pos_value,
+                false,
             );
             text.append(&mut n_text);
         }
@@ -292,7 +298,7 @@ pub(crate) fn generate_reader_nav_xml(
     let mut text = Vec::new();
     text.append(&mut format!(r#"<p height="1em" width="0pt" align="center"><font size="7"><b>Table of Contents</b></font></p>"#).as_bytes().to_vec());
 
-    let mut n_text = generate_reader_nav_item_xml(text.len() + start, nav, pos_value);
+    let mut n_text = generate_reader_nav_item_xml(text.len() + start, nav, pos_value, true);
 
     text.append(&mut n_text);
 
@@ -309,14 +315,13 @@ fn read_blockquote(reader: &mut Reader<Cursor<Vec<u8>>>, parent: &mut MobiNav) -
                 let name = String::from_utf8(e.name().as_ref().to_vec())?;
 
                 if name == "a" {
-                    now = Some(MobiNav::default(unsafe {
-                        ID.fetch_add(1, Ordering::SeqCst)
-                    }));
+                    let mut n = MobiNav::default(unsafe { ID.fetch_add(1, Ordering::SeqCst) });
 
                     // quick_xml 不支持 unquoted 的属性值解析，所以只能想办法自己来了
                     if let Some(pos) = e.get_file_pos() {
-                        now.as_mut().unwrap().href = pos;
+                        n.href = pos;
                     }
+                    now = Some(n);
                 } else if name == "p" {
                     // 读取到p，说明后面还有东西
                     return Ok(true);
@@ -326,7 +331,7 @@ fn read_blockquote(reader: &mut Reader<Cursor<Vec<u8>>>, parent: &mut MobiNav) -
                 if let Some(n) = &mut now {
                     n.title = match e.unescape() {
                         Ok(v) => v.deref().to_string(),
-                        Err(_) => return Err(IError::InvalidArchive(Cow::from("xml error"))),
+                        Err(e) => return Err(IError::Xml(e)),
                     };
                 }
             }
@@ -343,7 +348,8 @@ fn read_blockquote(reader: &mut Reader<Cursor<Vec<u8>>>, parent: &mut MobiNav) -
                 break;
             }
             Err(e) => {
-                return Err(IError::Xml(e));
+                return Err(IError::Xml(e)
+                    .with_context(ErrorContext::default().with_position(reader.error_position())));
             }
             _ => (),
         }
@@ -392,7 +398,8 @@ pub(crate) fn read_guide_filepos(html: &[u8]) -> IResult<Option<usize>> {
                 break;
             }
             Err(e) => {
-                return Err(IError::Xml(e));
+                return Err(IError::Xml(e)
+                    .with_context(ErrorContext::default().with_position(reader.error_position())));
             }
             _ => (),
         }
@@ -417,7 +424,10 @@ impl<'a> FilePosAttr for BytesStart<'a> {
             while j < key.len() {
                 if now == key[j] {
                     index += 1;
-                    now = attr[index];
+                    match attr.get(index) {
+                        Some(v) => now = *v,
+                        None => return None,
+                    }
                 } else {
                     break;
                 }
@@ -447,8 +457,7 @@ impl<'a> FilePosAttr for BytesStart<'a> {
                     now = attr[index];
                     if now >= 0x30 && now <= 0x39 {
                         // 数字0-9
-                        res = res * 10;
-                        res += (now - 0x30) as usize;
+                        res = res.checked_mul(10)?.checked_add((now - 0x30) as usize)?;
                     } else if now == 0x20 || now == 0x27 || now == 0x22 {
                         break;
                     } else {
@@ -944,8 +953,56 @@ mod tests {
         let text = generate_reader_nav_xml(0, nav.as_slice(), &pos_value);
 
         assert_eq!(
-            r#"<p height="1em" width="0pt" align="center"><font size="7"><b>Table of Contents</b></font></p><p height="1em" width="-19pt"><a filepos=0000000054>第一卷</a></p><blockquote height="0pt" width="0pt"><a filepos=0000000055>第一卷第一章</a></blockquote><blockquote height="0pt" width="0pt"><a filepos=0000000056>第一卷第2章</a></blockquote><blockquote height="0pt" width="0pt"><a filepos=0000000057>第2卷</a></blockquote>"#,
+            r#"<p height="1em" width="0pt" align="center"><font size="7"><b>Table of Contents</b></font></p><p height="1em" width="-19pt"><a filepos=0000000054>第一卷</a></p><blockquote height="0pt" width="0pt"><a filepos=0000000055>第一卷第一章</a></blockquote><blockquote height="0pt" width="0pt"><a filepos=0000000056>第一卷第2章</a></blockquote><p height="1em" width="-19pt"><a filepos=0000000057>第2卷</a></p>"#,
             String::from_utf8(text).unwrap()
         );
     }
+
+    #[test]
+    fn test_escape_property() {
+        fn prop(titles: Vec<String>) -> quickcheck::TestResult {
+            // 读取时会去除首尾空白，空标题则没有占位，这里只关注转义
+            let titles: Vec<String> = titles
+                .iter()
+                .map(|t| {
+                    t.chars()
+                        .filter(|c| *c >= ' ' && *c != '\u{FFFE}' && *c != '\u{FFFF}')
+                        .collect()
+                })
+                .collect();
+            if titles.is_empty()
+                || titles
+                    .iter()
+                    .any(|t: &String| t.is_empty() || t.trim() != t)
+            {
+                return quickcheck::TestResult::discard();
+            }
+            let nav: Vec<MobiNav> = titles
+                .iter()
+                .enumerate()
+                .map(|(index, title)| MobiNav {
+                    id: index,
+                    title: title.clone(),
+                    href: 1,
+                    children: Vec::new(),
+                    chap_id: index,
+                })
+                .collect();
+            let pos_value: HashMap<usize, usize> = (0..nav.len()).map(|v| (v, v)).collect();
+
+            let text = generate_reader_nav_xml(0, nav.as_slice(), &pos_value);
+            let n = match read_nav_xml(text) {
+                Ok(v) => v,
+                Err(_) => return quickcheck::TestResult::failed(),
+            };
+            quickcheck::TestResult::from_bool(
+                n.iter().map(|v| v.title.as_str()).collect::<Vec<&str>>() == titles,
+            )
+        }
+
+        assert!(!prop(vec!["<a & b>".to_string(), "\"'&#x20;".to_string()]).is_failure());
+        quickcheck::QuickCheck::new()
+            .tests(300)
+            .quickcheck(prop as fn(Vec<String>) -> quickcheck::TestResult);
+    }
 }
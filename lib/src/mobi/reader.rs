@@ -11,7 +11,7 @@ use std::{
 };
 
 use crate::{
-    common::{BookInfo, IError, IResult},
+    common::{BookInfo, ErrorContext, IError, IResult, ResultContext},
     mobi::core::MobiNav,
 };
 
@@ -35,7 +35,7 @@ fn vec_u8_to_u64(v: &[u8]) -> u64 {
 }
 
 impl PDBHeader {
-    fn load<T>(reader: &mut T) -> IResult<Self>
+    pub(crate) fn load<T>(reader: &mut T) -> IResult<Self>
     where
         T: Read + Seek,
     {
@@ -82,7 +82,7 @@ impl PDBHeader {
 }
 
 impl MOBIDOCHeader {
-    fn load<T>(reader: &mut T, offset: u64) -> IResult<Self>
+    pub(crate) fn load<T>(reader: &mut T, offset: u64) -> IResult<Self>
     where
         T: Read + Seek,
     {
@@ -128,9 +128,11 @@ impl MOBIHeader {
         header.first_non_book_index = reader.read_u32()?;
         // 规范里要求 offset 是从 record 0 开始，也就是当前这个 mobi header，但是为了方便，这里给改成从 文件开头开始索引
         header.full_name_offset = reader.read_u32()?;
-        header.full_name_offset += start as u32;
         // palm_doc 的16个字节也在 record 0 里面，所以还需要去掉
-        header.full_name_offset -= 16;
+        header.full_name_offset = header
+            .full_name_offset
+            .wrapping_add(start as u32)
+            .wrapping_sub(16);
 
         header.full_name_length = reader.read_u32()?;
         header.locale = reader.read_u32()?;
@@ -179,6 +181,9 @@ impl EXTHRecord {
         let mut v = Self::default();
         v._type = reader.read_u32()?;
         v.len = reader.read_u32()?;
+        if v.len < 8 {
+            return Err(IError::Parse(Cow::from("invalid exth record length")));
+        }
 
         reader.take((v.len - 8) as u64).read_to_end(&mut v.data)?;
 
@@ -212,7 +217,7 @@ impl EXTHHeader {
         let mut v = Self::default();
 
         if "EXTH" != reader.read_string(4)? {
-            return Err(IError::InvalidArchive(Cow::from("not a exth")));
+            return Err(IError::Parse(Cow::from("not a exth")));
         }
 
         v.len = reader.read_u32()?;
@@ -277,6 +282,20 @@ impl EXTHHeader {
                 503 => {
                     info.title = simple_utf8!(ele.data);
                 }
+                524 => {
+                    info.language = Some(simple_utf8!(ele.data));
+                }
+                525 => {
+                    let v = simple_utf8!(ele.data);
+                    info.writing_mode = Some(if v == "horizontal-lr" {
+                        "horizontal-tb".to_string()
+                    } else {
+                        v
+                    });
+                }
+                527 => {
+                    info.page_direction = Some(simple_utf8!(ele.data));
+                }
                 _ => {}
             }
         }
@@ -292,7 +311,7 @@ impl INDXRecord {
         let mut v = Self::default();
 
         if reader.read_string(4)? != "INDX" {
-            return Err(IError::InvalidArchive(Cow::from("not a indx")));
+            return Err(IError::Parse(Cow::from("not a indx")));
         }
 
         v.len = reader.read_u32()?;
@@ -309,7 +328,12 @@ impl INDXRecord {
         v.cncx_count = reader.read_u32()?;
 
         // 整个indx不止文档里的56个字节，多出的长度应该就是 index value
-        reader.skip((v.len - 56).into())?;
+        reader.skip(
+            v.len
+                .checked_sub(56)
+                .ok_or(IError::Parse(Cow::from("invalid indx length")))?
+                .into(),
+        )?;
         // reader.seek(SeekFrom::Start(start + v.len as u64))?;
 
         Ok(v)
@@ -317,7 +341,7 @@ impl INDXRecord {
 }
 
 /// 计算一个数字 有多少位是1
-fn count_bit(v: u32) -> usize {
+pub(crate) fn count_bit(v: u32) -> usize {
     let mut count = 0;
     let mut nv = v;
     while (nv) > 0 {
@@ -329,16 +353,16 @@ fn count_bit(v: u32) -> usize {
     return count;
 }
 /// 统计有多少位0 ，因为不同类型循环次数不同，会非常影响结果
-fn count_unset_end(v: u8) -> usize {
+pub(crate) fn count_unset_end(v: u8) -> usize {
     let mut count = 0;
     let mut x = v;
-    while (x & 1) == 0 {
+    while x != 0 && (x & 1) == 0 {
         x = x >> 1;
         count += 1;
     }
     count
 }
-fn get_var_len(byte: &[u8]) -> (usize, usize) {
+pub(crate) fn get_var_len(byte: &[u8]) -> (usize, usize) {
     let mut value: usize = 0;
     let mut length: usize = 0;
     for ele in byte {
@@ -507,12 +531,29 @@ impl<T: Read + Seek> MobiReader<T> {
         }
         reader.seek(SeekFrom::Start(0))?;
 
-        let pdb_header = PDBHeader::load(&mut reader)?;
-        let mobi_doc_header =
-            MOBIDOCHeader::load(&mut reader, pdb_header.record_info_list[0].offset as u64)?;
-        let mobi_header = MOBIHeader::load(&mut reader)?;
-
-        let exth_header = EXTHHeader::load(&mut reader, mobi_header.exth_flags)?;
+        let pdb_header = PDBHeader::load(&mut reader)
+            .context(|| ErrorContext::new("read pdb header").with_offset(0))?;
+        let start = pdb_header
+            .record_info_list
+            .first()
+            .ok_or(IError::Parse(Cow::from("mobi has no record")))
+            .context(|| ErrorContext::new("read pdb header").with_offset(0))?
+            .offset as u64;
+        let mobi_doc_header = MOBIDOCHeader::load(&mut reader, start)
+            .context(|| ErrorContext::new("read palmdoc header").with_record(0))?;
+        let offset = reader.stream_position()? - start;
+        let mobi_header = MOBIHeader::load(&mut reader).context(|| {
+            ErrorContext::new("read mobi header")
+                .with_record(0)
+                .with_offset(offset)
+        })?;
+
+        let offset = reader.stream_position()? - start;
+        let exth_header = EXTHHeader::load(&mut reader, mobi_header.exth_flags).context(|| {
+            ErrorContext::new("read exth header")
+                .with_record(0)
+                .with_offset(offset)
+        })?;
 
         Ok(MobiReader {
             reader,
@@ -551,13 +592,26 @@ impl<T: Read + Seek> MobiReader<T> {
     /// (当前的offset，下一个的offset)
     ///
     pub(crate) fn seek_record_offset(&mut self, index: u32) -> IResult<(u64, u64)> {
-        let offset = self.pdb_header.record_info_list[index as usize].offset as u64;
+        let list = &self.pdb_header.record_info_list;
+        let offset = match list.get(index as usize) {
+            Some(v) => v.offset as u64,
+            None => {
+                return Err(IError::Parse(Cow::from("record index out of range"))
+                    .with_context(ErrorContext::new("seek record").with_record(index as usize)));
+            }
+        };
+        // 最后一个record一直到文件末尾
+        let next = match list.get(index as usize + 1) {
+            Some(v) => v.offset as u64,
+            None => self.reader.seek(SeekFrom::End(0))?,
+        };
+        if next < offset {
+            return Err(IError::Parse(Cow::from("record offset out of order"))
+                .with_context(ErrorContext::new("seek record").with_record(index as usize)));
+        }
         self.reader.seek(SeekFrom::Start(offset))?;
 
-        Ok((
-            offset,
-            self.pdb_header.record_info_list[(index + 1) as usize].offset as u64,
-        ))
+        Ok((offset, next))
     }
 
     /// 从文本中获取目录信息
@@ -581,17 +635,29 @@ impl<T: Read + Seek> MobiReader<T> {
         if self.mobi_header.indx_record_offset < 0xffffffff {
             self.seek_record_offset(self.mobi_header.indx_record_offset)?;
 
-            let indx = INDXRecord::load(&mut self.reader)?;
+            let indx = INDXRecord::load(&mut self.reader).context(|| {
+                ErrorContext::new("read index")
+                    .with_record(self.mobi_header.indx_record_offset as usize)
+            })?;
 
             if self.reader.read_string(4)? != "TAGX" {
-                return Err(IError::InvalidArchive(Cow::from("not a tagx")));
+                return Err(IError::Parse(Cow::from("not a tagx")).with_context(
+                    ErrorContext::new("read index")
+                        .with_record(self.mobi_header.indx_record_offset as usize),
+                ));
             }
             let mut tagx_table: Vec<[u8; 4]> = Vec::new();
             let len = self.reader.read_u32()?;
             // the number of control bytes
             let tagx_control_byte_count = self.reader.read_u32()?;
 
-            for _ in 0..((len - 12) / 4) {
+            let count = len.checked_sub(12).ok_or_else(|| {
+                IError::Parse(Cow::from("invalid tagx length")).with_context(
+                    ErrorContext::new("read index")
+                        .with_record(self.mobi_header.indx_record_offset as usize),
+                )
+            })? / 4;
+            for _ in 0..count {
                 // 四个字节的含义
                 // The tag table entries are multiple of 4 bytes. The first byte is the tag, the second byte the number of values, the third byte the bit mask and the fourth byte indicates the end of the control byte. If the fourth byte is 0x01, all other bytes of the entry are zero.
                 let mut v = [0u8; 4];
@@ -605,7 +671,11 @@ impl<T: Read + Seek> MobiReader<T> {
             let mut cncx_record_offset = 0;
             for i in 0..indx.cncx_count {
                 let (now, offset) = self.seek_record_offset(
-                    self.mobi_header.indx_record_offset + indx.index_count + 1 + i,
+                    self.mobi_header
+                        .indx_record_offset
+                        .saturating_add(indx.index_count)
+                        .saturating_add(1)
+                        .saturating_add(i),
                 )?;
 
                 let mut record = Vec::new();
@@ -618,10 +688,12 @@ impl<T: Read + Seek> MobiReader<T> {
 
                 while pos < record.len() {
                     let index = pos;
-                    let bytes = &record[pos..(pos + 4)];
-                    let (value, length) = get_var_len(&bytes[0..]);
+                    let bytes = &record[pos..record.len().min(pos + 4)];
+                    let (value, length) = get_var_len(bytes);
                     pos += length;
-                    let result = &record[pos..(pos + value)];
+                    let Some(result) = record.get(pos..pos.saturating_add(value)) else {
+                        return Err(IError::Parse(Cow::from("invalid cncx record")));
+                    };
                     pos += value;
                     cntx.insert(
                         cncx_record_offset + index,
@@ -633,12 +705,17 @@ impl<T: Read + Seek> MobiReader<T> {
 
             let mut table = Vec::new();
             for i in 0..indx.index_count {
-                let (start, _) =
-                    self.seek_record_offset(self.mobi_header.indx_record_offset + 1 + i)?;
-                let n_index = INDXRecord::load(&mut self.reader)?;
+                let record = self
+                    .mobi_header
+                    .indx_record_offset
+                    .saturating_add(1)
+                    .saturating_add(i);
+                let (start, _) = self.seek_record_offset(record)?;
+                let n_index = INDXRecord::load(&mut self.reader)
+                    .context(|| ErrorContext::new("read index").with_record(record as usize))?;
 
                 for j in 0..n_index.index_count {
-                    let offset_offset = (n_index.idxt_start + 4 + 2 * j) as u64;
+                    let offset_offset = n_index.idxt_start as u64 + 4 + 2 * j as u64;
                     self.reader.seek(SeekFrom::Start(start + offset_offset))?;
                     let offset = self.reader.read_u16()? as u64;
                     self.reader.seek(SeekFrom::Start(start + offset as u64))?;
@@ -712,7 +789,7 @@ impl<T: Read + Seek> MobiReader<T> {
                             }
                         } else {
                             let mut count: usize = 0;
-                            while count < value_bytes.unwrap() {
+                            while count < value_bytes.unwrap_or_default() {
                                 let (value, length) =
                                     get_array_var_len(&mut self.reader, start, pos)?;
 
@@ -749,16 +826,17 @@ impl<T: Read + Seek> MobiReader<T> {
     pub(crate) fn read_cover(&mut self) -> IResult<Option<Cover>> {
         if let Some(exth) = &self.exth_header {
             if let Some(offset) = exth.get_cover_offset().or(exth.get_thumbnail_offset()) {
-                let (now, next) = self
-                    .seek_record_offset(self.mobi_header.first_image_index + offset as u32)
-                    .unwrap();
+                let (now, next) = self.seek_record_offset(
+                    self.mobi_header
+                        .first_image_index
+                        .saturating_add(offset as u32),
+                )?;
 
                 let mut image = Vec::new();
                 self.reader
                     .get_mut()
                     .take(next - now)
-                    .read_to_end(&mut image)
-                    .unwrap();
+                    .read_to_end(&mut image)?;
                 return Ok(Some(Cover(image)));
             }
         }
@@ -771,28 +849,25 @@ impl<T: Read + Seek> MobiReader<T> {
 
         let index = read_image_recindex_from_html(text.as_slice())?;
 
-        Ok(index
-            .iter()
-            .map(|f| {
-                let (now, next) = self
-                    .seek_record_offset(self.mobi_header.first_image_index + *f as u32)
-                    .unwrap();
-
-                let mut image = Vec::new();
-                self.reader
-                    .get_mut()
-                    .take(next - now)
-                    .read_to_end(&mut image)
-                    .unwrap();
-
-                return MobiAssets {
-                    _file_name: format!("{}.{}", f, get_suffix(image.as_slice())),
-                    media_type: String::new(),
-                    _data: Some(image),
-                    recindex: f.clone(),
-                };
-            })
-            .collect())
+        let mut assets = Vec::new();
+        for f in index {
+            let (now, next) = self
+                .seek_record_offset(self.mobi_header.first_image_index.saturating_add(f as u32))?;
+
+            let mut image = Vec::new();
+            self.reader
+                .get_mut()
+                .take(next - now)
+                .read_to_end(&mut image)?;
+
+            assets.push(MobiAssets {
+                _file_name: format!("{}.{}", f, get_suffix(image.as_slice())),
+                media_type: String::new(),
+                _data: Some(image),
+                recindex: f,
+            });
+        }
+        Ok(assets)
     }
 
     /// 读取文本，注意这里并不将文本解码，依然保留原始字节
@@ -803,31 +878,36 @@ impl<T: Read + Seek> MobiReader<T> {
         // 获取所有text record
         let mut text: Vec<u8> = Vec::new();
         // let reader = &mut self.reader;
-        let tail_circle_count = count_bit(self.mobi_header.extra_record_data_flags >> 1);
 
         // 第0个是header，所以从1开始
-        for i in 1..(self.mobi_doc_header.record_count + 1) {
+        for i in 1..=self.mobi_doc_header.record_count {
             let mut record: Vec<u8> = Vec::new();
 
-            let (start, end) = self.seek_record_offset(i as u32)?;
+            let (start, end) = self
+                .seek_record_offset(i as u32)
+                .context(|| ErrorContext::new("read text").with_record(i as usize))?;
             let len = end - start;
             // self.reader.seek(SeekFrom::Start(start))?;
-            self.reader.get_mut().take(len).read_to_end(&mut record)?;
+            self.reader
+                .get_mut()
+                .take(len)
+                .read_to_end(&mut record)
+                .context(|| ErrorContext::new("read text").with_record(i as usize))?;
 
             // 处理尾巴
-            let size = get_mobi_variable_width_len(
-                &record,
-                tail_circle_count,
-                self.mobi_header.extra_record_data_flags,
-            );
-
-            for _ in 0..size {
-                record.remove(record.len() - 1);
-            }
+            let size: usize =
+                read_trailing_entries(&record, self.mobi_header.extra_record_data_flags)
+                    .ok_or(IError::Parse(Cow::from("invalid trailing entries")))
+                    .context(|| ErrorContext::new("read text").with_record(i as usize))?
+                    .iter()
+                    .sum();
+            record.truncate(record.len() - size);
 
             if self.mobi_doc_header.compression == 2 {
                 // 解压缩
-                record = uncompression_lz77(&record);
+                record = uncompression_lz77(&record)
+                    .ok_or(IError::Parse(Cow::from("invalid lz77 data")))
+                    .context(|| ErrorContext::new("decompress text").with_record(i as usize))?;
             }
 
             text.append(&mut record);
@@ -844,7 +924,16 @@ impl<T: Read + Seek> MobiReader<T> {
             // iSO-8859-1
             Ok(data.iter().map(|&c| c as char).collect())
         } else {
-            String::from_utf8(data.to_vec()).map_err(|e| IError::Utf8(e))
+            String::from_utf8(data.to_vec()).map_err(|e| {
+                if self.mobi_header.text_encoding == 65001 {
+                    IError::Utf8(e)
+                } else {
+                    IError::Encoding(Cow::from(format!(
+                        "unsupported text encoding {}",
+                        self.mobi_header.text_encoding
+                    )))
+                }
+            })
         }
     }
 
@@ -864,17 +953,9 @@ impl<T: Read + Seek> MobiReader<T> {
         };
         let mut pos = vec![];
         while i < text.len() {
-            let mut now = &text[i];
-
             let mut j = 0;
-            while j < sub_bytes.len() {
-                if &sub_bytes[j] != now {
-                    // i += j;
-                    break;
-                } else {
-                    i += 1;
-                    now = &text[i];
-                }
+            while j < sub_bytes.len() && text.get(i) == Some(&sub_bytes[j]) {
+                i += 1;
                 j += 1;
             }
 
@@ -887,10 +968,11 @@ impl<T: Read + Seek> MobiReader<T> {
                     &text[(prev.start + if prev.start == 0 { 0 } else { sub_bytes.len() })
                         ..prev.end],
                 )?;
+                let (index, start) = (prev.index + 1, prev.end);
                 pos.push(prev);
                 prev = TextSection {
-                    index: pos.last().unwrap().index + 1,
-                    start: pos.last().unwrap().end,
+                    index,
+                    start,
                     end: 0,
                     data: String::new(),
                 }
@@ -899,8 +981,10 @@ impl<T: Read + Seek> MobiReader<T> {
 
             i += 1;
         }
-        prev.end = i;
-        prev.data = self.decode_text(&text[(prev.start + sub_bytes.len())..i])?;
+        // 分页符位于末尾时 i 会越过文本长度
+        prev.end = i.min(text.len());
+        prev.data =
+            self.decode_text(&text[(prev.start + sub_bytes.len()).min(prev.end)..prev.end])?;
         pos.push(prev);
 
         Ok(pos)
@@ -921,23 +1005,32 @@ pub(crate) struct TextSection {
 /// [https://wiki.mobileread.com/wiki/MOBI#Variable-width_integers]
 /// 看了好几遍，都还是没看懂文档是什么意思，只能把别的项目里的代码给翻译过来
 ///
-fn get_mobi_variable_width_len(data: &[u8], tail_circle_count: usize, flag: u32) -> usize {
-    let mut n_data = &data[..];
-    for _ in 0..tail_circle_count {
+/// # Returns
+///
+/// text record 末尾每个尾巴的长度，从后往前排列；长度超出 record 时返回None
+///
+pub(crate) fn read_trailing_entries(data: &[u8], flag: u32) -> Option<Vec<usize>> {
+    let mut n_data = data;
+    let mut entries = Vec::new();
+    for _ in 0..count_bit(flag >> 1) {
         let res = buffer_get_var_len(n_data) as usize;
-        n_data = &n_data[..(n_data.len() - res)];
+        n_data = &n_data[..n_data.len().checked_sub(res)?];
+        entries.push(res);
     }
 
     if flag & 1 > 0 {
-        let a = (n_data[n_data.len() - 1] & 0b11) + 1;
-        n_data = &n_data[..(n_data.len() - a as usize)];
+        let a = (n_data.last()? & 0b11) as usize + 1;
+        if a > n_data.len() {
+            return None;
+        }
+        entries.push(a);
     }
 
-    data.len() - n_data.len()
+    Some(entries)
 }
 
 fn buffer_get_var_len(data: &[u8]) -> u32 {
-    let array = &data[data.len() - 4..data.len()];
+    let array = &data[data.len().saturating_sub(4)..];
     let mut value: u32 = 0;
     for ele in array {
         if ele & 0b1000_0000 > 0 {
@@ -950,8 +1043,8 @@ fn buffer_get_var_len(data: &[u8]) -> u32 {
     return value;
 }
 
-/// 解压缩
-fn uncompression_lz77(data: &[u8]) -> Vec<u8> {
+/// 解压缩，数据不合法时返回None
+pub(crate) fn uncompression_lz77(data: &[u8]) -> Option<Vec<u8>> {
     let length = data.len();
     let mut offset = 0;
     let mut buffer = Vec::new();
@@ -963,23 +1056,22 @@ fn uncompression_lz77(data: &[u8]) -> Vec<u8> {
         if char == 0 {
             buffer.push(char);
         } else if char <= 8 {
-            for i in offset..(offset + char as usize) {
-                buffer.push(data[i]);
-            }
+            buffer.extend_from_slice(data.get(offset..(offset + char as usize))?);
             offset += char as usize;
         } else if char <= 0x7f {
             buffer.push(char);
         } else if char <= 0xbf {
-            let next = data[offset];
+            let next = *data.get(offset)?;
             offset += 1;
             let cc = char as usize;
             let distance = ((((cc << 8) | next as usize) >> 3) & 0x7ff) as usize;
             let lz_length = (next & 0x7) + 3;
-            let mut buffer_size = buffer.len();
+            if distance == 0 || distance > buffer.len() {
+                return None;
+            }
 
             for _ in 0..lz_length {
-                buffer.push(buffer[buffer_size - distance]);
-                buffer_size += 1;
+                buffer.push(buffer[buffer.len() - distance]);
             }
         } else {
             buffer.push(32);
@@ -987,7 +1079,7 @@ fn uncompression_lz77(data: &[u8]) -> Vec<u8> {
         }
     }
 
-    buffer
+    Some(buffer)
 }
 
 #[cfg(test)]
@@ -1084,6 +1176,49 @@ mod tests {
         // 尝试读取名字
     }
 
+    #[test]
+    fn test_load_no_panic() {
+        use crate::prelude::{MobiBuilder, MobiHtml};
+
+        let data = MobiBuilder::default()
+            .with_title("书名")
+            .with_creator("作者")
+            .append_title(true)
+            .custome_nav(false)
+            .add_chapter(
+                MobiHtml::new(1)
+                    .with_title("一")
+                    .with_data("<p>一</p><img src='1.png'/>".as_bytes().to_vec()),
+            )
+            .add_chapter(
+                MobiHtml::new(2)
+                    .with_title("二")
+                    .with_data("<p>二</p>".as_bytes().to_vec()),
+            )
+            .add_assets("1.png", b"\x89PNG\r\n\x1a\n".to_vec())
+            .cover(b"GIF89a".to_vec())
+            .mem()
+            .unwrap();
+        assert!(MobiReader::new(std::io::Cursor::new(data.clone()))
+            .and_then(|mut r| r.load())
+            .is_ok());
+
+        // 截断或者改写任意字节，只能返回错误，不能panic
+        let load = |v: Vec<u8>| {
+            let _ = MobiReader::new(std::io::Cursor::new(v)).and_then(|mut r| r.load());
+        };
+        for len in 0..data.len() {
+            load(data[..len].to_vec());
+        }
+        for i in 0..data.len() {
+            for b in [0x00, 0x7f, 0xff] {
+                let mut v = data.clone();
+                v[i] = b;
+                load(v);
+            }
+        }
+    }
+
     // fn read_text(r: &mut MOBIReader) {
     //     let mut reader = &mut r.reader;
 
@@ -6,11 +6,12 @@ use std::{
 };
 
 use crate::{
-    common::{IError, IResult},
+    common::{escape_xml, IError, IResult},
     mobi::{
         image::generate_text_img_xml,
         nav::{find_chap_file_pos, generate_reader_nav_xml},
     },
+    text::typography::set_paragraph_width,
 };
 
 use super::{
@@ -268,6 +269,23 @@ impl EXTHHeader {
         if let Some(v) = book.contributor() {
             record_list.push(gene(108, v));
         }
+        if let Some(v) = book.language() {
+            record_list.push(gene(524, v));
+        }
+        // kindle 的横排写作 horizontal-lr
+        if let Some(v) = book.writing_mode() {
+            record_list.push(gene(
+                525,
+                if v == "horizontal-tb" {
+                    "horizontal-lr"
+                } else {
+                    v
+                },
+            ));
+        }
+        if let Some(v) = book.page_direction() {
+            record_list.push(gene(527, v));
+        }
 
         if book.cover().is_some() {
             record_list.push(EXTHRecord {
@@ -309,12 +327,14 @@ impl EXTHHeader {
         }
         let now = writer.stream_position()?;
 
+        // 长度包括开头的 EXTH 四个字节，但是不包括填充
+        let len = n - pos + 4;
         writer.seek(std::io::SeekFrom::Start(pos))?;
-        writer.write_u32((n - pos) as u32)?;
+        writer.write_u32(len as u32)?;
 
         writer.seek(std::io::SeekFrom::Start(now))?;
 
-        Ok((n - pos) as usize)
+        Ok(len as usize)
     }
 }
 
@@ -577,9 +597,12 @@ impl<T: Write + Seek> MobiWriter<T> {
             );
             if self.append_title && !ele.title().is_empty() {
                 text.append(
-                    &mut format!(r#"<h1 style="text-align: center">{}</h1>"#, ele.title())
-                        .as_bytes()
-                        .to_vec(),
+                    &mut format!(
+                        r#"<h1 style="text-align: center">{}</h1>"#,
+                        escape_xml(ele.title())
+                    )
+                    .as_bytes()
+                    .to_vec(),
                 );
             }
             text.append(&mut v);
@@ -608,12 +631,10 @@ impl<T: Write + Seek> MobiWriter<T> {
     fn html_p_ident(&self, v: Option<&[u8]>) -> String {
         if let Some(v) = v {
             let text = String::from_utf8(v.to_vec()).unwrap_or_else(|_e| String::new());
-            // text.replace(from, to)
             if self.ident == 0 {
                 text
             } else {
-                let v = format!(r#"<p width="{}em">"#, self.ident);
-                text.replace("<p ", v.as_str()).replace("<p>", v.as_str())
+                set_paragraph_width(&text, &format!("{}em", self.ident))
             }
         } else {
             String::new()
@@ -668,11 +689,6 @@ impl<T: Write + Seek> MobiWriter<T> {
         )
     }
 
-    /// 添加结尾字节
-    fn write_uncrossable_breaks(_text: Vec<PDBRecord>) -> Vec<PDBRecord> {
-        todo!()
-    }
-
     fn write_header(
         &mut self,
         book: &MobiBook,
@@ -761,7 +777,10 @@ impl<T: Write + Seek> MobiWriter<T> {
             assets.push(PDBRecord {
                 index: assets.len() + text.len(),
                 magic: None,
-                data: cover.data().as_ref().unwrap().to_vec(),
+                data: cover
+                    .data()
+                    .ok_or_else(|| IError::MissingEntry(cover.file_name().to_string()))?
+                    .to_vec(),
             });
         }
         assets.append(&mut self.generate_image_record(book.assets().as_slice()));
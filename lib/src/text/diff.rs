@@ -0,0 +1,1218 @@
+//!
+//! 比较两本书的差异
+//!
+//! 两本书先转换为 [BookSnapshot]，再比较元数据、章节、目录以及资源，epub 和 mobi 之间也可以比较
+//!
+//! 章节依次按相同的内容和标题、相同的标题匹配，剩余的章节按内容相似度匹配，最后按相同的文件名匹配，
+//! 匹配后标题不同，或者内容有变化且文件名不同的视为重命名；
+//! 章节内容转换为纯文本（每个段落一行）后按行比较
+//!
+use std::collections::HashMap;
+
+use crate::epub::validate::json_string;
+use crate::prelude::*;
+use crate::text::export::html_text;
+
+/// 按内容匹配章节时的最低相似度
+const MIN_SIMILARITY: f64 = 0.5;
+/// 按行比较时的最大计算量，超过后不再计算最长公共子序列，剩余的行全部视为删除和新增
+const MAX_DIFF_CELLS: usize = 16_000_000;
+
+///
+/// 变化类型
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+    Renamed,
+}
+
+impl std::fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ChangeKind::Added => "added",
+                ChangeKind::Removed => "removed",
+                ChangeKind::Modified => "modified",
+                ChangeKind::Renamed => "renamed",
+            }
+        )
+    }
+}
+
+impl ChangeKind {
+    fn sign(&self) -> char {
+        match self {
+            ChangeKind::Added => '+',
+            ChangeKind::Removed => '-',
+            ChangeKind::Modified => '~',
+            ChangeKind::Renamed => '>',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ChapterSnapshot {
+    index: usize,
+    file_name: Option<String>,
+    title: String,
+    lines: Vec<String>,
+}
+
+///
+/// 用于比较的书本快照，包括元数据、章节纯文本、展开后的目录以及资源哈希
+///
+#[derive(Debug, Clone, Default)]
+pub struct BookSnapshot {
+    metadata: Vec<(&'static str, Option<String>)>,
+    chapters: Vec<ChapterSnapshot>,
+    nav: Vec<String>,
+    assets: Vec<(String, String)>,
+}
+
+macro_rules! snapshot_metadata {
+    ($book:expr) => {
+        vec![
+            (
+                "title",
+                Some($book.title().to_string()).filter(|v| !v.is_empty()),
+            ),
+            (
+                "identifier",
+                Some($book.identifier().to_string()).filter(|v| !v.is_empty()),
+            ),
+            ("creator", $book.creator().map(String::from)),
+            ("description", $book.description().map(String::from)),
+            ("contributor", $book.contributor().map(String::from)),
+            ("date", $book.date().map(String::from)),
+            ("format", $book.format().map(String::from)),
+            ("publisher", $book.publisher().map(String::from)),
+            ("subject", $book.subject().map(String::from)),
+            ("language", $book.language().map(String::from)),
+            ("writing_mode", $book.writing_mode().map(String::from)),
+            ("page_direction", $book.page_direction().map(String::from)),
+        ]
+    };
+}
+
+impl BookSnapshot {
+    ///
+    /// 生成 epub 的快照，生成的目录、封面以及导航文档不比较
+    ///
+    /// 延迟读取的章节和资源会先加载
+    ///
+    pub fn from_epub(book: &mut EpubBook) -> IResult<Self> {
+        let metadata = snapshot_metadata!(book);
+        let mut nav = Vec::new();
+        flatten_nav(
+            book.nav(),
+            &mut Vec::new(),
+            &mut nav,
+            |v: &EpubNav| v.title(),
+            |v| v.child(),
+        );
+        let mut chapters = Vec::new();
+        for (index, chap) in book.chapters_mut().enumerate() {
+            if !chap.is_content() {
+                continue;
+            }
+            let file_name = chap.file_name().to_string();
+            // 延迟读取的章节在加载后才有标题
+            let Some(data) = chap.data_mut() else {
+                continue;
+            };
+            let lines = text_lines(&String::from_utf8_lossy(data));
+            chapters.push(ChapterSnapshot {
+                index,
+                file_name: Some(file_name),
+                title: chap.title().to_string(),
+                lines,
+            });
+        }
+        let mut assets: Vec<(String, String)> = Vec::new();
+        for asset in book.assets_mut() {
+            let file_name = asset.file_name().to_string();
+            if asset.is_generated() || assets.iter().any(|(v, _)| *v == file_name) {
+                continue;
+            }
+            let hash = hash(asset.data_mut().unwrap_or_default());
+            assets.push((file_name, hash));
+        }
+        // 封面可能同时在资源中
+        if let Some(cover) = book.cover_mut() {
+            let file_name = cover.file_name().to_string();
+            if !assets.iter().any(|(v, _)| *v == file_name) {
+                let hash = hash(cover.data_mut().unwrap_or_default());
+                assets.push((file_name, hash));
+            }
+        }
+        Ok(BookSnapshot {
+            metadata,
+            chapters,
+            nav,
+            assets,
+        })
+    }
+
+    /// 生成 mobi 的快照
+    pub fn from_mobi(book: &MobiBook) -> IResult<Self> {
+        let metadata = snapshot_metadata!(book);
+        let mut nav = Vec::new();
+        flatten_nav(
+            book.nav(),
+            &mut Vec::new(),
+            &mut nav,
+            |v: &MobiNav| v.title(),
+            |v| v.children(),
+        );
+        let chapters = book
+            .chapters()
+            .enumerate()
+            .map(|(index, chap)| ChapterSnapshot {
+                index,
+                file_name: None,
+                title: chap.title().to_string(),
+                lines: text_lines(&chap.string_data()),
+            })
+            .collect();
+        let mut assets: Vec<(String, String)> = Vec::new();
+        for asset in book.assets().chain(book.cover()) {
+            if assets.iter().any(|(v, _)| v == asset.file_name()) {
+                continue;
+            }
+            assets.push((
+                asset.file_name().to_string(),
+                hash(asset.data().unwrap_or_default()),
+            ));
+        }
+        Ok(BookSnapshot {
+            metadata,
+            chapters,
+            nav,
+            assets,
+        })
+    }
+}
+
+///
+/// 元数据的变化
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataChange {
+    kind: ChangeKind,
+    field: &'static str,
+    old: Option<String>,
+    new: Option<String>,
+}
+
+impl MetadataChange {
+    pub fn kind(&self) -> ChangeKind {
+        self.kind
+    }
+
+    /// 字段名，例如 title、creator
+    pub fn field(&self) -> &str {
+        self.field
+    }
+
+    pub fn old_value(&self) -> Option<&str> {
+        self.old.as_deref()
+    }
+
+    pub fn new_value(&self) -> Option<&str> {
+        self.new.as_deref()
+    }
+
+    /// 转为json对象
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"kind":"{}","field":"{}","old":{},"new":{}}}"#,
+            self.kind,
+            self.field,
+            json_option(self.old.as_deref()),
+            json_option(self.new.as_deref())
+        )
+    }
+}
+
+///
+/// 文本或者目录中一行的变化
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineChange {
+    kind: ChangeKind,
+    line: usize,
+    text: String,
+}
+
+impl LineChange {
+    /// [ChangeKind::Added] 或者 [ChangeKind::Removed]
+    pub fn kind(&self) -> ChangeKind {
+        self.kind
+    }
+
+    /// 行号，从 1 开始，删除的行为旧书中的行号，新增的行为新书中的行号
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// 转为json对象
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"kind":"{}","line":{},"text":{}}}"#,
+            self.kind,
+            self.line,
+            json_string(&self.text)
+        )
+    }
+}
+
+///
+/// 章节的位置及标题
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChapterRef {
+    chapter: usize,
+    file_name: Option<String>,
+    title: String,
+}
+
+impl ChapterRef {
+    /// 章节序号，从 0 开始
+    pub fn chapter(&self) -> usize {
+        self.chapter
+    }
+
+    /// 章节文件名，mobi 没有文件名
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            r#"{{"chapter":{},"file_name":{},"title":{}}}"#,
+            self.chapter,
+            json_option(self.file_name.as_deref()),
+            json_string(&self.title)
+        )
+    }
+}
+
+impl std::fmt::Display for ChapterRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{} {}", self.chapter, self.title)?;
+        if let Some(file_name) = &self.file_name {
+            write!(f, " ({})", file_name)?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// 章节的变化
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChapterChange {
+    kind: ChangeKind,
+    old: Option<ChapterRef>,
+    new: Option<ChapterRef>,
+    similarity: f64,
+    lines: Vec<LineChange>,
+}
+
+impl ChapterChange {
+    pub fn kind(&self) -> ChangeKind {
+        self.kind
+    }
+
+    /// 旧书中的章节，新增的章节没有
+    pub fn old_chapter(&self) -> Option<&ChapterRef> {
+        self.old.as_ref()
+    }
+
+    /// 新书中的章节，删除的章节没有
+    pub fn new_chapter(&self) -> Option<&ChapterRef> {
+        self.new.as_ref()
+    }
+
+    /// 内容相似度，范围 0 到 1，新增和删除的章节为 0
+    pub fn similarity(&self) -> f64 {
+        self.similarity
+    }
+
+    /// 按行比较的结果，新增和删除的章节为空
+    pub fn lines(&self) -> &[LineChange] {
+        &self.lines
+    }
+
+    /// 转为json对象
+    pub fn to_json(&self) -> String {
+        let lines: Vec<String> = self.lines.iter().map(|v| v.to_json()).collect();
+        format!(
+            r#"{{"kind":"{}","old":{},"new":{},"similarity":{:.4},"lines":[{}]}}"#,
+            self.kind,
+            self.old
+                .as_ref()
+                .map_or_else(|| "null".to_string(), |v| v.to_json()),
+            self.new
+                .as_ref()
+                .map_or_else(|| "null".to_string(), |v| v.to_json()),
+            self.similarity,
+            lines.join(",")
+        )
+    }
+}
+
+///
+/// 资源的变化，按文件内容的哈希比较，内容相同但文件名不同的视为重命名
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetChange {
+    kind: ChangeKind,
+    old: Option<(String, String)>,
+    new: Option<(String, String)>,
+}
+
+impl AssetChange {
+    pub fn kind(&self) -> ChangeKind {
+        self.kind
+    }
+
+    pub fn old_file_name(&self) -> Option<&str> {
+        self.old.as_ref().map(|v| v.0.as_str())
+    }
+
+    /// 旧文件的 FNV-1a 64 位哈希
+    pub fn old_hash(&self) -> Option<&str> {
+        self.old.as_ref().map(|v| v.1.as_str())
+    }
+
+    pub fn new_file_name(&self) -> Option<&str> {
+        self.new.as_ref().map(|v| v.0.as_str())
+    }
+
+    /// 新文件的 FNV-1a 64 位哈希
+    pub fn new_hash(&self) -> Option<&str> {
+        self.new.as_ref().map(|v| v.1.as_str())
+    }
+
+    /// 转为json对象
+    pub fn to_json(&self) -> String {
+        let json = |v: &Option<(String, String)>| {
+            v.as_ref().map_or_else(
+                || "null".to_string(),
+                |(name, hash)| {
+                    format!(r#"{{"file_name":{},"hash":"{}"}}"#, json_string(name), hash)
+                },
+            )
+        };
+        format!(
+            r#"{{"kind":"{}","old":{},"new":{}}}"#,
+            self.kind,
+            json(&self.old),
+            json(&self.new)
+        )
+    }
+}
+
+///
+/// 两本书的差异
+///
+/// # Examples
+///
+/// ```
+/// use iepub::prelude::*;
+/// use iepub::prelude::diff::ChangeKind;
+///
+/// let book = |title: &str, text: &str| {
+///     EpubBuilder::new()
+///         .with_title(title)
+///         .add_chapter(
+///             EpubHtml::default()
+///                 .with_title("第一章")
+///                 .with_file_name("1.xhtml")
+///                 .with_data(text.as_bytes().to_vec()),
+///         )
+///         .book()
+///         .unwrap()
+/// };
+/// let mut old = book("书", "<p>第一段</p><p>第二段</p>");
+/// let mut new = book("新书", "<p>第一段</p><p>第二段，修订</p>");
+/// let diff = old.diff(&mut new).unwrap();
+/// assert_eq!("title", diff.metadata()[0].field());
+/// assert_eq!(ChangeKind::Modified, diff.chapters()[0].kind());
+/// assert_eq!("第二段，修订", diff.chapters()[0].lines()[1].text());
+/// println!("{}", diff);
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookDiff {
+    metadata: Vec<MetadataChange>,
+    chapters: Vec<ChapterChange>,
+    nav: Vec<LineChange>,
+    assets: Vec<AssetChange>,
+}
+
+impl BookDiff {
+    /// 比较两个快照
+    pub fn new(old: &BookSnapshot, new: &BookSnapshot) -> Self {
+        BookDiff {
+            metadata: diff_metadata(old, new),
+            chapters: diff_chapters(&old.chapters, &new.chapters),
+            nav: diff_lines(&old.nav, &new.nav),
+            assets: diff_assets(&old.assets, &new.assets),
+        }
+    }
+
+    pub fn metadata(&self) -> &[MetadataChange] {
+        &self.metadata
+    }
+
+    ///
+    /// 章节的变化，先是匹配的和新增的章节，按新书中的顺序排列，然后是删除的章节
+    ///
+    pub fn chapters(&self) -> &[ChapterChange] {
+        &self.chapters
+    }
+
+    /// 目录的变化，目录展开后每项一行，格式为 `卷一 > 第一章`
+    pub fn nav(&self) -> &[LineChange] {
+        &self.nav
+    }
+
+    pub fn assets(&self) -> &[AssetChange] {
+        &self.assets
+    }
+
+    /// 是否没有任何变化
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty()
+            && self.chapters.is_empty()
+            && self.nav.is_empty()
+            && self.assets.is_empty()
+    }
+
+    /// 转为json对象
+    pub fn to_json(&self) -> String {
+        let join = |v: Vec<String>| v.join(",");
+        format!(
+            r#"{{"metadata":[{}],"chapters":[{}],"nav":[{}],"assets":[{}]}}"#,
+            join(self.metadata.iter().map(|v| v.to_json()).collect()),
+            join(self.chapters.iter().map(|v| v.to_json()).collect()),
+            join(self.nav.iter().map(|v| v.to_json()).collect()),
+            join(self.assets.iter().map(|v| v.to_json()).collect())
+        )
+    }
+}
+
+impl std::fmt::Display for BookDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.metadata.is_empty() {
+            writeln!(f, "metadata:")?;
+            for v in &self.metadata {
+                writeln!(
+                    f,
+                    "  {} {}: {} -> {}",
+                    v.kind.sign(),
+                    v.field,
+                    v.old.as_deref().unwrap_or("-"),
+                    v.new.as_deref().unwrap_or("-")
+                )?;
+            }
+        }
+        if !self.chapters.is_empty() {
+            writeln!(f, "chapters:")?;
+            for v in &self.chapters {
+                match (&v.old, &v.new) {
+                    (Some(old), Some(new)) if v.kind == ChangeKind::Renamed => {
+                        writeln!(f, "  {} {} -> {}", v.kind.sign(), old, new)?
+                    }
+                    (_, Some(chap)) | (Some(chap), None) => {
+                        writeln!(f, "  {} {}", v.kind.sign(), chap)?
+                    }
+                    (None, None) => {}
+                }
+                for line in &v.lines {
+                    writeln!(f, "    {} {}: {}", line.kind.sign(), line.line, line.text)?;
+                }
+            }
+        }
+        if !self.nav.is_empty() {
+            writeln!(f, "nav:")?;
+            for v in &self.nav {
+                writeln!(f, "  {} {}", v.kind.sign(), v.text)?;
+            }
+        }
+        if !self.assets.is_empty() {
+            writeln!(f, "assets:")?;
+            for v in &self.assets {
+                match (&v.old, &v.new) {
+                    (Some(old), Some(new)) if v.kind == ChangeKind::Renamed => {
+                        writeln!(f, "  {} {} -> {} {}", v.kind.sign(), old.0, new.0, new.1)?
+                    }
+                    (Some(old), Some(new)) => {
+                        writeln!(f, "  {} {} {} -> {}", v.kind.sign(), new.0, old.1, new.1)?
+                    }
+                    (_, Some(v2)) | (Some(v2), None) => {
+                        writeln!(f, "  {} {} {}", v.kind.sign(), v2.0, v2.1)?
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn json_option(v: Option<&str>) -> String {
+    v.map_or_else(|| "null".to_string(), json_string)
+}
+
+/// 纯文本的非空行
+fn text_lines(html: &str) -> Vec<String> {
+    html_text(html)
+        .lines()
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// 目录展开为 `卷一 > 第一章` 的形式
+fn flatten_nav<'a, T: 'a, I: Iterator<Item = &'a T>>(
+    nav: I,
+    parent: &mut Vec<&'a str>,
+    out: &mut Vec<String>,
+    title: fn(&'a T) -> &'a str,
+    child: fn(&'a T) -> I,
+) {
+    for ele in nav {
+        parent.push(title(ele));
+        out.push(parent.join(" > "));
+        flatten_nav(child(ele), parent, out, title, child);
+        parent.pop();
+    }
+}
+
+/// FNV-1a 64 位哈希
+fn hash(data: &[u8]) -> String {
+    let mut v: u64 = 0xcbf29ce484222325;
+    for b in data {
+        v ^= *b as u64;
+        v = v.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", v)
+}
+
+fn diff_metadata(old: &BookSnapshot, new: &BookSnapshot) -> Vec<MetadataChange> {
+    let mut out = Vec::new();
+    for (field, value) in &new.metadata {
+        let old = old
+            .metadata
+            .iter()
+            .find(|(f, _)| f == field)
+            .and_then(|(_, v)| v.clone());
+        if old == *value {
+            continue;
+        }
+        let kind = match (&old, value) {
+            (None, _) => ChangeKind::Added,
+            (_, None) => ChangeKind::Removed,
+            _ => ChangeKind::Modified,
+        };
+        out.push(MetadataChange {
+            kind,
+            field,
+            old,
+            new: value.clone(),
+        });
+    }
+    out
+}
+
+/// 字符二元组及其总数
+struct Bigrams {
+    map: HashMap<(char, char), usize>,
+    total: usize,
+}
+
+impl Bigrams {
+    fn new(lines: &[String]) -> Self {
+        let mut map: HashMap<(char, char), usize> = HashMap::new();
+        for line in lines {
+            let chars: Vec<char> = line.chars().collect();
+            for w in chars.windows(2) {
+                *map.entry((w[0], w[1])).or_default() += 1;
+            }
+        }
+        let total = map.values().sum();
+        Bigrams { map, total }
+    }
+
+    /// Dice 系数
+    fn similarity(&self, other: &Bigrams) -> f64 {
+        let total = self.total + other.total;
+        if total == 0 {
+            return 0.0;
+        }
+        let common: usize = self
+            .map
+            .iter()
+            .map(|(k, v)| (*v).min(other.map.get(k).copied().unwrap_or(0)))
+            .sum();
+        2.0 * common as f64 / total as f64
+    }
+}
+
+fn chapter_ref(v: &ChapterSnapshot) -> ChapterRef {
+    ChapterRef {
+        chapter: v.index,
+        file_name: v.file_name.clone(),
+        title: v.title.clone(),
+    }
+}
+
+///
+/// 按相同的 key 匹配剩余的章节，key 为 [None] 的不匹配
+///
+fn match_by_key<K: PartialEq>(
+    old: &[Option<K>],
+    new: &[Option<K>],
+    matched: &mut [Option<usize>],
+    used: &mut [bool],
+) {
+    for (j, key) in new.iter().enumerate() {
+        let Some(key) = key.as_ref().filter(|_| matched[j].is_none()) else {
+            continue;
+        };
+        if let Some(i) = (0..old.len()).find(|i| !used[*i] && old[*i].as_ref() == Some(key)) {
+            used[i] = true;
+            matched[j] = Some(i);
+        }
+    }
+}
+
+fn diff_chapters(old: &[ChapterSnapshot], new: &[ChapterSnapshot]) -> Vec<ChapterChange> {
+    let content = |v: &ChapterSnapshot| hash(v.lines.join("\n").as_bytes());
+    let (old_hash, new_hash): (Vec<String>, Vec<String>) = (
+        old.iter().map(content).collect(),
+        new.iter().map(content).collect(),
+    );
+    // 每个章节只计算一次二元组
+    let (old_bigrams, new_bigrams): (Vec<Bigrams>, Vec<Bigrams>) = (
+        old.iter().map(|v| Bigrams::new(&v.lines)).collect(),
+        new.iter().map(|v| Bigrams::new(&v.lines)).collect(),
+    );
+    let similarity = |i: usize, j: usize| {
+        if old_hash[i] == new_hash[j] {
+            1.0
+        } else {
+            old_bigrams[i].similarity(&new_bigrams[j])
+        }
+    };
+
+    // 新书中每个章节匹配的旧章节
+    let mut matched: Vec<Option<usize>> = vec![None; new.len()];
+    let mut used = vec![false; old.len()];
+    // 内容和标题都相同，插入或删除章节导致文件名错位时也能匹配
+    match_by_key(
+        &old.iter()
+            .zip(&old_hash)
+            .map(|(v, h)| Some((h, &v.title)))
+            .collect::<Vec<_>>(),
+        &new.iter()
+            .zip(&new_hash)
+            .map(|(v, h)| Some((h, &v.title)))
+            .collect::<Vec<_>>(),
+        &mut matched,
+        &mut used,
+    );
+    let title: fn(&ChapterSnapshot) -> Option<&str> =
+        |v| Some(v.title.as_str()).filter(|f| !f.is_empty());
+    match_by_key(
+        &old.iter().map(title).collect::<Vec<_>>(),
+        &new.iter().map(title).collect::<Vec<_>>(),
+        &mut matched,
+        &mut used,
+    );
+    let mut pairs = Vec::new();
+    for (j, _) in matched.iter().enumerate().filter(|(_, v)| v.is_none()) {
+        for (i, _) in used.iter().enumerate().filter(|(_, v)| !**v) {
+            let s = similarity(i, j);
+            if s >= MIN_SIMILARITY {
+                pairs.push((s, i, j));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (_, i, j) in pairs {
+        if !used[i] && matched[j].is_none() {
+            used[i] = true;
+            matched[j] = Some(i);
+        }
+    }
+    // 剩余的章节按文件名匹配
+    let file_name: fn(&ChapterSnapshot) -> Option<&str> = |v| v.file_name.as_deref();
+    match_by_key(
+        &old.iter().map(file_name).collect::<Vec<_>>(),
+        &new.iter().map(file_name).collect::<Vec<_>>(),
+        &mut matched,
+        &mut used,
+    );
+
+    let mut out = Vec::new();
+    for (j, chap) in new.iter().enumerate() {
+        let Some(i) = matched[j] else {
+            out.push(ChapterChange {
+                kind: ChangeKind::Added,
+                old: None,
+                new: Some(chapter_ref(chap)),
+                similarity: 0.0,
+                lines: Vec::new(),
+            });
+            continue;
+        };
+        let lines = diff_lines(&old[i].lines, &chap.lines);
+        // epub 和 mobi 比较时没有文件名，不视为重命名；
+        // 内容和标题都相同时只是文件名变化，例如插入章节后按顺序编号的文件名错位，不视为变化
+        let renamed = match (&old[i].file_name, &chap.file_name) {
+            (Some(a), Some(b)) => a != b && !lines.is_empty(),
+            _ => false,
+        };
+        let kind = if renamed || old[i].title != chap.title {
+            ChangeKind::Renamed
+        } else if !lines.is_empty() {
+            ChangeKind::Modified
+        } else {
+            continue;
+        };
+        out.push(ChapterChange {
+            kind,
+            old: Some(chapter_ref(&old[i])),
+            new: Some(chapter_ref(chap)),
+            similarity: similarity(i, j),
+            lines,
+        });
+    }
+    for (_, chap) in old.iter().enumerate().filter(|(i, _)| !used[*i]) {
+        out.push(ChapterChange {
+            kind: ChangeKind::Removed,
+            old: Some(chapter_ref(chap)),
+            new: None,
+            similarity: 0.0,
+            lines: Vec::new(),
+        });
+    }
+    out
+}
+
+///
+/// 按行比较，基于最长公共子序列
+///
+/// 相同的开头和结尾不参与计算，剩余部分过大时全部视为删除和新增
+///
+fn diff_lines(old: &[String], new: &[String]) -> Vec<LineChange> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+    let (n, m) = (a.len(), b.len());
+
+    let change = |kind: ChangeKind, line: usize, text: &String| LineChange {
+        kind,
+        line: prefix + line + 1,
+        text: text.clone(),
+    };
+    let mut out = Vec::new();
+    if n.saturating_mul(m) > MAX_DIFF_CELLS {
+        out.extend(
+            a.iter()
+                .enumerate()
+                .map(|(i, v)| change(ChangeKind::Removed, i, v)),
+        );
+        out.extend(
+            b.iter()
+                .enumerate()
+                .map(|(j, v)| change(ChangeKind::Added, j, v)),
+        );
+        return out;
+    }
+    // lcs[i * (m + 1) + j] 为 a[i..] 和 b[j..] 的最长公共子序列长度
+    let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * (m + 1) + j] = if a[i] == b[j] {
+                lcs[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1]) {
+            out.push(change(ChangeKind::Removed, i, &a[i]));
+            i += 1;
+        } else {
+            out.push(change(ChangeKind::Added, j, &b[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+fn diff_assets(old: &[(String, String)], new: &[(String, String)]) -> Vec<AssetChange> {
+    let mut out = Vec::new();
+    let mut added = Vec::new();
+    for v in new {
+        match old.iter().find(|f| f.0 == v.0) {
+            Some(o) if o.1 != v.1 => out.push(AssetChange {
+                kind: ChangeKind::Modified,
+                old: Some(o.clone()),
+                new: Some(v.clone()),
+            }),
+            Some(_) => {}
+            None => added.push(v),
+        }
+    }
+    let mut removed: Vec<&(String, String)> = old
+        .iter()
+        .filter(|f| !new.iter().any(|v| v.0 == f.0))
+        .collect();
+    for v in added {
+        match removed.iter().position(|f| f.1 == v.1) {
+            Some(index) => out.push(AssetChange {
+                kind: ChangeKind::Renamed,
+                old: Some(removed.remove(index).clone()),
+                new: Some(v.clone()),
+            }),
+            None => out.push(AssetChange {
+                kind: ChangeKind::Added,
+                old: None,
+                new: Some(v.clone()),
+            }),
+        }
+    }
+    out.extend(removed.into_iter().map(|v| AssetChange {
+        kind: ChangeKind::Removed,
+        old: Some(v.clone()),
+        new: None,
+    }));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_lines, hash, BookSnapshot, ChangeKind};
+    use crate::prelude::*;
+
+    fn lines(v: &[&str]) -> Vec<String> {
+        v.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let v = diff_lines(
+            &lines(&["a", "b", "c", "d"]),
+            &lines(&["a", "c", "x", "d", "e"]),
+        );
+        assert_eq!(
+            vec![
+                (ChangeKind::Removed, 2, "b"),
+                (ChangeKind::Added, 3, "x"),
+                (ChangeKind::Added, 5, "e")
+            ],
+            v.iter()
+                .map(|v| (v.kind(), v.line(), v.text()))
+                .collect::<Vec<_>>()
+        );
+        assert!(diff_lines(&lines(&["a"]), &lines(&["a"])).is_empty());
+        assert_eq!(2, diff_lines(&lines(&["a"]), &lines(&["b"])).len());
+        assert_eq!("cbf29ce484222325", hash(&[]));
+    }
+
+    #[test]
+    fn test_diff_epub() {
+        let chapter = |title: &str, file_name: &str, text: &str| {
+            EpubHtml::default()
+                .with_title(title)
+                .with_file_name(file_name)
+                .with_data(text.as_bytes().to_vec())
+        };
+        let mut old = EpubBuilder::new()
+            .with_title("书")
+            .with_creator("作者")
+            .add_assets("1.png", crate::cbz::core::tests::png(1, 1))
+            .add_assets("2.png", crate::cbz::core::tests::png(2, 2))
+            .add_assets("style.css", "p{}".as_bytes().to_vec())
+            .add_chapter(chapter("第一章", "1.xhtml", "<p>第一章的内容</p>"))
+            .add_chapter(chapter(
+                "第二章",
+                "2.xhtml",
+                "<p>第二章的内容很长很长</p><p>第二段</p>",
+            ))
+            .add_chapter(chapter("第三章", "3.xhtml", "<p>将被删除</p>"))
+            .book()
+            .unwrap();
+        let mut new = EpubBuilder::new()
+            .with_title("书")
+            .with_description("简介")
+            .add_assets("1.png", crate::cbz::core::tests::png(3, 3))
+            .add_assets("images/2.png", crate::cbz::core::tests::png(2, 2))
+            .add_assets("style.css", "p{}".as_bytes().to_vec())
+            .add_chapter(chapter(
+                "第一章",
+                "1.xhtml",
+                "<p>第一章的内容</p><p>新增</p>",
+            ))
+            .add_chapter(chapter(
+                "第二章 开始",
+                "2.xhtml",
+                "<p>第二章的内容很长很长</p><p>第二段</p>",
+            ))
+            .add_chapter(chapter("第四章", "4.xhtml", "<p>新的章节</p>"))
+            .book()
+            .unwrap();
+        let diff = old.diff(&mut new).unwrap();
+
+        assert_eq!(
+            vec![
+                ("creator", ChangeKind::Removed),
+                ("description", ChangeKind::Added)
+            ],
+            diff.metadata()
+                .iter()
+                .map(|v| (v.field(), v.kind()))
+                .collect::<Vec<_>>()
+        );
+
+        let chapters = diff.chapters();
+        assert_eq!(
+            vec![
+                ChangeKind::Modified,
+                ChangeKind::Renamed,
+                ChangeKind::Added,
+                ChangeKind::Removed
+            ],
+            chapters.iter().map(|v| v.kind()).collect::<Vec<_>>()
+        );
+        assert_eq!(ChangeKind::Added, chapters[0].lines()[0].kind());
+        assert_eq!(2, chapters[0].lines()[0].line());
+        assert_eq!("第二章", chapters[1].old_chapter().unwrap().title());
+        assert_eq!("第二章 开始", chapters[1].new_chapter().unwrap().title());
+        assert_eq!(1.0, chapters[1].similarity());
+        assert!(chapters[1].lines().is_empty());
+        assert_eq!(
+            Some("4.xhtml"),
+            chapters[2].new_chapter().unwrap().file_name()
+        );
+        assert_eq!(
+            Some("3.xhtml"),
+            chapters[3].old_chapter().unwrap().file_name()
+        );
+
+        assert_eq!(
+            vec![
+                (ChangeKind::Added, "2. 第二章 开始"),
+                (ChangeKind::Added, "3. 第四章"),
+                (ChangeKind::Removed, "2. 第二章"),
+                (ChangeKind::Removed, "3. 第三章")
+            ]
+            .into_iter()
+            .collect::<std::collections::HashSet<_>>(),
+            diff.nav()
+                .iter()
+                .map(|v| (v.kind(), v.text()))
+                .collect::<std::collections::HashSet<_>>()
+        );
+
+        assert_eq!(
+            vec![
+                (ChangeKind::Modified, Some("1.png"), Some("1.png")),
+                (ChangeKind::Renamed, Some("2.png"), Some("images/2.png"))
+            ],
+            diff.assets()
+                .iter()
+                .map(|v| (v.kind(), v.old_file_name(), v.new_file_name()))
+                .collect::<Vec<_>>()
+        );
+
+        let json = diff.to_json();
+        assert!(
+            json.starts_with(
+                r#"{"metadata":[{"kind":"removed","field":"creator","old":"作者","new":null}"#
+            ),
+            "{}",
+            json
+        );
+        let text = diff.to_string();
+        assert!(
+            text.contains("  > #1 第二章 (2.xhtml) -> #1 第二章 开始 (2.xhtml)\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("    + 2: 新增\n"), "{}", text);
+
+        // 相同的书没有差异
+        let mut copy = EpubBuilder::new()
+            .with_title("书")
+            .with_creator("作者")
+            .add_chapter(chapter("第一章", "1.xhtml", "<p>第一章的内容</p>"))
+            .book()
+            .unwrap();
+        let mut same = EpubBuilder::new()
+            .with_title("书")
+            .with_creator("作者")
+            .add_chapter(chapter("第一章", "1.xhtml", "<p>第一章的内容</p>"))
+            .book()
+            .unwrap();
+        assert!(copy.diff(&mut same).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_diff_epub3() {
+        use crate::epub::reader::tests::epub3_with;
+
+        let chapter = |text: &str| {
+            format!(
+                r#"<?xml version="1.0" encoding="utf-8"?><html xmlns="http://www.w3.org/1999/xhtml"><head><title>第二章</title></head><body>{text}</body></html>"#
+            )
+        };
+        let (a, b) = (
+            chapter("<p>第一行</p><p>第二行</p>"),
+            chapter("<p>第一行</p><p>修改后的第二行</p>"),
+        );
+        // 延迟读取，卷下的章节在加载后才有标题
+        let mut old = read_from_vec(epub3_with(&[("EPUB/text/c2.xhtml", &a)])).unwrap();
+        let mut new = read_from_vec(epub3_with(&[("EPUB/text/c2.xhtml", &b)])).unwrap();
+        let diff = old.diff(&mut new).unwrap();
+
+        let chapters = diff.chapters();
+        assert_eq!(1, chapters.len(), "{}", diff);
+        assert_eq!(ChangeKind::Modified, chapters[0].kind());
+        assert_eq!("第二章", chapters[0].old_chapter().unwrap().title());
+        assert_eq!("第二章", chapters[0].new_chapter().unwrap().title());
+        assert_eq!(
+            vec![
+                (ChangeKind::Removed, 2, "第二行"),
+                (ChangeKind::Added, 2, "修改后的第二行")
+            ],
+            chapters[0]
+                .lines()
+                .iter()
+                .map(|v| (v.kind(), v.line(), v.text()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_diff_insert() {
+        let book = |titles: &[&str]| {
+            let mut builder = EpubBuilder::new().with_title("书");
+            for (index, title) in titles.iter().enumerate() {
+                builder = builder.add_chapter(
+                    EpubHtml::default()
+                        .with_title(*title)
+                        .with_file_name(format!("chapter_{:04}.xhtml", index + 1).as_str())
+                        .with_data(format!("<p>{title}的内容</p>").into_bytes()),
+                );
+            }
+            builder.book().unwrap()
+        };
+        let mut old = book(&["第一章", "第二章", "第三章"]);
+        let mut new = book(&["第一章", "插入", "第二章", "第三章"]);
+        // 插入章节后文件名错位，其他章节按内容和标题匹配
+        let diff = old.diff(&mut new).unwrap();
+        let chapters = diff.chapters();
+        assert_eq!(1, chapters.len(), "{}", diff);
+        assert_eq!(ChangeKind::Added, chapters[0].kind());
+        assert_eq!("插入", chapters[0].new_chapter().unwrap().title());
+        assert_eq!(
+            Some("chapter_0002.xhtml"),
+            chapters[0].new_chapter().unwrap().file_name()
+        );
+    }
+
+    #[test]
+    fn test_diff_nav_in_spine() {
+        use crate::epub::reader::tests::{epub3_with, EPUB3_NAV, EPUB3_OPF};
+
+        let opf = EPUB3_OPF.replace(
+            r#"<itemref idref="ch1"/>"#,
+            r#"<itemref idref="nav"/><itemref idref="ch1"/>"#,
+        );
+        let nav = EPUB3_NAV.replace("第三章</a>", "第三章（修订）</a>");
+        let mut old = read_from_vec(epub3_with(&[("EPUB/package.opf", &opf)])).unwrap();
+        let mut new = read_from_vec(epub3_with(&[
+            ("EPUB/package.opf", &opf),
+            ("EPUB/toc.xhtml", &nav),
+        ]))
+        .unwrap();
+        // 导航文档不作为章节比较
+        let snapshot = BookSnapshot::from_epub(&mut old).unwrap();
+        assert_eq!(4, snapshot.chapters.len());
+        let diff = old.diff(&mut new).unwrap();
+        assert!(diff.chapters().is_empty(), "{}", diff);
+    }
+
+    #[test]
+    fn test_diff_mobi() {
+        let book = |text: &str| {
+            MobiBuilder::new()
+                .with_title("书")
+                .cover(crate::cbz::core::tests::png(1, 1))
+                .add_chapter(
+                    MobiHtml::new(1)
+                        .with_title("第一章")
+                        .with_data(text.as_bytes().to_vec()),
+                )
+                .book()
+                .unwrap()
+        };
+        let diff = book("<p>正文</p>")
+            .diff(&book("<p>正文，修改</p>"))
+            .unwrap();
+        assert_eq!(1, diff.chapters().len());
+        assert_eq!(None, diff.chapters()[0].old_chapter().unwrap().file_name());
+        assert_eq!(2, diff.chapters()[0].lines().len());
+        assert!(diff.assets().is_empty());
+
+        // epub 与 mobi 比较
+        let mut epub = EpubBuilder::new()
+            .with_title("书")
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一章")
+                    .with_file_name("1.xhtml")
+                    .with_data("<p>正文</p>".as_bytes().to_vec()),
+            )
+            .book()
+            .unwrap();
+        let mobi = book("<p>正文</p>");
+        let diff = super::BookDiff::new(
+            &BookSnapshot::from_epub(&mut epub).unwrap(),
+            &BookSnapshot::from_mobi(&mobi).unwrap(),
+        );
+        assert!(diff.chapters().is_empty());
+    }
+}
@@ -0,0 +1,776 @@
+//!
+//! 导出为纯文本或 Markdown
+//!
+//! 章节内容先规范化为 xhtml 再逐个标签转换，保留标题、段落、强调、列表、链接以及图片引用，
+//! 目录中的标题按照层级输出为对应级别的标题
+//!
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::{
+    adapter::core::mobi_to_epub,
+    epub::{
+        layout::{normalize, parent},
+        xhtml::{normalize_html, NormalizePolicy},
+    },
+    prelude::*,
+};
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// 纯文本，每个段落一行
+    Text,
+    Markdown,
+}
+
+///
+/// 导出结果
+///
+#[derive(Debug, Default)]
+pub struct TextExport {
+    content: String,
+    images: Vec<(String, Vec<u8>)>,
+}
+
+impl TextExport {
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// 提取的图片，(Markdown 中引用的路径，图片数据)
+    pub fn images(&self) -> std::slice::Iter<'_, (String, Vec<u8>)> {
+        self.images.iter()
+    }
+}
+
+///
+/// 导出整本书
+///
+/// # Examples
+///
+/// ```no_run
+/// use iepub::prelude::*;
+/// use iepub::prelude::export::*;
+///
+/// let mut book = read_from_file("1.epub").unwrap();
+/// let export = TextExporter::new(ExportFormat::Markdown)
+///     .with_image_dir("images")
+///     .epub(&mut book)
+///     .unwrap();
+/// std::fs::write("1.md", export.content()).unwrap();
+/// for (path, data) in export.images() {
+///     // 写入 images 目录
+/// }
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct TextExporter {
+    format: ExportFormat,
+    image_dir: Option<String>,
+}
+
+impl TextExporter {
+    pub fn new(format: ExportFormat) -> Self {
+        TextExporter {
+            format,
+            image_dir: None,
+        }
+    }
+
+    ///
+    /// 提取图片，Markdown 中的图片引用改为 `{dir}/{文件名}`
+    ///
+    /// 图片数据通过 [TextExport::images] 获取，不设置时保留原有的引用
+    ///
+    pub fn with_image_dir<T: Into<String>>(mut self, dir: T) -> Self {
+        self.image_dir = Some(dir.into());
+        self
+    }
+
+    pub fn epub(&self, book: &mut EpubBook) -> IResult<TextExport> {
+        let markdown = self.format == ExportFormat::Markdown;
+        let mut navs = Vec::new();
+        flatten_nav(book.nav(), 0, &mut navs);
+
+        let mut blocks: Vec<Block> = Vec::new();
+        // (资源文件，引用路径)
+        let mut refs: Vec<(String, String)> = Vec::new();
+        for chap in book.chapters_mut() {
+            if chap.is_generated() {
+                continue;
+            }
+            let file = chap.file_name().to_string();
+            let Some(data) = chap.data_mut() else {
+                continue;
+            };
+            let html = String::from_utf8_lossy(data).to_string();
+
+            let mut image = |src: &str| self.image_ref(&file, src, &mut refs);
+            let mut content = Renderer::new(markdown, &mut image).render(&html);
+
+            let headings: Vec<&(String, String, usize)> = navs
+                .iter()
+                .filter(|(f, _, _)| f.split('#').next() == Some(file.as_str()))
+                .collect();
+            // 最后一个目录和正文的第一个标题相同时，直接使用正文的标题
+            let same = match (headings.last(), content.first()) {
+                (Some((_, title, _)), Some(b)) if b.heading.is_some() => {
+                    compact(&b.text.replace('\\', "")) == compact(title)
+                }
+                _ => false,
+            };
+            let base = headings
+                .last()
+                .map_or(1, |(_, _, depth)| depth + if same { 1 } else { 2 });
+            if let Some(min) = content.iter().filter_map(|b| b.heading).min() {
+                for b in &mut content {
+                    if let Some(level) = b.heading {
+                        b.heading = Some((level + base - min).clamp(1, 6));
+                    }
+                }
+            }
+
+            let count = headings.len() - usize::from(same);
+            for (_, title, depth) in headings.into_iter().take(count) {
+                // 父目录和第一个子目录指向同一个文件时可能已经输出过
+                if last_heading(&blocks) == Some(compact(title)) {
+                    continue;
+                }
+                blocks.push(Block {
+                    heading: Some((depth + 1).min(6)),
+                    text: if markdown {
+                        escape_markdown(title)
+                    } else {
+                        title.to_string()
+                    },
+                    ..Default::default()
+                });
+            }
+            if content.first().is_some_and(|b| {
+                b.heading.is_some()
+                    && last_heading(&blocks) == Some(compact(&b.text.replace('\\', "")))
+            }) {
+                content.remove(0);
+            }
+            blocks.append(&mut content);
+        }
+
+        let mut images = Vec::new();
+        for (file, path) in refs {
+            if let Some(data) = book.get_assets_mut(&file).and_then(|a| a.data_mut()) {
+                images.push((path, data.to_vec()));
+            }
+        }
+        Ok(TextExport {
+            content: serialize(&blocks, markdown),
+            images,
+        })
+    }
+
+    /// mobi 先转换为 epub 再导出
+    pub fn mobi(&self, book: &mut MobiBook) -> IResult<TextExport> {
+        self.epub(&mut mobi_to_epub(book)?)
+    }
+
+    /// 图片引用，返回 None 时不输出图片
+    fn image_ref(&self, chap: &str, src: &str, refs: &mut Vec<(String, String)>) -> Option<String> {
+        if self.format == ExportFormat::Text || src.is_empty() {
+            return None;
+        }
+        let Some(dir) = &self.image_dir else {
+            return Some(src.to_string());
+        };
+        if src.contains(':') {
+            // 外部链接或者 data:
+            return Some(src.to_string());
+        }
+        let file = normalize(parent(chap), src);
+        if let Some((_, path)) = refs.iter().find(|(f, _)| f == &file) {
+            return Some(path.clone());
+        }
+        let dir = dir.trim_end_matches('/');
+        let name = file.rsplit('/').next().unwrap_or(file.as_str());
+        let mut path = format!("{}/{}", dir, name);
+        if refs.iter().any(|(_, p)| p == &path) {
+            path = format!("{}/{}_{}", dir, refs.len(), name);
+        }
+        refs.push((file, path.clone()));
+        Some(path)
+    }
+}
+
+/// 章节的纯文本，每个段落一行
+pub(crate) fn html_text(html: &str) -> String {
+    let mut image = |_: &str| None;
+    serialize(&Renderer::new(false, &mut image).render(html), false)
+}
+
+/// 目录展开为 (文件，标题，层级)
+fn flatten_nav<'a>(
+    nav: impl Iterator<Item = &'a EpubNav>,
+    depth: usize,
+    out: &mut Vec<(String, String, usize)>,
+) {
+    for ele in nav {
+        out.push((ele.file_name().to_string(), ele.title().to_string(), depth));
+        flatten_nav(ele.child(), depth + 1, out);
+    }
+}
+
+/// 最后一个块是标题时返回标题
+fn last_heading(blocks: &[Block]) -> Option<String> {
+    blocks
+        .last()
+        .filter(|b| b.heading.is_some())
+        .map(|b| compact(&b.text.replace('\\', "")))
+}
+
+fn compact(v: &str) -> String {
+    v.split_whitespace().collect()
+}
+
+fn escape_markdown(v: &str) -> String {
+    let mut out = String::with_capacity(v.len());
+    for c in v.chars() {
+        if matches!(c, '\\' | '*' | '_' | '[' | ']' | '`' | '<') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// 行首会被当作 Markdown 语法的字符
+fn escape_line_start(v: String) -> String {
+    if v.starts_with(['#', '>', '-', '+', '=', '|']) {
+        return format!("\\{}", v);
+    }
+    let digits = v.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && v[digits..].starts_with(['.', ')']) {
+        return format!("{}\\{}", &v[..digits], &v[digits..]);
+    }
+    v
+}
+
+#[derive(Debug, Default)]
+struct Block {
+    /// 标题级别
+    heading: Option<usize>,
+    /// 是否是列表项
+    item: bool,
+    /// 是否是代码块
+    code: bool,
+    text: String,
+}
+
+fn serialize(blocks: &[Block], markdown: bool) -> String {
+    let mut out = String::new();
+    for (index, b) in blocks.iter().enumerate() {
+        if index > 0 {
+            let prev = &blocks[index - 1];
+            out.push_str(if markdown {
+                if b.item && prev.item {
+                    "\n"
+                } else {
+                    "\n\n"
+                }
+            } else if b.heading.is_some() || prev.heading.is_some() {
+                "\n\n"
+            } else {
+                "\n"
+            });
+        }
+        match (markdown, b.heading) {
+            (true, Some(level)) => {
+                out.push_str(&"#".repeat(level));
+                out.push(' ');
+                out.push_str(&b.text);
+            }
+            (true, None) if b.code => {
+                out.push_str("```\n");
+                out.push_str(&b.text);
+                out.push_str("\n```");
+            }
+            _ => out.push_str(&b.text),
+        }
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+///
+/// 将单个章节转换为块
+///
+struct Renderer<'a> {
+    markdown: bool,
+    blocks: Vec<Block>,
+    line: String,
+    /// 下一个文字前需要补充空格
+    space: bool,
+    heading: Option<usize>,
+    /// 当前列表项的前缀
+    item: Option<String>,
+    /// 嵌套的列表，有序列表记录当前序号
+    lists: Vec<Option<usize>>,
+    quote: usize,
+    /// pre 中的原始文本
+    pre: Option<String>,
+    /// 强调等标记的开始位置
+    marks: Vec<(usize, &'static str)>,
+    /// 链接的开始位置以及地址，内部链接为 None
+    links: Vec<Option<(usize, String)>>,
+    /// 跳过的元素层级
+    skip: usize,
+    image: &'a mut dyn FnMut(&str) -> Option<String>,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(markdown: bool, image: &'a mut dyn FnMut(&str) -> Option<String>) -> Self {
+        Renderer {
+            markdown,
+            blocks: Vec::new(),
+            line: String::new(),
+            space: false,
+            heading: None,
+            item: None,
+            lists: Vec::new(),
+            quote: 0,
+            pre: None,
+            marks: Vec::new(),
+            links: Vec::new(),
+            skip: 0,
+            image,
+        }
+    }
+
+    fn render(mut self, html: &str) -> Vec<Block> {
+        let (html, _) = normalize_html(html, &NormalizePolicy::default());
+        let mut reader = Reader::from_str(&html);
+        reader.config_mut().expand_empty_elements = true;
+        reader.config_mut().check_end_names = false;
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(e)) => self.start(&e),
+                Ok(Event::End(e)) => {
+                    let name = String::from_utf8_lossy(e.local_name().as_ref()).to_lowercase();
+                    self.end(&name);
+                }
+                Ok(Event::Text(e)) => {
+                    if let Ok(t) = e.unescape() {
+                        self.text(&t);
+                    }
+                }
+                Ok(Event::CData(e)) => self.text(&String::from_utf8_lossy(&e)),
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        self.flush();
+        self.blocks
+    }
+
+    fn start(&mut self, e: &BytesStart) {
+        if self.skip > 0 {
+            self.skip += 1;
+            return;
+        }
+        let name = String::from_utf8_lossy(e.local_name().as_ref()).to_lowercase();
+        let attr = |key: &str| {
+            e.try_get_attribute(key)
+                .ok()
+                .flatten()
+                .and_then(|v| v.unescape_value().ok())
+                .map(|v| v.to_string())
+        };
+        match name.as_str() {
+            "head" | "script" | "style" | "title" => self.skip = 1,
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.flush();
+                self.heading = name[1..].parse().ok();
+            }
+            "ul" | "ol" => {
+                self.flush();
+                self.lists.push(if name == "ol" {
+                    Some(
+                        attr("start")
+                            .and_then(|v| v.parse::<usize>().ok())
+                            .unwrap_or(1)
+                            .saturating_sub(1),
+                    )
+                } else {
+                    None
+                });
+            }
+            "li" => {
+                self.flush();
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                self.item = Some(match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}{}. ", indent, n)
+                    }
+                    _ => format!("{}- ", indent),
+                });
+            }
+            "blockquote" => {
+                self.flush();
+                self.quote += 1;
+            }
+            "pre" => {
+                self.flush();
+                self.pre = Some(String::new());
+            }
+            "br" => match &mut self.pre {
+                Some(pre) => pre.push('\n'),
+                None => {
+                    self.line
+                        .push_str(if self.markdown { "\\\n" } else { "\n" });
+                    self.space = false;
+                }
+            },
+            "hr" => {
+                self.flush();
+                if self.markdown {
+                    self.blocks.push(Block {
+                        text: "---".to_string(),
+                        ..Default::default()
+                    });
+                }
+            }
+            "em" | "i" | "cite" | "var" => self.open_mark("*"),
+            "strong" | "b" => self.open_mark("**"),
+            "code" | "kbd" | "samp" | "tt" if self.pre.is_none() => self.open_mark("`"),
+            "a" => {
+                let href = attr("href").filter(|v| v.contains("://") || v.starts_with("mailto:"));
+                match href {
+                    Some(href) if self.markdown => {
+                        self.inline();
+                        self.links.push(Some((self.line.len(), href)));
+                        self.line.push('[');
+                    }
+                    _ => self.links.push(None),
+                }
+            }
+            "img" | "image" => {
+                let src = attr("src")
+                    .or_else(|| attr("xlink:href"))
+                    .or_else(|| attr("href"))
+                    .unwrap_or_default();
+                if let Some(src) = (self.image)(&src) {
+                    let alt = attr("alt").unwrap_or_default();
+                    self.inline();
+                    self.line
+                        .push_str(&format!("![{}]({})", escape_markdown(&alt), src));
+                }
+            }
+            "td" | "th" if !self.line.is_empty() => {
+                self.line.push_str(if self.markdown { " | " } else { " " });
+                self.space = false;
+            }
+            v if is_block(v) => self.flush(),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return;
+        }
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "li" => self.flush(),
+            "ul" | "ol" => {
+                self.flush();
+                self.lists.pop();
+            }
+            "blockquote" => {
+                self.flush();
+                self.quote = self.quote.saturating_sub(1);
+            }
+            "pre" => {
+                if let Some(pre) = self.pre.take() {
+                    let text = pre.trim_matches('\n');
+                    if !text.trim().is_empty() {
+                        self.blocks.push(Block {
+                            code: true,
+                            text: text.to_string(),
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+            "em" | "i" | "cite" | "var" | "strong" | "b" => self.close_mark(),
+            "code" | "kbd" | "samp" | "tt" if self.pre.is_none() => self.close_mark(),
+            "a" => {
+                if let Some(Some((pos, href))) = self.links.pop() {
+                    if self.line.len() == pos + 1 {
+                        self.line.truncate(pos);
+                    } else {
+                        self.line.push_str(&format!("]({})", href));
+                    }
+                }
+            }
+            v if is_block(v) => self.flush(),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.skip > 0 {
+            return;
+        }
+        if let Some(pre) = &mut self.pre {
+            pre.push_str(text);
+            return;
+        }
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.space = !self.line.is_empty();
+                continue;
+            }
+            if self.space {
+                self.line.push(' ');
+                self.space = false;
+            }
+            if self.markdown && matches!(c, '\\' | '*' | '_' | '[' | ']' | '`' | '<') {
+                self.line.push('\\');
+            }
+            self.line.push(c);
+        }
+    }
+
+    /// 内联元素开始前补充空格
+    fn inline(&mut self) {
+        if self.space {
+            self.line.push(' ');
+            self.space = false;
+        }
+    }
+
+    fn open_mark(&mut self, mark: &'static str) {
+        if self.markdown {
+            self.inline();
+            self.marks.push((self.line.len(), mark));
+            self.line.push_str(mark);
+        }
+    }
+
+    fn close_mark(&mut self) {
+        if let Some((pos, mark)) = self.marks.pop() {
+            if self.line.len() == pos + mark.len() {
+                // 空的标记
+                self.line.truncate(pos);
+            } else {
+                self.line.push_str(mark);
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        let text = self.line.trim().to_string();
+        self.line.clear();
+        self.space = false;
+        self.marks.clear();
+        for link in &mut self.links {
+            *link = None;
+        }
+        let heading = self.heading.take();
+        if text.is_empty() {
+            return;
+        }
+        let item = self.item.take();
+        let mut text = if self.markdown && heading.is_none() {
+            escape_line_start(text)
+        } else {
+            text
+        };
+        if let Some(prefix) = &item {
+            text = format!("{}{}", prefix, text);
+        }
+        if self.markdown && self.quote > 0 {
+            let prefix = "> ".repeat(self.quote);
+            text = text
+                .lines()
+                .map(|l| format!("{}{}", prefix, l))
+                .collect::<Vec<String>>()
+                .join("\n");
+        }
+        self.blocks.push(Block {
+            heading,
+            item: item.is_some(),
+            code: false,
+            text,
+        });
+    }
+}
+
+/// 结束当前段落的元素
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "p" | "div"
+            | "section"
+            | "article"
+            | "header"
+            | "footer"
+            | "aside"
+            | "nav"
+            | "main"
+            | "body"
+            | "center"
+            | "address"
+            | "figure"
+            | "figcaption"
+            | "caption"
+            | "table"
+            | "tr"
+            | "dl"
+            | "dt"
+            | "dd"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExportFormat, TextExporter};
+    use crate::prelude::*;
+
+    fn book() -> EpubBook {
+        EpubBuilder::new()
+            .with_title("书")
+            .custome_nav(true)
+            .add_assets("Images/1.png", vec![1, 2, 3])
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一卷")
+                    .with_file_name("Text/0.xhtml")
+                    .with_data("<p>卷首</p>".as_bytes().to_vec()),
+            )
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一章")
+                    .with_file_name("Text/1.xhtml")
+                    .with_data(
+                        r#"<h1>第一章</h1><p>  正文 <em>强调</em><b></b>和<a href="https://a.com">链接</a>，<a href="2.xhtml">内部</a></p>
+<h2>小节</h2><ul><li>一</li><li><p>二</p><ol start="3"><li>三</li></ol></li></ul>
+<blockquote><p>引用*</p></blockquote><p><img src="../Images/1.png" alt="图"/></p><pre>let a = 1;
+a += 1;</pre><p>1. 不是列表<br/>换行</p>"#
+                            .as_bytes()
+                            .to_vec(),
+                    ),
+            )
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第二章")
+                    .with_file_name("Text/2.xhtml")
+                    .with_data("<div>  <p>&#12288;&#12288;第二章内容</p></div>".as_bytes().to_vec()),
+            )
+            .add_nav(
+                {
+                    let mut nav = EpubNav::default()
+                        .with_title("第一卷")
+                        .with_file_name("Text/0.xhtml");
+                    nav.push(
+                        EpubNav::default()
+                            .with_title("第一章")
+                            .with_file_name("Text/1.xhtml"),
+                    );
+                    nav.push(
+                        EpubNav::default()
+                            .with_title("第二章")
+                            .with_file_name("Text/2.xhtml#p1"),
+                    );
+                    nav
+                },
+            )
+            .book()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_markdown() {
+        let mut book = book();
+        let export = TextExporter::new(ExportFormat::Markdown)
+            .epub(&mut book)
+            .unwrap();
+        assert_eq!(
+            r#"# 第一卷
+
+卷首
+
+## 第一章
+
+正文 *强调*和[链接](https://a.com)，内部
+
+### 小节
+
+- 一
+- 二
+  3. 三
+
+> 引用\*
+
+![图](../Images/1.png)
+
+```
+let a = 1;
+a += 1;
+```
+
+1\. 不是列表\
+换行
+
+## 第二章
+
+第二章内容
+"#,
+            export.content()
+        );
+        assert_eq!(0, export.images().count());
+
+        let export = TextExporter::new(ExportFormat::Markdown)
+            .with_image_dir("images/")
+            .epub(&mut book)
+            .unwrap();
+        assert!(export.content().contains("![图](images/1.png)"));
+        let images: Vec<_> = export.images().collect();
+        assert_eq!(1, images.len());
+        assert_eq!("images/1.png", images[0].0);
+        assert_eq!(vec![1, 2, 3], images[0].1);
+    }
+
+    #[test]
+    fn test_text() {
+        let mut book = book();
+        let export = TextExporter::new(ExportFormat::Text)
+            .with_image_dir("images")
+            .epub(&mut book)
+            .unwrap();
+        assert_eq!(
+            "第一卷\n\n卷首\n\n第一章\n\n正文 强调和链接，内部\n\n小节\n\n- 一\n- 二\n  3. 三\n引用*\nlet a = 1;\na += 1;\n1. 不是列表\n换行\n\n第二章\n\n第二章内容\n",
+            export.content()
+        );
+        assert_eq!(0, export.images().count());
+    }
+
+    #[test]
+    fn test_mobi() {
+        let mut book = MobiBuilder::new()
+            .with_title("书")
+            .cover(Vec::new())
+            .add_chapter(
+                MobiHtml::new(1)
+                    .with_title("标题")
+                    .with_data("<p>内容</p>".as_bytes().to_vec()),
+            )
+            .book()
+            .unwrap();
+        let export = TextExporter::new(ExportFormat::Markdown)
+            .mobi(&mut book)
+            .unwrap();
+        assert_eq!("# 1. 标题\n\n内容\n", export.content());
+    }
+}
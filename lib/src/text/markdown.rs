@@ -0,0 +1,761 @@
+//!
+//! Markdown 导入
+//!
+//! 支持单个或多个 `.md` 文件，以及目录。目录中有 `book.toml` 时按照 mdBook 的结构读取，
+//! 元数据来自 `book.toml` 的 `[book]` 以及各个文件的 front matter，章节顺序和层级来自 `SUMMARY.md`，
+//! 否则按文件名顺序读取目录下的所有 `.md` 文件
+//!
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use pulldown_cmark::{html, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+use crate::{
+    common::{gen_uuid, ErrorContext, ResultContext},
+    prelude::*,
+    text::{export::html_text, typography::detect_language},
+};
+
+/// 元数据的 key 以及对应的设置方法
+const META_KEYS: &[(&str, &str)] = &[
+    ("title", "title"),
+    ("author", "creator"),
+    ("authors", "creator"),
+    ("creator", "creator"),
+    ("description", "description"),
+    ("date", "date"),
+    ("publisher", "publisher"),
+    ("subject", "subject"),
+    ("tags", "subject"),
+    ("identifier", "identifier"),
+    ("isbn", "identifier"),
+    ("contributor", "contributor"),
+    ("language", "language"),
+    ("lang", "language"),
+];
+
+/// 一个 Markdown 文件
+#[derive(Debug, Clone)]
+struct Source {
+    path: PathBuf,
+    /// 目录层级，从1开始
+    level: usize,
+    /// SUMMARY.md 中的标题
+    title: Option<String>,
+}
+
+/// 一个章节
+#[derive(Debug, Default)]
+struct Chapter {
+    level: usize,
+    title: String,
+    html: String,
+}
+
+///
+/// Markdown 导入
+///
+/// # Examples
+///
+/// ```no_run
+/// use iepub::prelude::markdown::MarkdownImporter;
+///
+/// MarkdownImporter::new()
+///     .add_file("intro.md")
+///     .add_file("chapter1.md")
+///     // 按照一二级标题拆分章节
+///     .with_split_level(2)
+///     .epub_builder()
+///     .unwrap()
+///     .with_creator("作者")
+///     .file("book.epub")
+///     .unwrap();
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownImporter {
+    files: Vec<PathBuf>,
+    split_level: Option<usize>,
+}
+
+impl MarkdownImporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加 Markdown 文件
+    pub fn add_file<P: Into<PathBuf>>(mut self, file: P) -> Self {
+        self.files.push(file.into());
+        self
+    }
+
+    /// 添加目录，有 `book.toml` 时按照 mdBook 的结构读取，否则读取目录下的所有 `.md` 文件
+    pub fn add_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.files.push(dir.into());
+        self
+    }
+
+    ///
+    /// 按标题拆分章节，级别小于等于 level 的标题都会开始一个新的章节，目录按照标题级别嵌套
+    ///
+    /// 不设置时每个文件作为一个章节
+    ///
+    pub fn with_split_level(mut self, level: usize) -> Self {
+        self.split_level = Some(level.clamp(1, 6));
+        self
+    }
+
+    ///
+    /// 生成 epub 构造器，已添加章节、目录、图片以及元数据
+    ///
+    /// 章节内容中已包含标题，构造器设置了 append_title(false)；
+    /// 元数据中没有标识时随机生成 uuid，没有语言时根据正文推断
+    ///
+    pub fn epub_builder(&self) -> IResult<EpubBuilder> {
+        let mut meta: Vec<(String, String)> = Vec::new();
+        let mut sources = Vec::new();
+        for path in &self.files {
+            if path.is_dir() {
+                read_dir(path, &mut sources, &mut meta)?;
+            } else {
+                sources.push(Source {
+                    path: path.clone(),
+                    level: 1,
+                    title: None,
+                });
+            }
+        }
+
+        let mut assets: Vec<(PathBuf, String)> = Vec::new();
+        let mut chapters: Vec<Chapter> = Vec::new();
+        for source in &sources {
+            let text = std::fs::read_to_string(&source.path).context(|| {
+                ErrorContext::new("read markdown").with_entry(source.path.to_string_lossy())
+            })?;
+            let (front, body) = split_front_matter(&text);
+            for (key, value) in &front {
+                set_meta(&mut meta, key, value);
+            }
+            let dir = source.path.parent().unwrap_or(Path::new(""));
+            let mut image = |url: CowStr<'static>| image_ref(dir, url, &mut assets);
+
+            match self.split_level {
+                Some(level) => split_by_heading(body, level, source, &mut image, &mut chapters),
+                None => {
+                    let html = render(Parser::new_ext(body, options()), &mut image);
+                    let title = source
+                        .title
+                        .clone()
+                        .or_else(|| first_heading(body))
+                        .or_else(|| {
+                            front
+                                .iter()
+                                .find(|(k, _)| k == "title")
+                                .map(|(_, v)| v.clone())
+                        })
+                        .unwrap_or_else(|| file_stem(&source.path));
+                    chapters.push(Chapter {
+                        level: source.level,
+                        title,
+                        html,
+                    });
+                }
+            }
+        }
+
+        let mut builder = EpubBuilder::new()
+            .custome_nav(true)
+            .append_title(false)
+            .with_normalize(NormalizePolicy::default());
+        for (key, value) in &meta {
+            builder = match key.as_str() {
+                "title" => builder.with_title(value),
+                "creator" => builder.with_creator(value),
+                "description" => builder.with_description(value),
+                "date" => builder.with_date(value),
+                "publisher" => builder.with_publisher(value),
+                "subject" => builder.with_subject(value),
+                "identifier" => builder.with_identifier(value),
+                "contributor" => builder.with_contributor(value),
+                "language" => builder.with_language(value),
+                _ => builder,
+            };
+        }
+        if !meta.iter().any(|(k, _)| k == "identifier") {
+            builder = builder.with_identifier(format!("urn:uuid:{}", gen_uuid()));
+        }
+        if !meta.iter().any(|(k, _)| k == "language") {
+            // 推断语言只需要开头的一部分正文
+            let mut text = String::new();
+            for chap in &chapters {
+                if text.len() > 16 * 1024 {
+                    break;
+                }
+                text.push_str(&html_text(&chap.html));
+            }
+            builder = builder.with_language(detect_language(&text));
+        }
+        for (path, name) in assets {
+            let data = std::fs::read(&path)
+                .context(|| ErrorContext::new("read image").with_entry(path.to_string_lossy()))?;
+            builder = builder.add_assets(name, data);
+        }
+
+        let mut nav = Vec::new();
+        for (index, chap) in chapters.iter().enumerate() {
+            let file_name = format!("chapter_{:04}.xhtml", index + 1);
+            builder = builder.add_chapter(
+                EpubHtml::default()
+                    .with_title(chap.title.as_str())
+                    .with_file_name(file_name.as_str())
+                    .with_data(chap.html.as_bytes().to_vec()),
+            );
+            nav.push((chap.level, chap.title.as_str(), file_name));
+        }
+        let mut index = 0;
+        for ele in build_nav(&nav, &mut index, 0) {
+            builder = builder.add_nav(ele);
+        }
+        Ok(builder)
+    }
+
+    /// 生成 epub
+    pub fn epub(&self) -> IResult<EpubBook> {
+        self.epub_builder()?.book()
+    }
+}
+
+fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+}
+
+/// 读取目录
+fn read_dir(
+    dir: &Path,
+    sources: &mut Vec<Source>,
+    meta: &mut Vec<(String, String)>,
+) -> IResult<()> {
+    let mut src = dir.to_path_buf();
+    let manifest = dir.join("book.toml");
+    if manifest.is_file() {
+        let text = std::fs::read_to_string(&manifest).context(|| {
+            ErrorContext::new("read manifest").with_entry(manifest.to_string_lossy())
+        })?;
+        for (key, value) in parse_meta(&text) {
+            if let Some(key) = key.strip_prefix("book.") {
+                if key == "src" {
+                    src = dir.join(&value);
+                } else {
+                    set_meta(meta, key, &value);
+                }
+            }
+        }
+        if src == dir {
+            src = dir.join("src");
+        }
+    }
+
+    let summary = src.join("SUMMARY.md");
+    if summary.is_file() {
+        let text = std::fs::read_to_string(&summary)
+            .context(|| ErrorContext::new("read summary").with_entry(summary.to_string_lossy()))?;
+        sources.append(&mut parse_summary(&text, &src));
+        return Ok(());
+    }
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&src)
+        .context(|| ErrorContext::new("read dir").with_entry(src.to_string_lossy()))?
+        .filter_map(|f| f.ok().map(|f| f.path()))
+        .filter(|f| {
+            f.is_file()
+                && f.extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("md") || e == "markdown")
+        })
+        .collect();
+    files.sort();
+    sources.extend(files.into_iter().map(|path| Source {
+        path,
+        level: 1,
+        title: None,
+    }));
+    Ok(())
+}
+
+///
+/// 解析 mdBook 的 SUMMARY.md，列表的缩进决定层级，没有链接的草稿章节会被忽略
+///
+fn parse_summary(text: &str, dir: &Path) -> Vec<Source> {
+    let mut sources = Vec::new();
+    let mut indents: Vec<usize> = Vec::new();
+    for line in text.lines() {
+        let Some(start) = line.find('[') else {
+            continue;
+        };
+        let Some((title, rest)) = line[start + 1..].split_once("](") else {
+            continue;
+        };
+        let Some((link, _)) = rest.split_once(')') else {
+            continue;
+        };
+        let link = link.split('#').next().unwrap_or_default().trim();
+        if link.is_empty() {
+            continue;
+        }
+        let prefix = &line[..start];
+        let indent = prefix.len() - prefix.trim_start().len();
+        while indents.last().is_some_and(|v| *v > indent) {
+            indents.pop();
+        }
+        if indents.last() != Some(&indent) {
+            indents.push(indent);
+        }
+        sources.push(Source {
+            path: dir.join(link),
+            level: indents.len(),
+            title: Some(title.to_string()),
+        });
+    }
+    sources
+}
+
+///
+/// 拆分出 front matter，支持 `---` 包裹的 yaml 以及 `+++` 包裹的 toml
+///
+fn split_front_matter(text: &str) -> (Vec<(String, String)>, &str) {
+    let text = text.trim_start_matches('\u{feff}');
+    for fence in ["---", "+++"] {
+        let Some(rest) = text
+            .strip_prefix(fence)
+            .and_then(|v| v.strip_prefix('\n').or_else(|| v.strip_prefix("\r\n")))
+        else {
+            continue;
+        };
+        let mut offset = 0;
+        for line in rest.split_inclusive('\n') {
+            if line.trim_end() == fence {
+                return (parse_meta(&rest[..offset]), &rest[offset + line.len()..]);
+            }
+            offset += line.len();
+        }
+    }
+    (Vec::new(), text)
+}
+
+///
+/// 解析简单的 `key: value`、`key = value` 格式，只支持字符串以及字符串数组
+///
+/// `[section]` 下的 key 会加上 `section.` 前缀，数组用 `, ` 连接
+///
+fn parse_meta(text: &str) -> Vec<(String, String)> {
+    let mut out: Vec<(String, String)> = Vec::new();
+    let mut section = String::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed.starts_with('[') && trimmed.ends_with(']') && !trimmed.contains(['=', ':']) {
+            section = format!("{}.", trimmed.trim_matches(['[', ']']).trim());
+            continue;
+        }
+        // yaml 列表
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            if let Some((_, value)) = out.last_mut() {
+                if !value.is_empty() {
+                    value.push_str(", ");
+                }
+                value.push_str(&unquote(item));
+            }
+            continue;
+        }
+        let Some(index) = trimmed.find(['=', ':']) else {
+            continue;
+        };
+        let key = unquote(&trimmed[..index]).to_lowercase();
+        let value = trimmed[index + 1..].trim();
+        let value = if value.starts_with('[') && value.ends_with(']') {
+            value[1..value.len() - 1]
+                .split(',')
+                .map(unquote)
+                .filter(|v| !v.is_empty())
+                .collect::<Vec<String>>()
+                .join(", ")
+        } else {
+            unquote(value)
+        };
+        out.push((format!("{}{}", section, key), value));
+    }
+    out
+}
+
+fn unquote(v: &str) -> String {
+    let v = v.trim();
+    for quote in ['"', '\''] {
+        if v.len() >= 2 && v.starts_with(quote) && v.ends_with(quote) {
+            return v[1..v.len() - 1].to_string();
+        }
+    }
+    v.to_string()
+}
+
+/// 按文件顺序，以第一个出现的值为准
+fn set_meta(meta: &mut Vec<(String, String)>, key: &str, value: &str) {
+    let Some((_, key)) = META_KEYS.iter().find(|(k, _)| *k == key) else {
+        return;
+    };
+    if !value.is_empty() && !meta.iter().any(|(k, _)| k == key) {
+        meta.push((key.to_string(), value.to_string()));
+    }
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn heading_level(level: HeadingLevel) -> usize {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+/// 第一个标题的文本
+fn first_heading(text: &str) -> Option<String> {
+    let mut title: Option<String> = None;
+    for event in Parser::new_ext(text, options()) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => title = Some(String::new()),
+            Event::End(TagEnd::Heading(_)) => return title,
+            Event::Text(t) | Event::Code(t) => {
+                if let Some(title) = &mut title {
+                    title.push_str(&t);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+///
+/// 按标题拆分，标题之前的内容追加到上一个章节，没有上一个章节时以文件名作为标题
+///
+fn split_by_heading(
+    text: &str,
+    split_level: usize,
+    source: &Source,
+    image: &mut dyn FnMut(CowStr<'static>) -> CowStr<'static>,
+    chapters: &mut Vec<Chapter>,
+) {
+    let mut events: Vec<Event> = Vec::new();
+    let mut title: Option<String> = None;
+    for event in Parser::new_ext(text, options()) {
+        match &event {
+            Event::Start(Tag::Heading { level, .. }) if heading_level(*level) <= split_level => {
+                flush(&mut events, source, image, chapters);
+                chapters.push(Chapter {
+                    level: heading_level(*level),
+                    ..Default::default()
+                });
+                title = Some(String::new());
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let (Some(t), Some(chap)) = (title.take(), chapters.last_mut()) {
+                    chap.title = t;
+                }
+            }
+            Event::Text(t) | Event::Code(t) => {
+                if let Some(title) = &mut title {
+                    title.push_str(t);
+                }
+            }
+            _ => {}
+        }
+        events.push(event);
+    }
+    flush(&mut events, source, image, chapters);
+
+    fn flush(
+        events: &mut Vec<Event>,
+        source: &Source,
+        image: &mut dyn FnMut(CowStr<'static>) -> CowStr<'static>,
+        chapters: &mut Vec<Chapter>,
+    ) {
+        if events.is_empty() {
+            return;
+        }
+        let html = render(events.drain(..), image);
+        match chapters.last_mut() {
+            Some(chap) => chap.html.push_str(&html),
+            None => chapters.push(Chapter {
+                level: 1,
+                title: source
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| file_stem(&source.path)),
+                html,
+            }),
+        }
+    }
+}
+
+/// 渲染为 html，同时修改图片的地址
+fn render<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+    image: &mut dyn FnMut(CowStr<'static>) -> CowStr<'static>,
+) -> String {
+    let mut out = String::new();
+    html::push_html(
+        &mut out,
+        events.map(|e| match e {
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => Event::Start(Tag::Image {
+                link_type,
+                dest_url: image(dest_url.into_static()),
+                title,
+                id,
+            }),
+            e => e,
+        }),
+    );
+    out
+}
+
+///
+/// 本地图片加入资源文件，返回章节中引用的地址
+///
+/// 资源文件为 `images/文件名`，章节在根目录，所以引用的地址和资源文件名相同
+///
+fn image_ref(
+    dir: &Path,
+    url: CowStr<'static>,
+    assets: &mut Vec<(PathBuf, String)>,
+) -> CowStr<'static> {
+    if url.is_empty() || url.contains(':') || url.starts_with('/') {
+        return url;
+    }
+    let path = dir.join(url.split(['#', '?']).next().unwrap_or_default());
+    if let Some((_, name)) = assets.iter().find(|(p, _)| p == &path) {
+        return CowStr::from(name.clone());
+    }
+    let file = file_name(&path);
+    let mut name = format!("images/{}", file);
+    if assets.iter().any(|(_, n)| n == &name) {
+        name = format!("images/{}_{}", assets.len(), file);
+    }
+    assets.push((path, name.clone()));
+    CowStr::from(name)
+}
+
+fn file_name(path: &Path) -> Cow<'_, str> {
+    path.file_name()
+        .map_or(Cow::Borrowed(""), |f| f.to_string_lossy())
+}
+
+/// 按照层级生成嵌套的目录
+fn build_nav(items: &[(usize, &str, String)], index: &mut usize, parent: usize) -> Vec<EpubNav> {
+    let mut out = Vec::new();
+    while let Some((level, title, file_name)) = items.get(*index) {
+        if *level <= parent {
+            break;
+        }
+        *index += 1;
+        let mut nav = EpubNav::default()
+            .with_title(*title)
+            .with_file_name(file_name.as_str());
+        for child in build_nav(items, index, *level) {
+            nav.push(child);
+        }
+        out.push(nav);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_meta, parse_summary, split_front_matter, MarkdownImporter};
+    use crate::prelude::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("iepub-markdown-{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src/images")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_meta() {
+        let (meta, body) = split_front_matter(
+            "---\ntitle: \"书名: 副标题\"\nauthors:\n  - 甲\n  - 乙\ntags: [a, 'b']\n---\n# 标题\n",
+        );
+        assert_eq!("# 标题\n", body);
+        assert_eq!(
+            vec![
+                ("title".to_string(), "书名: 副标题".to_string()),
+                ("authors".to_string(), "甲, 乙".to_string()),
+                ("tags".to_string(), "a, b".to_string()),
+            ],
+            meta
+        );
+
+        let (meta, _) = split_front_matter("+++\ndate = 2024-01-01\n+++\n");
+        assert_eq!(vec![("date".to_string(), "2024-01-01".to_string())], meta);
+        // 没有结束
+        assert_eq!(0, split_front_matter("---\ntitle: a\n").0.len());
+
+        let meta =
+            parse_meta("[book]\ntitle = \"T\"\nauthors = [\"A\", \"B\"]\n[output.html]\ntitle = x");
+        assert_eq!(("book.title".to_string(), "T".to_string()), meta[0]);
+        assert_eq!(("book.authors".to_string(), "A, B".to_string()), meta[1]);
+        assert_eq!("output.html.title", meta[2].0);
+
+        let summary = parse_summary(
+            "# Summary\n\n[Intro](intro.md)\n\n- [One](one.md)\n    - [One.1](one/1.md#a)\n    - [Draft]()\n- [Two](two.md)\n",
+            std::path::Path::new("src"),
+        );
+        let v: Vec<(usize, &str)> = summary
+            .iter()
+            .map(|s| (s.level, s.title.as_deref().unwrap()))
+            .collect();
+        assert_eq!(vec![(1, "Intro"), (1, "One"), (2, "One.1"), (1, "Two")], v);
+        assert_eq!(std::path::Path::new("src/one/1.md"), summary[2].path);
+    }
+
+    #[test]
+    fn test_split_by_heading() {
+        let dir = temp_dir("split");
+        let file = dir.join("src/book.md");
+        std::fs::write(
+            &file,
+            "---\ntitle: 书\nauthor: 作者\n---\n前言内容\n\n# 第一章\n\n段落 ![图](images/1.png)\n\n## 1.1 *小节*\n\n内容\n\n### 不拆分\n\n# 第二章\n\n<p>未闭合<br>",
+        )
+        .unwrap();
+        std::fs::write(dir.join("src/images/1.png"), [1, 2, 3]).unwrap();
+
+        let mut book = MarkdownImporter::new()
+            .add_file(&file)
+            .with_split_level(2)
+            .epub()
+            .unwrap();
+        assert_eq!("书", book.title());
+        assert_eq!(Some("作者"), book.creator());
+        assert_eq!(4, book.chapters().count());
+
+        let nav: Vec<(&str, usize)> = book.nav().map(|n| (n.title(), n.child().count())).collect();
+        assert_eq!(vec![("book", 0), ("第一章", 1), ("第二章", 0)], nav);
+        assert_eq!(
+            "1.1 小节",
+            book.nav().nth(1).unwrap().child().next().unwrap().title()
+        );
+
+        let chap = book.get_chapter_mut("chapter_0002.xhtml").unwrap();
+        let html = String::from_utf8(chap.data_mut().unwrap().to_vec()).unwrap();
+        assert!(html.starts_with("<h1>第一章</h1>"));
+        assert!(html.contains(r#"<img src="images/1.png" alt="图" />"#));
+        let chap = book.get_chapter_mut("chapter_0003.xhtml").unwrap();
+        let html = String::from_utf8(chap.data_mut().unwrap().to_vec()).unwrap();
+        assert!(html.contains("<h3>不拆分</h3>"));
+        // 规范化
+        let chap = book.get_chapter_mut("chapter_0004.xhtml").unwrap();
+        let html = String::from_utf8(chap.data_mut().unwrap().to_vec()).unwrap();
+        assert!(html.contains("<br/>"));
+
+        assert_eq!(
+            vec![1, 2, 3],
+            book.get_assets_mut("images/1.png")
+                .unwrap()
+                .data_mut()
+                .unwrap()
+                .to_vec()
+        );
+        EpubWriter::write_to_mem(&mut book, false).unwrap();
+
+        // 图片不存在
+        std::fs::remove_file(dir.join("src/images/1.png")).unwrap();
+        assert!(MarkdownImporter::new().add_file(&file).epub().is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_mdbook() {
+        let dir = temp_dir("mdbook");
+        std::fs::write(
+            dir.join("book.toml"),
+            "[book]\ntitle = \"mdBook\"\nauthors = [\"A\", \"B\"]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("src/SUMMARY.md"),
+            "# Summary\n\n- [Intro](intro.md)\n  - [Nested](nested.md)\n- [Other](other.md)\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("src/intro.md"),
+            "---\ntitle: ignored\n---\n# 介绍\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("src/nested.md"), "text").unwrap();
+        std::fs::write(dir.join("src/other.md"), "# Other\n\n## Sub").unwrap();
+
+        let book = MarkdownImporter::new().add_dir(&dir).epub().unwrap();
+        assert_eq!("mdBook", book.title());
+        assert_eq!(Some("A, B"), book.creator());
+        let nav: Vec<(&str, usize)> = book.nav().map(|n| (n.title(), n.child().count())).collect();
+        assert_eq!(vec![("Intro", 1), ("Other", 0)], nav);
+
+        // 没有 book.toml 时按文件名排序
+        std::fs::remove_file(dir.join("book.toml")).unwrap();
+        std::fs::remove_file(dir.join("src/SUMMARY.md")).unwrap();
+        let book = MarkdownImporter::new()
+            .add_dir(dir.join("src"))
+            .epub()
+            .unwrap();
+        assert_eq!("ignored", book.title());
+        let titles: Vec<&str> = book.chapters().map(|c| c.title()).collect();
+        assert_eq!(vec!["介绍", "nested", "Other"], titles);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_validate() {
+        let dir = temp_dir("validate");
+        let file = dir.join("src/book.md");
+        std::fs::write(&file, "---\ntitle: 书\n---\n# 第一章\n\n内容").unwrap();
+
+        let mut book = MarkdownImporter::new().add_file(&file).epub().unwrap();
+        assert!(book.identifier().starts_with("urn:uuid:"));
+        assert_eq!(Some("zh"), book.language());
+        let d = crate::prelude::validate::validate_book(&mut book).unwrap();
+        assert!(d.is_empty(), "{:?}", d);
+
+        std::fs::write(&file, "---\nisbn: 978-7-00\n---\n# One\n\nSome text").unwrap();
+        let book = MarkdownImporter::new().add_file(&file).epub().unwrap();
+        assert_eq!("978-7-00", book.identifier());
+        assert_eq!(Some("en"), book.language());
+
+        std::fs::write(&file, "---\nlanguage: zh-Hant\n---\n# One\n\nSome text").unwrap();
+        let mut book = MarkdownImporter::new().add_file(&file).epub().unwrap();
+        assert_eq!(Some("zh-Hant"), book.language());
+        let data = EpubWriter::write_to_mem(&mut book, false).unwrap();
+        let mut book = read_from_vec(data).unwrap();
+        assert_eq!(Some("zh-Hant"), book.language());
+        let html = book.chapters_mut().next().unwrap().raw_data().unwrap();
+        assert!(html.contains(r#"xml:lang="zh-Hant""#), "{}", html);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
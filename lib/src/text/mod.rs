@@ -0,0 +1,13 @@
+//! 纯文本的导入导出
+pub(crate) mod diff;
+pub(crate) mod export;
+pub(crate) mod markdown;
+pub(crate) mod rewrite;
+#[cfg(feature = "pinyin")]
+pub(crate) mod ruby;
+pub(crate) mod search;
+pub(crate) mod stats;
+pub(crate) mod txt;
+pub(crate) mod typography;
+#[cfg(feature = "zh_convert")]
+pub(crate) mod zh;
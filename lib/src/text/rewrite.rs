@@ -0,0 +1,331 @@
+//!
+//! 批量替换章节内容
+//!
+//! 只替换文本节点，标签、属性以及 script、style 中的内容保持不变；
+//! 只修改有替换的章节的数据。写入时所有章节仍由写入器重新生成，
+//! 和读取后直接写入一样，原文件中 head 内的样式表链接、body 的属性等不会保留
+//!
+use std::borrow::Cow;
+
+use regex::{NoExpand, Regex};
+
+use crate::{common::map_html_text, prelude::*};
+
+/// 单条替换规则
+#[derive(Debug, Clone)]
+struct RewriteRule {
+    regex: Regex,
+    replacement: String,
+    /// 是否是字面量，字面量的替换内容不展开 `$1`
+    literal: bool,
+}
+
+///
+/// 替换规则，按添加顺序依次执行
+///
+/// 匹配的文本没有反转义，例如 `&` 在文本中是 `&amp;`，替换内容会原样写入 html
+///
+/// # Examples
+///
+/// ```
+/// use iepub::prelude::*;
+/// use iepub::prelude::rewrite::RewriteRules;
+///
+/// let rules = RewriteRules::parse("re:请访问[a-z.]+\n最新章节 => ").unwrap();
+/// let mut book = EpubBuilder::new()
+///     .with_title("书")
+///     .add_chapter(
+///         EpubHtml::default()
+///             .with_file_name("1.xhtml")
+///             .with_data("<p title=\"最新章节\">正文，请访问www.a.com最新章节</p>".as_bytes().to_vec()),
+///     )
+///     .book()
+///     .unwrap();
+/// let reports = book.rewrite(&rules).unwrap();
+/// assert_eq!(2, reports[0].count());
+/// assert_eq!(
+///     "<p title=\"最新章节\">正文，</p>",
+///     book.get_chapter("1.xhtml").unwrap().string_data()
+/// );
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct RewriteRules {
+    rules: Vec<RewriteRule>,
+}
+
+impl RewriteRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加字面量规则
+    pub fn add_literal<T: Into<String>>(&mut self, pattern: &str, replacement: T) {
+        if pattern.is_empty() {
+            return;
+        }
+        self.rules.push(RewriteRule {
+            regex: Regex::new(&regex::escape(pattern)).unwrap(),
+            replacement: replacement.into(),
+            literal: true,
+        });
+    }
+
+    pub fn with_literal<T: Into<String>>(mut self, pattern: &str, replacement: T) -> Self {
+        self.add_literal(pattern, replacement);
+        self
+    }
+
+    ///
+    /// 添加正则规则，替换内容可以使用 `$1`、`${name}` 引用分组
+    ///
+    pub fn add_regex<T: Into<String>>(&mut self, pattern: &str, replacement: T) -> IResult<()> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| IError::Parse(Cow::from(format!("invalid pattern {}: {}", pattern, e))))?;
+        self.rules.push(RewriteRule {
+            regex,
+            replacement: replacement.into(),
+            literal: false,
+        });
+        Ok(())
+    }
+
+    pub fn with_regex<T: Into<String>>(mut self, pattern: &str, replacement: T) -> IResult<Self> {
+        self.add_regex(pattern, replacement)?;
+        Ok(self)
+    }
+
+    ///
+    /// 解析规则文件
+    ///
+    /// 每行一条规则，格式为 `查找内容 => 替换内容`，没有 ` => ` 时删除查找内容；
+    /// 以 `re:` 开头的为正则，空行以及 `#` 开头的行忽略
+    ///
+    /// ```text
+    /// # 删除广告
+    /// re:请访问[a-z.]+
+    /// 笔趣阁 =>
+    /// re:第(\d+)章 => 第 $1 章
+    /// ```
+    ///
+    pub fn parse(v: &str) -> IResult<Self> {
+        let mut rules = Self::new();
+        for (index, line) in v.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (pattern, replacement) = line
+                .split_once(" => ")
+                .or_else(|| line.strip_suffix(" =>").map(|f| (f, "")))
+                .unwrap_or((line, ""));
+            match pattern.strip_prefix("re:") {
+                Some(pattern) => {
+                    let regex = Regex::new(pattern).map_err(|e| {
+                        IError::Parse(Cow::from(format!(
+                            "invalid pattern {} at line {}: {}",
+                            pattern,
+                            index + 1,
+                            e
+                        )))
+                    })?;
+                    rules.rules.push(RewriteRule {
+                        regex,
+                        replacement: replacement.to_string(),
+                        literal: false,
+                    });
+                }
+                None => rules.add_literal(pattern, replacement),
+            }
+        }
+        Ok(rules)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 替换文本，返回替换后的文本以及替换次数
+    pub fn rewrite_text<'a>(&self, text: &'a str) -> (Cow<'a, str>, usize) {
+        let mut text = Cow::Borrowed(text);
+        let mut count = 0;
+        for rule in &self.rules {
+            let n = rule.regex.find_iter(&text).count();
+            if n == 0 {
+                continue;
+            }
+            count += n;
+            let value = if rule.literal {
+                rule.regex.replace_all(&text, NoExpand(&rule.replacement))
+            } else {
+                rule.regex.replace_all(&text, rule.replacement.as_str())
+            }
+            .into_owned();
+            text = Cow::Owned(value);
+        }
+        (text, count)
+    }
+
+    /// 替换 html 的文本节点，返回替换后的 html 以及替换次数
+    pub fn rewrite_html(&self, html: &str) -> (String, usize) {
+        let mut count = 0;
+        let value = map_html_text(html, |text| {
+            let (value, n) = self.rewrite_text(text);
+            count += n;
+            value.into_owned()
+        });
+        (value, count)
+    }
+}
+
+///
+/// 单个章节的替换结果
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RewriteReport {
+    chapter: usize,
+    file_name: Option<String>,
+    title: String,
+    count: usize,
+}
+
+impl RewriteReport {
+    pub(crate) fn new(
+        chapter: usize,
+        file_name: Option<String>,
+        title: &str,
+        count: usize,
+    ) -> Self {
+        RewriteReport {
+            chapter,
+            file_name,
+            title: title.to_string(),
+            count,
+        }
+    }
+
+    /// 章节序号，从 0 开始
+    pub fn chapter(&self) -> usize {
+        self.chapter
+    }
+
+    /// 章节文件名，mobi 没有文件名
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// 替换次数
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RewriteRules;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_parse() {
+        let rules =
+            RewriteRules::parse("# 注释\n\nre:第(\\d+)章 => 第 $1 章\r\n$1 => x\n广告\n尾部 =>\n")
+                .unwrap();
+        let (v, n) = rules.rewrite_text("第12章 $1，广告，尾部");
+        assert_eq!(("第 12 章 x，，", 4), (v.as_ref(), n));
+        let (v, n) = rules.rewrite_text("正文");
+        assert_eq!(("正文", 0), (v.as_ref(), n));
+
+        let err = RewriteRules::parse("a\nre:(").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+        assert!(RewriteRules::parse("# a\n\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rewrite_html() {
+        let rules = RewriteRules::new()
+            .with_literal("广告", "")
+            .with_regex(r"\s*(www|WWW)\.\w+\.com", "")
+            .unwrap();
+        let (v, n) = rules.rewrite_html(
+            r#"<p class="广告">正文广告 www.a.com</p><img alt="广告"/><script>广告</script><p>广<b>告</b></p>"#,
+        );
+        assert_eq!(
+            r#"<p class="广告">正文</p><img alt="广告"/><script>广告</script><p>广<b>告</b></p>"#,
+            v
+        );
+        assert_eq!(2, n);
+    }
+
+    #[test]
+    fn test_rewrite_book() {
+        let rules = RewriteRules::new().with_literal("广告", "");
+        let mut book = EpubBuilder::new()
+            .with_title("书")
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一章")
+                    .with_file_name("1.xhtml")
+                    .with_data("<p>广告正文广告</p>".as_bytes().to_vec()),
+            )
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第二章")
+                    .with_file_name("2.xhtml")
+                    .with_data("<P>正文<br>".as_bytes().to_vec()),
+            )
+            .book()
+            .unwrap();
+        let reports = book.rewrite(&rules).unwrap();
+        assert_eq!(1, reports.len());
+        assert_eq!(0, reports[0].chapter());
+        assert_eq!(Some("1.xhtml"), reports[0].file_name());
+        assert_eq!("第一章", reports[0].title());
+        assert_eq!(2, reports[0].count());
+        assert_eq!(
+            "<p>正文</p>",
+            book.get_chapter("1.xhtml").unwrap().string_data()
+        );
+        // 没有替换的章节数据不变
+        assert_eq!(
+            "<P>正文<br>",
+            book.get_chapter("2.xhtml").unwrap().string_data()
+        );
+
+        let mut book = MobiBuilder::new()
+            .with_title("书")
+            .cover(crate::cbz::core::tests::png(1, 1))
+            .add_chapter(
+                MobiHtml::new(1)
+                    .with_title("第一章")
+                    .with_data("<p>正文广告</p>".as_bytes().to_vec()),
+            )
+            .book()
+            .unwrap();
+        let reports = book.rewrite(&rules).unwrap();
+        assert_eq!(1, reports[0].count());
+        assert_eq!(None, reports[0].file_name());
+        assert_eq!("<p>正文</p>", book.chapters().next().unwrap().string_data());
+    }
+
+    #[test]
+    fn test_rewrite_skip_nav() {
+        use crate::epub::reader::tests::{epub3_with, EPUB3_OPF};
+
+        let opf = EPUB3_OPF.replace(
+            r#"<itemref idref="ch1"/>"#,
+            r#"<itemref idref="nav"/><itemref idref="ch1"/>"#,
+        );
+        let mut book = read_from_vec(epub3_with(&[("EPUB/package.opf", &opf)])).unwrap();
+        let rules = RewriteRules::new().with_literal("第", "卷");
+        // spine 中的导航文档不替换
+        let reports = book.rewrite(&rules).unwrap();
+        assert_eq!(
+            vec![1, 2, 3],
+            reports.iter().map(|f| f.chapter()).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//!
+//! 注音
+//!
+//! 为汉字添加 `<ruby>` 注音，读音来自内置的拼音词典，多音字优先按词组匹配，找不到时使用单字的常用读音
+//!
+//! html 只修改文本节点，标签和已有的 `<ruby>` 保持不变
+//!
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use crate::{
+    common::{map_html_text_skip, ErrorContext, ResultContext},
+    prelude::*,
+};
+
+/// 内置词典
+struct Dictionary {
+    chars: HashMap<char, &'static str>,
+    phrases: HashMap<&'static str, Vec<&'static str>>,
+    /// 最长词组的字数
+    max_len: usize,
+}
+
+static PINYIN: OnceLock<Dictionary> = OnceLock::new();
+
+fn dictionary() -> &'static Dictionary {
+    PINYIN.get_or_init(|| {
+        let mut dict = Dictionary {
+            chars: HashMap::new(),
+            phrases: HashMap::new(),
+            max_len: 1,
+        };
+        // 每行为 `字\t读音`
+        for (key, value) in parse_lines(include_str!("data/pinyin.txt")) {
+            if let Some(k) = key.chars().next() {
+                dict.chars.insert(k, value);
+            }
+        }
+        // 每行为 `词组\t读音1 读音2`
+        for (key, value) in parse_lines(include_str!("data/pinyin_phrases.txt")) {
+            let readings: Vec<&str> = value.split(' ').collect();
+            let len = key.chars().count();
+            if readings.len() == len {
+                dict.max_len = dict.max_len.max(len);
+                dict.phrases.insert(key, readings);
+            }
+        }
+        dict
+    })
+}
+
+fn parse_lines(v: &'static str) -> impl Iterator<Item = (&'static str, &'static str)> {
+    v.lines().filter_map(|line| {
+        let (key, value) = line.split_once('\t')?;
+        if key.is_empty() || value.is_empty() {
+            None
+        } else {
+            Some((key, value))
+        }
+    })
+}
+
+///
+/// 汉字注音
+///
+/// # Examples
+///
+/// ```
+/// use iepub::prelude::ruby::RubyAnnotator;
+///
+/// let annotator = RubyAnnotator::new().with_known_chars("我");
+/// assert_eq!(
+///     "我<ruby>长<rp>(</rp><rt>zhǎng</rt><rp>)</rp></ruby><ruby>大<rp>(</rp><rt>dà</rt><rp>)</rp></ruby>",
+///     annotator.annotate("我长大")
+/// );
+/// ```
+///
+pub struct RubyAnnotator {
+    dict: &'static Dictionary,
+    /// 不注音的字
+    known: HashSet<char>,
+    /// 自定义读音，优先于内置词典
+    chars: HashMap<char, String>,
+    phrases: HashMap<String, Vec<String>>,
+    max_len: usize,
+}
+
+impl Default for RubyAnnotator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RubyAnnotator {
+    pub fn new() -> Self {
+        let dict = dictionary();
+        RubyAnnotator {
+            dict,
+            known: HashSet::new(),
+            chars: HashMap::new(),
+            phrases: HashMap::new(),
+            max_len: dict.max_len,
+        }
+    }
+
+    ///
+    /// 设置常用字表，表中的字不注音，例如只给常用 2500 字以外的字注音
+    ///
+    /// 表中的空白、数字等字符会被忽略，可以直接使用字频表的内容
+    ///
+    pub fn with_known_chars(mut self, chars: &str) -> Self {
+        self.known
+            .extend(chars.chars().filter(|c| !c.is_whitespace()));
+        self
+    }
+
+    /// 设置单字的读音
+    pub fn add_reading<T: Into<String>>(&mut self, c: char, reading: T) {
+        self.chars.insert(c, reading.into());
+    }
+
+    pub fn with_reading<T: Into<String>>(mut self, c: char, reading: T) -> Self {
+        self.add_reading(c, reading);
+        self
+    }
+
+    ///
+    /// 添加词组，`readings` 为空格分隔的每个字的读音，例如 `add_phrase("银行", "yín háng")`
+    ///
+    /// 读音数量和字数不一致时忽略
+    ///
+    pub fn add_phrase(&mut self, phrase: &str, readings: &str) {
+        let readings: Vec<String> = readings.split_whitespace().map(String::from).collect();
+        let len = phrase.chars().count();
+        if len == 0 || readings.len() != len {
+            return;
+        }
+        self.max_len = self.max_len.max(len);
+        self.phrases.insert(phrase.to_string(), readings);
+    }
+
+    pub fn with_phrase(mut self, phrase: &str, readings: &str) -> Self {
+        self.add_phrase(phrase, readings);
+        self
+    }
+
+    /// 单字的读音
+    pub fn reading(&self, c: char) -> Option<&str> {
+        self.chars
+            .get(&c)
+            .map(|v| v.as_str())
+            .or_else(|| self.dict.chars.get(&c).copied())
+    }
+
+    /// 从 `key` 开始的词组的读音
+    fn phrase(&self, key: &str) -> Option<Vec<&str>> {
+        self.phrases
+            .get(key)
+            .map(|v| v.iter().map(|v| v.as_str()).collect())
+            .or_else(|| self.dict.phrases.get(key).cloned())
+    }
+
+    fn push(&self, out: &mut String, c: char, reading: Option<&str>) {
+        match reading {
+            Some(reading) if !self.known.contains(&c) => {
+                out.push_str("<ruby>");
+                out.push(c);
+                out.push_str("<rp>(</rp><rt>");
+                out.push_str(reading);
+                out.push_str("</rt><rp>)</rp></ruby>");
+            }
+            _ => out.push(c),
+        }
+    }
+
+    ///
+    /// 为文本添加注音，返回 html 片段
+    ///
+    /// 传入的文本需要已经转义
+    ///
+    pub fn annotate(&self, text: &str) -> String {
+        // 每个字的起始位置，方便直接切片查找词组
+        let offsets: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        let count = offsets.len() - 1;
+        let mut out = String::with_capacity(text.len() * 4);
+        let mut index = 0;
+        'outer: while index < count {
+            for len in (2..=self.max_len.min(count - index)).rev() {
+                let key = &text[offsets[index]..offsets[index + len]];
+                if let Some(readings) = self.phrase(key) {
+                    for (c, reading) in key.chars().zip(readings) {
+                        self.push(&mut out, c, Some(reading));
+                    }
+                    index += len;
+                    continue 'outer;
+                }
+            }
+            let c = text[offsets[index]..].chars().next().unwrap_or_default();
+            self.push(&mut out, c, self.reading(c));
+            index += 1;
+        }
+        out
+    }
+
+    /// 为 html 添加注音，只修改文本节点，已有的 ruby 不会重复注音
+    pub fn annotate_html(&self, html: &str) -> String {
+        map_html_text_skip(html, &["ruby"], |text| self.annotate(text))
+    }
+
+    /// 为章节内容添加注音，延迟读取的章节会先加载
+    pub fn annotate_chapter(&self, chap: &mut EpubHtml) -> IResult<()> {
+        let Some(data) = chap.data_mut() else {
+            return Ok(());
+        };
+        let html = String::from_utf8(data.to_vec())
+            .map_err(IError::Utf8)
+            .context(|| ErrorContext::new("annotate chapter").with_entry(chap.file_name()))?;
+        chap.set_data(self.annotate_html(&html).into_bytes());
+        Ok(())
+    }
+
+    /// 为所有章节添加注音
+    pub fn annotate_epub(&self, book: &mut EpubBook) -> IResult<()> {
+        for chap in book.chapters_mut() {
+            self.annotate_chapter(chap)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RubyAnnotator;
+    use crate::prelude::*;
+
+    fn ruby(c: &str, reading: &str) -> String {
+        format!("<ruby>{c}<rp>(</rp><rt>{reading}</rt><rp>)</rp></ruby>")
+    }
+
+    #[test]
+    fn test_annotate() {
+        let annotator = RubyAnnotator::new();
+        assert_eq!(Some("hàn"), annotator.reading('汉'));
+        assert_eq!(Some("bù"), annotator.reading('不'));
+        assert_eq!(None, annotator.reading('a'));
+        assert_eq!(
+            format!("{}{}，ok", ruby("汉", "hàn"), ruby("字", "zì")),
+            annotator.annotate("汉字，ok")
+        );
+        // 多音字按词组区分
+        assert_eq!(
+            format!("{}{}", ruby("长", "zhǎng"), ruby("大", "dà")),
+            annotator.annotate("长大")
+        );
+        assert_eq!(
+            format!("{}{}", ruby("很", "hěn"), ruby("长", "cháng")),
+            annotator.annotate("很长")
+        );
+
+        let annotator = RubyAnnotator::new()
+            .with_known_chars("很\n大 1")
+            .with_phrase("银行", "yín háng")
+            .with_phrase("错误", "a")
+            .with_reading('长', "zhǎng");
+        assert_eq!(
+            format!("很{}大", ruby("长", "zhǎng")),
+            annotator.annotate("很长大")
+        );
+        assert_eq!(
+            format!("{}{}", ruby("银", "yín"), ruby("行", "háng")),
+            annotator.annotate("银行")
+        );
+        assert_eq!(
+            format!("{}{}", ruby("错", "cuò"), ruby("误", "wù")),
+            annotator.annotate("错误")
+        );
+    }
+
+    #[test]
+    fn test_annotate_html() {
+        let annotator = RubyAnnotator::new().with_known_chars("一");
+        assert_eq!(
+            format!(
+                r#"<p title="书">一{}&amp;<ruby>书<rt>shū</rt></ruby></p><style>.书{{}}</style>"#,
+                ruby("本", "běn")
+            ),
+            annotator.annotate_html(
+                r#"<p title="书">一本&amp;<ruby>书<rt>shū</rt></ruby></p><style>.书{}</style>"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_annotate_epub() {
+        let mut book = EpubBuilder::new()
+            .with_title("书")
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一章")
+                    .with_file_name("1.xhtml")
+                    .with_data("<p>中文</p>".as_bytes().to_vec()),
+            )
+            .book()
+            .unwrap();
+        RubyAnnotator::new()
+            .with_known_chars("中")
+            .annotate_epub(&mut book)
+            .unwrap();
+        let chap = book.get_chapter_mut("1.xhtml").unwrap();
+        assert_eq!("第一章", chap.title());
+        assert_eq!(
+            format!("<p>中{}</p>", ruby("文", "wén")),
+            chap.string_data()
+        );
+    }
+}
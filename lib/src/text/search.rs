@@ -0,0 +1,370 @@
+//!
+//! 全文搜索
+//!
+//! 章节内容先转换为纯文本（每个段落一行）再搜索，偏移量为该文本中的字符位置
+//!
+use std::{borrow::Cow, sync::OnceLock};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::prelude::*;
+
+///
+/// 搜索条件
+///
+/// 默认按字面量搜索，区分大小写，前后各保留 20 个字符的上下文
+///
+/// # Examples
+///
+/// ```
+/// use iepub::prelude::*;
+/// use iepub::prelude::search::SearchQuery;
+///
+/// let mut book = EpubBuilder::new()
+///     .with_title("书")
+///     .add_chapter(
+///         EpubHtml::default()
+///             .with_file_name("1.xhtml")
+///             .with_data("<p>本章完，请访问ＷＷＷ.Example.com</p>".as_bytes().to_vec()),
+///     )
+///     .book()
+///     .unwrap();
+/// let query = SearchQuery::new("www.example.com").with_ignore_case(true);
+/// let matches = book.search(&query).unwrap();
+/// assert_eq!("ＷＷＷ.Example.com", matches[0].text());
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pattern: String,
+    regex: bool,
+    ignore_case: bool,
+    context: usize,
+    /// 编译后的正则，搜索多本书时只编译一次
+    compiled: OnceLock<Result<Regex, String>>,
+}
+
+impl SearchQuery {
+    pub fn new<T: Into<String>>(pattern: T) -> Self {
+        SearchQuery {
+            pattern: pattern.into(),
+            regex: false,
+            ignore_case: false,
+            context: 20,
+            compiled: OnceLock::new(),
+        }
+    }
+
+    /// 使用正则表达式
+    pub fn with_regex(mut self, value: bool) -> Self {
+        self.regex = value;
+        self.compiled = OnceLock::new();
+        self
+    }
+
+    ///
+    /// 忽略大小写，同时忽略全角半角，例如 `ＡＢＣ` 可以匹配 `abc`，全角空格视为半角空格
+    ///
+    pub fn with_ignore_case(mut self, value: bool) -> Self {
+        self.ignore_case = value;
+        self.compiled = OnceLock::new();
+        self
+    }
+
+    /// 匹配内容前后保留的字符数，上下文不会跨越段落
+    pub fn with_context(mut self, value: usize) -> Self {
+        self.context = value;
+        self
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// 检查搜索内容，内容为空或者不是有效的正则时返回错误
+    pub fn check(&self) -> IResult<()> {
+        self.compile().map(|_| ())
+    }
+
+    fn compile(&self) -> IResult<&Regex> {
+        self.compiled
+            .get_or_init(|| {
+                if self.pattern.is_empty() {
+                    return Err("empty pattern".to_string());
+                }
+                let pattern = match (self.regex, self.ignore_case) {
+                    (true, true) => self.pattern.chars().map(fold_width).collect(),
+                    (true, false) => self.pattern.clone(),
+                    (false, true) => {
+                        regex::escape(&self.pattern.chars().map(fold).collect::<String>())
+                    }
+                    (false, false) => regex::escape(&self.pattern),
+                };
+                RegexBuilder::new(&pattern)
+                    .case_insensitive(self.ignore_case)
+                    .build()
+                    .map_err(|e| e.to_string())
+            })
+            .as_ref()
+            .map_err(|e| {
+                IError::Parse(Cow::from(format!(
+                    "invalid pattern {}: {}",
+                    self.pattern, e
+                )))
+            })
+    }
+
+    ///
+    /// 在纯文本中搜索，返回的结果只有偏移量、匹配内容以及上下文
+    ///
+    pub(crate) fn find(&self, text: &str) -> IResult<Vec<SearchMatch>> {
+        let regex = self.compile()?;
+        let chars: Vec<char> = text.chars().collect();
+        let haystack: Cow<str> = if self.ignore_case {
+            Cow::Owned(chars.iter().map(|c| fold(*c)).collect())
+        } else {
+            Cow::Borrowed(text)
+        };
+        // 字节位置转为字符位置，折叠前后字符数量相同
+        let offsets: Vec<usize> = haystack.char_indices().map(|(i, _)| i).collect();
+        let index = |byte: usize| offsets.partition_point(|v| *v < byte);
+
+        let mut out = Vec::new();
+        for m in regex.find_iter(&haystack).filter(|m| !m.is_empty()) {
+            let (start, end) = (index(m.start()), index(m.end()));
+            let before = chars[start.saturating_sub(self.context)..start]
+                .iter()
+                .rev()
+                .take_while(|c| **c != '\n')
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .collect();
+            let after = chars[end..(end + self.context).min(chars.len())]
+                .iter()
+                .take_while(|c| **c != '\n')
+                .collect();
+            out.push(SearchMatch {
+                offset: start,
+                text: chars[start..end].iter().collect(),
+                before,
+                after,
+                ..Default::default()
+            });
+        }
+        Ok(out)
+    }
+}
+
+///
+/// 搜索结果
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub(crate) chapter: usize,
+    pub(crate) file_name: Option<String>,
+    pub(crate) nav: Vec<String>,
+    offset: usize,
+    text: String,
+    before: String,
+    after: String,
+}
+
+impl SearchMatch {
+    /// 章节序号，从 0 开始
+    pub fn chapter(&self) -> usize {
+        self.chapter
+    }
+
+    /// 章节文件名，mobi 没有文件名
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// 章节所属的目录，从顶层开始，没有目录时为章节标题
+    pub fn nav(&self) -> &[String] {
+        &self.nav
+    }
+
+    /// 匹配内容在章节纯文本中的字符位置
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// 匹配的内容
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// 匹配内容前的文本
+    pub fn before(&self) -> &str {
+        &self.before
+    }
+
+    /// 匹配内容后的文本
+    pub fn after(&self) -> &str {
+        &self.after
+    }
+}
+
+/// 全角字符转为半角
+fn fold_width(c: char) -> char {
+    match c {
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        _ => c,
+    }
+}
+
+/// 忽略大小写以及全角半角，只处理转换后仍是单个字符的情况，保证位置不变
+fn fold(c: char) -> char {
+    let c = fold_width(c);
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(v), None) => v,
+        _ => c,
+    }
+}
+
+/// 章节所属的目录，优先使用最深的一级
+pub(crate) fn epub_nav_path<'a>(
+    nav: impl Iterator<Item = &'a EpubNav>,
+    file_name: &str,
+) -> Option<Vec<String>> {
+    for ele in nav {
+        if let Some(mut path) = epub_nav_path(ele.child(), file_name) {
+            path.insert(0, ele.title().to_string());
+            return Some(path);
+        }
+        if ele.file_name().split('#').next() == Some(file_name) {
+            return Some(vec![ele.title().to_string()]);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SearchQuery;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_find() {
+        let text = "第一段，Rust 很好\n第二段ＲＵＳＴ，rust";
+        let query = SearchQuery::new("rust").with_context(3);
+        let v = query.find(text).unwrap();
+        assert_eq!(1, v.len());
+        assert_eq!(20, v[0].offset());
+        assert_eq!("ＳＴ，", v[0].before());
+        assert_eq!("", v[0].after());
+
+        let v = SearchQuery::new("rust")
+            .with_context(3)
+            .with_ignore_case(true)
+            .find(text)
+            .unwrap();
+        assert_eq!(
+            vec![
+                (4, "Rust", "一段，", " 很好"),
+                (15, "ＲＵＳＴ", "第二段", "，ru"),
+                (20, "rust", "ＳＴ，", "")
+            ],
+            v.iter()
+                .map(|m| (m.offset(), m.text(), m.before(), m.after()))
+                .collect::<Vec<_>>()
+        );
+
+        let v = SearchQuery::new(r"第.段")
+            .with_regex(true)
+            .find(text)
+            .unwrap();
+        assert_eq!(
+            vec!["第一段", "第二段"],
+            v.iter().map(|m| m.text()).collect::<Vec<_>>()
+        );
+        // 字面量不解析正则
+        assert!(SearchQuery::new("第.段").find(text).unwrap().is_empty());
+
+        assert!(SearchQuery::new("(").with_regex(true).check().is_err());
+        assert!(SearchQuery::new("(").check().is_ok());
+        assert!(SearchQuery::new("").find(text).is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let mut nav = EpubNav::default()
+            .with_title("卷一")
+            .with_file_name("1.xhtml");
+        nav.push(
+            EpubNav::default()
+                .with_title("第一章")
+                .with_file_name("1.xhtml#p1"),
+        );
+        let mut book = EpubBuilder::new()
+            .with_title("书")
+            .custome_nav(true)
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一章")
+                    .with_file_name("1.xhtml")
+                    .with_data(
+                        "<p>正文</p><p>广告：<b>www</b>.a.com</p>"
+                            .as_bytes()
+                            .to_vec(),
+                    ),
+            )
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第二章")
+                    .with_file_name("2.xhtml")
+                    .with_data("<p>广告&amp;</p>".as_bytes().to_vec()),
+            )
+            .add_nav(nav)
+            .book()
+            .unwrap();
+        let v = book.search(&SearchQuery::new("广告")).unwrap();
+        assert_eq!(2, v.len());
+        assert_eq!(0, v[0].chapter());
+        assert_eq!(Some("1.xhtml"), v[0].file_name());
+        assert_eq!(&["卷一".to_string(), "第一章".to_string()], v[0].nav());
+        assert_eq!(3, v[0].offset());
+        assert_eq!("：www.a.com", v[0].after());
+        assert_eq!(Some("2.xhtml"), v[1].file_name());
+        assert_eq!(&["第二章".to_string()], v[1].nav());
+        assert_eq!("&", v[1].after());
+
+        let book = MobiBuilder::new()
+            .with_title("书")
+            .cover(crate::cbz::core::tests::png(1, 1))
+            .add_chapter(
+                MobiHtml::new(1)
+                    .with_title("第一章")
+                    .with_data("<p>广告</p>".as_bytes().to_vec()),
+            )
+            .book()
+            .unwrap();
+        let v = book.search(&SearchQuery::new("广告")).unwrap();
+        assert_eq!(1, v.len());
+        assert_eq!(None, v[0].file_name());
+        assert_eq!(&["1. 第一章".to_string()], v[0].nav());
+    }
+
+    #[test]
+    fn test_search_lazy() {
+        use crate::epub::reader::tests::{epub3_with, EPUB3_OPF};
+
+        let opf = EPUB3_OPF.replace(
+            r#"<itemref idref="ch1"/>"#,
+            r#"<itemref idref="nav"/><itemref idref="ch1"/>"#,
+        );
+        let mut book = read_from_vec(epub3_with(&[("EPUB/package.opf", &opf)])).unwrap();
+        // 导航文档不搜索
+        assert!(book.search(&SearchQuery::new("第一卷")).unwrap().is_empty());
+        // 不在目录中的章节使用加载后的标题
+        let v = book.search(&SearchQuery::new("附录")).unwrap();
+        assert_eq!(1, v.len());
+        assert_eq!(3, v[0].chapter());
+        assert_eq!(Some("text/c4.xhtml"), v[0].file_name());
+        assert_eq!(&["附录".to_string()], v[0].nav());
+    }
+}
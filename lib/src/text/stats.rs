@@ -0,0 +1,472 @@
+//!
+//! 书籍统计
+//!
+//! 章节内容先转换为纯文本（每个段落一行）再统计字数，图片数量按 html 中的 img、image 标签计算
+//!
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::epub::validate::json_string;
+use crate::text::{export::html_text, typography::is_cjk};
+
+/// 默认每分钟阅读的中日韩字符数
+const CJK_PER_MINUTE: usize = 300;
+/// 默认每分钟阅读的拉丁单词数
+const WORDS_PER_MINUTE: usize = 200;
+
+///
+/// 单个章节的统计
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChapterStats {
+    chapter: usize,
+    file_name: Option<String>,
+    title: String,
+    cjk: usize,
+    words: usize,
+    paragraphs: usize,
+    images: usize,
+}
+
+impl ChapterStats {
+    pub(crate) fn new(chapter: usize, file_name: Option<String>, title: &str, html: &str) -> Self {
+        let text = html_text(html);
+        let (cjk, words) = count_text(&text);
+        ChapterStats {
+            chapter,
+            file_name,
+            title: title.to_string(),
+            cjk,
+            words,
+            paragraphs: text.lines().filter(|f| !f.trim().is_empty()).count(),
+            images: image_regex().find_iter(html).count(),
+        }
+    }
+
+    /// 正文章节的序号，从 0 开始
+    pub fn chapter(&self) -> usize {
+        self.chapter
+    }
+
+    /// 章节文件名，mobi 没有文件名
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// 中日韩字符数，不含标点
+    pub fn cjk(&self) -> usize {
+        self.cjk
+    }
+
+    /// 拉丁单词数，连续的数字也算一个单词
+    pub fn words(&self) -> usize {
+        self.words
+    }
+
+    /// 段落数，标题也算一个段落
+    pub fn paragraphs(&self) -> usize {
+        self.paragraphs
+    }
+
+    /// 图片数
+    pub fn images(&self) -> usize {
+        self.images
+    }
+
+    /// 转为json对象
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"chapter":{},"file_name":{},"title":{},"cjk":{},"words":{},"paragraphs":{},"images":{}}}"#,
+            self.chapter,
+            self.file_name
+                .as_deref()
+                .map_or_else(|| "null".to_string(), json_string),
+            json_string(&self.title),
+            self.cjk,
+            self.words,
+            self.paragraphs,
+            self.images
+        )
+    }
+}
+
+///
+/// 同一类型资源的统计
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssetStats {
+    media_type: String,
+    count: usize,
+    size: usize,
+}
+
+impl AssetStats {
+    pub fn media_type(&self) -> &str {
+        &self.media_type
+    }
+
+    /// 文件数
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// 总字节数
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// 转为json对象
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"media_type":{},"count":{},"size":{}}}"#,
+            json_string(&self.media_type),
+            self.count,
+            self.size
+        )
+    }
+}
+
+///
+/// 书籍统计
+///
+/// # Examples
+///
+/// ```
+/// use iepub::prelude::*;
+///
+/// let mut book = EpubBuilder::new()
+///     .with_title("书")
+///     .add_chapter(
+///         EpubHtml::default()
+///             .with_title("第一章")
+///             .with_file_name("1.xhtml")
+///             .with_data("<p>你好，Hello world</p><p><img src=\"1.png\"/></p>".as_bytes().to_vec()),
+///     )
+///     .book()
+///     .unwrap();
+/// let stats = book.stats().unwrap();
+/// assert_eq!(2, stats.cjk());
+/// assert_eq!(2, stats.words());
+/// assert_eq!(1, stats.images());
+/// assert_eq!(1, stats.reading_minutes());
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BookStats {
+    chapters: Vec<ChapterStats>,
+    assets: Vec<AssetStats>,
+}
+
+impl BookStats {
+    ///
+    /// # Arguments
+    ///
+    /// * `assets` - 资源的 (类型，字节数)，按类型汇总
+    ///
+    pub(crate) fn new(
+        chapters: Vec<ChapterStats>,
+        assets: impl IntoIterator<Item = (String, usize)>,
+    ) -> Self {
+        let mut out: Vec<AssetStats> = Vec::new();
+        for (media_type, size) in assets {
+            match out.iter_mut().find(|f| f.media_type == media_type) {
+                Some(v) => {
+                    v.count += 1;
+                    v.size += size;
+                }
+                None => out.push(AssetStats {
+                    media_type,
+                    count: 1,
+                    size,
+                }),
+            }
+        }
+        out.sort_by(|a, b| a.media_type.cmp(&b.media_type));
+        BookStats {
+            chapters,
+            assets: out,
+        }
+    }
+
+    pub fn chapters(&self) -> &[ChapterStats] {
+        &self.chapters
+    }
+
+    /// 按类型汇总的资源，按类型排序
+    pub fn assets(&self) -> &[AssetStats] {
+        &self.assets
+    }
+
+    /// 中日韩字符总数
+    pub fn cjk(&self) -> usize {
+        self.chapters.iter().map(|f| f.cjk).sum()
+    }
+
+    /// 拉丁单词总数
+    pub fn words(&self) -> usize {
+        self.chapters.iter().map(|f| f.words).sum()
+    }
+
+    /// 段落总数
+    pub fn paragraphs(&self) -> usize {
+        self.chapters.iter().map(|f| f.paragraphs).sum()
+    }
+
+    /// 章节中的图片总数
+    pub fn images(&self) -> usize {
+        self.chapters.iter().map(|f| f.images).sum()
+    }
+
+    /// 资源总字节数
+    pub fn asset_size(&self) -> usize {
+        self.assets.iter().map(|f| f.size).sum()
+    }
+
+    ///
+    /// 预计阅读时间，单位分钟，向上取整
+    ///
+    /// 按每分钟 300 个中日韩字符、200 个拉丁单词计算
+    ///
+    pub fn reading_minutes(&self) -> usize {
+        self.reading_minutes_with(CJK_PER_MINUTE, WORDS_PER_MINUTE)
+    }
+
+    ///
+    /// 按指定速度计算预计阅读时间，单位分钟，向上取整
+    ///
+    /// # Arguments
+    ///
+    /// * `cjk_per_minute` - 每分钟阅读的中日韩字符数
+    /// * `words_per_minute` - 每分钟阅读的拉丁单词数
+    ///
+    pub fn reading_minutes_with(&self, cjk_per_minute: usize, words_per_minute: usize) -> usize {
+        let minutes = self.cjk() as f64 / cjk_per_minute.max(1) as f64
+            + self.words() as f64 / words_per_minute.max(1) as f64;
+        minutes.ceil() as usize
+    }
+
+    /// 转为json对象
+    pub fn to_json(&self) -> String {
+        let chapters: Vec<String> = self.chapters.iter().map(|v| v.to_json()).collect();
+        let assets: Vec<String> = self.assets.iter().map(|v| v.to_json()).collect();
+        format!(
+            r#"{{"cjk":{},"words":{},"paragraphs":{},"images":{},"asset_size":{},"reading_minutes":{},"chapters":[{}],"assets":[{}]}}"#,
+            self.cjk(),
+            self.words(),
+            self.paragraphs(),
+            self.images(),
+            self.asset_size(),
+            self.reading_minutes(),
+            chapters.join(","),
+            assets.join(",")
+        )
+    }
+}
+
+fn image_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"(?i)<(img|image)\b").unwrap())
+}
+
+/// 统计 (中日韩字符数，拉丁单词数)
+fn count_text(text: &str) -> (usize, usize) {
+    let (mut cjk, mut words) = (0, 0);
+    let mut in_word = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if is_cjk(c) || matches!(c, '\u{1100}'..='\u{11ff}' | '\u{ac00}'..='\u{d7af}') {
+            cjk += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            if !in_word {
+                words += 1;
+                in_word = true;
+            }
+        } else if in_word
+            && matches!(c, '\'' | '’')
+            && chars
+                .peek()
+                .is_some_and(|c| c.is_alphanumeric() && !is_cjk(*c))
+        {
+            // don't、it’s 算一个单词
+        } else {
+            in_word = false;
+        }
+    }
+    (cjk, words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_text, BookStats, ChapterStats};
+    use crate::prelude::*;
+
+    #[test]
+    fn test_count_text() {
+        assert_eq!((4, 0), count_text("你好，世界！"));
+        assert_eq!((0, 4), count_text("Don't panic, it’s 42."));
+        assert_eq!((4, 2), count_text("使用Rust编写 v2"));
+        assert_eq!((5, 0), count_text("ひらがな한"));
+        assert_eq!((0, 1), count_text("'quote'"));
+
+        let v = ChapterStats::new(
+            1,
+            None,
+            "标题",
+            "<h1>标题</h1><p>正文</p>\n<p> </p><p><IMG src=\"a.png\"/><image href=\"b.png\"/></p>",
+        );
+        assert_eq!(4, v.cjk());
+        assert_eq!(2, v.paragraphs());
+        assert_eq!(2, v.images());
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut book = EpubBuilder::new()
+            .with_title("书")
+            .cover("cover.png", crate::cbz::core::tests::png(1, 1))
+            .add_assets("1.png", crate::cbz::core::tests::png(2, 2))
+            .add_assets("style.css", "p{}".as_bytes().to_vec())
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一章")
+                    .with_file_name("1.xhtml")
+                    .with_data("<p>一二三四五</p><p>six seven</p>".as_bytes().to_vec()),
+            )
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第二章")
+                    .with_file_name("2.xhtml")
+                    .with_data("<p>一\"二\"</p><img src=\"1.png\"/>".as_bytes().to_vec()),
+            )
+            .book()
+            .unwrap();
+        let stats = book.stats().unwrap();
+        assert_eq!(2, stats.chapters().len());
+        assert_eq!(Some("2.xhtml"), stats.chapters()[1].file_name());
+        // 标题优先使用目录中的标题
+        assert_eq!("2. 第二章", stats.chapters()[1].title());
+        assert_eq!(7, stats.cjk());
+        assert_eq!(2, stats.words());
+        assert_eq!(3, stats.paragraphs());
+        assert_eq!(1, stats.images());
+        assert_eq!(1, stats.reading_minutes());
+        assert_eq!(
+            vec![("image/png", 2), ("text/css", 1)],
+            stats
+                .assets()
+                .iter()
+                .map(|f| (f.media_type(), f.count()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(3, stats.assets()[1].size());
+        let json = stats.to_json();
+        assert!(json.starts_with(r#"{"cjk":7,"words":2,"#), "{}", json);
+        assert!(
+            json.contains(r#""title":"1. 第一章","cjk":5,"words":2"#),
+            "{}",
+            json
+        );
+
+        let book = MobiBuilder::new()
+            .with_title("书")
+            .cover(crate::cbz::core::tests::png(1, 1))
+            .add_chapter(
+                MobiHtml::new(1)
+                    .with_title("第一章")
+                    .with_data("<p>一二三</p>".as_bytes().to_vec()),
+            )
+            .book()
+            .unwrap();
+        let stats = book.stats().unwrap();
+        assert_eq!(3, stats.cjk());
+        assert_eq!(None, stats.chapters()[0].file_name());
+        assert_eq!("image/png", stats.assets()[0].media_type());
+        assert_eq!(1, stats.assets()[0].count());
+    }
+
+    #[test]
+    fn test_stats_epub3() {
+        let mut book = read_from_vec(crate::epub::reader::tests::epub3()).unwrap();
+        let stats = book.stats().unwrap();
+        // 标题在加载后获取，卷和章节指向同一文件时使用章节的标题
+        assert_eq!(
+            vec![
+                (0, "text/c1.xhtml", "第一章"),
+                (1, "text/c2.xhtml", "第二章"),
+                (2, "text/c3.xhtml", "第三章"),
+                (3, "text/c4.xhtml", "附录"),
+            ],
+            stats
+                .chapters()
+                .iter()
+                .map(|f| (f.chapter(), f.file_name().unwrap_or_default(), f.title()))
+                .collect::<Vec<_>>()
+        );
+        // 导航文档不是资源
+        assert_eq!(
+            vec![("text/css", 1)],
+            stats
+                .assets()
+                .iter()
+                .map(|f| (f.media_type(), f.count()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_stats_nav_in_spine() {
+        use crate::epub::reader::tests::{epub3_with, EPUB3_OPF};
+
+        let opf = EPUB3_OPF.replace(
+            r#"<itemref idref="ch1"/>"#,
+            r#"<itemref idref="nav"/><itemref idref="ch1"/>"#,
+        );
+        let mut book = read_from_vec(epub3_with(&[("EPUB/package.opf", &opf)])).unwrap();
+        assert_eq!(5, book.chapters().count());
+        assert!(book.chapters().next().unwrap().is_nav());
+        // spine 中的导航文档不统计
+        let stats = book.stats().unwrap();
+        assert_eq!(
+            vec![
+                "text/c1.xhtml",
+                "text/c2.xhtml",
+                "text/c3.xhtml",
+                "text/c4.xhtml"
+            ],
+            stats
+                .chapters()
+                .iter()
+                .map(|f| f.file_name().unwrap_or_default())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![("text/css", 1)],
+            stats
+                .assets()
+                .iter()
+                .map(|f| (f.media_type(), f.count()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_reading_minutes() {
+        let stats = BookStats::new(
+            vec![ChapterStats {
+                cjk: 600,
+                words: 300,
+                ..Default::default()
+            }],
+            Vec::new(),
+        );
+        assert_eq!(4, stats.reading_minutes());
+        assert_eq!(2, stats.reading_minutes_with(600, 600));
+        assert_eq!(0, BookStats::default().reading_minutes());
+    }
+}
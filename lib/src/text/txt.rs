@@ -0,0 +1,539 @@
+//!
+//! txt 小说导入
+//!
+//! 自动识别文本编码，按照章节规则拆分章节后生成 [EpubBook] 或 [MobiBook]
+//!
+use std::borrow::Cow;
+
+use encoding_rs::{Encoding, BIG5, GB18030, UTF_8};
+use regex::Regex;
+
+use crate::{
+    common::{escape_xml, gen_uuid},
+    prelude::*,
+    text::typography::detect_language,
+};
+
+/// 第一个章节之前的内容所在章节的标题
+const PREFACE_TITLE: &str = "前言";
+/// 没有识别到任何章节时，全文所在章节的标题
+const BODY_TITLE: &str = "正文";
+
+/// 简繁常用字，用于判断 GB18030 和 Big5 哪个解码结果更合理
+const COMMON_CHARS: &str = "的一是不了在人有我他她这个们中来上大为和国地到以说时要就出会可也你对生能而子那得于着下自之年过发后作里用道行所然家种事成方多经么去法学如都同现当没动面起看定天分还进好小部其些主样理心本前开但因只从想实這個們來為國說時會對過發後裡經麼學現當動還進從實無與見";
+
+///
+/// 章节识别预设
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxtPreset {
+    /// `第X章`、`第X回`、`第X节`、`楔子` 等为章节，`第X卷`、`第X部` 为卷
+    Chinese,
+    /// `Chapter N`、`Prologue` 等为章节，`Volume N`、`Part N`、`Book N` 为卷
+    English,
+}
+
+impl TxtPreset {
+    /// 章节标题的正则
+    pub fn chapter_pattern(&self) -> &'static str {
+        match self {
+            TxtPreset::Chinese => {
+                r"^(?:第[0-9０-９零〇一二两三四五六七八九十百千万]+[章回节集]|(?:序章|楔子|引子|序言|尾声|后记)(?:\s|$)).{0,40}$"
+            }
+            TxtPreset::English => {
+                r"(?i)^(?:chapter\s+(?:\d+|[ivxlcdm]+)\b|prologue\b|epilogue\b).{0,60}$"
+            }
+        }
+    }
+
+    /// 卷标题的正则
+    pub fn volume_pattern(&self) -> &'static str {
+        match self {
+            TxtPreset::Chinese => {
+                r"^(?:第[0-9０-９零〇一二两三四五六七八九十百千万]+[卷部]|卷[0-9０-９零〇一二两三四五六七八九十百千万]+).{0,40}$"
+            }
+            TxtPreset::English => r"(?i)^(?:volume|book|part)\s+(?:\d+|[ivxlcdm]+)\b.{0,60}$",
+        }
+    }
+}
+
+///
+/// 拆分后的章节
+///
+/// 卷也是一个章节，卷下的章节在 children 中
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxtSection {
+    title: String,
+    paragraphs: Vec<String>,
+    children: Vec<TxtSection>,
+}
+
+impl TxtSection {
+    fn new<T: Into<String>>(title: T) -> Self {
+        TxtSection {
+            title: title.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// 段落，已去除首尾空白
+    pub fn paragraphs(&self) -> std::slice::Iter<'_, String> {
+        self.paragraphs.iter()
+    }
+
+    pub fn children(&self) -> std::slice::Iter<'_, TxtSection> {
+        self.children.iter()
+    }
+
+    /// 每个段落包裹在 `<p>` 中
+    pub fn to_html(&self) -> String {
+        self.paragraphs
+            .iter()
+            .map(|p| format!("<p>{}</p>", escape_xml(p)))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+///
+/// 检测文本编码，返回编码名称，例如 `UTF-8`、`gb18030`、`Big5`、`UTF-16LE`
+///
+/// 有 BOM 时以 BOM 为准，否则依次尝试 UTF-8、GB18030(兼容GBK)、Big5
+///
+pub fn detect_encoding(data: &[u8]) -> &'static str {
+    if let Some((encoding, _)) = Encoding::for_bom(data) {
+        return encoding.name();
+    }
+    if std::str::from_utf8(data).is_ok() {
+        return UTF_8.name();
+    }
+    // 解码出错的排除，剩下的按常用字数量判断
+    let score = |encoding: &'static Encoding| {
+        let (text, had_errors) = encoding.decode_without_bom_handling(data);
+        (
+            !had_errors,
+            text.chars().filter(|c| COMMON_CHARS.contains(*c)).count(),
+        )
+    };
+    if score(BIG5) > score(GB18030) {
+        BIG5.name()
+    } else {
+        GB18030.name()
+    }
+}
+
+///
+/// txt 导入
+///
+/// # Examples
+///
+/// ```rust
+/// use iepub::prelude::*;
+/// use iepub::prelude::txt::{TxtImporter, TxtPreset};
+///
+/// let txt = "第一章 开始\n　　内容\n第二章 结束\n　　内容".as_bytes();
+/// let book = TxtImporter::new()
+///     .with_preset(TxtPreset::Chinese)
+///     .epub_builder(txt)
+///     .unwrap()
+///     .with_title("书名")
+///     .book()
+///     .unwrap();
+/// assert_eq!(2, book.chapters().count());
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct TxtImporter {
+    chapter: Vec<String>,
+    volume: Vec<String>,
+    encoding: Option<String>,
+}
+
+impl Default for TxtImporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TxtImporter {
+    /// 默认同时使用中文和英文预设
+    pub fn new() -> Self {
+        let presets = [TxtPreset::Chinese, TxtPreset::English];
+        TxtImporter {
+            chapter: presets
+                .iter()
+                .map(|p| p.chapter_pattern().to_string())
+                .collect(),
+            volume: presets
+                .iter()
+                .map(|p| p.volume_pattern().to_string())
+                .collect(),
+            encoding: None,
+        }
+    }
+
+    /// 只使用指定预设的规则
+    pub fn with_preset(mut self, preset: TxtPreset) -> Self {
+        self.chapter = vec![preset.chapter_pattern().to_string()];
+        self.volume = vec![preset.volume_pattern().to_string()];
+        self
+    }
+
+    ///
+    /// 使用自定义的章节正则，替换已有的章节规则
+    ///
+    /// 正则匹配的是去除首尾空白后的单行文本
+    ///
+    pub fn with_chapter_pattern<T: Into<String>>(mut self, pattern: T) -> Self {
+        self.chapter = vec![pattern.into()];
+        self
+    }
+
+    /// 使用自定义的卷正则，替换已有的卷规则，传入空字符串则不识别卷
+    pub fn with_volume_pattern<T: Into<String>>(mut self, pattern: T) -> Self {
+        self.volume = vec![pattern.into()];
+        self
+    }
+
+    /// 指定编码，例如 `gbk`、`big5`，不指定时自动检测
+    pub fn with_encoding<T: Into<String>>(mut self, encoding: T) -> Self {
+        self.encoding = Some(encoding.into());
+        self
+    }
+
+    /// 解码文本
+    pub fn decode(&self, data: &[u8]) -> IResult<String> {
+        let encoding = match &self.encoding {
+            Some(label) => Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| {
+                IError::Encoding(Cow::from(format!("unknown encoding {}", label)))
+            })?,
+            None => Encoding::for_label(detect_encoding(data).as_bytes()).unwrap_or(UTF_8),
+        };
+        // 有BOM时会去掉BOM
+        let (text, _, _) = encoding.decode(data);
+        Ok(text.into_owned())
+    }
+
+    ///
+    /// 拆分章节
+    ///
+    /// 第一个章节之前的内容作为 `前言`，卷之后的章节都属于该卷，没有识别到章节时全文作为一章
+    ///
+    pub fn split(&self, text: &str) -> IResult<Vec<TxtSection>> {
+        let chapter = compile(&self.chapter)?;
+        let volume = compile(&self.volume)?;
+
+        let mut preface = TxtSection::new(PREFACE_TITLE);
+        let mut sections: Vec<TxtSection> = Vec::new();
+        let mut in_volume = false;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if volume.iter().any(|r| r.is_match(line)) {
+                sections.push(TxtSection::new(line));
+                in_volume = true;
+            } else if chapter.iter().any(|r| r.is_match(line)) {
+                match sections.last_mut() {
+                    Some(v) if in_volume => v.children.push(TxtSection::new(line)),
+                    _ => sections.push(TxtSection::new(line)),
+                }
+            } else {
+                let section = match sections.last_mut() {
+                    Some(v) if v.children.is_empty() => v,
+                    Some(v) => v.children.last_mut().unwrap_or(&mut preface),
+                    None => &mut preface,
+                };
+                section.paragraphs.push(line.to_string());
+            }
+        }
+
+        if sections.is_empty() {
+            preface.title = BODY_TITLE.to_string();
+            sections.push(preface);
+        } else if !preface.paragraphs.is_empty() {
+            sections.insert(0, preface);
+        }
+        Ok(sections)
+    }
+
+    ///
+    /// 生成 epub 构造器，已添加章节和目录
+    ///
+    /// 卷的目录指向卷内第一个章节，卷首的内容作为卷内第一个章节
+    ///
+    /// 标识为随机生成的 uuid，语言根据文本推断，书名、作者、封面等可以继续在构造器上设置
+    ///
+    pub fn epub_builder(&self, data: &[u8]) -> IResult<EpubBuilder> {
+        let text = self.decode(data)?;
+        let sections = self.split(&text)?;
+        let mut builder = EpubBuilder::new()
+            .custome_nav(true)
+            .with_identifier(format!("urn:uuid:{}", gen_uuid()))
+            .with_language(detect_language(&text));
+        let mut index = 0;
+        for section in &sections {
+            let nav;
+            (builder, nav) = add_epub_section(builder, section, &mut index);
+            builder = builder.add_nav(nav);
+        }
+        Ok(builder)
+    }
+
+    /// 生成 epub
+    pub fn epub(&self, data: &[u8]) -> IResult<EpubBook> {
+        self.epub_builder(data)?.book()
+    }
+
+    ///
+    /// 生成 mobi 构造器，已添加章节和目录
+    ///
+    /// mobi 必须有封面，需要在构造器上设置封面或者自动生成封面
+    ///
+    pub fn mobi_builder(&self, data: &[u8]) -> IResult<MobiBuilder> {
+        let text = self.decode(data)?;
+        let sections = self.split(&text)?;
+        let mut builder = MobiBuilder::new()
+            .custome_nav(true)
+            .with_language(detect_language(&text));
+        let mut index = 0;
+        let mut nav_id = 0;
+        for section in &sections {
+            let nav;
+            (builder, nav) = add_mobi_section(builder, section, &mut index, &mut nav_id);
+            builder = builder.add_nav(nav);
+        }
+        Ok(builder)
+    }
+
+    /// 生成 mobi
+    pub fn mobi(&self, data: &[u8]) -> IResult<MobiBook> {
+        self.mobi_builder(data)?.book()
+    }
+}
+
+fn compile(patterns: &[String]) -> IResult<Vec<Regex>> {
+    patterns
+        .iter()
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            Regex::new(p)
+                .map_err(|e| IError::Parse(Cow::from(format!("invalid pattern {}: {}", p, e))))
+        })
+        .collect()
+}
+
+///
+/// 添加章节及目录
+///
+/// 卷的目录指向第一个子章节，卷首的内容作为第一个子章节
+///
+fn add_epub_section(
+    mut builder: EpubBuilder,
+    section: &TxtSection,
+    index: &mut usize,
+) -> (EpubBuilder, EpubNav) {
+    let file_name = format!("chapter_{:04}.xhtml", *index + 1);
+    let mut nav = EpubNav::default()
+        .with_title(section.title())
+        .with_file_name(file_name.as_str());
+    if section.children.is_empty() {
+        *index += 1;
+        builder = builder.add_chapter(
+            EpubHtml::default()
+                .with_title(section.title())
+                .with_file_name(file_name.as_str())
+                .with_data(section.to_html().into_bytes()),
+        );
+        return (builder, nav);
+    }
+    for child in volume_intro(section).iter().chain(section.children()) {
+        let child_nav;
+        (builder, child_nav) = add_epub_section(builder, child, index);
+        nav.push(child_nav);
+    }
+    (builder, nav)
+}
+
+fn add_mobi_section(
+    mut builder: MobiBuilder,
+    section: &TxtSection,
+    index: &mut usize,
+    nav_id: &mut usize,
+) -> (MobiBuilder, MobiNav) {
+    *nav_id += 1;
+    let mut nav = MobiNav::new(*nav_id, *index + 1).with_title(section.title());
+    if section.children.is_empty() {
+        *index += 1;
+        builder = builder.add_chapter(
+            MobiHtml::new(*index)
+                .with_title(section.title())
+                .with_data(section.to_html().into_bytes()),
+        );
+        return (builder, nav);
+    }
+    for child in volume_intro(section).iter().chain(section.children()) {
+        let child_nav;
+        (builder, child_nav) = add_mobi_section(builder, child, index, nav_id);
+        nav.add_child(child_nav);
+    }
+    (builder, nav)
+}
+
+/// 卷首的内容
+fn volume_intro(section: &TxtSection) -> Option<TxtSection> {
+    (!section.paragraphs.is_empty()).then(|| TxtSection {
+        title: section.title.clone(),
+        paragraphs: section.paragraphs.clone(),
+        children: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{BIG5, GB18030, UTF_16LE};
+
+    use super::{detect_encoding, TxtImporter, TxtPreset};
+
+    const TEXT: &str = "书名：测试\n作者：佚名\n\n第一卷 风起\n　　卷首语\n第一章 开始\n　　第一段\r\n\n　　第二段<>\n第二章 继续\n第二段\n第二卷 云涌\n第三章 结束\n　　完";
+
+    #[test]
+    fn test_detect_encoding() {
+        assert_eq!("UTF-8", detect_encoding(TEXT.as_bytes()));
+
+        let (gbk, _, _) = GB18030.encode(TEXT);
+        assert_eq!("gb18030", detect_encoding(&gbk));
+        let (big5, _, _) = BIG5.encode("第一章 開始\n這個時候，他們說了很多話，還沒有來得及回家。");
+        assert_eq!("Big5", detect_encoding(&big5));
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend(TEXT.encode_utf16().flat_map(|c| c.to_le_bytes()));
+        assert_eq!(UTF_16LE.name(), detect_encoding(&utf16));
+
+        let importer = TxtImporter::new();
+        assert_eq!(TEXT, importer.decode(&gbk).unwrap());
+        assert_eq!(TEXT, importer.decode(&utf16).unwrap());
+        assert_eq!(
+            TEXT,
+            importer.clone().with_encoding("gbk").decode(&gbk).unwrap()
+        );
+        assert!(importer.with_encoding("unknown").decode(&gbk).is_err());
+    }
+
+    #[test]
+    fn test_split() {
+        let sections = TxtImporter::new().split(TEXT).unwrap();
+        assert_eq!(3, sections.len());
+
+        assert_eq!("前言", sections[0].title());
+        assert_eq!(2, sections[0].paragraphs().count());
+
+        assert_eq!("第一卷 风起", sections[1].title());
+        assert_eq!("<p>卷首语</p>", sections[1].to_html());
+        let chap: Vec<_> = sections[1].children().collect();
+        assert_eq!(2, chap.len());
+        assert_eq!("第一章 开始", chap[0].title());
+        assert_eq!("<p>第一段</p>\n<p>第二段&lt;&gt;</p>", chap[0].to_html());
+        assert_eq!(1, chap[1].paragraphs().count());
+
+        assert_eq!(
+            "第三章 结束",
+            sections[2].children().next().unwrap().title()
+        );
+
+        // 不识别卷
+        let sections = TxtImporter::new()
+            .with_volume_pattern("")
+            .split(TEXT)
+            .unwrap();
+        assert_eq!(4, sections.len());
+        assert_eq!("第二章 继续", sections[2].title());
+        assert_eq!(2, sections[2].paragraphs().count());
+
+        // 没有章节
+        let sections = TxtImporter::new().split("只有\n一段").unwrap();
+        assert_eq!(1, sections.len());
+        assert_eq!("正文", sections[0].title());
+
+        let sections = TxtImporter::new()
+            .with_preset(TxtPreset::English)
+            .split("Preface\nCHAPTER I The Start\ntext\nPart 2\nChapter 12: End\ntext\n第一章 不是章节")
+            .unwrap();
+        assert_eq!(3, sections.len());
+        assert_eq!("CHAPTER I The Start", sections[1].title());
+        assert_eq!(
+            "Chapter 12: End",
+            sections[2].children().next().unwrap().title()
+        );
+
+        assert!(TxtImporter::new()
+            .with_chapter_pattern("(")
+            .split(TEXT)
+            .is_err());
+    }
+
+    #[test]
+    fn test_to_book() {
+        let (gbk, _, _) = GB18030.encode(TEXT);
+        let mut book = TxtImporter::new()
+            .epub_builder(&gbk)
+            .unwrap()
+            .with_title("测试")
+            .book()
+            .unwrap();
+        assert_eq!(5, book.chapters().count());
+        assert_eq!(3, book.nav().count());
+        // 卷首的内容作为第一个子章节，卷指向第一个子章节
+        let volume = book.nav().nth(1).unwrap();
+        assert_eq!("第一卷 风起", volume.title());
+        assert_eq!("chapter_0002.xhtml", volume.file_name());
+        assert_eq!(3, volume.child().count());
+        assert_eq!(
+            "chapter_0003.xhtml",
+            volume.child().nth(1).unwrap().file_name()
+        );
+        let volume = book.nav().nth(2).unwrap();
+        assert_eq!("chapter_0005.xhtml", volume.file_name());
+        assert_eq!(1, volume.child().count());
+        let chap = book.get_chapter_mut("chapter_0003.xhtml").unwrap();
+        assert_eq!(
+            "<p>第一段</p>\n<p>第二段&lt;&gt;</p>",
+            String::from_utf8(chap.data_mut().unwrap().to_vec()).unwrap()
+        );
+        crate::prelude::EpubWriter::write_to_mem(&mut book, true).unwrap();
+
+        let book = TxtImporter::new()
+            .mobi_builder(&gbk)
+            .unwrap()
+            .cover(Vec::new())
+            .book()
+            .unwrap();
+        assert_eq!(5, book.chapters().count());
+        assert_eq!(3, book.nav().nth(1).unwrap().children().count());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut book = TxtImporter::new()
+            .epub_builder(TEXT.as_bytes())
+            .unwrap()
+            .with_title("测试")
+            .book()
+            .unwrap();
+        assert!(book.identifier().starts_with("urn:uuid:"));
+        assert_eq!(Some("zh"), book.language());
+        let d = crate::prelude::validate::validate_book(&mut book).unwrap();
+        assert!(d.is_empty(), "{:?}", d);
+
+        let book = TxtImporter::new()
+            .epub("Chapter 1\nIt was a dark and stormy night".as_bytes())
+            .unwrap();
+        assert_eq!(Some("en"), book.language());
+    }
+}
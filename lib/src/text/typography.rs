@@ -0,0 +1,502 @@
+//!
+//! 排版规范化
+//!
+//! 抓取的文本经常混用全角半角标点、使用 `&nbsp;` 缩进段落、包含大量空段落以及英文引号，
+//! 这里按 [TypographyPolicy] 统一处理
+//!
+//! 只修改文本节点和段落标签，pre、code 等元素中的内容以及实体保持不变
+//!
+use std::{cell::Cell, sync::OnceLock};
+
+use regex::Regex;
+
+use crate::common::{map_html_text_tags, tag_name};
+
+/// 内容不处理的元素
+const SKIP: &[&str] = &["pre", "code", "kbd", "samp", "ruby"];
+/// 块级元素，引号不跨越这些元素配对
+const BLOCK: &[&str] = &["p", "h1", "h2", "h3", "h4", "h5", "h6", "li", "div"];
+
+///
+/// 排版规则，默认全部开启，首行缩进为 2em
+///
+/// # Examples
+///
+/// ```
+/// use iepub::prelude::typography::TypographyPolicy;
+///
+/// let policy = TypographyPolicy::default()
+///     .with_quotes(false)
+///     .with_text_indent(None);
+/// assert_eq!(None, policy.text_indent());
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct TypographyPolicy {
+    punctuation: bool,
+    spacing: bool,
+    quotes: bool,
+    blank: bool,
+    indent: Option<String>,
+}
+
+impl Default for TypographyPolicy {
+    fn default() -> Self {
+        TypographyPolicy {
+            punctuation: true,
+            spacing: true,
+            quotes: true,
+            blank: true,
+            indent: Some("2em".to_string()),
+        }
+    }
+}
+
+impl TypographyPolicy {
+    ///
+    /// 标点宽度，中文后的半角标点转为全角，全角字母数字转为半角
+    ///
+    pub fn with_punctuation(mut self, value: bool) -> Self {
+        self.punctuation = value;
+        self
+    }
+
+    /// 中文和英文、数字之间添加空格
+    pub fn with_spacing(mut self, value: bool) -> Self {
+        self.spacing = value;
+        self
+    }
+
+    /// 英文引号 `"` `'` 转为弯引号
+    pub fn with_quotes(mut self, value: bool) -> Self {
+        self.quotes = value;
+        self
+    }
+
+    /// 移除只有空白的段落，合并连续的换行
+    pub fn with_blank(mut self, value: bool) -> Self {
+        self.blank = value;
+        self
+    }
+
+    ///
+    /// 首行缩进，例如 `2em`
+    ///
+    /// 会移除段首的空格、`&nbsp;`、全角空格，epub 改为使用 css 的 text-indent，mobi 使用 p 标签的 width 属性。
+    /// 为 None 时不处理段首空白
+    ///
+    pub fn with_text_indent(mut self, value: Option<&str>) -> Self {
+        self.indent = value.map(|v| v.to_string());
+        self
+    }
+
+    pub fn text_indent(&self) -> Option<&str> {
+        self.indent.as_deref()
+    }
+}
+
+/// 段首的空白
+static LEADING_SPACE: OnceLock<Regex> = OnceLock::new();
+/// 只有空白的段落
+static BLANK_PARAGRAPH: OnceLock<Regex> = OnceLock::new();
+/// 连续的换行
+static REPEATED_BR: OnceLock<Regex> = OnceLock::new();
+/// 段落开始标签
+static PARAGRAPH: OnceLock<Regex> = OnceLock::new();
+
+const SPACE: &str = r"\s|\x{a0}|\x{3000}|&nbsp;|&#160;|&#[xX][aA]0;|&#12288;|&#[xX]3000;";
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: impl FnOnce() -> String) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(&pattern()).unwrap())
+}
+
+///
+/// 按规则处理 html
+///
+pub(crate) fn typeset_html(html: &str, policy: &TypographyPolicy) -> String {
+    let mut html = std::borrow::Cow::Borrowed(html);
+    if policy.blank {
+        let re = regex(&BLANK_PARAGRAPH, || {
+            format!(r"(?i)<p(?:\s[^>]*)?/>\s*|<p(?:\s[^>]*)?>(?:{SPACE}|<br\s*/?>)*</p>\s*")
+        });
+        html = re.replace_all(&html, "").into_owned().into();
+        let re = regex(&REPEATED_BR, || {
+            r"(?i)<br\s*/?>(?:\s*<br\s*/?>)+".to_string()
+        });
+        html = re.replace_all(&html, "<br/>").into_owned().into();
+    }
+    if policy.indent.is_some() {
+        let re = regex(&LEADING_SPACE, || {
+            format!(r"(?i)(<p(?:\s[^>]*)?>)(?:{SPACE})+")
+        });
+        html = re.replace_all(&html, "$1").into_owned().into();
+    }
+    if !policy.punctuation && !policy.quotes && !policy.spacing {
+        return html.into_owned();
+    }
+    // 引号跨越文本节点配对，例如 `"<b>加粗</b>"`，遇到块级元素时重新开始
+    let open = Cell::new(false);
+    let text = |text: &str| {
+        let mut tokens = tokenize(text);
+        if policy.punctuation {
+            tokens = punctuation(&tokens);
+        }
+        if policy.quotes {
+            let mut v = open.get();
+            quotes(&mut tokens, &mut v);
+            open.set(v);
+        }
+        if policy.spacing {
+            tokens = spacing(&tokens);
+        }
+        tokens
+            .iter()
+            .fold(String::with_capacity(text.len()), |mut out, t| {
+                match t {
+                    Token::Char(c) => out.push(*c),
+                    Token::Entity(v) => out.push_str(v),
+                }
+                out
+            })
+    };
+    map_html_text_tags(&html, SKIP, text, |tag| {
+        if BLOCK.contains(&tag_name(tag).as_str()) {
+            open.set(false);
+        }
+    })
+}
+
+///
+/// 为 p 标签添加 width 属性作为首行缩进，已有 width 的保持不变
+///
+/// mobi 不支持 css，kindle 使用这个属性缩进
+///
+pub(crate) fn set_paragraph_width(html: &str, width: &str) -> String {
+    let re = regex(&PARAGRAPH, || r"(?i)<p(\s[^>]*)?>".to_string());
+    re.replace_all(html, |caps: &regex::Captures| {
+        let attrs = caps.get(1).map(|v| v.as_str()).unwrap_or_default();
+        if attrs.to_lowercase().contains("width=") {
+            caps[0].to_string()
+        } else {
+            format!(r#"<p width="{width}"{attrs}>"#)
+        }
+    })
+    .into_owned()
+}
+
+/// 汉字、假名
+pub(crate) fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{20000}'..='\u{2ffff}')
+}
+
+///
+/// 根据文本推断语言，只统计前 4096 个字符
+///
+/// 假名较多时为 ja，谚文较多时为 ko，汉字比拉丁单词多时为 zh，否则为 en，没有文字时为 zh
+///
+pub(crate) fn detect_language(text: &str) -> &'static str {
+    let (mut han, mut kana, mut hangul, mut latin) = (0, 0, 0, 0);
+    for c in text.chars().take(4096) {
+        match c {
+            '\u{3040}'..='\u{30ff}' => kana += 1,
+            '\u{1100}'..='\u{11ff}' | '\u{ac00}'..='\u{d7af}' => hangul += 1,
+            c if is_cjk(c) => han += 1,
+            c if c.is_ascii_alphabetic() => latin += 1,
+            _ => {}
+        }
+    }
+    // 按 5 个字母一个单词估算
+    let words = latin / 5;
+    if kana * 10 > han + kana && kana >= words {
+        "ja"
+    } else if hangul > han && hangul >= words {
+        "ko"
+    } else if han >= words {
+        "zh"
+    } else {
+        "en"
+    }
+}
+
+/// 全角标点
+fn is_cjk_punctuation(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{303f}'
+        | '\u{ff01}'..='\u{ff0f}'
+        | '\u{ff1a}'..='\u{ff20}'
+        | '“' | '”' | '‘' | '’' | '…' | '—')
+}
+
+fn to_full_width(c: char) -> Option<char> {
+    match c {
+        ',' => Some('，'),
+        ';' => Some('；'),
+        ':' => Some('：'),
+        '?' => Some('？'),
+        '!' => Some('！'),
+        '.' => Some('。'),
+        '(' => Some('（'),
+        ')' => Some('）'),
+        _ => None,
+    }
+}
+
+/// 文本节点中的字符以及不能修改的实体
+#[derive(Clone, Copy)]
+enum Token<'a> {
+    Char(char),
+    Entity(&'a str),
+}
+
+impl Token<'_> {
+    fn char(&self) -> Option<char> {
+        match self {
+            Token::Char(c) => Some(*c),
+            Token::Entity(_) => None,
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '&' {
+            let end = rest[1..]
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '#')
+                .map(|i| i + 1);
+            if let Some(end) = end.filter(|i| *i > 1 && rest[*i..].starts_with(';')) {
+                tokens.push(Token::Entity(&rest[..=end]));
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+        tokens.push(Token::Char(c));
+        rest = &rest[c.len_utf8()..];
+    }
+    tokens
+}
+
+fn punctuation<'a>(tokens: &[Token<'a>]) -> Vec<Token<'a>> {
+    let mut out: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = tokens[index];
+        index += 1;
+        let Token::Char(c) = token else {
+            out.push(token);
+            continue;
+        };
+        // 全角字母数字转为半角
+        if matches!(c, '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ') {
+            out.push(Token::Char(char::from_u32(c as u32 - 0xfee0).unwrap_or(c)));
+            continue;
+        }
+        let Some(full) = to_full_width(c) else {
+            out.push(token);
+            continue;
+        };
+        let prev = out.last().and_then(Token::char);
+        let next = tokens.get(index).and_then(Token::char);
+        let after_cjk = prev.is_some_and(|c| is_cjk(c) || is_cjk_punctuation(c));
+        let convert = match c {
+            '(' => next.is_some_and(is_cjk),
+            // 避免转换小数点、网址、省略号
+            '.' => {
+                prev.is_some_and(is_cjk)
+                    && next.is_none_or(|c| c.is_whitespace() || is_cjk(c) || is_cjk_punctuation(c))
+            }
+            _ => after_cjk,
+        };
+        if !convert {
+            out.push(token);
+            continue;
+        }
+        out.push(Token::Char(full));
+        // 全角标点自带间距，去掉后面的空格
+        if c != '(' {
+            while tokens.get(index).and_then(Token::char) == Some(' ') {
+                index += 1;
+            }
+        }
+    }
+    out
+}
+
+fn quotes(tokens: &mut [Token], open: &mut bool) {
+    for index in 0..tokens.len() {
+        let prev = index.checked_sub(1).and_then(|i| tokens[i].char());
+        // 文本中的引号也可能是转义后的
+        let quote = match tokens[index] {
+            Token::Char(c) => c,
+            Token::Entity("&quot;" | "&#34;" | "&#x22;") => '"',
+            Token::Entity("&apos;" | "&#39;" | "&#x27;") => '\'',
+            Token::Entity(_) => continue,
+        };
+        match quote {
+            '"' => {
+                tokens[index] = Token::Char(if *open { '”' } else { '“' });
+                *open = !*open;
+            }
+            '\'' => {
+                let next = tokens.get(index + 1).and_then(Token::char);
+                // 缩写或者所有格，例如 `don't`
+                let close = prev.is_some_and(|c| c.is_alphanumeric())
+                    || (prev.is_some_and(|c| !c.is_whitespace() && !"(（[“‘".contains(c))
+                        && next.is_none_or(|c| !c.is_alphanumeric()));
+                tokens[index] = Token::Char(if close { '’' } else { '‘' });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn spacing<'a>(tokens: &[Token<'a>]) -> Vec<Token<'a>> {
+    let mut out: Vec<Token> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        if let (Some(prev), Some(c)) = (out.last().and_then(Token::char), token.char()) {
+            if (is_cjk(prev) && c.is_ascii_alphanumeric())
+                || (prev.is_ascii_alphanumeric() && is_cjk(c))
+            {
+                out.push(Token::Char(' '));
+            }
+        }
+        out.push(*token);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_language, set_paragraph_width, typeset_html, TypographyPolicy};
+
+    fn typeset(html: &str) -> String {
+        typeset_html(html, &TypographyPolicy::default())
+    }
+
+    #[test]
+    fn test_punctuation() {
+        let policy = TypographyPolicy::default()
+            .with_spacing(false)
+            .with_quotes(false);
+        let f = |v: &str| typeset_html(v, &policy);
+        assert_eq!("<p>你好，世界！</p>", f("<p>你好, 世界!</p>"));
+        assert_eq!("<p>结束。下一句</p>", f("<p>结束. 下一句</p>"));
+        assert_eq!("<p>版本1.2，例如e.g.</p>", f("<p>版本1.2，例如e.g.</p>"));
+        assert_eq!("<p>（注释）和(a)</p>", f("<p>(注释)和(a)</p>"));
+        assert_eq!(
+            "<p>Hello, world. ABC12</p>",
+            f("<p>Hello, world. ＡＢＣ１２</p>")
+        );
+        assert_eq!("<p>中&amp;, a</p>", f("<p>中&amp;, a</p>"));
+    }
+
+    #[test]
+    fn test_quotes() {
+        let policy = TypographyPolicy::default().with_spacing(false);
+        let f = |v: &str| typeset_html(v, &policy);
+        assert_eq!("<p>他说：“你好”</p>", f(r#"<p>他说:"你好"</p>"#));
+        assert_eq!("<p>“a” ‘b’</p>", f("<p>&quot;a&quot; &#39;b&#39;</p>"));
+        assert_eq!(
+            "<p>“<b>加粗</b>” don’t ‘a’</p>",
+            f(r#"<p>"<b>加粗</b>" don't 'a'</p>"#)
+        );
+        // 属性中的引号不处理
+        assert_eq!(r#"<p class="a">“a”</p>"#, f(r#"<p class="a">"a"</p>"#));
+        // 未闭合的引号不影响后面的段落
+        assert_eq!(
+            "<p>他说：“未完</p><p>“你好”</p><h1>“标题”</h1>",
+            f(r#"<p>他说:"未完</p><p>"你好"</p><h1>"标题"</h1>"#)
+        );
+    }
+
+    #[test]
+    fn test_spacing() {
+        assert_eq!(
+            "<p>使用 Rust 编写 2 个 <code>库a</code></p>",
+            typeset("<p>使用Rust编写2个 <code>库a</code></p>")
+        );
+    }
+
+    #[test]
+    fn test_blank() {
+        assert_eq!(
+            "<p>一</p>\n<p>二<br/>三</p>",
+            typeset("<p>&nbsp;&nbsp;一</p>\n<p> &nbsp;</p>\n<p/><p class=\"a\"><br/></p><p>\u{3000}\u{3000}二<br><br />三</p>")
+        );
+        let policy = TypographyPolicy::default()
+            .with_blank(false)
+            .with_text_indent(None);
+        assert_eq!(
+            "<p>&nbsp;一</p><p></p>",
+            typeset_html("<p>&nbsp;一</p><p></p>", &policy)
+        );
+        assert_eq!("<pre>a,b</pre>", typeset_html("<pre>a,b</pre>", &policy));
+    }
+
+    #[test]
+    fn test_typeset_book() {
+        use crate::prelude::*;
+
+        let mut book = EpubBuilder::new()
+            .with_title("书")
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一章")
+                    .with_file_name("1.xhtml")
+                    .with_data("<p>&nbsp;&nbsp;你好,world</p><p> </p>".as_bytes().to_vec()),
+            )
+            .with_typography(TypographyPolicy::default())
+            .book()
+            .unwrap();
+        let chap = book.get_chapter_mut("1.xhtml").unwrap();
+        assert_eq!("<p>你好，world</p>", chap.string_data());
+        assert_eq!(Some("p{text-indent:2em;}"), chap.css());
+        // 已有缩进时不重复添加
+        chap.typeset(&TypographyPolicy::default()).unwrap();
+        assert_eq!(Some("p{text-indent:2em;}"), chap.css());
+
+        let mut book = MobiBuilder::new()
+            .with_title("书")
+            .cover(crate::cbz::core::tests::png(1, 1))
+            .add_chapter(
+                MobiHtml::new(1)
+                    .with_data("<p class=\"a\">\u{3000}中文abc</p>".as_bytes().to_vec()),
+            )
+            .with_typography(TypographyPolicy::default().with_text_indent(Some("1em")))
+            .book()
+            .unwrap();
+        assert_eq!(
+            r#"<p width="1em" class="a">中文 abc</p>"#,
+            book.chapters_mut().next().unwrap().string_data()
+        );
+    }
+
+    #[test]
+    fn test_set_paragraph_width() {
+        assert_eq!(
+            r#"<p width="2em">a</p><p width="2em" class="b">b</p><p width="0">c</p><pre>d</pre>"#,
+            set_paragraph_width(
+                r#"<p>a</p><p class="b">b</p><p width="0">c</p><pre>d</pre>"#,
+                "2em"
+            )
+        );
+    }
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!("zh", detect_language("第一章 开始\n使用 Rust 编写"));
+        assert_eq!("ja", detect_language("吾輩は猫である。名前はまだ無い。"));
+        assert_eq!("ko", detect_language("나는 고양이로소이다"));
+        assert_eq!(
+            "en",
+            detect_language("Chapter 1 It was a dark and stormy night")
+        );
+        assert_eq!("zh", detect_language(""));
+    }
+}
@@ -0,0 +1,398 @@
+//!
+//! 简繁转换
+//!
+//! 基于词典转换，先按最长匹配查找词组，找不到时再逐字转换。词典使用 OpenCC 的格式，
+//! 每行为 `原文\t结果1 结果2`，有多个结果时使用第一个，一对多的字通过词组区分
+//!
+//! html 只转换文本节点，标签和属性保持不变
+//!
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::{
+    common::{map_html_text, ErrorContext, ResultContext},
+    prelude::*,
+};
+
+/// 转换的目标文字
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChineseScript {
+    /// 简体
+    Simplified,
+    /// 繁体
+    Traditional,
+}
+
+impl ChineseScript {
+    /// 对应的语言标签，转换后写入 dc:language
+    pub fn language(&self) -> &'static str {
+        match self {
+            ChineseScript::Simplified => "zh-Hans",
+            ChineseScript::Traditional => "zh-Hant",
+        }
+    }
+}
+
+/// 内置词典
+struct Dictionary {
+    chars: HashMap<char, char>,
+    phrases: HashMap<String, String>,
+    /// 最长词组的字数
+    max_len: usize,
+}
+
+impl Dictionary {
+    fn parse(chars: &str, phrases: &str) -> Self {
+        let mut dict = Dictionary {
+            chars: HashMap::new(),
+            phrases: HashMap::new(),
+            max_len: 1,
+        };
+        for (key, value) in parse_lines(chars) {
+            if let (Some(k), Some(v)) = (key.chars().next(), value.chars().next()) {
+                dict.chars.entry(k).or_insert(v);
+            }
+        }
+        for (key, value) in parse_lines(phrases) {
+            dict.max_len = dict.max_len.max(key.chars().count());
+            dict.phrases
+                .entry(key.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+        dict
+    }
+}
+
+/// 解析词典的每一行，只取第一个结果
+fn parse_lines(v: &str) -> impl Iterator<Item = (&str, &str)> {
+    v.lines().filter_map(|line| {
+        let (key, value) = line.split_once('\t')?;
+        let value = value.split(' ').next()?;
+        if key.is_empty() || value.is_empty() {
+            None
+        } else {
+            Some((key, value))
+        }
+    })
+}
+
+static S2T: OnceLock<Dictionary> = OnceLock::new();
+static T2S: OnceLock<Dictionary> = OnceLock::new();
+
+fn dictionary(to: ChineseScript) -> &'static Dictionary {
+    match to {
+        ChineseScript::Traditional => S2T.get_or_init(|| {
+            Dictionary::parse(
+                include_str!("data/STCharacters.txt"),
+                include_str!("data/STPhrases.txt"),
+            )
+        }),
+        ChineseScript::Simplified => T2S.get_or_init(|| {
+            Dictionary::parse(
+                include_str!("data/TSCharacters.txt"),
+                include_str!("data/TSPhrases.txt"),
+            )
+        }),
+    }
+}
+
+///
+/// 简繁转换
+///
+/// # Examples
+///
+/// ```
+/// use iepub::prelude::zh::{ChineseConverter, ChineseScript};
+///
+/// let converter = ChineseConverter::new(ChineseScript::Traditional);
+/// assert_eq!("頭髮乾淨", converter.convert("头发干净"));
+/// assert_eq!("<p class=\"发\">發現</p>", converter.convert_html("<p class=\"发\">发现</p>"));
+/// ```
+///
+pub struct ChineseConverter {
+    to: ChineseScript,
+    dict: &'static Dictionary,
+    /// 自定义词组，优先于内置词典
+    phrases: HashMap<String, String>,
+    max_len: usize,
+}
+
+impl ChineseConverter {
+    pub fn new(to: ChineseScript) -> Self {
+        let dict = dictionary(to);
+        ChineseConverter {
+            to,
+            dict,
+            phrases: HashMap::new(),
+            max_len: dict.max_len,
+        }
+    }
+
+    ///
+    /// 添加自定义词组，例如人名、地名
+    ///
+    /// 匹配时优先于内置词典，也可以用来覆盖单字的转换
+    ///
+    pub fn add_phrase<F: Into<String>, T: Into<String>>(&mut self, from: F, to: T) {
+        let from = from.into();
+        if from.is_empty() {
+            return;
+        }
+        self.max_len = self.max_len.max(from.chars().count());
+        self.phrases.insert(from, to.into());
+    }
+
+    pub fn with_phrase<F: Into<String>, T: Into<String>>(mut self, from: F, to: T) -> Self {
+        self.add_phrase(from, to);
+        self
+    }
+
+    /// 转换的目标文字
+    pub fn script(&self) -> ChineseScript {
+        self.to
+    }
+
+    /// 转换纯文本
+    pub fn convert(&self, text: &str) -> String {
+        // 每个字的起始位置，方便直接切片查找词组
+        let offsets: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        let count = offsets.len() - 1;
+        let mut out = String::with_capacity(text.len());
+        let mut index = 0;
+        'outer: while index < count {
+            for len in (1..=self.max_len.min(count - index)).rev() {
+                let key = &text[offsets[index]..offsets[index + len]];
+                if let Some(v) = self.phrases.get(key).or_else(|| self.dict.phrases.get(key)) {
+                    out.push_str(v);
+                    index += len;
+                    continue 'outer;
+                }
+            }
+            let c = text[offsets[index]..].chars().next().unwrap_or_default();
+            out.push(self.dict.chars.get(&c).copied().unwrap_or(c));
+            index += 1;
+        }
+        out
+    }
+
+    /// 转换 html，只修改文本节点
+    pub fn convert_html(&self, html: &str) -> String {
+        map_html_text(html, |text| self.convert(text))
+    }
+
+    fn convert_option(&self, value: Option<&str>) -> Option<String> {
+        value.map(|v| self.convert(v))
+    }
+
+    ///
+    /// 转换 epub 的章节内容、章节标题、目录以及元数据，并更新语言
+    ///
+    /// 延迟读取的章节会先加载
+    ///
+    pub fn convert_epub(&self, book: &mut EpubBook) -> IResult<()> {
+        book.set_title(self.convert(book.title()));
+        if let Some(v) = self.convert_option(book.creator()) {
+            book.set_creator(v);
+        }
+        if let Some(v) = self.convert_option(book.description()) {
+            book.set_description(v);
+        }
+        if let Some(v) = self.convert_option(book.contributor()) {
+            book.set_contributor(v);
+        }
+        if let Some(v) = self.convert_option(book.publisher()) {
+            book.set_publisher(v);
+        }
+        if let Some(v) = self.convert_option(book.subject()) {
+            book.set_subject(v);
+        }
+        for index in 0..book.meta_len() {
+            if let Some(meta) = book.get_meta_mut(index) {
+                if let Some(v) = meta.text().map(|v| self.convert(v)) {
+                    meta.set_text(v);
+                }
+            }
+        }
+        book.set_language(self.to.language());
+
+        for chap in book.chapters_mut() {
+            // 加载数据时会从 html 中读取标题，所以先加载再转换标题
+            if let Some(data) = chap.data_mut() {
+                let html = String::from_utf8(data.to_vec())
+                    .map_err(IError::Utf8)
+                    .context(|| {
+                        ErrorContext::new("convert chapter").with_entry(chap.file_name())
+                    })?;
+                chap.set_data(self.convert_html(&html).into_bytes());
+            }
+            let title = self.convert(chap.title());
+            chap.set_title(title);
+        }
+        self.convert_epub_nav(book.nav_mut());
+        Ok(())
+    }
+
+    fn convert_epub_nav(&self, nav: std::slice::IterMut<'_, EpubNav>) {
+        for ele in nav {
+            let title = self.convert(ele.title());
+            ele.set_title(title);
+            self.convert_epub_nav(ele.child_mut());
+        }
+    }
+
+    /// 转换 mobi 的章节内容、章节标题、目录以及元数据，并更新语言
+    pub fn convert_mobi(&self, book: &mut MobiBook) -> IResult<()> {
+        book.set_title(self.convert(book.title()));
+        if let Some(v) = self.convert_option(book.creator()) {
+            book.set_creator(v);
+        }
+        if let Some(v) = self.convert_option(book.description()) {
+            book.set_description(v);
+        }
+        if let Some(v) = self.convert_option(book.contributor()) {
+            book.set_contributor(v);
+        }
+        if let Some(v) = self.convert_option(book.publisher()) {
+            book.set_publisher(v);
+        }
+        if let Some(v) = self.convert_option(book.subject()) {
+            book.set_subject(v);
+        }
+        book.set_language(self.to.language());
+
+        for (index, chap) in book.chapters_mut().enumerate() {
+            let title = self.convert(chap.title());
+            chap.set_title(title);
+            let html = String::from_utf8(chap.data().unwrap_or_default().to_vec())
+                .map_err(IError::Utf8)
+                .context(|| {
+                    ErrorContext::new("convert chapter").with_entry(format!("chapter {}", index))
+                })?;
+            chap.set_data(self.convert_html(&html).into_bytes());
+        }
+        self.convert_mobi_nav(book.nav_mut());
+        Ok(())
+    }
+
+    fn convert_mobi_nav(&self, nav: std::slice::IterMut<'_, MobiNav>) {
+        for ele in nav {
+            let title = self.convert(ele.title());
+            ele.set_title(title);
+            self.convert_mobi_nav(ele.children_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChineseConverter, ChineseScript};
+    use crate::prelude::*;
+
+    #[test]
+    fn test_convert() {
+        let s2t = ChineseConverter::new(ChineseScript::Traditional);
+        assert_eq!("這是一本書", s2t.convert("这是一本书"));
+        // 一对多的字按词组区分
+        assert_eq!("頭髮", s2t.convert("头发"));
+        assert_eq!("發現", s2t.convert("发现"));
+        assert_eq!("乾淨的幹部", s2t.convert("干净的干部"));
+        assert_eq!("麵包和裡面", s2t.convert("面包和里面"));
+        assert_eq!("一隻貓只是", s2t.convert("一只猫只是"));
+        assert_eq!("abc，123", s2t.convert("abc，123"));
+
+        let t2s = ChineseConverter::new(ChineseScript::Simplified);
+        assert_eq!("这是一本书", t2s.convert("這是一本書"));
+        assert_eq!("头发干了", t2s.convert("頭髮乾了"));
+        assert_eq!("著名的乾隆", t2s.convert("著名的乾隆"));
+        assert_eq!("看着", t2s.convert("看著"));
+
+        let custom = ChineseConverter::new(ChineseScript::Traditional)
+            .with_phrase("范闲", "范閒")
+            .with_phrase("后", "后");
+        assert_eq!("范閒之后", custom.convert("范闲之后"));
+    }
+
+    #[test]
+    fn test_convert_html() {
+        let s2t = ChineseConverter::new(ChineseScript::Traditional);
+        assert_eq!(
+            r#"<p class="发" title='头发 > 里'>頭髮&amp;<!-- 头发 --><b>裡</b></p><style>.发{}</style>"#,
+            s2t.convert_html(
+                r#"<p class="发" title='头发 > 里'>头发&amp;<!-- 头发 --><b>里</b></p><style>.发{}</style>"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_convert_epub() {
+        let mut book = EpubBuilder::new()
+            .with_title("简体书")
+            .with_creator("作者")
+            .with_language("zh-CN")
+            .custome_nav(true)
+            .add_chapter(
+                EpubHtml::default()
+                    .with_title("第一章 开始")
+                    .with_file_name("1.xhtml")
+                    .with_data("<p>头发</p>".as_bytes().to_vec()),
+            )
+            .add_nav(
+                EpubNav::default()
+                    .with_title("第一卷")
+                    .with_file_name("1.xhtml"),
+            )
+            .book()
+            .unwrap();
+
+        ChineseConverter::new(ChineseScript::Traditional)
+            .convert_epub(&mut book)
+            .unwrap();
+        assert_eq!("簡體書", book.title());
+        assert_eq!(Some("作者"), book.creator());
+        assert_eq!(Some("zh-Hant"), book.language());
+        assert_eq!("第一卷", book.nav().next().unwrap().title());
+        let chap = book.get_chapter_mut("1.xhtml").unwrap();
+        assert_eq!("第一章 開始", chap.title());
+        assert_eq!("<p>頭髮</p>", chap.string_data());
+
+        // 写入后能读取到语言
+        let data = EpubWriter::write_to_mem(&mut book, false).unwrap();
+        let mut book = read_from_vec(data).unwrap();
+        assert_eq!(Some("zh-Hant"), book.language());
+        ChineseConverter::new(ChineseScript::Simplified)
+            .convert_epub(&mut book)
+            .unwrap();
+        assert_eq!("简体书", book.title());
+        assert_eq!(Some("zh-Hans"), book.language());
+    }
+
+    #[test]
+    fn test_convert_mobi() {
+        let mut book = MobiBuilder::new()
+            .with_title("简体书")
+            .cover(crate::cbz::core::tests::png(1, 1))
+            .custome_nav(true)
+            .add_chapter(
+                MobiHtml::new(1)
+                    .with_title("开始")
+                    .with_data("<p>发现</p>".as_bytes().to_vec()),
+            )
+            .add_nav(MobiNav::new(1, 1).with_title("第一卷"))
+            .book()
+            .unwrap();
+
+        ChineseConverter::new(ChineseScript::Traditional)
+            .convert_mobi(&mut book)
+            .unwrap();
+        assert_eq!("簡體書", book.title());
+        assert_eq!(Some("zh-Hant"), book.language());
+        let chap = book.chapters().next().unwrap();
+        assert_eq!("開始", chap.title());
+        assert_eq!("<p>發現</p>", chap.string_data());
+        assert_eq!("第一卷", book.nav().next().unwrap().title());
+    }
+}